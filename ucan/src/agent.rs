@@ -0,0 +1,765 @@
+//! Local signing agent.
+//!
+//! Keeps Ed25519 secret keys in a single process instead of in every service
+//! that issues UCANs. The [`SigningAgent`] listens on a Unix socket and answers
+//! two requests: list the DIDs it holds, and sign bytes as one of them. Each
+//! message is a DAG-CBOR value prefixed by its length as a big-endian `u32`.
+//!
+//! Applications connect with an [`AgentClient`] and get an [`AgentSigner`] per
+//! DID. [`AgentSigner`] is an [`AsyncDidSigner`], so it can be passed straight
+//! to [`DelegationBuilder::issuer`] and signed with `try_build_async`. The
+//! socket I/O behind it is blocking, though: see [`AgentKey`].
+//!
+//! An optional [`Approve`] hook sees the command being signed (decoded from the
+//! payload) and can refuse, e.g. by prompting on a terminal with [`TerminalPrompt`].
+//!
+//! [`DelegationBuilder::issuer`]: crate::delegation::builder::DelegationBuilder::issuer
+
+use crate::{
+    command::Command,
+    did::{AsyncDidSigner, Ed25519Did, Ed25519Signer},
+};
+use alloc::{collections::TryReserveError, string::String, sync::Arc, vec::Vec};
+use core::convert::Infallible;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use signature::{Signer, Verifier};
+use std::{
+    io::{self, BufRead, Read, Write},
+    os::unix::{
+        fs::PermissionsExt,
+        net::{UnixListener, UnixStream},
+    },
+    path::Path,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+use thiserror::Error;
+
+/// Largest frame either side will read, in bytes.
+pub const MAX_FRAME_LEN: u32 = 1 << 20;
+
+/// Default limit on connections a [`SigningAgent`] serves at once.
+pub const DEFAULT_MAX_CONNECTIONS: usize = 64;
+
+/// A request from a client to the agent.
+#[allow(clippy::large_enum_variant)] // short-lived, one per frame
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum AgentRequest {
+    /// List the DIDs the agent can sign for.
+    #[serde(rename = "list")]
+    ListDids,
+
+    /// Sign `payload` with the key for `did`.
+    #[serde(rename = "sign")]
+    Sign {
+        /// The DID to sign as.
+        did: Ed25519Did,
+
+        /// The bytes to sign.
+        #[serde(with = "serde_bytes")]
+        payload: Vec<u8>,
+    },
+}
+
+/// A response from the agent to a client.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum AgentResponse {
+    /// The DIDs the agent can sign for.
+    #[serde(rename = "dids")]
+    Dids(Vec<Ed25519Did>),
+
+    /// The signature over the requested payload.
+    #[serde(rename = "sig")]
+    Signature(#[serde(with = "serde_bytes")] Vec<u8>),
+
+    /// The agent does not hold a key for the requested DID.
+    #[serde(rename = "unknown_did")]
+    UnknownDid,
+
+    /// The request was refused by the approval hook.
+    #[serde(rename = "denied")]
+    Denied,
+}
+
+/// Write one length-prefixed DAG-CBOR frame.
+///
+/// # Errors
+///
+/// If the value cannot be encoded, is larger than [`MAX_FRAME_LEN`], or writing fails.
+pub fn write_frame<W: Write, T: Serialize>(writer: &mut W, value: &T) -> Result<(), AgentError> {
+    let bytes = serde_ipld_dagcbor::to_vec(value)?;
+    let len = u32::try_from(bytes.len())
+        .ok()
+        .filter(|len| *len <= MAX_FRAME_LEN)
+        .ok_or(AgentError::FrameTooLarge(bytes.len()))?;
+    writer.write_all(&len.to_be_bytes())?;
+    writer.write_all(&bytes)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Read one length-prefixed DAG-CBOR frame.
+///
+/// Returns `Ok(None)` if the stream ends cleanly before a new frame.
+///
+/// # Errors
+///
+/// If reading fails, the frame is larger than [`MAX_FRAME_LEN`], or it cannot be decoded.
+pub fn read_frame<R: Read, T: DeserializeOwned>(reader: &mut R) -> Result<Option<T>, AgentError> {
+    let mut len = [0u8; 4];
+    match reader.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+
+    let len = u32::from_be_bytes(len);
+    if len > MAX_FRAME_LEN {
+        return Err(AgentError::FrameTooLarge(len as usize));
+    }
+
+    let mut bytes = alloc::vec![0u8; len as usize];
+    reader.read_exact(&mut bytes)?;
+    Ok(Some(serde_ipld_dagcbor::from_slice(&bytes)?))
+}
+
+/// Bind a Unix socket that only the current user can connect to.
+///
+/// The socket is bound inside a fresh `0700` directory next to `path`, made
+/// `0600`, and only then renamed into place, so it is never reachable by
+/// other users while it still has the permissions the umask gave it.
+///
+/// # Errors
+///
+/// If the socket cannot be bound, its permissions cannot be set, or it
+/// cannot be moved to `path`.
+pub fn bind<P: AsRef<Path>>(path: P) -> io::Result<UnixListener> {
+    use std::os::unix::fs::DirBuilderExt;
+
+    let path = path.as_ref();
+    let name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "socket path has no name"))?;
+    let mut staging_name = std::ffi::OsString::from(".");
+    staging_name.push(name);
+    staging_name.push(format!(".{}.tmp", std::process::id()));
+    let staging = path.with_file_name(staging_name);
+
+    // `create` fails if the directory already exists, so nobody else owns it
+    std::fs::DirBuilder::new().mode(0o700).create(&staging)?;
+    let bound = (|| {
+        let socket = staging.join(name);
+        let listener = UnixListener::bind(&socket)?;
+        std::fs::set_permissions(&socket, std::fs::Permissions::from_mode(0o600))?;
+        std::fs::rename(&socket, path)?;
+        Ok(listener)
+    })();
+    std::fs::remove_dir_all(&staging).ok();
+    bound
+}
+
+/// A pending signature, shown to an [`Approve`] hook.
+#[derive(Debug, Clone, Copy)]
+pub struct SignRequest<'a> {
+    /// The DID being signed as.
+    pub did: &'a Ed25519Did,
+
+    /// The UCAN command, if the payload is a delegation or invocation.
+    pub command: Option<&'a Command>,
+
+    /// The raw bytes to be signed.
+    pub payload: &'a [u8],
+}
+
+/// Decides whether the agent signs a request.
+pub trait Approve: Send + Sync {
+    /// Return `true` to sign.
+    fn approve(&self, request: &SignRequest<'_>) -> bool;
+}
+
+impl<F: Fn(&SignRequest<'_>) -> bool + Send + Sync> Approve for F {
+    fn approve(&self, request: &SignRequest<'_>) -> bool {
+        self(request)
+    }
+}
+
+/// Asks on the agent's terminal before signing commands under any of `commands`.
+///
+/// Payloads that are not a UCAN (no command can be decoded) always prompt.
+/// Other UCANs are signed without a prompt. An empty list prompts for every
+/// request.
+///
+/// The agent serves each connection on its own thread. Every prompt holds one
+/// lock from printing the question until the answer is read, so concurrent
+/// requests queue up instead of interleaving their questions or taking each
+/// other's answers.
+#[derive(Debug, Clone)]
+pub struct TerminalPrompt {
+    commands: Vec<Command>,
+    terminal: Arc<Mutex<Terminal>>,
+}
+
+/// Where a [`TerminalPrompt`] asks its questions and reads the answers.
+struct Terminal {
+    input: Box<dyn BufRead + Send>,
+    output: Box<dyn Write + Send>,
+}
+
+impl core::fmt::Debug for Terminal {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Terminal").finish_non_exhaustive()
+    }
+}
+
+impl TerminalPrompt {
+    /// Prompt for requests whose command starts with any of `commands`.
+    ///
+    /// Every prompt built this way shares the process's stdin and stderr.
+    #[must_use]
+    pub fn new(commands: Vec<Command>) -> Self {
+        static STDIO: std::sync::OnceLock<Arc<Mutex<Terminal>>> = std::sync::OnceLock::new();
+
+        let terminal = STDIO.get_or_init(|| {
+            Arc::new(Mutex::new(Terminal {
+                input: Box::new(io::BufReader::new(io::stdin())),
+                output: Box::new(io::stderr()),
+            }))
+        });
+        TerminalPrompt {
+            commands,
+            terminal: Arc::clone(terminal),
+        }
+    }
+
+    #[cfg(test)]
+    fn with_terminal(
+        commands: Vec<Command>,
+        input: impl BufRead + Send + 'static,
+        output: impl Write + Send + 'static,
+    ) -> Self {
+        TerminalPrompt {
+            commands,
+            terminal: Arc::new(Mutex::new(Terminal {
+                input: Box::new(input),
+                output: Box::new(output),
+            })),
+        }
+    }
+
+    fn needs_prompt(&self, command: Option<&Command>) -> bool {
+        self.commands.is_empty()
+            || command.is_none_or(|cmd| self.commands.iter().any(|p| cmd.starts_with(p)))
+    }
+}
+
+impl Default for TerminalPrompt {
+    fn default() -> Self {
+        Self::new(Vec::new())
+    }
+}
+
+impl Approve for TerminalPrompt {
+    fn approve(&self, request: &SignRequest<'_>) -> bool {
+        if !self.needs_prompt(request.command) {
+            return true;
+        }
+
+        let command = request
+            .command
+            .map_or_else(|| String::from("<not a UCAN>"), ToString::to_string);
+
+        // Held until the answer is read; a poisoned lock still guards the terminal
+        let mut terminal = self
+            .terminal
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let Terminal { input, output } = &mut *terminal;
+        let asked = write!(
+            output,
+            "Sign {} ({} bytes) as {}? [y/N] ",
+            command,
+            request.payload.len(),
+            request.did
+        )
+        .and_then(|()| output.flush());
+
+        let mut answer = String::new();
+        asked.is_ok()
+            && input.read_line(&mut answer).is_ok()
+            && matches!(answer.trim(), "y" | "Y" | "yes")
+    }
+}
+
+/// Holds signing keys and answers [`AgentRequest`]s.
+#[allow(missing_debug_implementations)]
+pub struct SigningAgent {
+    signers: Vec<Ed25519Signer>,
+    approver: Option<Box<dyn Approve>>,
+    max_connections: usize,
+    connections: AtomicUsize,
+}
+
+impl SigningAgent {
+    /// An agent holding `signers`, signing every request.
+    #[must_use]
+    pub fn new(signers: Vec<Ed25519Signer>) -> Self {
+        SigningAgent {
+            signers,
+            approver: None,
+            max_connections: DEFAULT_MAX_CONNECTIONS,
+            connections: AtomicUsize::new(0),
+        }
+    }
+
+    /// Ask `approver` before each signature.
+    #[must_use]
+    pub fn with_approver<A: Approve + 'static>(mut self, approver: A) -> Self {
+        self.approver = Some(Box::new(approver));
+        self
+    }
+
+    /// Serve at most `max` connections at once (default
+    /// [`DEFAULT_MAX_CONNECTIONS`]). Connections beyond that are closed
+    /// immediately.
+    #[must_use]
+    pub const fn with_max_connections(mut self, max: usize) -> Self {
+        self.max_connections = max;
+        self
+    }
+
+    /// Answer a single request.
+    #[must_use]
+    pub fn respond(&self, request: AgentRequest) -> AgentResponse {
+        match request {
+            AgentRequest::ListDids => {
+                AgentResponse::Dids(self.signers.iter().map(|s| *s.did()).collect())
+            }
+            AgentRequest::Sign { did, payload } => {
+                let Some(signer) = self.signers.iter().find(|s| *s.did() == did) else {
+                    return AgentResponse::UnknownDid;
+                };
+
+                if let Some(approver) = &self.approver {
+                    let command = payload_command(&payload);
+                    let request = SignRequest {
+                        did: &did,
+                        command: command.as_ref(),
+                        payload: &payload,
+                    };
+                    if !approver.approve(&request) {
+                        return AgentResponse::Denied;
+                    }
+                }
+
+                AgentResponse::Signature(signer.signer().sign(&payload).to_vec())
+            }
+        }
+    }
+
+    /// Answer requests on `stream` until the client disconnects.
+    ///
+    /// # Errors
+    ///
+    /// If reading or writing a frame fails.
+    pub fn handle_connection(&self, mut stream: UnixStream) -> Result<(), AgentError> {
+        while let Some(request) = read_frame(&mut stream)? {
+            write_frame(&mut stream, &self.respond(request))?;
+        }
+        Ok(())
+    }
+
+    /// Accept connections on `listener`, one thread per connection.
+    ///
+    /// At most [`Self::with_max_connections`] connections are served at
+    /// once; further connections are closed without a response.
+    ///
+    /// # Errors
+    ///
+    /// If accepting a connection fails.
+    pub fn serve(self: Arc<Self>, listener: &UnixListener) -> io::Result<()> {
+        for stream in listener.incoming() {
+            let stream = stream?;
+            if self.connections.fetch_add(1, Ordering::AcqRel) >= self.max_connections {
+                self.connections.fetch_sub(1, Ordering::AcqRel);
+                tracing::warn!("signing agent at connection limit; closing connection");
+                continue;
+            }
+            let agent = Arc::clone(&self);
+            std::thread::spawn(move || {
+                if let Err(e) = agent.handle_connection(stream) {
+                    tracing::warn!("signing agent connection failed: {e}");
+                }
+                agent.connections.fetch_sub(1, Ordering::AcqRel);
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Extract the `cmd` field from a DAG-CBOR delegation or invocation payload.
+fn payload_command(payload: &[u8]) -> Option<Command> {
+    #[derive(Deserialize)]
+    struct WithCommand {
+        cmd: Command,
+    }
+
+    serde_ipld_dagcbor::from_slice::<WithCommand>(payload)
+        .ok()
+        .map(|p| p.cmd)
+}
+
+/// A connection to a [`SigningAgent`].
+#[derive(Debug)]
+pub struct AgentClient {
+    stream: Mutex<UnixStream>,
+}
+
+impl AgentClient {
+    /// Connect to the agent listening at `path`.
+    ///
+    /// # Errors
+    ///
+    /// If the socket cannot be connected to.
+    pub fn connect<P: AsRef<Path>>(path: P) -> Result<Self, AgentError> {
+        Ok(Self::from_stream(UnixStream::connect(path)?))
+    }
+
+    /// Use an already-connected stream.
+    #[must_use]
+    pub const fn from_stream(stream: UnixStream) -> Self {
+        AgentClient {
+            stream: Mutex::new(stream),
+        }
+    }
+
+    fn call(&self, request: &AgentRequest) -> Result<AgentResponse, AgentError> {
+        let mut stream = self.stream.lock().map_err(|_| AgentError::Poisoned)?;
+        write_frame(&mut *stream, request)?;
+        read_frame(&mut *stream)?.ok_or(AgentError::Disconnected)
+    }
+
+    /// List the DIDs the agent can sign for.
+    ///
+    /// # Errors
+    ///
+    /// If the request fails or the agent answers unexpectedly.
+    pub fn list_dids(&self) -> Result<Vec<Ed25519Did>, AgentError> {
+        match self.call(&AgentRequest::ListDids)? {
+            AgentResponse::Dids(dids) => Ok(dids),
+            AgentResponse::Signature(_) | AgentResponse::UnknownDid | AgentResponse::Denied => {
+                Err(AgentError::UnexpectedResponse)
+            }
+        }
+    }
+
+    /// Ask the agent to sign `payload` as `did`.
+    ///
+    /// The returned signature is checked against `did` before it is returned.
+    ///
+    /// # Errors
+    ///
+    /// If the agent does not hold the key, refuses, or returns an invalid signature.
+    pub fn sign(
+        &self,
+        did: &Ed25519Did,
+        payload: &[u8],
+    ) -> Result<ed25519_dalek::Signature, AgentError> {
+        let request = AgentRequest::Sign {
+            did: *did,
+            payload: payload.to_vec(),
+        };
+        match self.call(&request)? {
+            AgentResponse::Signature(bytes) => {
+                let sig = ed25519_dalek::Signature::from_slice(&bytes)
+                    .map_err(|_| AgentError::InvalidSignature)?;
+                did.0
+                    .verify(payload, &sig)
+                    .map_err(|_| AgentError::InvalidSignature)?;
+                Ok(sig)
+            }
+            AgentResponse::UnknownDid => Err(AgentError::UnknownDid),
+            AgentResponse::Denied => Err(AgentError::Denied),
+            AgentResponse::Dids(_) => Err(AgentError::UnexpectedResponse),
+        }
+    }
+
+    /// A signer for `did` that signs through this connection.
+    #[must_use]
+    pub fn signer(self: &Arc<Self>, did: Ed25519Did) -> AgentSigner {
+        AgentSigner {
+            did,
+            key: AgentKey {
+                did,
+                client: Arc::clone(self),
+            },
+        }
+    }
+}
+
+/// The signing half of an [`AgentSigner`].
+///
+/// This blocks. Signing writes to and reads from the agent socket with
+/// blocking I/O, and its [`async_signature::AsyncSigner`] impl is the blanket
+/// impl over [`Signer`], so awaiting it blocks the calling thread until the
+/// agent answers, which includes any time spent waiting on an [`Approve`]
+/// prompt. On an async runtime, sign from a thread that may block (e.g.
+/// `tokio::task::spawn_blocking`).
+#[derive(Debug, Clone)]
+pub struct AgentKey {
+    did: Ed25519Did,
+    client: Arc<AgentClient>,
+}
+
+impl Signer<ed25519_dalek::Signature> for AgentKey {
+    fn try_sign(&self, msg: &[u8]) -> Result<ed25519_dalek::Signature, signature::Error> {
+        self.client
+            .sign(&self.did, msg)
+            .map_err(signature::Error::from_source)
+    }
+}
+
+/// A DID whose key is held by a [`SigningAgent`].
+///
+/// Signing blocks the calling thread; see [`AgentKey`].
+#[derive(Debug, Clone)]
+pub struct AgentSigner {
+    did: Ed25519Did,
+    key: AgentKey,
+}
+
+impl AsyncDidSigner for AgentSigner {
+    type Did = Ed25519Did;
+    type AsyncSigner = AgentKey;
+
+    fn did(&self) -> &Ed25519Did {
+        &self.did
+    }
+
+    fn async_signer(&self) -> &AgentKey {
+        &self.key
+    }
+}
+
+/// Errors talking to a signing agent.
+#[derive(Debug, Error)]
+pub enum AgentError {
+    /// Socket I/O failed.
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    /// A message could not be encoded.
+    #[error("unable to encode message: {0}")]
+    Encode(#[from] serde_ipld_dagcbor::EncodeError<TryReserveError>),
+
+    /// A message could not be decoded.
+    #[error("unable to decode message: {0}")]
+    Decode(#[from] serde_ipld_dagcbor::DecodeError<Infallible>),
+
+    /// A frame exceeded [`MAX_FRAME_LEN`].
+    #[error("frame of {0} bytes exceeds the maximum")]
+    FrameTooLarge(usize),
+
+    /// The agent closed the connection.
+    #[error("agent disconnected")]
+    Disconnected,
+
+    /// The agent does not hold a key for the DID.
+    #[error("agent has no key for this DID")]
+    UnknownDid,
+
+    /// The agent refused to sign.
+    #[error("signing request denied")]
+    Denied,
+
+    /// The agent returned a signature that does not verify.
+    #[error("agent returned an invalid signature")]
+    InvalidSignature,
+
+    /// The agent answered with the wrong kind of response.
+    #[error("unexpected response from agent")]
+    UnexpectedResponse,
+
+    /// Another thread panicked while using the connection.
+    #[error("connection lock poisoned")]
+    Poisoned,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        delegation::subject::DelegatedSubject,
+        test_support::{block_on, signer, TempDir},
+        DelegationBuilder,
+    };
+    use testresult::TestResult;
+
+    fn spawn(agent: SigningAgent) -> TestResult<Arc<AgentClient>> {
+        let (client, server) = UnixStream::pair()?;
+        std::thread::spawn(move || agent.handle_connection(server));
+        Ok(Arc::new(AgentClient::from_stream(client)))
+    }
+
+    #[test]
+    fn test_end_to_end_over_socket() -> TestResult {
+        let root = signer(1);
+        let dir = TempDir::new("agent")?;
+        let path = dir.path().join("agent.sock");
+
+        let listener = bind(&path)?;
+        assert_eq!(
+            std::fs::metadata(&path)?.permissions().mode() & 0o777,
+            0o600
+        );
+        assert_eq!(std::fs::read_dir(dir.path())?.count(), 1);
+
+        let agent = Arc::new(SigningAgent::new(vec![root.clone()]));
+        std::thread::spawn(move || agent.serve(&listener));
+
+        let client = Arc::new(AgentClient::connect(&path)?);
+        assert_eq!(client.list_dids()?, vec![*root.did()]);
+
+        let delegation = block_on(
+            DelegationBuilder::new()
+                .issuer(client.signer(*root.did()))
+                .audience(*signer(2).did())
+                .subject(DelegatedSubject::Specific(*root.did()))
+                .command_from_str("/crud/read")?
+                .try_build_async(),
+        )?;
+        delegation.verify_signature()?;
+        assert_eq!(delegation.issuer(), root.did());
+        Ok(())
+    }
+
+    #[test]
+    fn test_connection_limit() -> TestResult {
+        let dir = TempDir::new("agent-limit")?;
+        let path = dir.path().join("agent.sock");
+        let listener = bind(&path)?;
+        let agent = Arc::new(SigningAgent::new(vec![signer(1)]).with_max_connections(0));
+        std::thread::spawn(move || agent.serve(&listener));
+
+        assert!(AgentClient::connect(&path)?.list_dids().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_unknown_did() -> TestResult {
+        let client = spawn(SigningAgent::new(vec![signer(1)]))?;
+        assert!(matches!(
+            client.sign(signer(2).did(), b"hello"),
+            Err(AgentError::UnknownDid)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_approval_per_command() -> TestResult {
+        let root = signer(1);
+        let delete = Command::parse("/crud/delete")?;
+        let agent =
+            SigningAgent::new(vec![root.clone()]).with_approver(move |req: &SignRequest<'_>| {
+                !req.command.is_some_and(|cmd| cmd.starts_with(&delete))
+            });
+        let client = spawn(agent)?;
+
+        let build = |cmd: &str| {
+            DelegationBuilder::new()
+                .issuer(client.signer(*root.did()))
+                .audience(*root.did())
+                .subject(DelegatedSubject::Any)
+                .command_from_str(cmd)
+                .map(|b| block_on(b.try_build_async()))
+        };
+
+        build("/crud/read")??.verify_signature()?;
+        assert!(build("/crud/delete")?.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_terminal_prompt_scope() -> TestResult {
+        let prompt = TerminalPrompt::new(vec![Command::parse("/crud/delete")?]);
+        assert!(prompt.needs_prompt(Some(&Command::parse("/crud/delete/all")?)));
+        assert!(!prompt.needs_prompt(Some(&Command::parse("/crud/read")?)));
+        assert!(prompt.needs_prompt(None));
+        assert!(TerminalPrompt::default().needs_prompt(None));
+        Ok(())
+    }
+
+    #[test]
+    fn test_terminal_prompt_serializes_concurrent_requests() -> TestResult {
+        #[derive(Clone, Default)]
+        struct Shared(Arc<Mutex<Vec<u8>>>);
+
+        impl Write for Shared {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.0
+                    .lock()
+                    .map_err(|_| io::Error::other("poisoned"))?
+                    .write(buf)
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let root = signer(1);
+        let output = Shared::default();
+        let prompt = TerminalPrompt::with_terminal(
+            Vec::new(),
+            io::Cursor::new(b"y\nn\n".to_vec()),
+            output.clone(),
+        );
+        let agent = Arc::new(SigningAgent::new(vec![root.clone()]).with_approver(prompt));
+
+        let did = *root.did();
+        let answers = std::thread::scope(|scope| {
+            let requests = [b"first".as_slice(), b"second".as_slice()].map(|payload| {
+                let agent = Arc::clone(&agent);
+                scope.spawn(move || {
+                    let (client, server) = UnixStream::pair()?;
+                    std::thread::spawn(move || agent.handle_connection(server));
+                    let signed = AgentClient::from_stream(client).sign(&did, payload);
+                    Ok::<_, io::Error>((payload.len(), signed.is_ok()))
+                })
+            });
+            requests.map(|handle| handle.join().map_err(|_| "request panicked"))
+        });
+
+        let asked = String::from_utf8(output.0.lock().map_err(|_| "poisoned")?.clone())?;
+        let prompts: Vec<&str> = asked.split_inclusive("[y/N] ").collect();
+        assert_eq!(prompts.len(), 2, "{asked}");
+
+        // Whichever question came first got the "y"
+        for answer in answers {
+            let (len, signed) = answer??;
+            let asked_first = prompts
+                .first()
+                .is_some_and(|p| p.contains(&format!("({len} bytes)")));
+            assert_eq!(signed, asked_first, "{asked}");
+        }
+        for prompt in prompts {
+            assert!(prompt.starts_with("Sign <not a UCAN> ("), "{asked}");
+            assert!(
+                prompt.ends_with(&format!("as {}? [y/N] ", root.did())),
+                "{asked}"
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejects_oversized_frame() {
+        let mut bytes = (MAX_FRAME_LEN + 1).to_be_bytes().to_vec();
+        bytes.extend_from_slice(&[0; 8]);
+        assert!(matches!(
+            read_frame::<_, AgentRequest>(&mut bytes.as_slice()),
+            Err(AgentError::FrameTooLarge(_))
+        ));
+    }
+}
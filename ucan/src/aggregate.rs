@@ -0,0 +1,190 @@
+//! Aggregate signature verification for BLS12-381 chains.
+//!
+//! When every token in a chain is issued by a [`Bls12381Did`], all of the
+//! envelope signatures can be checked together with a single multi-pairing
+//! rather than one pairing check per hop.
+//!
+//! This only covers signatures; chain semantics (issuer/audience linkage,
+//! commands, policies) are still checked by
+//! [`InvocationPayload::syntatic_checks`].
+//!
+//! [`InvocationPayload::syntatic_checks`]: crate::invocation::InvocationPayload::syntatic_checks
+
+use crate::{delegation::DelegationPayload, did::Bls12381Did, invocation::Invocation, Delegation};
+use alloc::vec::Vec;
+use thiserror::Error;
+use varsig::{
+    codec::{Codec, DagCborCodec},
+    signature::bls::{aggregate_verify, AggregateVerifyError},
+};
+
+/// Verify the signatures on a set of BLS12-381 delegations in one aggregate check.
+///
+/// # Errors
+///
+/// Returns an [`AggregateChainError`] if a payload cannot be encoded or the
+/// aggregate signature does not verify.
+pub fn verify_delegations<'a, I>(delegations: I) -> Result<(), AggregateChainError>
+where
+    I: IntoIterator<Item = &'a Delegation<Bls12381Did>>,
+{
+    let signed = delegations
+        .into_iter()
+        .map(|dlg| {
+            Ok((
+                &dlg.issuer().0,
+                DagCborCodec.encode_payload(dlg.payload())?,
+                dlg.signature(),
+            ))
+        })
+        .collect::<Result<Vec<_>, AggregateChainError>>()?;
+
+    aggregate_verify(
+        signed
+            .iter()
+            .map(|(pk, msg, sig)| (*pk, msg.as_slice(), *sig)),
+    )?;
+    Ok(())
+}
+
+/// Verify the signatures on an invocation and all of its proofs in one aggregate check.
+///
+/// # Errors
+///
+/// Returns an [`AggregateChainError`] if a payload cannot be encoded or the
+/// aggregate signature does not verify.
+pub fn verify_invocation<'a, I>(
+    invocation: &Invocation<Bls12381Did>,
+    proofs: I,
+) -> Result<(), AggregateChainError>
+where
+    I: IntoIterator<Item = &'a Delegation<Bls12381Did>>,
+{
+    let inv_msg = DagCborCodec.encode_payload(invocation.payload())?;
+    let mut signed = Vec::new();
+    for dlg in proofs {
+        signed.push((
+            &dlg.issuer().0,
+            DagCborCodec.encode_payload(dlg.payload())?,
+            dlg.signature(),
+        ));
+    }
+
+    aggregate_verify(
+        core::iter::once((
+            &invocation.issuer().0,
+            inv_msg.as_slice(),
+            invocation.signature(),
+        ))
+        .chain(
+            signed
+                .iter()
+                .map(|(pk, msg, sig)| (*pk, msg.as_slice(), *sig)),
+        ),
+    )?;
+    Ok(())
+}
+
+/// Errors from aggregate chain verification.
+#[derive(Debug, Error)]
+pub enum AggregateChainError {
+    /// A payload could not be encoded for verification.
+    #[error(transparent)]
+    Encoding(#[from] <DagCborCodec as Codec<DelegationPayload<Bls12381Did>>>::EncodingError),
+
+    /// The aggregate signature was rejected.
+    #[error(transparent)]
+    Verification(#[from] AggregateVerifyError),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        delegation::subject::DelegatedSubject, did::Bls12381Signer, envelope::Envelope,
+        invocation::builder::InvocationBuilder, DelegationBuilder,
+    };
+    use signature::Signer;
+    use testresult::TestResult;
+    use varsig::signature::bls::{Bls12381MinPk, BlsSignature, BlsSigningKey};
+
+    fn signer(seed: u8) -> Result<Bls12381Signer, signature::Error> {
+        BlsSigningKey::from_ikm(&[seed; 32]).map(Bls12381Signer::new)
+    }
+
+    fn chain() -> TestResult<(Vec<Bls12381Signer>, Vec<Delegation<Bls12381Did>>)> {
+        let principals = (1..=4).map(signer).collect::<Result<Vec<_>, _>>()?;
+        let root = principals.first().ok_or("no root")?;
+
+        let delegations = principals
+            .windows(2)
+            .map(|pair| match pair {
+                [iss, aud] => Ok(DelegationBuilder::new()
+                    .issuer(iss.clone())
+                    .audience(*aud.did())
+                    .subject(DelegatedSubject::Specific(*root.did()))
+                    .command_from_str("/crud/read")?
+                    .try_build()?),
+                _ => Err("bad window".into()),
+            })
+            .collect::<TestResult<Vec<_>>>()?;
+
+        Ok((principals, delegations))
+    }
+
+    #[test]
+    fn test_verify_delegations() -> TestResult {
+        let (_, delegations) = chain()?;
+        verify_delegations(&delegations)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_delegations_rejects_forged_signature() -> TestResult {
+        let (principals, delegations) = chain()?;
+        let mallory = principals.last().ok_or("no leaf")?;
+        let first = delegations.first().ok_or("no delegation")?;
+
+        // Sign the first hop's payload with the wrong key, keeping the original issuer
+        let forged_sig = mallory
+            .signer()
+            .try_sign(&serde_ipld_dagcbor::to_vec(first.payload())?)?;
+        let mut envelope: Envelope<Bls12381MinPk, DelegationPayload<Bls12381Did>, BlsSignature> =
+            serde_ipld_dagcbor::from_slice(&serde_ipld_dagcbor::to_vec(first)?)?;
+        envelope.0 = forged_sig;
+        let forged: Delegation<Bls12381Did> =
+            serde_ipld_dagcbor::from_slice(&serde_ipld_dagcbor::to_vec(&envelope)?)?;
+
+        let mut chain = delegations.clone();
+        if let Some(slot) = chain.first_mut() {
+            *slot = forged;
+        }
+
+        assert!(matches!(
+            verify_delegations(&chain),
+            Err(AggregateChainError::Verification(
+                AggregateVerifyError::VerificationFailed
+            ))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_invocation() -> TestResult {
+        let (principals, delegations) = chain()?;
+        let root = principals.first().ok_or("no root")?;
+        let leaf = principals.last().ok_or("no leaf")?;
+
+        let invocation = InvocationBuilder::new()
+            .issuer(leaf.clone())
+            .audience(*root.did())
+            .subject(*root.did())
+            .command_from_str("/crud/read")?
+            .proofs(delegations.iter().map(Delegation::to_cid).collect())
+            .try_build()?;
+
+        invocation.payload().syntatic_checks(delegations.iter())?;
+        verify_invocation(&invocation, &delegations)?;
+        Ok(())
+    }
+}
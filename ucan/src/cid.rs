@@ -1,8 +1,112 @@
 //! CID helpers.
+//!
+//! UCAN tokens are addressed by DAG-CBOR, version 1 CIDs. The hash defaults to
+//! SHA2-256, but a [`CidBuilder`] can pick any multihash compiled into
+//! [`varsig::hash`] (e.g. BLAKE3 with the `blake3` feature).
 
 use ipld_core::cid::{multihash::Multihash, Cid};
 use serde::Serialize;
-use sha2::Digest;
+use varsig::hash::{digest_fn_by_tag, DigestFn, Multihasher, Sha2_256};
+
+/// DAG-CBOR multicodec code.
+const DAG_CBOR: u64 = 0x71;
+
+/// Builds DAG-CBOR, version 1 CIDs with a configurable multihash.
+///
+/// Builders compare equal when they use the same multihash code.
+#[derive(Debug, Clone, Copy)]
+pub struct CidBuilder {
+    hash_code: u64,
+    digest: DigestFn,
+}
+
+impl CidBuilder {
+    /// A builder that hashes with `H`.
+    #[must_use]
+    pub const fn new<H: Multihasher>() -> Self {
+        CidBuilder {
+            hash_code: H::MULTIHASH_TAG,
+            digest: H::digest,
+        }
+    }
+
+    /// A builder for the multihash `code`, if that hash is compiled in.
+    #[must_use]
+    pub fn from_hash_code(code: u64) -> Option<Self> {
+        digest_fn_by_tag(code).map(|digest| CidBuilder {
+            hash_code: code,
+            digest,
+        })
+    }
+
+    /// A builder matching the hash of an existing CID, if that hash is compiled in.
+    #[must_use]
+    pub fn for_cid(cid: &Cid) -> Option<Self> {
+        Self::from_hash_code(cid.hash().code())
+    }
+
+    /// The multihash code this builder hashes with.
+    #[must_use]
+    pub const fn hash_code(&self) -> u64 {
+        self.hash_code
+    }
+
+    /// Serialize a value to DAG-CBOR and address it with this builder's hash.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the value cannot be serialized or if the multihash cannot be created.
+    /// We assume that all UCANs are comparible with IPLD, and so serialization (and hashing)
+    /// "should" never fail unless something is deeply wrong.
+    #[must_use]
+    pub fn build<T: Serialize>(&self, t: &T) -> Cid {
+        #[allow(clippy::expect_used)]
+        let bytes = serde_ipld_dagcbor::to_vec(t).expect("not serializable");
+        self.build_from_dagcbor(&bytes)
+    }
+
+    /// Address already-encoded DAG-CBOR bytes with this builder's hash.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the multihash cannot be created, as with [`CidBuilder::build`].
+    #[must_use]
+    pub fn build_from_dagcbor(&self, bytes: &[u8]) -> Cid {
+        let digest = (self.digest)(bytes);
+        #[allow(clippy::expect_used)]
+        let multihash =
+            Multihash::wrap(self.hash_code, &digest).expect("unable to create multihash");
+        Cid::new_v1(DAG_CBOR, multihash)
+    }
+
+    /// Check whether `cid` addresses `t`, hashing with whatever hash `cid` uses.
+    ///
+    /// Returns `false` if the CID is not DAG-CBOR or its hash is not compiled in.
+    #[must_use]
+    pub fn matches<T: Serialize>(cid: &Cid, t: &T) -> bool {
+        cid.codec() == DAG_CBOR && Self::for_cid(cid).is_some_and(|b| b.build(t) == *cid)
+    }
+}
+
+impl PartialEq for CidBuilder {
+    fn eq(&self, other: &Self) -> bool {
+        self.hash_code == other.hash_code
+    }
+}
+
+impl Eq for CidBuilder {}
+
+impl core::hash::Hash for CidBuilder {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        self.hash_code.hash(state);
+    }
+}
+
+impl Default for CidBuilder {
+    fn default() -> Self {
+        Self::new::<Sha2_256>()
+    }
+}
 
 /// Serialize a value to a DAG-CBOR/SHA2-256 CID.
 ///
@@ -12,10 +116,64 @@ use sha2::Digest;
 /// We assume that all UCANs are comparible with IPLD, and so serialization (and hashing)
 /// "should" never fail unless something is deeply wrong.
 pub fn to_dagcbor_cid<T: Serialize>(t: &T) -> Cid {
-    #[allow(clippy::expect_used)]
-    let bytes = serde_ipld_dagcbor::to_vec(t).expect("not serializable");
-    let digest = sha2::Sha256::digest(bytes);
-    #[allow(clippy::expect_used)]
-    let multihash = Multihash::wrap(0x12, &digest).expect("unable to create multihash");
-    ipld_core::cid::Cid::new_v1(0x71, multihash)
+    CidBuilder::default().build(t)
+}
+
+/// Serialize a value to a DAG-CBOR CID, hashed with the multihash `tag`.
+///
+/// Returns `None` if no compiled-in hash matches `tag`
+/// (see [`varsig::hash::digest_by_tag`]).
+///
+/// # Panics
+///
+/// Will panic if the value cannot be serialized, as with [`to_dagcbor_cid`].
+pub fn to_dagcbor_cid_with_hash<T: Serialize>(t: &T, tag: u64) -> Option<Cid> {
+    CidBuilder::from_hash_code(tag).map(|builder| builder.build(t))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use testresult::TestResult;
+
+    #[test]
+    fn test_default_is_sha2_256() {
+        let cid = to_dagcbor_cid(&"hello");
+        assert_eq!(cid.hash().code(), 0x12);
+        assert_eq!(cid.codec(), DAG_CBOR);
+        assert_eq!(CidBuilder::default().hash_code(), 0x12);
+    }
+
+    #[test]
+    fn test_sha2_512_builder() -> TestResult {
+        let builder = CidBuilder::new::<varsig::hash::Sha2_512>();
+        let cid = builder.build(&"hello");
+        assert_eq!(cid.hash().code(), 0x13);
+        assert_eq!(cid.hash().size(), 64);
+        assert_eq!(CidBuilder::for_cid(&cid).ok_or("not compiled in")?, builder);
+        Ok(())
+    }
+
+    #[test]
+    fn test_matches_across_hashes() {
+        let sha256 = to_dagcbor_cid(&"hello");
+        let sha512 = CidBuilder::new::<varsig::hash::Sha2_512>().build(&"hello");
+
+        assert!(CidBuilder::matches(&sha256, &"hello"));
+        assert!(CidBuilder::matches(&sha512, &"hello"));
+        assert!(!CidBuilder::matches(&sha512, &"goodbye"));
+    }
+
+    #[test]
+    fn test_unknown_hash_code() {
+        assert_eq!(CidBuilder::from_hash_code(0xdead_beef), None);
+    }
+
+    #[cfg(feature = "blake3")]
+    #[test]
+    fn test_blake3_builder() {
+        let cid = CidBuilder::new::<varsig::hash::Blake3>().build(&"hello");
+        assert_eq!(cid.hash().code(), 0x1e);
+        assert!(CidBuilder::matches(&cid, &"hello"));
+    }
 }
@@ -4,13 +4,14 @@
 //! [the GitHub repo](https://github.com/ucan-wg/delegation/).
 
 pub mod builder;
+pub mod detached;
 pub mod policy;
 pub mod store;
 pub mod subject;
 
 use self::subject::DelegatedSubject;
 use crate::{
-    cid::to_dagcbor_cid,
+    cid::{to_dagcbor_cid, to_dagcbor_cid_with_hash, CidBuilder},
     command::Command,
     crypto::nonce::Nonce,
     did::Did,
@@ -32,7 +33,12 @@ use serde::{
     de::{self, MapAccess, Visitor},
     Deserialize, Deserializer, Serialize,
 };
-use varsig::verify::Verify;
+use varsig::{
+    codec::{Codec, DagCborCodec},
+    policy::VerificationPolicy,
+    verify::{VerificationError, Verify},
+    Varsig,
+};
 
 /// Top-level UCAN Delegation.
 #[derive(Clone)]
@@ -92,10 +98,69 @@ impl<D: Did> Delegation<D> {
         &self.0 .1.payload.nonce
     }
 
+    /// Getter for the signed payload.
+    pub const fn payload(&self) -> &DelegationPayload<D> {
+        &self.0 .1.payload
+    }
+
+    /// Getter for the Varsig header.
+    pub const fn header(&self) -> &Varsig<D::VarsigConfig, DagCborCodec, DelegationPayload<D>> {
+        &self.0 .1.header
+    }
+
+    /// Getter for the envelope signature.
+    pub const fn signature(&self) -> &<D::VarsigConfig as Verify>::Signature {
+        &self.0 .0
+    }
+
+    /// Verify the envelope signature against the issuer's key.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`VerificationError`] if the payload cannot be encoded or the
+    /// signature is invalid.
+    pub fn verify_signature(
+        &self,
+    ) -> Result<(), VerificationError<<DagCborCodec as Codec<DelegationPayload<D>>>::EncodingError>>
+    {
+        self.verify_signature_with_policy(&VerificationPolicy::default())
+    }
+
+    /// Verify the envelope signature, subject to a [`VerificationPolicy`].
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`VerificationError`] if the policy rejects the header, the
+    /// payload cannot be encoded, or the signature is invalid.
+    pub fn verify_signature_with_policy(
+        &self,
+        policy: &VerificationPolicy,
+    ) -> Result<(), VerificationError<<DagCborCodec as Codec<DelegationPayload<D>>>::EncodingError>>
+    {
+        self.0 .1.header.try_verify_with_policy(
+            policy,
+            self.issuer().verifier(),
+            self.payload(),
+            self.signature(),
+        )
+    }
+
     /// Compute the CID for this delegation.
     pub fn to_cid(&self) -> Cid {
         to_dagcbor_cid(&self)
     }
+
+    /// Compute the CID for this delegation with a specific hash.
+    pub fn to_cid_with(&self, builder: &CidBuilder) -> Cid {
+        builder.build(&self)
+    }
+
+    /// Compute the CID for this delegation using the hash named in its Varsig header.
+    ///
+    /// Returns `None` if the header names no hash, or one that is not compiled in.
+    pub fn to_cid_with_header_hash(&self) -> Option<Cid> {
+        to_dagcbor_cid_with_hash(&self, self.0 .1.header.hash_tag()?)
+    }
 }
 
 impl<D: Did> Debug for Delegation<D> {
@@ -439,6 +504,23 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn cid_with_header_hash_uses_sha2_512() -> TestResult {
+        let b64 = "glhA0rict5hwniXnh54Y7b0v/ZEDNSlPdBx0rsoWDYC2Ylv+UzDr00s7ojPsfvNwrofqKItK911ZGJggZSkeQIB3DqJhaEg0Ae0B7QETcXN1Y2FuL2RsZ0AxLjAuMC1yYy4xqWNhdWR4OGRpZDprZXk6ejZNa2ZGSkJ4U0JGZ29BcVRRTFM3YlRmUDhNZ3lEeXB2YTVpNkNMNVBKTjhSSlpyY2NtZGEvY2V4cPZjaXNzeDhkaWQ6a2V5Ono2TWtyQXNxMU03dEVmUHZXNWRSMlVGQ3daU3pSTU5YWWVUVzh0R1pTS3ZVbTlFWmNuYmYaaSTxp2Nwb2yAY3N1YvZkbWV0YaBlbm9uY2VMVkDFeab+58p8SMpW";
+        let bytes = BASE64_STANDARD.decode(b64)?;
+        let delegation: Delegation<Ed25519Did> = serde_ipld_dagcbor::from_slice(&bytes)?;
+
+        // The Ed25519 header names SHA2-512
+        let cid = delegation
+            .to_cid_with_header_hash()
+            .ok_or("no header hash")?;
+        assert_eq!(cid.hash().code(), 0x13);
+        assert_eq!(cid.hash().size(), 64);
+        assert_eq!(cid.codec(), 0x71);
+        assert_ne!(cid, delegation.to_cid());
+        Ok(())
+    }
+
     #[test]
     fn delegation_b64_fixture_roundtrip() -> TestResult {
         // Sample delegation with sub: null, cmd: "/", exp: null, meta: {}
@@ -1,17 +1,21 @@
 //! Typesafe builder for [`Delegation`].
 
-use super::{policy::predicate::Predicate, subject::DelegatedSubject};
+use super::{
+    detached::{DetachedIssuer, UnsignedDelegation},
+    policy::predicate::Predicate,
+    subject::DelegatedSubject,
+};
 use crate::{
     command::{Command, CommandParseError},
     crypto::nonce::Nonce,
-    did::{Did, DidSigner},
+    did::{AsyncDidSigner, Did, DidSigner},
     envelope::{Envelope, EnvelopePayload},
     sealed::{CommandOrUnset, DelegatedSubjectOrUnset, DidOrUnset, DidSignerOrUnset},
     time::timestamp::Timestamp,
     unset::Unset,
 };
 use alloc::{collections::BTreeMap, string::String, vec::Vec};
-use core::marker::PhantomData;
+use core::{convert::Infallible, marker::PhantomData};
 use ipld_core::ipld::Ipld;
 use varsig::{
     codec::DagCborCodec,
@@ -82,7 +86,7 @@ impl<
     > DelegationBuilder<D, Audience, Subject, Cmd>
 {
     /// Sets the issuer of the [`Delegation`].
-    pub fn issuer<NewD: DidSigner>(
+    pub fn issuer<NewD: AsyncDidSigner>(
         self,
         issuer: NewD,
     ) -> DelegationBuilder<NewD, Audience, Subject, Cmd> {
@@ -100,6 +104,28 @@ impl<
         }
     }
 
+    /// Sets the issuer of the [`Delegation`] by DID alone, for detached signing.
+    ///
+    /// The builder then produces an [`UnsignedDelegation`] via
+    /// [`DelegationBuilder::build_unsigned`] instead of a signed [`Delegation`].
+    pub fn issuer_did<NewD: Did>(
+        self,
+        issuer: NewD,
+    ) -> DelegationBuilder<DetachedIssuer<NewD>, Audience, Subject, Cmd> {
+        DelegationBuilder {
+            issuer: DetachedIssuer(issuer),
+            audience: self.audience,
+            subject: self.subject,
+            command: self.command,
+            policy: self.policy,
+            expiration: self.expiration,
+            not_before: self.not_before,
+            meta: self.meta,
+            nonce: self.nonce,
+            _marker: PhantomData,
+        }
+    }
+
     /// Sets the audience of the [`Delegation`].
     pub fn audience<NewAudience: Did>(
         self,
@@ -228,18 +254,8 @@ impl<D: DidSigner>
     ///
     /// Panics if no nonce was provided and the `getrandom` feature is enabled
     /// but the CSPRNG fails.
-    #[allow(clippy::expect_used)]
     pub fn into_payload(self) -> super::DelegationPayload<D::Did> {
-        let nonce = self.nonce.unwrap_or_else(|| {
-            #[cfg(feature = "getrandom")]
-            {
-                Nonce::generate_16().expect("failed to generate nonce")
-            }
-            #[cfg(not(feature = "getrandom"))]
-            {
-                panic!("nonce is required: either call .nonce() or enable the `getrandom` feature")
-            }
-        });
+        let nonce = resolve_nonce(self.nonce);
 
         super::DelegationPayload {
             issuer: self.issuer.did().clone(),
@@ -267,7 +283,6 @@ impl<D: DidSigner>
     ///
     /// Panics if no nonce was provided and the `getrandom` feature is enabled
     /// but the CSPRNG fails.
-    #[allow(clippy::expect_used)]
     #[allow(clippy::type_complexity)]
     pub fn try_build(
         self,
@@ -278,17 +293,7 @@ impl<D: DidSigner>
             <<D::Did as Did>::VarsigConfig as Sign>::SignError,
         >,
     > {
-        let nonce = self.nonce.unwrap_or_else(|| {
-            #[cfg(feature = "getrandom")]
-            {
-                #[allow(clippy::expect_used)]
-                Nonce::generate_16().expect("failed to generate nonce")
-            }
-            #[cfg(not(feature = "getrandom"))]
-            {
-                panic!("nonce is required: either call .nonce() or enable the `getrandom` feature")
-            }
-        });
+        let nonce = resolve_nonce(self.nonce);
 
         let payload: super::DelegationPayload<D::Did> = super::DelegationPayload {
             issuer: self.issuer.did().clone(),
@@ -329,3 +334,117 @@ impl<D: DidSigner>
         Ok(super::Delegation(envelope))
     }
 }
+
+impl<D: AsyncDidSigner>
+    DelegationBuilder<
+        D,
+        <D as AsyncDidSigner>::Did,
+        DelegatedSubject<<D as AsyncDidSigner>::Did>,
+        Command,
+    >
+{
+    /// Builds the complete, signed [`Delegation`] with an asynchronous signer.
+    ///
+    /// A nonce must either have been provided via [`DelegationBuilder::nonce`],
+    /// or the `getrandom` feature must be enabled.
+    ///
+    /// # Errors
+    ///
+    /// * `SignerError` if signing the delegation fails.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no nonce was provided and the `getrandom` feature is enabled
+    /// but the CSPRNG fails.
+    #[allow(clippy::type_complexity)]
+    pub async fn try_build_async(
+        self,
+    ) -> Result<
+        super::Delegation<D::Did>,
+        SignerError<
+            <DagCborCodec as varsig::codec::Codec<super::DelegationPayload<D::Did>>>::EncodingError,
+            Infallible,
+        >,
+    > {
+        let nonce = resolve_nonce(self.nonce);
+        let issuer = AsyncDidSigner::did(&self.issuer);
+
+        let payload: super::DelegationPayload<D::Did> = super::DelegationPayload {
+            issuer: issuer.clone(),
+            audience: self.audience,
+            subject: self.subject,
+            command: self.command,
+            policy: self.policy,
+            expiration: self.expiration,
+            not_before: self.not_before,
+            meta: self.meta,
+            nonce,
+        };
+
+        let header: Varsig<
+            <D::Did as Did>::VarsigConfig,
+            DagCborCodec,
+            super::DelegationPayload<D::Did>,
+        > = Varsig::new(issuer.varsig_config().clone(), DagCborCodec);
+
+        let (sig, _) = header
+            .try_sign_with_async(self.issuer.async_signer(), &payload)
+            .await?;
+
+        Ok(super::Delegation(Envelope(
+            sig,
+            EnvelopePayload { header, payload },
+        )))
+    }
+}
+
+impl<I: Did + Clone> DelegationBuilder<DetachedIssuer<I>, I, DelegatedSubject<I>, Command> {
+    /// Builds an [`UnsignedDelegation`] to be signed elsewhere.
+    ///
+    /// A nonce must either have been provided via [`DelegationBuilder::nonce`],
+    /// or the `getrandom` feature must be enabled.
+    ///
+    /// # Errors
+    ///
+    /// If the payload cannot be encoded as DAG-CBOR.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no nonce was provided and the `getrandom` feature is enabled
+    /// but the CSPRNG fails.
+    pub fn build_unsigned(
+        self,
+    ) -> Result<
+        UnsignedDelegation<I>,
+        <DagCborCodec as varsig::codec::Codec<super::DelegationPayload<I>>>::EncodingError,
+    > {
+        let nonce = resolve_nonce(self.nonce);
+
+        UnsignedDelegation::new(super::DelegationPayload {
+            issuer: self.issuer.0,
+            audience: self.audience,
+            subject: self.subject,
+            command: self.command,
+            policy: self.policy,
+            expiration: self.expiration,
+            not_before: self.not_before,
+            meta: self.meta,
+            nonce,
+        })
+    }
+}
+
+/// Use the given nonce, or generate one if the `getrandom` feature is enabled.
+#[allow(clippy::expect_used)]
+pub(crate) fn resolve_nonce(nonce: Option<Nonce>) -> Nonce {
+    nonce.unwrap_or_else(|| {
+        #[cfg(feature = "getrandom")]
+        {
+            Nonce::generate_16().expect("failed to generate nonce")
+        }
+        #[cfg(not(feature = "getrandom"))]
+        {
+            panic!("nonce is required: either call .nonce() or enable the `getrandom` feature")
+        }
+    })
+}
@@ -0,0 +1,327 @@
+//! Detached (offline) signing for [`Delegation`]s.
+//!
+//! When the issuer's key is not available in-process (e.g. it lives on an
+//! air-gapped machine), a delegation can be built from the issuer's DID alone
+//! with [`DelegationBuilder::issuer_did`]. The resulting [`UnsignedDelegation`]
+//! carries the Varsig header and the exact bytes to be signed, and serializes
+//! to a portable DAG-CBOR file. Once signed elsewhere, the signature is
+//! attached with [`UnsignedDelegation::attach_signature`], which checks it
+//! against the embedded issuer DID.
+//!
+//! [`DelegationBuilder::issuer_did`]: super::builder::DelegationBuilder::issuer_did
+
+use super::{Delegation, DelegationPayload};
+use crate::{
+    did::{Did, DidSigner},
+    envelope::{Envelope, EnvelopePayload},
+};
+use alloc::vec::Vec;
+use core::{fmt::Debug, marker::PhantomData};
+use serde::{
+    de::{self, SeqAccess, Visitor},
+    ser::SerializeTuple,
+    Deserialize, Deserializer, Serialize,
+};
+use signature::Signer;
+use thiserror::Error;
+use varsig::{
+    codec::{Codec, DagCborCodec},
+    verify::Verify,
+    Varsig,
+};
+
+/// An issuer known only by its DID, for building an [`UnsignedDelegation`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DetachedIssuer<D: Did>(pub D);
+
+/// A delegation payload and Varsig header, awaiting a signature from the issuer.
+///
+/// Serializes as a DAG-CBOR 2-tuple of `[header, signing_bytes]`, where
+/// `signing_bytes` is the DAG-CBOR encoded payload that the issuer signs.
+#[derive(Clone)]
+pub struct UnsignedDelegation<D: Did> {
+    header: Varsig<D::VarsigConfig, DagCborCodec, DelegationPayload<D>>,
+    payload: DelegationPayload<D>,
+    signing_bytes: Vec<u8>,
+}
+
+impl<D: Did> UnsignedDelegation<D> {
+    /// Wrap a payload with the issuer's Varsig header.
+    ///
+    /// # Errors
+    ///
+    /// If the payload cannot be encoded as DAG-CBOR.
+    pub fn new(
+        payload: DelegationPayload<D>,
+    ) -> Result<Self, <DagCborCodec as Codec<DelegationPayload<D>>>::EncodingError> {
+        let header = Varsig::new(payload.issuer.varsig_config().clone(), DagCborCodec);
+        let signing_bytes = DagCborCodec.encode_payload(&payload)?;
+        Ok(UnsignedDelegation {
+            header,
+            payload,
+            signing_bytes,
+        })
+    }
+
+    /// Getter for the Varsig header.
+    pub const fn header(&self) -> &Varsig<D::VarsigConfig, DagCborCodec, DelegationPayload<D>> {
+        &self.header
+    }
+
+    /// Getter for the payload to be signed.
+    pub const fn payload(&self) -> &DelegationPayload<D> {
+        &self.payload
+    }
+
+    /// The exact bytes the issuer must sign.
+    #[must_use]
+    pub fn signing_bytes(&self) -> &[u8] {
+        &self.signing_bytes
+    }
+
+    /// Sign with the issuer's key, e.g. on the offline machine.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`DetachedSignError`] if the signer is not the issuer,
+    /// or if signing fails.
+    pub fn sign_with<S: DidSigner<Did = D>>(
+        &self,
+        signer: &S,
+    ) -> Result<<D::VarsigConfig as Verify>::Signature, DetachedSignError> {
+        if *signer.did() != self.payload.issuer {
+            return Err(DetachedSignError::IssuerMismatch);
+        }
+
+        signer
+            .signer()
+            .try_sign(&self.signing_bytes)
+            .map_err(DetachedSignError::SigningFailed)
+    }
+
+    /// Attach a signature, producing a [`Delegation`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`AttachSignatureError::SignatureMismatch`] if the signature
+    /// does not verify against the issuer DID.
+    pub fn attach_signature(
+        self,
+        signature: <D::VarsigConfig as Verify>::Signature,
+    ) -> Result<Delegation<D>, AttachSignatureError> {
+        self.header
+            .try_verify(self.payload.issuer.verifier(), &self.payload, &signature)
+            .map_err(|_| AttachSignatureError::SignatureMismatch)?;
+
+        Ok(Delegation(Envelope(
+            signature,
+            EnvelopePayload {
+                header: self.header,
+                payload: self.payload,
+            },
+        )))
+    }
+
+    /// Attach a signature given as raw bytes, producing a [`Delegation`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`AttachSignatureError`] if the bytes are not a signature
+    /// for this algorithm, or the signature does not verify against the issuer DID.
+    pub fn attach_signature_bytes(
+        self,
+        signature: &[u8],
+    ) -> Result<Delegation<D>, AttachSignatureError> {
+        let signature = <D::VarsigConfig as Verify>::Signature::try_from(signature)
+            .map_err(|_| AttachSignatureError::InvalidSignatureBytes)?;
+        self.attach_signature(signature)
+    }
+}
+
+impl<D: Did> Debug for UnsignedDelegation<D> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("UnsignedDelegation")
+            .field("header", &self.header)
+            .field("payload", &self.payload)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<D: Did> Serialize for UnsignedDelegation<D> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_tuple(2)?;
+        seq.serialize_element(&self.header)?;
+        seq.serialize_element(&serde_bytes::Bytes::new(&self.signing_bytes))?;
+        seq.end()
+    }
+}
+
+impl<'de, D: Did> Deserialize<'de> for UnsignedDelegation<D> {
+    fn deserialize<De: Deserializer<'de>>(deserializer: De) -> Result<Self, De::Error> {
+        struct UnsignedVisitor<D: Did>(PhantomData<D>);
+
+        impl<'de, D: Did> Visitor<'de> for UnsignedVisitor<D> {
+            type Value = UnsignedDelegation<D>;
+
+            fn expecting(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                f.write_str("a 2-element sequence [header, signing bytes]")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let header: Varsig<D::VarsigConfig, DagCborCodec, DelegationPayload<D>> = seq
+                    .next_element()?
+                    .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+                let signing_bytes: serde_bytes::ByteBuf = seq
+                    .next_element()?
+                    .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+
+                let payload: DelegationPayload<D> = DagCborCodec
+                    .decode_payload(&signing_bytes)
+                    .map_err(|e| de::Error::custom(format_args!("invalid payload: {e}")))?;
+
+                // Only accept canonical bytes for the payload and a header
+                // that matches the issuer, so what gets signed is exactly
+                // what will be verified
+                let unsigned = UnsignedDelegation::new(payload)
+                    .map_err(|e| de::Error::custom(format_args!("invalid payload: {e}")))?;
+                if unsigned.signing_bytes != *signing_bytes {
+                    return Err(de::Error::custom(
+                        "payload bytes are not canonical DAG-CBOR",
+                    ));
+                }
+                if unsigned.header.tags() != header.tags() {
+                    return Err(de::Error::custom("header does not match the issuer DID"));
+                }
+
+                Ok(unsigned)
+            }
+        }
+
+        deserializer.deserialize_tuple(2, UnsignedVisitor(PhantomData))
+    }
+}
+
+/// Errors from [`UnsignedDelegation::sign_with`].
+#[derive(Debug, Error)]
+pub enum DetachedSignError {
+    /// The signer's DID is not the delegation's issuer.
+    #[error("signer is not the delegation issuer")]
+    IssuerMismatch,
+
+    /// The underlying signer failed.
+    #[error("signing failed: {0}")]
+    SigningFailed(signature::Error),
+}
+
+/// Errors from attaching a detached signature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum AttachSignatureError {
+    /// The bytes are not a signature for the issuer's algorithm.
+    #[error("invalid signature bytes")]
+    InvalidSignatureBytes,
+
+    /// The signature does not verify against the issuer DID.
+    #[error("signature does not match the issuer")]
+    SignatureMismatch,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        delegation::subject::DelegatedSubject,
+        did::{Ed25519Did, Ed25519Signer},
+        test_support::signer,
+        DelegationBuilder,
+    };
+    use testresult::TestResult;
+
+    fn unsigned(issuer: &Ed25519Signer) -> TestResult<UnsignedDelegation<Ed25519Did>> {
+        let audience = signer(2);
+        Ok(DelegationBuilder::new()
+            .issuer_did(*issuer.did())
+            .audience(*audience.did())
+            .subject(DelegatedSubject::Specific(*issuer.did()))
+            .command_from_str("/crud/read")?
+            .build_unsigned()?)
+    }
+
+    #[test]
+    fn test_offline_round_trip() -> TestResult {
+        let root = signer(1);
+
+        // Online: export the unsigned delegation
+        let file = serde_ipld_dagcbor::to_vec(&unsigned(&root)?)?;
+
+        // Offline: import, inspect, and sign
+        let offline: UnsignedDelegation<Ed25519Did> = serde_ipld_dagcbor::from_slice(&file)?;
+        assert_eq!(offline.payload().issuer, *root.did());
+        let sig = offline.sign_with(&root)?.to_bytes();
+
+        // Online: attach the returned signature
+        let online: UnsignedDelegation<Ed25519Did> = serde_ipld_dagcbor::from_slice(&file)?;
+        let delegation = online.attach_signature_bytes(&sig)?;
+        delegation.verify_signature()?;
+        assert_eq!(delegation.issuer(), root.did());
+        Ok(())
+    }
+
+    #[test]
+    fn test_matches_in_process_signing() -> TestResult {
+        let root = signer(1);
+        let nonce = crate::crypto::nonce::Nonce::generate_16()?;
+        let build = || {
+            DelegationBuilder::new()
+                .audience(*signer(2).did())
+                .subject(DelegatedSubject::Specific(*root.did()))
+                .command_from_str("/crud/read")
+                .map(|b| b.nonce(nonce.clone()))
+        };
+
+        let signed = build()?.issuer(root.clone()).try_build()?;
+        let unsigned = build()?.issuer_did(*root.did()).build_unsigned()?;
+        let detached = unsigned
+            .clone()
+            .attach_signature(unsigned.sign_with(&root)?)?;
+
+        assert_eq!(detached.to_cid(), signed.to_cid());
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejects_wrong_signer() -> TestResult {
+        let root = signer(1);
+        let mallory = signer(3);
+        let unsigned = unsigned(&root)?;
+
+        assert!(matches!(
+            unsigned.sign_with(&mallory),
+            Err(DetachedSignError::IssuerMismatch)
+        ));
+
+        let forged = mallory.signer().try_sign(unsigned.signing_bytes())?;
+        assert_eq!(
+            unsigned.clone().attach_signature(forged).err(),
+            Some(AttachSignatureError::SignatureMismatch)
+        );
+        assert_eq!(
+            unsigned.attach_signature_bytes(&[0; 3]).err(),
+            Some(AttachSignatureError::InvalidSignatureBytes)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejects_tampered_file() -> TestResult {
+        let root = signer(1);
+        let unsigned = unsigned(&root)?;
+
+        // Re-export with non-canonical payload bytes (an extra trailing byte)
+        let mut bytes = unsigned.signing_bytes().to_vec();
+        bytes.push(0);
+        let file =
+            serde_ipld_dagcbor::to_vec(&(unsigned.header(), serde_bytes::Bytes::new(&bytes)))?;
+        assert!(serde_ipld_dagcbor::from_slice::<UnsignedDelegation<Ed25519Did>>(&file).is_err());
+        Ok(())
+    }
+}
@@ -1,14 +1,52 @@
 //! Delegation stores.
 
+#[cfg(feature = "encrypted")]
+pub mod encrypted;
+#[cfg(feature = "std")]
+pub mod fs;
+pub mod gc;
+#[cfg(feature = "http")]
+pub mod http;
+pub mod index;
+pub mod layered;
+#[cfg(feature = "std")]
+pub mod lru;
+#[cfg(feature = "std")]
+pub mod sharded;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+pub mod sync;
+
+#[cfg(all(feature = "sqlite", feature = "encrypted"))]
+pub use encrypted::SqliteBlobStore;
+#[cfg(feature = "encrypted")]
+pub use encrypted::{
+    BlobStore, EncryptedStore, EncryptedStoreError, EncryptionKey, FsBlobStore, KeyIdConflict,
+};
+#[cfg(feature = "std")]
+pub use fs::{FsStore, FsStoreError};
+pub use gc::{ExpiryCollector, GcError, GcReport};
+#[cfg(feature = "http")]
+pub use http::{HttpStoreClient, HttpStoreError, HttpStoreServer};
+pub use index::{DelegationIndex, DelegationQuery};
+pub use layered::{Layered, LayeredError};
+#[cfg(feature = "std")]
+pub use lru::LruStore;
+#[cfg(feature = "std")]
+pub use sharded::ShardedStore;
+#[cfg(feature = "sqlite")]
+pub use sqlite::{SqliteStore, SqliteStoreError};
+pub use sync::{InProcess, Replicator, SyncError, SyncMessage, SyncReport, SyncTransport};
+
 use alloc::{rc::Rc, vec::Vec};
-use core::{borrow::Borrow, cell::RefCell, convert::Infallible, error::Error};
+use core::{borrow::Borrow, cell::RefCell, convert::Infallible, error::Error, ops::Bound};
 
 use alloc::collections::BTreeMap;
 use future_form::{FutureForm, Local};
 use ipld_core::cid::Cid;
 use thiserror::Error;
 
-use crate::did::Did;
+use crate::{cid::CidBuilder, did::Did};
 
 use super::Delegation;
 
@@ -21,6 +59,9 @@ use {
 };
 
 /// Delegation store.
+///
+/// This is the minimal interface the invocation checker needs. Stores that
+/// can also be edited and paged through implement [`ListableStore`].
 pub trait DelegationStore<K: FutureForm, D: Did, T: Borrow<Delegation<D>>> {
     /// Error type for insertion operations.
     type InsertError: Error;
@@ -39,6 +80,159 @@ pub trait DelegationStore<K: FutureForm, D: Did, T: Borrow<Delegation<D>>> {
     ) -> K::Future<'_, Result<(), Self::InsertError>>;
 }
 
+/// A [`DelegationStore`] that can also remove, count, and page through its
+/// entries.
+///
+/// These methods are split out of [`DelegationStore`] so that existing stores
+/// keep compiling. Trait methods cannot have default bodies that return a
+/// [`FutureForm`] future (it cannot name the `Send` bounds), so each store
+/// implements them itself.
+pub trait ListableStore<K: FutureForm, D: Did, T: Borrow<Delegation<D>>>:
+    DelegationStore<K, D, T>
+{
+    /// Error type for listing and counting operations.
+    type ListError: Error;
+
+    /// Error type for removal.
+    type RemoveError: Error;
+
+    /// Looks up each CID, returning its delegation with the key it is stored
+    /// under (an equivalent CID if it was stored with another hash), or
+    /// `None` if it is not stored.
+    fn find_all<'a>(&'a self, cid: &'a [Cid]) -> K::Future<'a, Result<Found<T>, Self::GetError>>;
+
+    /// Inserts several delegations by their CIDs.
+    fn insert_many_by_cid(
+        &self,
+        delegations: Vec<(Cid, T)>,
+    ) -> K::Future<'_, Result<(), Self::InsertError>>;
+
+    /// Removes a delegation, returning it if it was present.
+    fn remove(&self, cid: Cid) -> K::Future<'_, Result<Option<T>, Self::RemoveError>>;
+
+    /// Whether a delegation is stored under `cid` (or an equivalent CID).
+    fn contains(&self, cid: Cid) -> K::Future<'_, Result<bool, Self::ListError>>;
+
+    /// The number of stored delegations.
+    fn count(&self) -> K::Future<'_, Result<usize, Self::ListError>>;
+
+    /// Lists one page of stored CIDs, in ascending order.
+    ///
+    /// The order is [`Cid`]'s `Ord`, not that of the CIDs' binary encoding:
+    /// [`Layered`] merges pages from two stores relying on it.
+    fn list_cids(&self, page: Page) -> K::Future<'_, Result<Vec<Cid>, Self::ListError>>;
+
+    /// Retrieves one page of stored delegations with their CIDs, in ascending
+    /// CID order, as for [`list_cids`](ListableStore::list_cids).
+    fn list(&self, page: Page) -> K::Future<'_, Result<Matches<T>, Self::ListError>>;
+}
+
+/// Delegations with the CIDs they are stored under.
+pub type Matches<T> = Vec<(Cid, T)>;
+
+/// One entry per requested CID: its delegation with the CID it is stored
+/// under, or `None` if it is not stored.
+pub type Found<T> = Vec<Option<(Cid, T)>>;
+
+/// A window over a store's entries, ordered by [`Cid`]'s `Ord`.
+///
+/// Start with [`Page::first`], then pass the last CID of each page to
+/// [`Page::after`] to get the next. Entries inserted or removed between calls
+/// may be skipped or seen, but nothing is returned twice.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Page {
+    /// Only return entries with CIDs strictly greater than this.
+    pub after: Option<Cid>,
+
+    /// The maximum number of entries to return.
+    pub limit: usize,
+}
+
+impl Page {
+    /// The first `limit` entries.
+    #[must_use]
+    pub const fn first(limit: usize) -> Self {
+        Page { after: None, limit }
+    }
+
+    /// Every entry.
+    #[must_use]
+    pub const fn all() -> Self {
+        Page::first(usize::MAX)
+    }
+
+    /// The page following the entry with CID `last`.
+    #[must_use]
+    pub const fn after(self, last: Cid) -> Self {
+        Page {
+            after: Some(last),
+            limit: self.limit,
+        }
+    }
+
+    /// Select this page from a store's entries, in any order.
+    ///
+    /// Only the `limit` smallest CIDs seen so far are kept, so a page costs
+    /// O(n log limit) rather than sorting every remaining entry.
+    #[cfg(feature = "std")]
+    fn select<'a, T: Clone + 'a>(
+        self,
+        entries: impl IntoIterator<Item = (&'a Cid, &'a T)>,
+    ) -> Matches<T> {
+        if self.limit == 0 {
+            return Vec::new();
+        }
+        let mut page: BTreeMap<&Cid, &T> = BTreeMap::new();
+        for (cid, dlg) in entries {
+            if self.after.is_some_and(|after| *cid <= after) {
+                continue;
+            }
+            if page.len() == self.limit {
+                match page.last_key_value() {
+                    Some((last, _)) if cid < *last => {
+                        page.pop_last();
+                    }
+                    _ => continue,
+                }
+            }
+            page.insert(cid, dlg);
+        }
+        page.into_iter()
+            .map(|(cid, dlg)| (*cid, dlg.clone()))
+            .collect()
+    }
+
+    /// Select this page from a map ordered by CID, starting at `after`.
+    fn select_range<T: Clone>(self, map: &BTreeMap<Cid, T>) -> Matches<T> {
+        let start = self.after.map_or(Bound::Unbounded, Bound::Excluded);
+        map.range((start, Bound::Unbounded))
+            .take(self.limit)
+            .map(|(cid, dlg)| (*cid, dlg.clone()))
+            .collect()
+    }
+}
+
+/// Find the entry that stores `cid`'s delegation under a key with a different hash.
+///
+/// Stores are keyed by whatever CID a delegation was inserted with (SHA2-256
+/// by default). A CID over the same bytes with another multihash (e.g. BLAKE3)
+/// names the same delegation, so after an exact lookup misses the stores fall
+/// back to rehashing their entries with the requested CID's hash. Entries
+/// whose key already uses that hash cannot match (the exact lookup would have
+/// found them) and are skipped without hashing, so a miss in a store keyed
+/// uniformly by the requested hash never rehashes anything.
+fn find_equivalent<'a, D: Did + 'a, T: Borrow<Delegation<D>> + 'a>(
+    entries: impl IntoIterator<Item = (&'a Cid, &'a T)>,
+    cid: &Cid,
+) -> Option<(&'a Cid, &'a T)> {
+    let code = cid.hash().code();
+    let builder = CidBuilder::for_cid(cid)?;
+    entries
+        .into_iter()
+        .filter(|(key, _)| key.hash().code() != code)
+        .find(|(_, dlg)| Borrow::<Delegation<D>>::borrow(*dlg).to_cid_with(&builder) == *cid)
+}
+
 /// Inserts a delegation and returns its CID.
 ///
 /// # Errors
@@ -59,6 +253,30 @@ pub async fn insert<
     Ok(cid)
 }
 
+/// Inserts several delegations and returns their CIDs, in the same order.
+///
+/// # Errors
+///
+/// If insertion fails, an error defined by the `impl DelegationStore` is returned
+/// (the `S::InsertError` associated type).
+pub async fn insert_many<
+    K: FutureForm,
+    D: Did,
+    T: Borrow<Delegation<D>>,
+    S: ListableStore<K, D, T>,
+>(
+    store: &S,
+    delegations: Vec<T>,
+) -> Result<Vec<Cid>, S::InsertError> {
+    let entries: Vec<(Cid, T)> = delegations
+        .into_iter()
+        .map(|dlg| (dlg.borrow().to_cid(), dlg))
+        .collect();
+    let cids = entries.iter().map(|(cid, _)| *cid).collect();
+    store.insert_many_by_cid(entries).await?;
+    Ok(cids)
+}
+
 // ---------------------------------------------------------------------------
 // no_std: Rc<RefCell<BTreeMap>> store
 // ---------------------------------------------------------------------------
@@ -88,7 +306,10 @@ impl<D: Did> DelegationStore<Local, D, Rc<Delegation<D>>>
             let store = RefCell::borrow(self);
             let mut dlgs = Vec::new();
             for c in cid {
-                if let Some(dlg) = store.get(c) {
+                if let Some(dlg) = store
+                    .get(c)
+                    .or_else(|| find_equivalent(store.iter(), c).map(|(_, dlg)| dlg))
+                {
                     dlgs.push(dlg.clone());
                 } else {
                     return Err(Missing(*c));
@@ -99,6 +320,91 @@ impl<D: Did> DelegationStore<Local, D, Rc<Delegation<D>>>
     }
 }
 
+impl<D: Did> ListableStore<Local, D, Rc<Delegation<D>>>
+    for Rc<RefCell<BTreeMap<Cid, Rc<Delegation<D>>>>>
+{
+    type ListError = Infallible;
+    type RemoveError = Infallible;
+
+    fn find_all<'a>(
+        &'a self,
+        cid: &'a [Cid],
+    ) -> <Local as FutureForm>::Future<'a, Result<Found<Rc<Delegation<D>>>, Self::GetError>> {
+        Local::from_future(async move {
+            let store = RefCell::borrow(self);
+            Ok(cid
+                .iter()
+                .map(|c| {
+                    store
+                        .get_key_value(c)
+                        .or_else(|| find_equivalent(store.iter(), c))
+                        .map(|(key, dlg)| (*key, dlg.clone()))
+                })
+                .collect())
+        })
+    }
+
+    fn insert_many_by_cid(
+        &self,
+        delegations: Vec<(Cid, Rc<Delegation<D>>)>,
+    ) -> <Local as FutureForm>::Future<'_, Result<(), Self::InsertError>> {
+        Local::from_future(async move {
+            self.borrow_mut().extend(delegations);
+            Ok(())
+        })
+    }
+
+    fn remove(
+        &self,
+        cid: Cid,
+    ) -> <Local as FutureForm>::Future<'_, Result<Option<Rc<Delegation<D>>>, Self::RemoveError>>
+    {
+        Local::from_future(async move {
+            let mut store = self.borrow_mut();
+            if let Some(dlg) = store.remove(&cid) {
+                return Ok(Some(dlg));
+            }
+            let key = find_equivalent(store.iter(), &cid).map(|(key, _)| *key);
+            Ok(key.and_then(|key| store.remove(&key)))
+        })
+    }
+
+    fn contains(
+        &self,
+        cid: Cid,
+    ) -> <Local as FutureForm>::Future<'_, Result<bool, Self::ListError>> {
+        Local::from_future(async move {
+            let store = RefCell::borrow(self);
+            Ok(store.contains_key(&cid) || find_equivalent(store.iter(), &cid).is_some())
+        })
+    }
+
+    fn count(&self) -> <Local as FutureForm>::Future<'_, Result<usize, Self::ListError>> {
+        Local::from_future(async move { Ok(RefCell::borrow(self).len()) })
+    }
+
+    fn list_cids(
+        &self,
+        page: Page,
+    ) -> <Local as FutureForm>::Future<'_, Result<Vec<Cid>, Self::ListError>> {
+        Local::from_future(async move {
+            Ok(page
+                .select_range(&RefCell::borrow(self))
+                .into_iter()
+                .map(|(cid, _)| cid)
+                .collect())
+        })
+    }
+
+    fn list(
+        &self,
+        page: Page,
+    ) -> <Local as FutureForm>::Future<'_, Result<Matches<Rc<Delegation<D>>>, Self::ListError>>
+    {
+        Local::from_future(async move { Ok(page.select_range(&RefCell::borrow(self))) })
+    }
+}
+
 // ---------------------------------------------------------------------------
 // std: Rc<RefCell<HashMap>> store
 // ---------------------------------------------------------------------------
@@ -129,7 +435,10 @@ impl<D: Did, H: BuildHasher> DelegationStore<Local, D, Rc<Delegation<D>>>
             let store = RefCell::borrow(self);
             let mut dlgs = Vec::new();
             for c in cid {
-                if let Some(dlg) = store.get(c) {
+                if let Some(dlg) = store
+                    .get(c)
+                    .or_else(|| find_equivalent(store.iter(), c).map(|(_, dlg)| dlg))
+                {
                     dlgs.push(dlg.clone());
                 } else {
                     return Err(Missing(*c));
@@ -140,6 +449,92 @@ impl<D: Did, H: BuildHasher> DelegationStore<Local, D, Rc<Delegation<D>>>
     }
 }
 
+#[cfg(feature = "std")]
+impl<D: Did, H: BuildHasher> ListableStore<Local, D, Rc<Delegation<D>>>
+    for Rc<RefCell<HashMap<Cid, Rc<Delegation<D>>, H>>>
+{
+    type ListError = Infallible;
+    type RemoveError = Infallible;
+
+    fn find_all<'a>(
+        &'a self,
+        cid: &'a [Cid],
+    ) -> <Local as FutureForm>::Future<'a, Result<Found<Rc<Delegation<D>>>, Self::GetError>> {
+        Local::from_future(async move {
+            let store = RefCell::borrow(self);
+            Ok(cid
+                .iter()
+                .map(|c| {
+                    store
+                        .get_key_value(c)
+                        .or_else(|| find_equivalent(store.iter(), c))
+                        .map(|(key, dlg)| (*key, dlg.clone()))
+                })
+                .collect())
+        })
+    }
+
+    fn insert_many_by_cid(
+        &self,
+        delegations: Vec<(Cid, Rc<Delegation<D>>)>,
+    ) -> <Local as FutureForm>::Future<'_, Result<(), Self::InsertError>> {
+        Local::from_future(async move {
+            self.borrow_mut().extend(delegations);
+            Ok(())
+        })
+    }
+
+    fn remove(
+        &self,
+        cid: Cid,
+    ) -> <Local as FutureForm>::Future<'_, Result<Option<Rc<Delegation<D>>>, Self::RemoveError>>
+    {
+        Local::from_future(async move {
+            let mut store = self.borrow_mut();
+            if let Some(dlg) = store.remove(&cid) {
+                return Ok(Some(dlg));
+            }
+            let key = find_equivalent(store.iter(), &cid).map(|(key, _)| *key);
+            Ok(key.and_then(|key| store.remove(&key)))
+        })
+    }
+
+    fn contains(
+        &self,
+        cid: Cid,
+    ) -> <Local as FutureForm>::Future<'_, Result<bool, Self::ListError>> {
+        Local::from_future(async move {
+            let store = RefCell::borrow(self);
+            Ok(store.contains_key(&cid) || find_equivalent(store.iter(), &cid).is_some())
+        })
+    }
+
+    fn count(&self) -> <Local as FutureForm>::Future<'_, Result<usize, Self::ListError>> {
+        Local::from_future(async move { Ok(RefCell::borrow(self).len()) })
+    }
+
+    fn list_cids(
+        &self,
+        page: Page,
+    ) -> <Local as FutureForm>::Future<'_, Result<Vec<Cid>, Self::ListError>> {
+        Local::from_future(async move {
+            Ok(page
+                .select(RefCell::borrow(self).iter())
+                .into_iter()
+                .map(|(cid, _)| cid)
+                .collect())
+        })
+    }
+
+    fn list(
+        &self,
+        page: Page,
+    ) -> <Local as FutureForm>::Future<'_, Result<Matches<Rc<Delegation<D>>>, Self::ListError>>
+    {
+        Local::from_future(async move { Ok(page.select(RefCell::borrow(self).iter())) })
+    }
+}
+
 // ---------------------------------------------------------------------------
 // std: Arc<Mutex<HashMap>> store (Send + !Send variants)
 // ---------------------------------------------------------------------------
@@ -179,7 +574,10 @@ impl<K: FutureForm, D: Did, H: BuildHasher> DelegationStore<K, D, Arc<Delegation
             let locked = self.lock().map_err(|_| StorePoisoned)?;
             let mut dlgs = Vec::new();
             for c in cid {
-                if let Some(dlg) = locked.get(c) {
+                if let Some(dlg) = locked
+                    .get(c)
+                    .or_else(|| find_equivalent(locked.iter(), c).map(|(_, dlg)| dlg))
+                {
                     dlgs.push(dlg.clone());
                 } else {
                     return Err(Missing(*c).into());
@@ -190,6 +588,97 @@ impl<K: FutureForm, D: Did, H: BuildHasher> DelegationStore<K, D, Arc<Delegation
     }
 }
 
+#[cfg(feature = "std")]
+#[future_form(
+    Local,
+    Sendable where
+        D: Send + Sync,
+        H: Send,
+        <D as Did>::VarsigConfig: Send + Sync,
+        <<D as Did>::VarsigConfig as Verify>::Signature: Send + Sync
+)]
+impl<K: FutureForm, D: Did, H: BuildHasher> ListableStore<K, D, Arc<Delegation<D>>>
+    for Arc<Mutex<HashMap<Cid, Arc<Delegation<D>>, H>>>
+{
+    type ListError = StorePoisoned;
+    type RemoveError = StorePoisoned;
+
+    fn find_all<'a>(
+        &'a self,
+        cid: &'a [Cid],
+    ) -> K::Future<'a, Result<Found<Arc<Delegation<D>>>, Self::GetError>> {
+        K::from_future(async move {
+            let locked = self.lock().map_err(|_| StorePoisoned)?;
+            Ok(cid
+                .iter()
+                .map(|c| {
+                    locked
+                        .get_key_value(c)
+                        .or_else(|| find_equivalent(locked.iter(), c))
+                        .map(|(key, dlg)| (*key, dlg.clone()))
+                })
+                .collect())
+        })
+    }
+
+    fn insert_many_by_cid(
+        &self,
+        delegations: Vec<(Cid, Arc<Delegation<D>>)>,
+    ) -> K::Future<'_, Result<(), Self::InsertError>> {
+        K::from_future(async move {
+            let mut locked = self.lock().map_err(|_| StorePoisoned)?;
+            locked.extend(delegations);
+            Ok(())
+        })
+    }
+
+    fn remove(
+        &self,
+        cid: Cid,
+    ) -> K::Future<'_, Result<Option<Arc<Delegation<D>>>, Self::RemoveError>> {
+        K::from_future(async move {
+            let mut locked = self.lock().map_err(|_| StorePoisoned)?;
+            if let Some(dlg) = locked.remove(&cid) {
+                return Ok(Some(dlg));
+            }
+            let key = find_equivalent(locked.iter(), &cid).map(|(key, _)| *key);
+            Ok(key.and_then(|key| locked.remove(&key)))
+        })
+    }
+
+    fn contains(&self, cid: Cid) -> K::Future<'_, Result<bool, Self::ListError>> {
+        K::from_future(async move {
+            let locked = self.lock().map_err(|_| StorePoisoned)?;
+            Ok(locked.contains_key(&cid) || find_equivalent(locked.iter(), &cid).is_some())
+        })
+    }
+
+    fn count(&self) -> K::Future<'_, Result<usize, Self::ListError>> {
+        K::from_future(async move { Ok(self.lock().map_err(|_| StorePoisoned)?.len()) })
+    }
+
+    fn list_cids(&self, page: Page) -> K::Future<'_, Result<Vec<Cid>, Self::ListError>> {
+        K::from_future(async move {
+            let locked = self.lock().map_err(|_| StorePoisoned)?;
+            Ok(page
+                .select(locked.iter())
+                .into_iter()
+                .map(|(cid, _)| cid)
+                .collect())
+        })
+    }
+
+    fn list(
+        &self,
+        page: Page,
+    ) -> K::Future<'_, Result<Matches<Arc<Delegation<D>>>, Self::ListError>> {
+        K::from_future(async move {
+            let locked = self.lock().map_err(|_| StorePoisoned)?;
+            Ok(page.select(locked.iter()))
+        })
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Error types
 // ---------------------------------------------------------------------------
@@ -217,3 +706,160 @@ pub enum LockedStoreGetError {
     #[error(transparent)]
     StorePoisoned(#[from] StorePoisoned),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        delegation::subject::DelegatedSubject,
+        did::Ed25519Did,
+        test_support::{block_on, delegation_with, signer},
+    };
+    use testresult::TestResult;
+    use varsig::hash::Sha2_512;
+
+    /// A self-issued delegation; distinct seeds give distinct delegations.
+    fn seeded(seed: u8) -> TestResult<Delegation<Ed25519Did>> {
+        delegation_with(
+            seed,
+            seed,
+            DelegatedSubject::Specific(*signer(seed).did()),
+            "/crud/read",
+            None,
+        )
+    }
+
+    #[test]
+    fn test_get_by_equivalent_cid() -> TestResult {
+        let store: Rc<RefCell<BTreeMap<Cid, Rc<Delegation<Ed25519Did>>>>> = Rc::default();
+        let dlg = seeded(1)?;
+        let other = seeded(2)?;
+        block_on(insert(&store, Rc::new(other)))?;
+        let cid = block_on(insert(&store, Rc::new(dlg.clone())))?;
+
+        let sha512 = dlg.to_cid_with(&CidBuilder::new::<Sha2_512>());
+        assert_ne!(cid, sha512);
+
+        let found = block_on(store.get_all(&[sha512]))?;
+        assert_eq!(found.first().map(|d| d.to_cid()), Some(cid));
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_equivalent_skips_keys_with_requested_hash() -> TestResult {
+        let dlg = seeded(1)?;
+        let sha512 = dlg.to_cid_with(&CidBuilder::new::<Sha2_512>());
+        let unrelated = seeded(2)?.to_cid();
+
+        // Only entries keyed with another hash are rehashed
+        let entries = BTreeMap::from([(unrelated, dlg.clone())]);
+        assert!(find_equivalent(&entries, &dlg.to_cid()).is_none());
+        assert_eq!(
+            find_equivalent(&entries, &sha512).map(|(key, _)| *key),
+            Some(unrelated)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_remove_contains_count() -> TestResult {
+        let store: Rc<RefCell<BTreeMap<Cid, Rc<Delegation<Ed25519Did>>>>> = Rc::default();
+        let dlg = seeded(1)?;
+        let cids = block_on(insert_many(
+            &store,
+            vec![Rc::new(dlg.clone()), Rc::new(seeded(2)?)],
+        ))?;
+        assert_eq!(cids.first(), Some(&dlg.to_cid()));
+        assert_eq!(block_on(store.count())?, 2);
+
+        let sha512 = dlg.to_cid_with(&CidBuilder::new::<Sha2_512>());
+        assert!(block_on(store.contains(sha512))?);
+
+        let removed = block_on(store.remove(sha512))?;
+        assert_eq!(removed.map(|d| d.to_cid()), Some(dlg.to_cid()));
+        assert!(!block_on(store.contains(dlg.to_cid()))?);
+        assert!(block_on(store.remove(dlg.to_cid()))?.is_none());
+        assert_eq!(block_on(store.count())?, 1);
+        Ok(())
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_pagination() -> TestResult {
+        let store: Rc<RefCell<HashMap<Cid, Rc<Delegation<Ed25519Did>>>>> = Rc::default();
+        let dlgs = (1..=5)
+            .map(|seed| seeded(seed).map(Rc::new))
+            .collect::<Result<Vec<_>, _>>()?;
+        let ordered: Rc<RefCell<BTreeMap<Cid, Rc<Delegation<Ed25519Did>>>>> = Rc::default();
+        block_on(insert_many(&ordered, dlgs.clone()))?;
+        let mut expected = block_on(insert_many(&store, dlgs))?;
+        expected.sort();
+
+        let mut seen = Vec::new();
+        let mut page = Page::first(2);
+        loop {
+            let cids = block_on(store.list_cids(page))?;
+            let entries = block_on(store.list(page))?;
+            assert_eq!(entries.iter().map(|(c, _)| *c).collect::<Vec<_>>(), cids);
+            assert!(cids.len() <= 2);
+            match cids.last() {
+                Some(last) => page = page.after(*last),
+                None => break,
+            }
+            seen.extend(cids);
+        }
+        assert_eq!(seen, expected);
+        assert_eq!(block_on(store.list_cids(Page::all()))?, expected);
+
+        let second = *expected.get(1).ok_or("too few entries")?;
+        for page in [Page::first(0), Page::first(3), Page::first(2).after(second)] {
+            assert_eq!(
+                block_on(ordered.list_cids(page))?,
+                block_on(store.list_cids(page))?
+            );
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_locked_store_get_by_equivalent_cid() -> TestResult {
+        let store: Arc<Mutex<HashMap<Cid, Arc<Delegation<Ed25519Did>>>>> = Arc::default();
+        let dlg = seeded(1)?;
+        let cid = block_on(insert::<Sendable, _, _, _>(&store, Arc::new(dlg.clone())))?;
+
+        let sha512 = dlg.to_cid_with(&CidBuilder::new::<Sha2_512>());
+        let found = block_on(DelegationStore::<Sendable, _, _>::get_all(
+            &store,
+            &[sha512],
+        ))?;
+        assert_eq!(found.first().map(|d| d.to_cid()), Some(cid));
+
+        let missing = seeded(3)?.to_cid_with(&CidBuilder::new::<Sha2_512>());
+        assert!(matches!(
+            block_on(DelegationStore::<Sendable, _, _>::get_all(
+                &store,
+                &[missing]
+            )),
+            Err(LockedStoreGetError::Missing(Missing(c))) if c == missing
+        ));
+        Ok(())
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_locked_store_bulk_and_remove() -> TestResult {
+        let store: Arc<Mutex<HashMap<Cid, Arc<Delegation<Ed25519Did>>>>> = Arc::default();
+        let cids = block_on(insert_many::<Sendable, _, _, _>(
+            &store,
+            vec![Arc::new(seeded(1)?), Arc::new(seeded(2)?)],
+        ))?;
+        assert_eq!(block_on(ListableStore::<Sendable, _, _>::count(&store))?, 2);
+
+        for cid in &cids {
+            assert!(block_on(ListableStore::<Sendable, _, _>::remove(&store, *cid))?.is_some());
+        }
+        assert!(block_on(ListableStore::<Sendable, _, _>::list(&store, Page::all()))?.is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,946 @@
+//! Encryption at rest for delegation stores.
+//!
+//! Delegations reveal who holds which capabilities. [`EncryptedStore`] seals
+//! each delegation's DAG-CBOR bytes with XChaCha20-Poly1305 before handing
+//! them to an inner [`BlobStore`], so the backing storage only ever sees
+//! ciphertext. CIDs are still computed over the plaintext, and are bound to
+//! their ciphertext as associated data: a sealed delegation moved to another
+//! CID fails to decrypt.
+//!
+//! Every sealed delegation records the id of the key that sealed it. Keys are
+//! rotated with [`EncryptedStore::rotate`], which seals new writes under the
+//! new key while old ones stay readable, and
+//! [`EncryptedStore::reencrypt`], which reseals everything under the current
+//! key so the old ones can be [retired](EncryptedStore::retire).
+//!
+//! CIDs, sizes, and the number of delegations are not hidden.
+
+pub mod fs;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+
+pub use fs::FsBlobStore;
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteBlobStore;
+
+use alloc::{
+    collections::{BTreeMap, TryReserveError},
+    sync::Arc,
+    vec::Vec,
+};
+use core::{convert::Infallible, error::Error, fmt::Debug, marker::PhantomData, ops::Bound};
+use std::sync::{Mutex, MutexGuard, PoisonError, RwLock};
+
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    XChaCha20Poly1305, XNonce,
+};
+use future_form::{future_form, FutureForm, Local, Sendable};
+use ipld_core::cid::Cid;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use varsig::verify::Verify;
+use zeroize::Zeroizing;
+
+use crate::{cid::CidBuilder, delegation::Delegation, did::Did};
+
+use super::{DelegationStore, Found, ListableStore, Matches, Missing, Page, StorePoisoned};
+
+/// The number of CIDs listed per page while re-encrypting.
+const REENCRYPT_BATCH_SIZE: usize = 256;
+
+/// Opaque bytes stored by CID, e.g. a directory, a database table, or an
+/// object store bucket.
+///
+/// Implemented in memory, by [`FsBlobStore`], and with the `sqlite` feature
+/// by `SqliteBlobStore`.
+pub trait BlobStore<K: FutureForm> {
+    /// Error type for all operations.
+    type Error: Error;
+
+    /// The bytes stored under exactly `cid`.
+    fn get(&self, cid: Cid) -> K::Future<'_, Result<Option<Vec<u8>>, Self::Error>>;
+
+    /// Store `bytes` under `cid`, replacing what was there.
+    fn put(&self, cid: Cid, bytes: Vec<u8>) -> K::Future<'_, Result<(), Self::Error>>;
+
+    /// Store `bytes` under `cid` only if it still holds `current`, returning
+    /// whether it did. Checking and writing must be atomic.
+    fn replace(
+        &self,
+        cid: Cid,
+        current: Vec<u8>,
+        bytes: Vec<u8>,
+    ) -> K::Future<'_, Result<bool, Self::Error>>;
+
+    /// Remove the bytes stored under `cid`, returning them if present.
+    fn remove(&self, cid: Cid) -> K::Future<'_, Result<Option<Vec<u8>>, Self::Error>>;
+
+    /// The number of stored blobs.
+    fn count(&self) -> K::Future<'_, Result<usize, Self::Error>>;
+
+    /// Lists one page of stored CIDs, in ascending order by [`Cid`]'s `Ord`.
+    fn list_cids(&self, page: Page) -> K::Future<'_, Result<Vec<Cid>, Self::Error>>;
+}
+
+#[future_form(Local, Sendable)]
+impl<K: FutureForm> BlobStore<K> for Arc<Mutex<BTreeMap<Cid, Vec<u8>>>> {
+    type Error = StorePoisoned;
+
+    fn get(&self, cid: Cid) -> K::Future<'_, Result<Option<Vec<u8>>, Self::Error>> {
+        K::from_future(async move { Ok(lock(self)?.get(&cid).cloned()) })
+    }
+
+    fn put(&self, cid: Cid, bytes: Vec<u8>) -> K::Future<'_, Result<(), Self::Error>> {
+        K::from_future(async move {
+            lock(self)?.insert(cid, bytes);
+            Ok(())
+        })
+    }
+
+    fn replace(
+        &self,
+        cid: Cid,
+        current: Vec<u8>,
+        bytes: Vec<u8>,
+    ) -> K::Future<'_, Result<bool, Self::Error>> {
+        K::from_future(async move {
+            let mut locked = lock(self)?;
+            match locked.get_mut(&cid) {
+                Some(held) if *held == current => {
+                    *held = bytes;
+                    Ok(true)
+                }
+                _ => Ok(false),
+            }
+        })
+    }
+
+    fn remove(&self, cid: Cid) -> K::Future<'_, Result<Option<Vec<u8>>, Self::Error>> {
+        K::from_future(async move { Ok(lock(self)?.remove(&cid)) })
+    }
+
+    fn count(&self) -> K::Future<'_, Result<usize, Self::Error>> {
+        K::from_future(async move { Ok(lock(self)?.len()) })
+    }
+
+    fn list_cids(&self, page: Page) -> K::Future<'_, Result<Vec<Cid>, Self::Error>> {
+        K::from_future(async move {
+            let locked = lock(self)?;
+            let keys: Vec<Cid> = match page.after {
+                Some(after) => locked
+                    .range((Bound::Excluded(after), Bound::Unbounded))
+                    .map(|(cid, _)| *cid)
+                    .take(page.limit)
+                    .collect(),
+                None => locked.keys().copied().take(page.limit).collect(),
+            };
+            Ok(keys)
+        })
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> Result<MutexGuard<'_, T>, StorePoisoned> {
+    mutex.lock().map_err(|_| StorePoisoned)
+}
+
+/// A 256-bit symmetric key with an id recorded alongside everything it seals.
+#[derive(Clone)]
+pub struct EncryptionKey {
+    id: u32,
+    key: Zeroizing<[u8; 32]>,
+}
+
+impl EncryptionKey {
+    /// The key `key`, identified by `id`.
+    #[must_use]
+    pub fn new(id: u32, key: [u8; 32]) -> Self {
+        EncryptionKey {
+            id,
+            key: Zeroizing::new(key),
+        }
+    }
+
+    /// A random key identified by `id`.
+    ///
+    /// # Errors
+    ///
+    /// If random bytes cannot be generated.
+    pub fn generate(id: u32) -> Result<Self, getrandom::Error> {
+        let mut key = Zeroizing::new([0; 32]);
+        getrandom::getrandom(key.as_mut())?;
+        Ok(EncryptionKey { id, key })
+    }
+
+    /// The key's id.
+    #[must_use]
+    pub const fn id(&self) -> u32 {
+        self.id
+    }
+}
+
+impl Debug for EncryptionKey {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("EncryptionKey")
+            .field("id", &self.id)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The keys an [`EncryptedStore`] can decrypt with, and the one it seals with.
+#[derive(Debug)]
+struct Keyring {
+    current: u32,
+    keys: BTreeMap<u32, EncryptionKey>,
+}
+
+impl Keyring {
+    /// Add `key`, unless its id is taken by a different key.
+    fn add(&mut self, key: EncryptionKey) -> Result<(), KeyIdConflict> {
+        match self.keys.get(&key.id) {
+            Some(held) if held.key != key.key => Err(KeyIdConflict(key.id)),
+            Some(_) => Ok(()),
+            None => {
+                self.keys.insert(key.id, key);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// A delegation as handed to the inner store.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Sealed {
+    #[serde(rename = "k")]
+    key_id: u32,
+
+    #[serde(with = "serde_bytes")]
+    nonce: Vec<u8>,
+
+    #[serde(rename = "ct", with = "serde_bytes")]
+    ciphertext: Vec<u8>,
+}
+
+/// A [`DelegationStore`] that encrypts delegations before they reach an inner
+/// [`BlobStore`].
+///
+/// Lookups only match the exact CID a delegation was inserted under: finding
+/// one by an equivalent CID with another hash would mean decrypting every
+/// entry, and a miss would fail once any of their keys was retired.
+///
+/// ```
+/// # use std::{collections::BTreeMap, sync::{Arc, Mutex}};
+/// # use ipld_core::cid::Cid;
+/// # use ucan::{delegation::store::encrypted::{EncryptedStore, EncryptionKey}, did::Ed25519Did};
+/// # fn main() -> Result<(), getrandom::Error> {
+/// let store: EncryptedStore<Arc<Mutex<BTreeMap<Cid, Vec<u8>>>>, Ed25519Did> =
+///     EncryptedStore::new(Arc::default(), EncryptionKey::generate(1)?);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct EncryptedStore<S, D: Did> {
+    inner: S,
+    keyring: RwLock<Keyring>,
+    _did: PhantomData<fn() -> D>,
+}
+
+impl<S, D: Did> EncryptedStore<S, D>
+where
+    <D::VarsigConfig as Verify>::Signature: for<'de> Deserialize<'de>,
+{
+    /// Seal delegations stored in `inner` with `key`.
+    #[must_use]
+    pub fn new(inner: S, key: EncryptionKey) -> Self {
+        EncryptedStore {
+            inner,
+            keyring: RwLock::new(Keyring {
+                current: key.id,
+                keys: BTreeMap::from([(key.id, key)]),
+            }),
+            _did: PhantomData,
+        }
+    }
+
+    /// Also decrypt delegations sealed with `key`, e.g. one that was
+    /// rotated out before a restart.
+    ///
+    /// # Errors
+    ///
+    /// If the store already holds a different key with the same id.
+    pub fn with_key(self, key: EncryptionKey) -> Result<Self, KeyIdConflict> {
+        self.keyring
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .add(key)?;
+        Ok(self)
+    }
+
+    /// Seal new writes with `key`, keeping the previous keys for reading.
+    ///
+    /// Follow with [`EncryptedStore::reencrypt`] to reseal existing delegations.
+    ///
+    /// # Errors
+    ///
+    /// If the store already holds a different key with the same id; the
+    /// current key is then unchanged. Entries only record a key's id, so
+    /// reusing one would make everything sealed under it unreadable.
+    pub fn rotate(&self, key: EncryptionKey) -> Result<(), KeyIdConflict> {
+        let mut keyring = self.keyring.write().unwrap_or_else(PoisonError::into_inner);
+        let id = key.id;
+        keyring.add(key)?;
+        keyring.current = id;
+        Ok(())
+    }
+
+    /// Forget the key with `id`, returning whether it was dropped.
+    ///
+    /// The current key is never dropped. Delegations still sealed with a
+    /// retired key can no longer be read.
+    pub fn retire(&self, id: u32) -> bool {
+        let mut keyring = self.keyring.write().unwrap_or_else(PoisonError::into_inner);
+        id != keyring.current && keyring.keys.remove(&id).is_some()
+    }
+
+    /// The id of the key new writes are sealed with.
+    #[must_use]
+    pub fn current_key_id(&self) -> u32 {
+        self.keyring
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .current
+    }
+
+    /// The store holding the ciphertext.
+    #[must_use]
+    pub const fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    /// Reseal every delegation that is not sealed with the current key,
+    /// returning how many were resealed.
+    ///
+    /// Each delegation is written back with [`BlobStore::replace`], so one
+    /// removed or rewritten since it was read is left as it is, and this can
+    /// run while the store is in use.
+    ///
+    /// # Errors
+    ///
+    /// If the inner store fails, or a delegation is sealed with an unknown
+    /// key or fails to decrypt. Delegations resealed before the failure stay
+    /// resealed, so it is safe to run again.
+    pub async fn reencrypt<K: FutureForm>(&self) -> Result<usize, EncryptedStoreError<S::Error>>
+    where
+        S: BlobStore<K>,
+    {
+        let mut resealed = 0;
+        let mut page = Page::first(REENCRYPT_BATCH_SIZE);
+        loop {
+            let cids = self
+                .inner
+                .list_cids(page)
+                .await
+                .map_err(EncryptedStoreError::Inner)?;
+            let Some(last) = cids.last() else {
+                break;
+            };
+            page = page.after(*last);
+
+            let current = self.current_key_id();
+            for cid in &cids {
+                let Some(bytes) = self
+                    .inner
+                    .get(*cid)
+                    .await
+                    .map_err(EncryptedStoreError::Inner)?
+                else {
+                    continue;
+                };
+                let sealed: Sealed = serde_ipld_dagcbor::from_slice(&bytes)?;
+                if sealed.key_id == current {
+                    continue;
+                }
+                let plaintext = self.decrypt(cid, &sealed)?;
+                if self
+                    .inner
+                    .replace(*cid, bytes, self.seal(cid, &plaintext)?)
+                    .await
+                    .map_err(EncryptedStoreError::Inner)?
+                {
+                    resealed += 1;
+                }
+            }
+
+            if cids.len() < REENCRYPT_BATCH_SIZE {
+                break;
+            }
+        }
+        Ok(resealed)
+    }
+
+    /// Encrypt `plaintext` under the current key, bound to `cid`.
+    fn seal<E>(&self, cid: &Cid, plaintext: &[u8]) -> Result<Vec<u8>, EncryptedStoreError<E>> {
+        let keyring = self.keyring.read().unwrap_or_else(PoisonError::into_inner);
+        let key = keyring
+            .keys
+            .get(&keyring.current)
+            .ok_or(EncryptedStoreError::UnknownKey(keyring.current))?;
+
+        let mut nonce = [0; 24];
+        getrandom::getrandom(&mut nonce)?;
+        let ciphertext = XChaCha20Poly1305::new(key.key.as_ref().into())
+            .encrypt(
+                &XNonce::from(nonce),
+                Payload {
+                    msg: plaintext,
+                    aad: &cid.to_bytes(),
+                },
+            )
+            .map_err(|_| EncryptedStoreError::Encrypt)?;
+
+        Ok(serde_ipld_dagcbor::to_vec(&Sealed {
+            key_id: key.id,
+            nonce: nonce.to_vec(),
+            ciphertext,
+        })?)
+    }
+
+    fn decrypt<E>(
+        &self,
+        cid: &Cid,
+        sealed: &Sealed,
+    ) -> Result<Zeroizing<Vec<u8>>, EncryptedStoreError<E>> {
+        let keyring = self.keyring.read().unwrap_or_else(PoisonError::into_inner);
+        let key = keyring
+            .keys
+            .get(&sealed.key_id)
+            .ok_or(EncryptedStoreError::UnknownKey(sealed.key_id))?;
+        let nonce = <[u8; 24]>::try_from(sealed.nonce.as_slice())
+            .map_err(|_| EncryptedStoreError::Decrypt(*cid))?;
+
+        XChaCha20Poly1305::new(key.key.as_ref().into())
+            .decrypt(
+                &XNonce::from(nonce),
+                Payload {
+                    msg: &sealed.ciphertext,
+                    aad: &cid.to_bytes(),
+                },
+            )
+            .map(Zeroizing::new)
+            .map_err(|_| EncryptedStoreError::Decrypt(*cid))
+    }
+
+    /// Decrypt and decode the delegation sealed in `bytes` under `cid`.
+    fn open<E>(&self, cid: &Cid, bytes: &[u8]) -> Result<Delegation<D>, EncryptedStoreError<E>> {
+        let sealed: Sealed = serde_ipld_dagcbor::from_slice(bytes)?;
+        let plaintext = self.decrypt(cid, &sealed)?;
+        Ok(serde_ipld_dagcbor::from_slice(&plaintext)?)
+    }
+
+    /// Seal `delegation` for storage under `cid`, which must address it.
+    fn seal_delegation<E>(
+        &self,
+        cid: &Cid,
+        delegation: &Delegation<D>,
+    ) -> Result<Vec<u8>, EncryptedStoreError<E>> {
+        let plaintext = Zeroizing::new(serde_ipld_dagcbor::to_vec(delegation)?);
+        let builder = CidBuilder::for_cid(cid).ok_or(EncryptedStoreError::UnsupportedHash(*cid))?;
+        if builder.build_from_dagcbor(&plaintext) != *cid {
+            return Err(EncryptedStoreError::CidMismatch(*cid));
+        }
+        self.seal(cid, &plaintext)
+    }
+
+    /// The delegation stored under exactly `cid`.
+    async fn find<K: FutureForm>(
+        &self,
+        cid: &Cid,
+    ) -> Result<Option<Delegation<D>>, EncryptedStoreError<S::Error>>
+    where
+        S: BlobStore<K>,
+    {
+        self.inner
+            .get(*cid)
+            .await
+            .map_err(EncryptedStoreError::Inner)?
+            .map(|bytes| self.open(cid, &bytes))
+            .transpose()
+    }
+}
+
+#[future_form(
+    Local,
+    Sendable where
+        S: Sync,
+        D: Send + Sync,
+        <D as Did>::VarsigConfig: Send + Sync,
+        <<D as Did>::VarsigConfig as Verify>::Signature: Send + Sync,
+        <S as BlobStore<Sendable>>::Error: Send
+)]
+impl<K: FutureForm, D: Did + 'static, S: BlobStore<K>> DelegationStore<K, D, Arc<Delegation<D>>>
+    for EncryptedStore<S, D>
+where
+    S::Error: 'static,
+    <D::VarsigConfig as Verify>::Signature: for<'de> Deserialize<'de>,
+{
+    type InsertError = EncryptedStoreError<S::Error>;
+    type GetError = EncryptedStoreError<S::Error>;
+
+    fn get_all<'a>(
+        &'a self,
+        cid: &'a [Cid],
+    ) -> K::Future<'a, Result<Vec<Arc<Delegation<D>>>, Self::GetError>> {
+        K::from_future(async move {
+            let mut dlgs = Vec::with_capacity(cid.len());
+            for c in cid {
+                let dlg = self.find(c).await?.ok_or(Missing(*c))?;
+                dlgs.push(Arc::new(dlg));
+            }
+            Ok(dlgs)
+        })
+    }
+
+    fn insert_by_cid(
+        &self,
+        cid: Cid,
+        delegation: Arc<Delegation<D>>,
+    ) -> K::Future<'_, Result<(), Self::InsertError>> {
+        K::from_future(async move {
+            let sealed = self.seal_delegation(&cid, &delegation)?;
+            self.inner
+                .put(cid, sealed)
+                .await
+                .map_err(EncryptedStoreError::Inner)
+        })
+    }
+}
+
+#[future_form(
+    Local,
+    Sendable where
+        S: Sync,
+        D: Send + Sync,
+        <D as Did>::VarsigConfig: Send + Sync,
+        <<D as Did>::VarsigConfig as Verify>::Signature: Send + Sync,
+        <S as BlobStore<Sendable>>::Error: Send
+)]
+impl<K: FutureForm, D: Did + 'static, S: BlobStore<K>> ListableStore<K, D, Arc<Delegation<D>>>
+    for EncryptedStore<S, D>
+where
+    S::Error: 'static,
+    <D::VarsigConfig as Verify>::Signature: for<'de> Deserialize<'de>,
+{
+    type ListError = EncryptedStoreError<S::Error>;
+    type RemoveError = EncryptedStoreError<S::Error>;
+
+    fn find_all<'a>(
+        &'a self,
+        cid: &'a [Cid],
+    ) -> K::Future<'a, Result<Found<Arc<Delegation<D>>>, Self::GetError>> {
+        K::from_future(async move {
+            let mut found = Vec::with_capacity(cid.len());
+            for c in cid {
+                found.push(self.find(c).await?.map(|dlg| (*c, Arc::new(dlg))));
+            }
+            Ok(found)
+        })
+    }
+
+    fn insert_many_by_cid(
+        &self,
+        delegations: Vec<(Cid, Arc<Delegation<D>>)>,
+    ) -> K::Future<'_, Result<(), Self::InsertError>> {
+        K::from_future(async move {
+            for (cid, delegation) in delegations {
+                let sealed = self.seal_delegation(&cid, &delegation)?;
+                self.inner
+                    .put(cid, sealed)
+                    .await
+                    .map_err(EncryptedStoreError::Inner)?;
+            }
+            Ok(())
+        })
+    }
+
+    fn remove(
+        &self,
+        cid: Cid,
+    ) -> K::Future<'_, Result<Option<Arc<Delegation<D>>>, Self::RemoveError>> {
+        K::from_future(async move {
+            let Some(dlg) = self.find(&cid).await? else {
+                return Ok(None);
+            };
+            self.inner
+                .remove(cid)
+                .await
+                .map_err(EncryptedStoreError::Inner)?;
+            Ok(Some(Arc::new(dlg)))
+        })
+    }
+
+    fn contains(&self, cid: Cid) -> K::Future<'_, Result<bool, Self::ListError>> {
+        K::from_future(async move {
+            Ok(self
+                .inner
+                .get(cid)
+                .await
+                .map_err(EncryptedStoreError::Inner)?
+                .is_some())
+        })
+    }
+
+    fn count(&self) -> K::Future<'_, Result<usize, Self::ListError>> {
+        K::from_future(async move { self.inner.count().await.map_err(EncryptedStoreError::Inner) })
+    }
+
+    fn list_cids(&self, page: Page) -> K::Future<'_, Result<Vec<Cid>, Self::ListError>> {
+        K::from_future(async move {
+            self.inner
+                .list_cids(page)
+                .await
+                .map_err(EncryptedStoreError::Inner)
+        })
+    }
+
+    fn list(
+        &self,
+        page: Page,
+    ) -> K::Future<'_, Result<Matches<Arc<Delegation<D>>>, Self::ListError>> {
+        K::from_future(async move {
+            let cids = self
+                .inner
+                .list_cids(page)
+                .await
+                .map_err(EncryptedStoreError::Inner)?;
+            let mut entries = Vec::with_capacity(cids.len());
+            for cid in cids {
+                if let Some(bytes) = self
+                    .inner
+                    .get(cid)
+                    .await
+                    .map_err(EncryptedStoreError::Inner)?
+                {
+                    entries.push((cid, Arc::new(self.open(&cid, &bytes)?)));
+                }
+            }
+            Ok(entries)
+        })
+    }
+}
+
+/// A key was added under an id an [`EncryptedStore`] already uses for a
+/// different key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+#[error("key id {0} is already used by a different key")]
+pub struct KeyIdConflict(pub u32);
+
+/// Errors from an [`EncryptedStore`].
+#[derive(Debug, Error)]
+pub enum EncryptedStoreError<E> {
+    /// The inner store failed.
+    #[error("inner store failed: {0}")]
+    Inner(#[source] E),
+
+    /// A delegation could not be encoded.
+    #[error("unable to encode delegation: {0}")]
+    Encode(#[from] serde_ipld_dagcbor::EncodeError<TryReserveError>),
+
+    /// A stored entry or decrypted delegation could not be decoded.
+    #[error("unable to decode delegation: {0}")]
+    Decode(#[from] serde_ipld_dagcbor::DecodeError<Infallible>),
+
+    /// Random bytes could not be generated.
+    #[error("unable to generate randomness: {0}")]
+    Random(#[from] getrandom::Error),
+
+    /// Encryption failed.
+    #[error("unable to encrypt delegation")]
+    Encrypt,
+
+    /// Wrong key, or the ciphertext was modified or moved to another CID.
+    #[error("unable to decrypt delegation {0}")]
+    Decrypt(Cid),
+
+    /// An entry is sealed with a key this store does not hold.
+    #[error("no encryption key with id {0}")]
+    UnknownKey(u32),
+
+    /// The CID does not address the delegation being inserted.
+    #[error("CID {0} does not match the delegation")]
+    CidMismatch(Cid),
+
+    /// The CID's hash is not compiled in.
+    #[error("unsupported hash in CID {0}")]
+    UnsupportedHash(Cid),
+
+    /// A requested delegation is not stored.
+    #[error(transparent)]
+    Missing(#[from] Missing),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        delegation::store::{insert, insert_many},
+        did::Ed25519Did,
+        test_support::{block_on, delegation, TempDir},
+    };
+    use testresult::TestResult;
+    use varsig::hash::Sha2_512;
+
+    type Blobs = Arc<Mutex<BTreeMap<Cid, Vec<u8>>>>;
+    type Store = EncryptedStore<Blobs, Ed25519Did>;
+
+    fn sealed_key_ids(blobs: &Blobs) -> TestResult<Vec<u32>> {
+        let locked = blobs.lock().map_err(|_| "poisoned")?;
+        Ok(locked
+            .values()
+            .map(|bytes| serde_ipld_dagcbor::from_slice::<Sealed>(bytes).map(|s| s.key_id))
+            .collect::<Result<_, _>>()?)
+    }
+
+    #[test]
+    fn test_round_trip() -> TestResult {
+        let blobs = Blobs::default();
+        let store = Store::new(Arc::clone(&blobs), EncryptionKey::new(1, [7; 32]));
+        let dlg: Arc<Delegation<Ed25519Did>> = delegation("/secret/resource")?;
+        let cid = block_on(insert::<Sendable, _, _, _>(&store, dlg.clone()))?;
+        assert_eq!(cid, dlg.to_cid());
+
+        // The inner store sees neither the envelope nor its command
+        let plaintext = serde_ipld_dagcbor::to_vec(dlg.as_ref())?;
+        let stored = blobs.lock().map_err(|_| "poisoned")?.get(&cid).cloned();
+        let stored = stored.ok_or("not stored")?;
+        assert!(!stored.windows(plaintext.len()).any(|w| w == plaintext));
+        assert!(!stored.windows(7).any(|w| w == b"/secret"));
+
+        let found = block_on(DelegationStore::<Sendable, _, _>::get_all(&store, &[cid]))?;
+        assert_eq!(found.first().map(|d| d.to_cid()), Some(cid));
+
+        // Only the exact key matches
+        let sha512 = dlg.to_cid_with(&CidBuilder::new::<Sha2_512>());
+        assert!(!block_on(ListableStore::<Local, _, _>::contains(
+            &store, sha512
+        ))?);
+        assert!(block_on(ListableStore::<Sendable, _, _>::remove(&store, sha512))?.is_none());
+
+        let listed = block_on(ListableStore::<Sendable, _, _>::list(&store, Page::all()))?;
+        assert_eq!(listed.first().map(|(c, _)| *c), Some(cid));
+
+        assert!(block_on(ListableStore::<Sendable, _, _>::remove(&store, cid))?.is_some());
+        assert_eq!(block_on(ListableStore::<Sendable, _, _>::count(&store))?, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_misses_ignore_retired_keys() -> TestResult {
+        let store = Store::new(Blobs::default(), EncryptionKey::new(1, [7; 32]));
+        let dlg: Arc<Delegation<Ed25519Did>> = delegation("/a")?;
+        let cid = block_on(insert::<Sendable, _, _, _>(&store, dlg.clone()))?;
+        store.rotate(EncryptionKey::new(2, [9; 32]))?;
+        assert!(store.retire(1));
+
+        // A miss never decrypts the entries it does not match
+        let sha512 = dlg.to_cid_with(&CidBuilder::new::<Sha2_512>());
+        let missing = delegation::<Delegation<Ed25519Did>>("/b")?.to_cid();
+        let found = block_on(ListableStore::<Sendable, _, _>::find_all(
+            &store,
+            &[sha512, missing],
+        ))?;
+        assert!(found.iter().all(Option::is_none));
+        assert!(matches!(
+            block_on(DelegationStore::<Sendable, _, _>::get_all(&store, &[cid])),
+            Err(EncryptedStoreError::UnknownKey(1))
+        ));
+        Ok(())
+    }
+
+    /// Blobs that are removed right after each read, as if by a concurrent
+    /// writer.
+    #[derive(Debug, Default)]
+    struct RemovedAfterRead(Blobs);
+
+    #[future_form(Sendable)]
+    impl<K: FutureForm> BlobStore<K> for RemovedAfterRead {
+        type Error = StorePoisoned;
+
+        fn get(&self, cid: Cid) -> K::Future<'_, Result<Option<Vec<u8>>, Self::Error>> {
+            K::from_future(async move { Ok(lock(&self.0)?.remove(&cid)) })
+        }
+
+        fn put(&self, cid: Cid, bytes: Vec<u8>) -> K::Future<'_, Result<(), Self::Error>> {
+            BlobStore::<K>::put(&self.0, cid, bytes)
+        }
+
+        fn replace(
+            &self,
+            cid: Cid,
+            current: Vec<u8>,
+            bytes: Vec<u8>,
+        ) -> K::Future<'_, Result<bool, Self::Error>> {
+            BlobStore::<K>::replace(&self.0, cid, current, bytes)
+        }
+
+        fn remove(&self, cid: Cid) -> K::Future<'_, Result<Option<Vec<u8>>, Self::Error>> {
+            BlobStore::<K>::remove(&self.0, cid)
+        }
+
+        fn count(&self) -> K::Future<'_, Result<usize, Self::Error>> {
+            BlobStore::<K>::count(&self.0)
+        }
+
+        fn list_cids(&self, page: Page) -> K::Future<'_, Result<Vec<Cid>, Self::Error>> {
+            BlobStore::<K>::list_cids(&self.0, page)
+        }
+    }
+
+    #[test]
+    fn test_reencrypt_keeps_concurrent_removals() -> TestResult {
+        let store: EncryptedStore<RemovedAfterRead, Ed25519Did> =
+            EncryptedStore::new(RemovedAfterRead::default(), EncryptionKey::new(1, [7; 32]));
+        block_on(insert::<Sendable, _, _, _>(&store, delegation("/a")?))?;
+        store.rotate(EncryptionKey::new(2, [9; 32]))?;
+
+        assert_eq!(block_on(store.reencrypt::<Sendable>())?, 0);
+        assert_eq!(block_on(ListableStore::<Sendable, _, _>::count(&store))?, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejects_wrong_key_and_moved_ciphertext() -> TestResult {
+        let blobs = Blobs::default();
+        let store = Store::new(Arc::clone(&blobs), EncryptionKey::new(1, [7; 32]));
+        let a = block_on(insert::<Sendable, _, _, _>(&store, delegation("/a")?))?;
+        let b = block_on(insert::<Sendable, _, _, _>(&store, delegation("/b")?))?;
+
+        let other = Store::new(Arc::clone(&blobs), EncryptionKey::new(1, [8; 32]));
+        assert!(matches!(
+            block_on(DelegationStore::<Sendable, _, _>::get_all(&other, &[a])),
+            Err(EncryptedStoreError::Decrypt(c)) if c == a
+        ));
+
+        {
+            let mut locked = blobs.lock().map_err(|_| "poisoned")?;
+            let sealed_b = locked.get(&b).cloned().ok_or("not stored")?;
+            locked.insert(a, sealed_b);
+        }
+        assert!(matches!(
+            block_on(DelegationStore::<Sendable, _, _>::get_all(&store, &[a])),
+            Err(EncryptedStoreError::Decrypt(c)) if c == a
+        ));
+
+        let wrong: Arc<Delegation<Ed25519Did>> = delegation("/c")?;
+        assert!(matches!(
+            block_on(DelegationStore::<Sendable, _, _>::insert_by_cid(&store, a, wrong)),
+            Err(EncryptedStoreError::CidMismatch(c)) if c == a
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_key_rotation() -> TestResult {
+        let blobs = Blobs::default();
+        let store = Store::new(Arc::clone(&blobs), EncryptionKey::new(1, [7; 32]));
+        let old = block_on(insert_many::<Sendable, _, _, _>(
+            &store,
+            vec![delegation("/a")?, delegation("/b")?],
+        ))?;
+
+        assert_eq!(
+            store.rotate(EncryptionKey::new(1, [9; 32])),
+            Err(KeyIdConflict(1))
+        );
+        assert_eq!(store.current_key_id(), 1);
+        store.rotate(EncryptionKey::new(2, [9; 32]))?;
+        assert_eq!(store.current_key_id(), 2);
+        let new = block_on(insert::<Sendable, _, _, _>(&store, delegation("/c")?))?;
+        let mut ids = sealed_key_ids(&blobs)?;
+        ids.sort_unstable();
+        assert_eq!(ids, vec![1, 1, 2]);
+
+        // Old entries stay readable until resealed
+        block_on(DelegationStore::<Sendable, _, _>::get_all(&store, &old))?;
+        assert!(!store.retire(2));
+        assert_eq!(block_on(store.reencrypt::<Sendable>())?, 2);
+        assert_eq!(block_on(store.reencrypt::<Sendable>())?, 0);
+        assert_eq!(sealed_key_ids(&blobs)?, vec![2, 2, 2]);
+
+        assert!(store.retire(1));
+        let all = [old.as_slice(), &[new]].concat();
+        assert_eq!(
+            block_on(DelegationStore::<Sendable, _, _>::get_all(&store, &all))?.len(),
+            3
+        );
+
+        // A restarted store needs the old key for anything not yet resealed
+        let restarted = Store::new(Arc::clone(&blobs), EncryptionKey::new(3, [1; 32]));
+        assert!(matches!(
+            block_on(DelegationStore::<Sendable, _, _>::get_all(
+                &restarted,
+                &[new]
+            )),
+            Err(EncryptedStoreError::UnknownKey(2))
+        ));
+        let restarted = restarted
+            .with_key(EncryptionKey::new(2, [9; 32]))?
+            .with_key(EncryptionKey::new(2, [9; 32]))?;
+        assert_eq!(restarted.current_key_id(), 3);
+        assert_eq!(block_on(restarted.reencrypt::<Local>())?, 3);
+        assert!(matches!(
+            restarted.with_key(EncryptionKey::new(3, [2; 32])),
+            Err(KeyIdConflict(3))
+        ));
+        Ok(())
+    }
+
+    /// Seal two delegations into `blobs`, reseal them under a new key, and
+    /// remove one.
+    fn exercise<S: BlobStore<Sendable> + Sync>(
+        blobs: S,
+    ) -> TestResult<EncryptedStore<S, Ed25519Did>>
+    where
+        S::Error: Send + Sync + 'static,
+    {
+        let store = EncryptedStore::new(blobs, EncryptionKey::new(1, [7; 32]));
+        let cids = block_on(insert_many::<Sendable, _, _, _>(
+            &store,
+            vec![delegation("/a")?, delegation("/b")?],
+        ))?;
+        store.rotate(EncryptionKey::new(2, [9; 32]))?;
+        assert_eq!(block_on(store.reencrypt::<Sendable>())?, 2);
+
+        let first = *cids.first().ok_or("no cid")?;
+        assert!(block_on(ListableStore::<Sendable, _, _>::remove(&store, first))?.is_some());
+        assert!(block_on(ListableStore::<Sendable, _, _>::remove(&store, first))?.is_none());
+        assert_eq!(block_on(ListableStore::<Sendable, _, _>::count(&store))?, 1);
+        Ok(store)
+    }
+
+    #[test]
+    fn test_fs_blob_store() -> TestResult {
+        let dir = TempDir::new("encrypted-fs")?;
+        let store = exercise(FsBlobStore::open(dir.path())?)?;
+        let cids = block_on(ListableStore::<Sendable, _, _>::list_cids(
+            &store,
+            Page::all(),
+        ))?;
+
+        let reopened: EncryptedStore<_, Ed25519Did> = EncryptedStore::new(
+            FsBlobStore::open(dir.path())?,
+            EncryptionKey::new(2, [9; 32]),
+        );
+        assert_eq!(
+            block_on(DelegationStore::<Sendable, _, _>::get_all(&reopened, &cids))?.len(),
+            1
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_sqlite_blob_store() -> TestResult {
+        exercise(SqliteBlobStore::open_in_memory()?)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,142 @@
+//! Sealed delegations on the filesystem.
+//!
+//! [`FsBlobStore`] keeps the sealed delegations of an
+//! [`EncryptedStore`](super::EncryptedStore) as [`FsStore`](super::super::FsStore)
+//! keeps plain ones, one `<cid>.sealed` file each.
+//!
+//! All I/O is blocking, including inside the returned futures.
+
+use alloc::{format, sync::Arc, vec::Vec};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard, PoisonError},
+};
+
+use future_form::{future_form, FutureForm, Local, Sendable};
+use ipld_core::cid::Cid;
+
+use super::{
+    super::{
+        fs::{list_dir, write_atomic},
+        Page,
+    },
+    BlobStore,
+};
+
+/// Extension of blob files.
+const BLOB_EXTENSION: &str = "sealed";
+
+/// A [`BlobStore`] keeping each blob in its own file, `<cid>.sealed`.
+///
+/// Writes are atomic, as for [`FsStore`](super::super::FsStore). Blobs are
+/// opaque, so reads cannot check them against their CID;
+/// [`EncryptedStore`](super::EncryptedStore) authenticates them instead.
+///
+/// Writes through one store and its clones are serialized, which makes
+/// [`BlobStore::replace`] atomic among them, but not against other processes
+/// sharing the directory.
+#[derive(Debug, Clone)]
+pub struct FsBlobStore {
+    dir: PathBuf,
+    writes: Arc<Mutex<()>>,
+}
+
+impl FsBlobStore {
+    /// Open a blob store in `dir`, creating the directory if needed.
+    ///
+    /// # Errors
+    ///
+    /// If the directory cannot be created.
+    pub fn open<P: AsRef<Path>>(dir: P) -> io::Result<Self> {
+        fs::create_dir_all(&dir)?;
+        Ok(FsBlobStore {
+            dir: dir.as_ref().to_path_buf(),
+            writes: Arc::default(),
+        })
+    }
+
+    /// The directory holding the blob files.
+    #[must_use]
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn path_for(&self, cid: &Cid) -> PathBuf {
+        self.dir.join(format!("{cid}.{BLOB_EXTENSION}"))
+    }
+
+    /// Hold off other writes. The lock guards no data, so poisoning is ignored.
+    fn lock_writes(&self) -> MutexGuard<'_, ()> {
+        self.writes.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn read(&self, cid: &Cid) -> io::Result<Option<Vec<u8>>> {
+        match fs::read(self.path_for(cid)) {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[future_form(Local, Sendable)]
+impl<K: FutureForm> BlobStore<K> for FsBlobStore {
+    type Error = io::Error;
+
+    fn get(&self, cid: Cid) -> K::Future<'_, Result<Option<Vec<u8>>, Self::Error>> {
+        K::from_future(async move { self.read(&cid) })
+    }
+
+    fn put(&self, cid: Cid, bytes: Vec<u8>) -> K::Future<'_, Result<(), Self::Error>> {
+        K::from_future(async move {
+            let _writes = self.lock_writes();
+            write_atomic(&self.dir, &self.path_for(&cid), &bytes)
+        })
+    }
+
+    fn replace(
+        &self,
+        cid: Cid,
+        current: Vec<u8>,
+        bytes: Vec<u8>,
+    ) -> K::Future<'_, Result<bool, Self::Error>> {
+        K::from_future(async move {
+            let _writes = self.lock_writes();
+            if self.read(&cid)? != Some(current) {
+                return Ok(false);
+            }
+            write_atomic(&self.dir, &self.path_for(&cid), &bytes)?;
+            Ok(true)
+        })
+    }
+
+    fn remove(&self, cid: Cid) -> K::Future<'_, Result<Option<Vec<u8>>, Self::Error>> {
+        K::from_future(async move {
+            let _writes = self.lock_writes();
+            let Some(bytes) = self.read(&cid)? else {
+                return Ok(None);
+            };
+            match fs::remove_file(self.path_for(&cid)) {
+                Ok(()) => Ok(Some(bytes)),
+                // Removed concurrently
+                Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+                Err(e) => Err(e),
+            }
+        })
+    }
+
+    fn count(&self) -> K::Future<'_, Result<usize, Self::Error>> {
+        K::from_future(async move { Ok(list_dir(&self.dir, BLOB_EXTENSION)?.len()) })
+    }
+
+    fn list_cids(&self, page: Page) -> K::Future<'_, Result<Vec<Cid>, Self::Error>> {
+        K::from_future(async move {
+            Ok(list_dir(&self.dir, BLOB_EXTENSION)?
+                .into_iter()
+                .filter(|cid| page.after.is_none_or(|after| *cid > after))
+                .take(page.limit)
+                .collect())
+        })
+    }
+}
@@ -0,0 +1,208 @@
+//! Sealed delegations in `SQLite`.
+//!
+//! [`SqliteBlobStore`] keeps the sealed delegations of an
+//! [`EncryptedStore`](super::EncryptedStore) in a table of its own.
+//!
+//! All I/O is blocking, including inside the returned futures.
+
+use alloc::vec::Vec;
+use std::{
+    path::Path,
+    sync::{Mutex, MutexGuard},
+};
+
+use future_form::{future_form, FutureForm, Local, Sendable};
+use ipld_core::cid::Cid;
+use rusqlite::{params, Connection, OptionalExtension};
+
+use super::{
+    super::{
+        sqlite::{sort_key, SqliteStoreError},
+        Page,
+    },
+    BlobStore,
+};
+
+/// Creates the table of a [`SqliteBlobStore`], if it does not exist.
+const BLOB_SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS sealed_delegations (
+    cid      BLOB PRIMARY KEY NOT NULL,
+    bytes    BLOB NOT NULL,
+    sort_key BLOB UNIQUE NOT NULL
+);
+";
+
+/// A [`BlobStore`] keeping blobs in one `sealed_delegations` table, keyed by
+/// binary CID.
+///
+/// It may share a database with a [`SqliteStore`](super::super::SqliteStore). As for that store, pages
+/// are ordered by [`Cid`]'s `Ord`, through a `sort_key` column.
+#[derive(Debug)]
+pub struct SqliteBlobStore {
+    conn: Mutex<Connection>,
+}
+
+impl SqliteBlobStore {
+    /// Open (or create) a database file.
+    ///
+    /// # Errors
+    ///
+    /// If the database cannot be opened or the table cannot be created.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, SqliteStoreError> {
+        Self::from_connection(Connection::open(path)?)
+    }
+
+    /// Open a private in-memory database.
+    ///
+    /// # Errors
+    ///
+    /// If the table cannot be created.
+    pub fn open_in_memory() -> Result<Self, SqliteStoreError> {
+        Self::from_connection(Connection::open_in_memory()?)
+    }
+
+    /// Use an existing connection, creating the table if needed.
+    ///
+    /// # Errors
+    ///
+    /// If the table cannot be created.
+    pub fn from_connection(conn: Connection) -> Result<Self, SqliteStoreError> {
+        conn.execute_batch(BLOB_SCHEMA)?;
+        Ok(SqliteBlobStore {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, Connection>, SqliteStoreError> {
+        self.conn.lock().map_err(|_| SqliteStoreError::Poisoned)
+    }
+
+    fn read(conn: &Connection, cid: &Cid) -> Result<Option<Vec<u8>>, SqliteStoreError> {
+        Ok(conn
+            .prepare_cached("SELECT bytes FROM sealed_delegations WHERE cid = ?1")?
+            .query_row([cid.to_bytes()], |row| row.get(0))
+            .optional()?)
+    }
+}
+
+#[future_form(Local, Sendable)]
+impl<K: FutureForm> BlobStore<K> for SqliteBlobStore {
+    type Error = SqliteStoreError;
+
+    fn get(&self, cid: Cid) -> K::Future<'_, Result<Option<Vec<u8>>, Self::Error>> {
+        K::from_future(async move { Self::read(&*self.lock()?, &cid) })
+    }
+
+    fn put(&self, cid: Cid, bytes: Vec<u8>) -> K::Future<'_, Result<(), Self::Error>> {
+        K::from_future(async move {
+            self.lock()?
+                .prepare_cached(
+                    "INSERT OR REPLACE INTO sealed_delegations (cid, bytes, sort_key)
+                     VALUES (?1, ?2, ?3)",
+                )?
+                .execute(params![cid.to_bytes(), bytes, sort_key(&cid)])?;
+            Ok(())
+        })
+    }
+
+    fn replace(
+        &self,
+        cid: Cid,
+        current: Vec<u8>,
+        bytes: Vec<u8>,
+    ) -> K::Future<'_, Result<bool, Self::Error>> {
+        K::from_future(async move {
+            let replaced = self
+                .lock()?
+                .prepare_cached(
+                    "UPDATE sealed_delegations SET bytes = ?1 WHERE cid = ?2 AND bytes = ?3",
+                )?
+                .execute(params![bytes, cid.to_bytes(), current])?;
+            Ok(replaced == 1)
+        })
+    }
+
+    fn remove(&self, cid: Cid) -> K::Future<'_, Result<Option<Vec<u8>>, Self::Error>> {
+        K::from_future(async move {
+            let conn = self.lock()?;
+            let removed = Self::read(&conn, &cid)?;
+            if removed.is_some() {
+                conn.execute(
+                    "DELETE FROM sealed_delegations WHERE cid = ?1",
+                    [cid.to_bytes()],
+                )?;
+            }
+            Ok(removed)
+        })
+    }
+
+    fn count(&self) -> K::Future<'_, Result<usize, Self::Error>> {
+        K::from_future(async move {
+            let count: i64 =
+                self.lock()?
+                    .query_row("SELECT COUNT(*) FROM sealed_delegations", [], |row| {
+                        row.get(0)
+                    })?;
+            Ok(usize::try_from(count).unwrap_or_default())
+        })
+    }
+
+    fn list_cids(&self, page: Page) -> K::Future<'_, Result<Vec<Cid>, Self::Error>> {
+        K::from_future(async move {
+            let conn = self.lock()?;
+            let mut stmt = conn.prepare_cached(
+                "SELECT cid FROM sealed_delegations WHERE ?1 IS NULL OR sort_key > ?1
+                 ORDER BY sort_key LIMIT ?2",
+            )?;
+            let mut rows = stmt.query(params![
+                page.after.as_ref().map(sort_key),
+                i64::try_from(page.limit).unwrap_or(i64::MAX)
+            ])?;
+            let mut cids = Vec::new();
+            while let Some(row) = rows.next()? {
+                cids.push(
+                    Cid::try_from(row.get::<_, Vec<u8>>(0)?)
+                        .map_err(|_| SqliteStoreError::InvalidCid)?,
+                );
+            }
+            Ok(cids)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::block_on;
+    use ipld_core::cid::multihash::Multihash;
+    use testresult::TestResult;
+
+    #[test]
+    fn test_pages_in_cid_order() -> TestResult {
+        let mh = |code: u64| Multihash::<64>::wrap(code, &[0; 32]);
+        // Ordered differently by `Ord` and by the binary encoding
+        let mut cids = vec![
+            Cid::new_v0(mh(0x12)?)?,
+            Cid::new_v1(0xff, mh(0x12)?),
+            Cid::new_v1(0x100, mh(0x12)?),
+            Cid::new_v1(0x71, mh(0xff)?),
+            Cid::new_v1(0x71, mh(0x100)?),
+        ];
+        let blobs = SqliteBlobStore::open_in_memory()?;
+        for cid in &cids {
+            block_on(BlobStore::<Sendable>::put(&blobs, *cid, vec![0]))?;
+        }
+
+        let mut paged = Vec::new();
+        let mut page = Page::first(2);
+        loop {
+            let cids = block_on(BlobStore::<Sendable>::list_cids(&blobs, page))?;
+            let Some(last) = cids.last() else { break };
+            page = page.after(*last);
+            paged.extend(cids);
+        }
+        cids.sort();
+        assert_eq!(paged, cids);
+        Ok(())
+    }
+}
@@ -0,0 +1,890 @@
+//! Filesystem-backed delegation store.
+//!
+//! [`FsStore`] keeps each delegation in its own file, `<cid>.cbor`, holding
+//! the delegation's DAG-CBOR bytes. Files are written to a temporary name and
+//! renamed into place, so readers never see a partial write, and every read
+//! checks that the file's bytes hash to the CID in its name.
+//!
+//! An indexed store ([`FsStore::open_indexed`]) also keeps the audience and
+//! command of every delegation in `.index.cbor`, so [`DelegationIndex`] queries
+//! by audience or command only read the files that can match. Writes append
+//! their changes to `.index.log`, which is folded into `.index.cbor` once it
+//! outgrows it.
+//!
+//! All I/O is blocking, including inside the returned futures.
+
+use alloc::{
+    collections::{BTreeMap, TryReserveError},
+    format,
+    sync::Arc,
+    vec::Vec,
+};
+use core::{
+    convert::Infallible,
+    sync::atomic::{AtomicU64, Ordering},
+};
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard},
+};
+
+use future_form::{future_form, FutureForm, Local, Sendable};
+use ipld_core::cid::Cid;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use varsig::{
+    hash::{Multihasher, Sha2_256},
+    verify::Verify,
+};
+
+use crate::{cid::CidBuilder, command::Command, delegation::Delegation, did::Did};
+
+use super::{
+    DelegationIndex, DelegationQuery, DelegationStore, Found, ListableStore, Matches, Missing, Page,
+};
+
+/// Extension of delegation files.
+const EXTENSION: &str = "cbor";
+
+/// Name of the index file.
+const INDEX_FILE: &str = ".index.cbor";
+
+/// Name of the log of index changes since `INDEX_FILE` was written.
+const INDEX_LOG: &str = ".index.log";
+
+/// The log is only compacted once it holds more changes than this (or than
+/// the index has entries, whichever is larger).
+const MIN_LOG_COMPACTION: usize = 256;
+
+/// Distinguishes temporary files written concurrently by this process.
+static TMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// The indexed fields of one stored delegation.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(bound = "")]
+struct IndexEntry<D: Did> {
+    cid: Cid,
+    aud: D,
+    cmd: Command,
+}
+
+/// One change appended to the index log.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(bound = "")]
+enum IndexChange<D: Did> {
+    Insert(IndexEntry<D>),
+    Remove(Cid),
+}
+
+/// The in-memory index, and the number of changes in its log.
+#[derive(Debug)]
+struct Index<D: Did> {
+    entries: BTreeMap<Cid, IndexEntry<D>>,
+    logged: usize,
+
+    /// How many entries are keyed by a CID not hashed with SHA2-256.
+    other_hashes: usize,
+}
+
+/// A [`DelegationStore`] backed by a directory of `<cid>.cbor` files.
+///
+/// Every method does blocking file I/O, including inside the returned
+/// futures; run them on a thread that may block. A lookup that misses its
+/// exact file name lists the directory to look for the delegation under
+/// another hash. An indexed store skips that listing when the index holds no
+/// keys that could match.
+#[derive(Debug)]
+pub struct FsStore<D: Did> {
+    dir: PathBuf,
+    index: Option<Mutex<Index<D>>>,
+}
+
+impl<D: Did + Clone> FsStore<D>
+where
+    <D::VarsigConfig as Verify>::Signature: for<'de> Deserialize<'de>,
+{
+    /// Open a store in `dir`, creating the directory if needed.
+    ///
+    /// # Errors
+    ///
+    /// If the directory cannot be created.
+    pub fn open<P: AsRef<Path>>(dir: P) -> Result<Self, FsStoreError> {
+        fs::create_dir_all(&dir)?;
+        Ok(FsStore {
+            dir: dir.as_ref().to_path_buf(),
+            index: None,
+        })
+    }
+
+    /// Open a store in `dir` with an on-disk index of audiences and commands.
+    ///
+    /// The index is loaded from `.index.cbor` and `.index.log`, and rebuilt
+    /// from the delegation files if it is missing, unreadable, or out of date.
+    ///
+    /// # Errors
+    ///
+    /// If the directory cannot be created or read, or rebuilding the index fails.
+    pub fn open_indexed<P: AsRef<Path>>(dir: P) -> Result<Self, FsStoreError> {
+        let mut store = Self::open(dir)?;
+        let stored = store.stored_cids()?;
+        let index = match store.load_index() {
+            Some(index) if index.entries.keys().eq(stored.iter()) => index,
+            _ => store.build_index(&stored)?,
+        };
+        store.index = Some(Mutex::new(index));
+        Ok(store)
+    }
+
+    /// The directory holding the delegation files.
+    #[must_use]
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Whether this store keeps an on-disk index.
+    #[must_use]
+    pub const fn is_indexed(&self) -> bool {
+        self.index.is_some()
+    }
+
+    /// Rebuild the index from the delegation files, e.g. after they were
+    /// changed by another process. Does nothing for an unindexed store.
+    ///
+    /// # Errors
+    ///
+    /// If a delegation file cannot be read or the index cannot be written.
+    pub fn rebuild_index(&self) -> Result<(), FsStoreError> {
+        if let Some(index) = &self.index {
+            let rebuilt = self.build_index(&self.stored_cids()?)?;
+            *lock(index)? = rebuilt;
+        }
+        Ok(())
+    }
+
+    fn path_for(&self, cid: &Cid) -> PathBuf {
+        self.dir.join(format!("{cid}.{EXTENSION}"))
+    }
+
+    /// Read and verify the delegation stored under exactly `cid`.
+    fn read(&self, cid: &Cid) -> Result<Option<Delegation<D>>, FsStoreError> {
+        let bytes = match fs::read(self.path_for(cid)) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        let builder = CidBuilder::for_cid(cid).ok_or(FsStoreError::UnsupportedHash(*cid))?;
+        if builder.build_from_dagcbor(&bytes) != *cid {
+            return Err(FsStoreError::Corrupt(*cid));
+        }
+
+        Ok(Some(serde_ipld_dagcbor::from_slice(&bytes)?))
+    }
+
+    /// Read the delegation stored under `cid` or a CID equivalent to it.
+    ///
+    /// After a miss, only files named with a different hash than `cid` are
+    /// read and rehashed: one with the same hash would have been found.
+    fn find(&self, cid: &Cid) -> Result<Option<(Cid, Delegation<D>)>, FsStoreError> {
+        if let Some(dlg) = self.read(cid)? {
+            return Ok(Some((*cid, dlg)));
+        }
+
+        let Some(builder) = CidBuilder::for_cid(cid) else {
+            return Ok(None);
+        };
+        let code = cid.hash().code();
+        let stored = match &self.index {
+            Some(index) => {
+                let index = lock(index)?;
+                if !index.may_hold_equivalent(cid) {
+                    return Ok(None);
+                }
+                index.entries.keys().copied().collect()
+            }
+            None => self.stored_cids()?,
+        };
+        for stored in stored.into_iter().filter(|key| key.hash().code() != code) {
+            if let Some(dlg) = self.read(&stored)? {
+                if dlg.to_cid_with(&builder) == *cid {
+                    return Ok(Some((stored, dlg)));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Write a delegation under `cid`, after checking that `cid` addresses it.
+    fn write(&self, cid: &Cid, delegation: &Delegation<D>) -> Result<(), FsStoreError> {
+        let bytes = serde_ipld_dagcbor::to_vec(delegation)?;
+        let builder = CidBuilder::for_cid(cid).ok_or(FsStoreError::UnsupportedHash(*cid))?;
+        if builder.build_from_dagcbor(&bytes) != *cid {
+            return Err(FsStoreError::CidMismatch(*cid));
+        }
+        self.write_atomic(&self.path_for(cid), &bytes)?;
+        Ok(())
+    }
+
+    fn write_atomic(&self, path: &Path, bytes: &[u8]) -> io::Result<()> {
+        write_atomic(&self.dir, path, bytes)
+    }
+
+    /// Every CID with a delegation file, in ascending order.
+    fn stored_cids(&self) -> Result<Vec<Cid>, FsStoreError> {
+        Ok(list_dir(&self.dir, EXTENSION)?)
+    }
+
+    /// Load the index file and replay its log.
+    ///
+    /// A record cut short by a crash ends the replay; the caller then finds
+    /// the index out of date and rebuilds it.
+    fn load_index(&self) -> Option<Index<D>> {
+        let bytes = fs::read(self.dir.join(INDEX_FILE)).ok()?;
+        let entries: Vec<IndexEntry<D>> = serde_ipld_dagcbor::from_slice(&bytes).ok()?;
+        let mut index = Index::default();
+        for entry in entries {
+            index.apply(IndexChange::Insert(entry));
+        }
+
+        let log = match fs::read(self.dir.join(INDEX_LOG)) {
+            Ok(log) => log,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(_) => return None,
+        };
+        for change in serde_ipld_dagcbor::de::Deserializer::from_slice(&log)
+            .into_iter::<IndexChange<D>>()
+            .map_while(Result::ok)
+        {
+            index.apply(change);
+            index.logged += 1;
+        }
+        Some(index)
+    }
+
+    fn build_index(&self, cids: &[Cid]) -> Result<Index<D>, FsStoreError> {
+        let mut index = Index::default();
+        for cid in cids {
+            if let Some(dlg) = self.read(cid)? {
+                index.apply(IndexChange::Insert(IndexEntry::new(*cid, &dlg)));
+            }
+        }
+        self.save_index(&mut index)?;
+        Ok(index)
+    }
+
+    /// Write the whole index to the index file and clear its log.
+    ///
+    /// Replaying a log over an index that already includes it gives the same
+    /// index, so a crash between the two steps loses nothing.
+    fn save_index(&self, index: &mut Index<D>) -> Result<(), FsStoreError> {
+        let entries: Vec<&IndexEntry<D>> = index.entries.values().collect();
+        let bytes = serde_ipld_dagcbor::to_vec(&entries)?;
+        self.write_atomic(&self.dir.join(INDEX_FILE), &bytes)?;
+        match fs::remove_file(self.dir.join(INDEX_LOG)) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+        index.logged = 0;
+        Ok(())
+    }
+
+    /// Apply `changes` to the index (if any) and append them to its log,
+    /// compacting the log into the index file once it outgrows it.
+    fn update_index(
+        &self,
+        changes: impl IntoIterator<Item = IndexChange<D>>,
+    ) -> Result<(), FsStoreError> {
+        let Some(index) = &self.index else {
+            return Ok(());
+        };
+        let mut locked = lock(index)?;
+        let mut record = Vec::new();
+        for change in changes {
+            record.extend(serde_ipld_dagcbor::to_vec(&change)?);
+            locked.apply(change);
+            locked.logged += 1;
+        }
+
+        if locked.logged > locked.entries.len().max(MIN_LOG_COMPACTION) {
+            return self.save_index(&mut locked);
+        }
+        let mut log = fs::OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(self.dir.join(INDEX_LOG))?;
+        log.write_all(&record)?;
+        log.sync_data()?;
+        Ok(())
+    }
+
+    fn insert_all(&self, delegations: &[(Cid, Arc<Delegation<D>>)]) -> Result<(), FsStoreError> {
+        for (cid, dlg) in delegations {
+            self.write(cid, dlg)?;
+        }
+        self.update_index(
+            delegations
+                .iter()
+                .map(|(cid, dlg)| IndexChange::Insert(IndexEntry::new(*cid, dlg))),
+        )
+    }
+
+    fn remove_cid(&self, cid: &Cid) -> Result<Option<Arc<Delegation<D>>>, FsStoreError> {
+        let Some((stored, dlg)) = self.find(cid)? else {
+            return Ok(None);
+        };
+        match fs::remove_file(self.path_for(&stored)) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        }
+        self.update_index([IndexChange::Remove(stored)])?;
+        Ok(Some(Arc::new(dlg)))
+    }
+
+    fn query_all(
+        &self,
+        query: &DelegationQuery<D>,
+    ) -> Result<Matches<Arc<Delegation<D>>>, FsStoreError> {
+        let candidates = match &self.index {
+            Some(index) => lock(index)?
+                .entries
+                .values()
+                .filter(|entry| {
+                    query
+                        .audience_criterion()
+                        .is_none_or(|audience| *audience == entry.aud)
+                        && query.command_matches(&entry.cmd)
+                })
+                .map(|entry| entry.cid)
+                .collect(),
+            None => self.stored_cids()?,
+        };
+
+        let mut found = Vec::new();
+        for cid in candidates {
+            if let Some(dlg) = self.read(&cid)? {
+                if query.matches(&dlg) {
+                    found.push((cid, Arc::new(dlg)));
+                }
+            }
+        }
+        Ok(found)
+    }
+}
+
+impl<D: Did + Clone> IndexEntry<D> {
+    fn new(cid: Cid, delegation: &Delegation<D>) -> Self {
+        IndexEntry {
+            cid,
+            aud: delegation.audience().clone(),
+            cmd: delegation.command().clone(),
+        }
+    }
+}
+
+impl<D: Did> Default for Index<D> {
+    fn default() -> Self {
+        Index {
+            entries: BTreeMap::new(),
+            logged: 0,
+            other_hashes: 0,
+        }
+    }
+}
+
+impl<D: Did> Index<D> {
+    fn apply(&mut self, change: IndexChange<D>) {
+        match change {
+            IndexChange::Insert(entry) => {
+                let cid = entry.cid;
+                if self.entries.insert(cid, entry).is_none() && !is_canonical(&cid) {
+                    self.other_hashes += 1;
+                }
+            }
+            IndexChange::Remove(cid) => {
+                if self.entries.remove(&cid).is_some() && !is_canonical(&cid) {
+                    self.other_hashes -= 1;
+                }
+            }
+        }
+    }
+
+    /// Whether an entry keyed with another hash than `cid`'s may be indexed.
+    const fn may_hold_equivalent(&self, cid: &Cid) -> bool {
+        !is_canonical(cid) || self.other_hashes > 0
+    }
+}
+
+/// Whether `cid` uses the default CID hash, SHA2-256.
+const fn is_canonical(cid: &Cid) -> bool {
+    cid.hash().code() == Sha2_256::MULTIHASH_TAG
+}
+
+/// Write `bytes` to a temporary file in `dir`, sync it, and rename it to `path`.
+pub(super) fn write_atomic(dir: &Path, path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let tmp = dir.join(format!(
+        ".{}.{}.tmp",
+        std::process::id(),
+        TMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    let mut file = fs::File::create(&tmp)?;
+    file.write_all(bytes)?;
+    file.sync_all()?;
+    fs::rename(&tmp, path)
+}
+
+/// Every CID naming a `<cid>.<extension>` file in `dir`, in ascending order.
+pub(super) fn list_dir(dir: &Path, extension: &str) -> io::Result<Vec<Cid>> {
+    let mut cids = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some(extension) {
+            continue;
+        }
+        if let Some(cid) = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| Cid::try_from(stem).ok())
+        {
+            cids.push(cid);
+        }
+    }
+    cids.sort_unstable();
+    Ok(cids)
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> Result<MutexGuard<'_, T>, FsStoreError> {
+    mutex.lock().map_err(|_| FsStoreError::IndexPoisoned)
+}
+
+#[future_form(
+    Local,
+    Sendable where
+        D: Send + Sync,
+        <D as Did>::VarsigConfig: Send + Sync,
+        <<D as Did>::VarsigConfig as Verify>::Signature: Send + Sync
+)]
+impl<K: FutureForm, D: Did + Clone> DelegationStore<K, D, Arc<Delegation<D>>> for FsStore<D>
+where
+    <D::VarsigConfig as Verify>::Signature: for<'de> Deserialize<'de>,
+{
+    type InsertError = FsStoreError;
+    type GetError = FsStoreError;
+
+    fn get_all<'a>(
+        &'a self,
+        cid: &'a [Cid],
+    ) -> K::Future<'a, Result<Vec<Arc<Delegation<D>>>, Self::GetError>> {
+        K::from_future(async move {
+            let mut dlgs = Vec::new();
+            for c in cid {
+                let (_, dlg) = self.find(c)?.ok_or(Missing(*c))?;
+                dlgs.push(Arc::new(dlg));
+            }
+            Ok(dlgs)
+        })
+    }
+
+    fn insert_by_cid(
+        &self,
+        cid: Cid,
+        delegation: Arc<Delegation<D>>,
+    ) -> K::Future<'_, Result<(), Self::InsertError>> {
+        K::from_future(async move { self.insert_all(&[(cid, delegation)]) })
+    }
+}
+
+#[future_form(
+    Local,
+    Sendable where
+        D: Send + Sync,
+        <D as Did>::VarsigConfig: Send + Sync,
+        <<D as Did>::VarsigConfig as Verify>::Signature: Send + Sync
+)]
+impl<K: FutureForm, D: Did + Clone> ListableStore<K, D, Arc<Delegation<D>>> for FsStore<D>
+where
+    <D::VarsigConfig as Verify>::Signature: for<'de> Deserialize<'de>,
+{
+    type ListError = FsStoreError;
+    type RemoveError = FsStoreError;
+
+    fn find_all<'a>(
+        &'a self,
+        cid: &'a [Cid],
+    ) -> K::Future<'a, Result<Found<Arc<Delegation<D>>>, Self::GetError>> {
+        K::from_future(async move {
+            cid.iter()
+                .map(|c| Ok(self.find(c)?.map(|(key, dlg)| (key, Arc::new(dlg)))))
+                .collect()
+        })
+    }
+
+    fn insert_many_by_cid(
+        &self,
+        delegations: Vec<(Cid, Arc<Delegation<D>>)>,
+    ) -> K::Future<'_, Result<(), Self::InsertError>> {
+        K::from_future(async move { self.insert_all(&delegations) })
+    }
+
+    fn remove(
+        &self,
+        cid: Cid,
+    ) -> K::Future<'_, Result<Option<Arc<Delegation<D>>>, Self::RemoveError>> {
+        K::from_future(async move { self.remove_cid(&cid) })
+    }
+
+    fn contains(&self, cid: Cid) -> K::Future<'_, Result<bool, Self::ListError>> {
+        K::from_future(async move {
+            if self.path_for(&cid).try_exists()? {
+                return Ok(true);
+            }
+            Ok(self.find(&cid)?.is_some())
+        })
+    }
+
+    fn count(&self) -> K::Future<'_, Result<usize, Self::ListError>> {
+        K::from_future(async move { Ok(self.stored_cids()?.len()) })
+    }
+
+    fn list_cids(&self, page: Page) -> K::Future<'_, Result<Vec<Cid>, Self::ListError>> {
+        K::from_future(async move {
+            Ok(self
+                .stored_cids()?
+                .into_iter()
+                .filter(|cid| page.after.is_none_or(|after| *cid > after))
+                .take(page.limit)
+                .collect())
+        })
+    }
+
+    fn list(
+        &self,
+        page: Page,
+    ) -> K::Future<'_, Result<Matches<Arc<Delegation<D>>>, Self::ListError>> {
+        K::from_future(async move {
+            let mut entries = Vec::new();
+            for cid in self
+                .stored_cids()?
+                .into_iter()
+                .filter(|cid| page.after.is_none_or(|after| *cid > after))
+            {
+                if entries.len() >= page.limit {
+                    break;
+                }
+                if let Some(dlg) = self.read(&cid)? {
+                    entries.push((cid, Arc::new(dlg)));
+                }
+            }
+            Ok(entries)
+        })
+    }
+}
+
+#[future_form(
+    Local,
+    Sendable where
+        D: Send + Sync,
+        <D as Did>::VarsigConfig: Send + Sync,
+        <<D as Did>::VarsigConfig as Verify>::Signature: Send + Sync
+)]
+impl<K: FutureForm, D: Did + Clone> DelegationIndex<K, D, Arc<Delegation<D>>> for FsStore<D>
+where
+    <D::VarsigConfig as Verify>::Signature: for<'de> Deserialize<'de>,
+{
+    type QueryError = FsStoreError;
+
+    fn query<'a>(
+        &'a self,
+        query: &'a DelegationQuery<D>,
+    ) -> K::Future<'a, Result<Matches<Arc<Delegation<D>>>, Self::QueryError>> {
+        K::from_future(async move { self.query_all(query) })
+    }
+}
+
+/// Errors from an [`FsStore`].
+#[derive(Debug, Error)]
+pub enum FsStoreError {
+    /// File I/O failed.
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    /// A delegation or the index could not be encoded.
+    #[error("unable to encode: {0}")]
+    Encode(#[from] serde_ipld_dagcbor::EncodeError<TryReserveError>),
+
+    /// A stored delegation could not be decoded.
+    #[error("unable to decode delegation: {0}")]
+    Decode(#[from] serde_ipld_dagcbor::DecodeError<Infallible>),
+
+    /// A delegation file does not hash to the CID in its name.
+    #[error("delegation file for {0} does not match its CID")]
+    Corrupt(Cid),
+
+    /// A delegation was inserted under a CID that does not address it.
+    #[error("delegation does not match CID {0}")]
+    CidMismatch(Cid),
+
+    /// The CID's hash is not compiled in.
+    #[error("unsupported hash in CID {0}")]
+    UnsupportedHash(Cid),
+
+    /// A requested delegation is not stored.
+    #[error(transparent)]
+    Missing(#[from] Missing),
+
+    /// Another thread panicked while updating the index.
+    #[error("delegation store index poisoned")]
+    IndexPoisoned,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        delegation::{store::insert, subject::DelegatedSubject},
+        did::Ed25519Did,
+        test_support::{block_on, delegation_with, signer, TempDir},
+    };
+    use testresult::TestResult;
+    use varsig::hash::Sha2_512;
+
+    #[test]
+    fn test_persists_across_reopen() -> TestResult {
+        let dir = TempDir::new("reopen")?;
+        let dlg: Arc<Delegation<Ed25519Did>> =
+            delegation_with(1, 2, DelegatedSubject::Any, "/crud/read", None)?;
+        let cid = {
+            let store = FsStore::<Ed25519Did>::open(dir.path())?;
+            block_on(insert::<Sendable, _, _, _>(&store, dlg.clone()))?
+        };
+        assert!(dir.path().join(format!("{cid}.cbor")).exists());
+
+        let store = FsStore::<Ed25519Did>::open(dir.path())?;
+        let found = block_on(DelegationStore::<Sendable, _, _>::get_all(&store, &[cid]))?;
+        assert_eq!(found.first().map(|d| d.to_cid()), Some(cid));
+
+        let sha512 = dlg.to_cid_with(&CidBuilder::new::<Sha2_512>());
+        let found = block_on(DelegationStore::<Local, _, _>::get_all(&store, &[sha512]))?;
+        assert_eq!(found.first().map(|d| d.to_cid()), Some(cid));
+        Ok(())
+    }
+
+    #[test]
+    fn test_detects_corruption() -> TestResult {
+        let dir = TempDir::new("corrupt")?;
+        let store = FsStore::<Ed25519Did>::open(dir.path())?;
+        let cid = block_on(insert::<Sendable, _, _, _>(
+            &store,
+            delegation_with(1, 2, DelegatedSubject::Any, "/a", None)?,
+        ))?;
+        let other: Arc<Delegation<Ed25519Did>> =
+            delegation_with(3, 4, DelegatedSubject::Any, "/b", None)?;
+
+        // Swap in another delegation's bytes under the same name
+        fs::write(
+            dir.path().join(format!("{cid}.cbor")),
+            serde_ipld_dagcbor::to_vec(&*other)?,
+        )?;
+        assert!(matches!(
+            block_on(DelegationStore::<Sendable, _, _>::get_all(&store, &[cid])),
+            Err(FsStoreError::Corrupt(c)) if c == cid
+        ));
+
+        // Refuse to store a delegation under the wrong CID
+        assert!(matches!(
+            block_on(DelegationStore::<Sendable, _, _>::insert_by_cid(
+                &store, cid, other
+            )),
+            Err(FsStoreError::CidMismatch(c)) if c == cid
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_remove_and_list() -> TestResult {
+        let dir = TempDir::new("list")?;
+        let store = FsStore::<Ed25519Did>::open(dir.path())?;
+        let dlgs = vec![
+            delegation_with(1, 2, DelegatedSubject::Any, "/a", None)?,
+            delegation_with(1, 2, DelegatedSubject::Any, "/b", None)?,
+            delegation_with(1, 2, DelegatedSubject::Any, "/c", None)?,
+        ];
+        let mut cids = block_on(super::super::insert_many::<Sendable, _, _, _>(&store, dlgs))?;
+        cids.sort();
+        fs::write(dir.path().join("notes.txt"), b"ignored")?;
+
+        let first = block_on(ListableStore::<Sendable, _, _>::list_cids(
+            &store,
+            Page::first(2),
+        ))?;
+        assert_eq!(first.as_slice(), cids.get(..2).unwrap_or_default());
+        let rest = block_on(ListableStore::<Sendable, _, _>::list(
+            &store,
+            Page::first(2).after(*first.last().ok_or("empty page")?),
+        ))?;
+        assert_eq!(
+            rest.iter().map(|(c, _)| *c).collect::<Vec<_>>().as_slice(),
+            cids.get(2..).unwrap_or_default()
+        );
+
+        let removed = *cids.first().ok_or("no cids")?;
+        assert!(block_on(ListableStore::<Sendable, _, _>::remove(&store, removed))?.is_some());
+        assert!(!block_on(ListableStore::<Sendable, _, _>::contains(
+            &store, removed
+        ))?);
+        assert_eq!(block_on(ListableStore::<Sendable, _, _>::count(&store))?, 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_indexed_query() -> TestResult {
+        let dir = TempDir::new("index")?;
+        let write: Arc<Delegation<Ed25519Did>> =
+            delegation_with(1, 2, DelegatedSubject::Any, "/crud/write", None)?;
+        let cids = {
+            let store = FsStore::<Ed25519Did>::open_indexed(dir.path())?;
+            block_on(super::super::insert_many::<Sendable, _, _, _>(
+                &store,
+                vec![
+                    write.clone(),
+                    delegation_with(1, 2, DelegatedSubject::Any, "/msg/send", None)?,
+                    delegation_with(1, 3, DelegatedSubject::Any, "/crud/write", None)?,
+                ],
+            ))?
+        };
+        assert!(dir.path().join(INDEX_FILE).exists());
+
+        let store = FsStore::<Ed25519Did>::open_indexed(dir.path())?;
+        let query = DelegationQuery::new()
+            .audience(*signer(2).did())
+            .grants(Command::parse("/crud/write/all")?);
+        let found = block_on(DelegationIndex::<Sendable, _, _>::query(&store, &query))?;
+        assert_eq!(
+            found.into_iter().map(|(c, _)| c).collect::<Vec<_>>(),
+            vec![write.to_cid()]
+        );
+
+        // The index is rebuilt when files change behind its back
+        let stale = *cids.get(1).ok_or("missing cid")?;
+        fs::remove_file(dir.path().join(format!("{stale}.cbor")))?;
+        let reopened = FsStore::<Ed25519Did>::open_indexed(dir.path())?;
+        let all = block_on(DelegationIndex::<Sendable, _, _>::query(
+            &reopened,
+            &DelegationQuery::new(),
+        ))?;
+        assert_eq!(all.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_changes_are_logged() -> TestResult {
+        let dir = TempDir::new("index-log")?;
+        let store = FsStore::<Ed25519Did>::open_indexed(dir.path())?;
+        let snapshot = fs::read(dir.path().join(INDEX_FILE))?;
+        let cids = block_on(super::super::insert_many::<Sendable, _, _, _>(
+            &store,
+            vec![
+                delegation_with(1, 2, DelegatedSubject::Any, "/a", None)?,
+                delegation_with(1, 2, DelegatedSubject::Any, "/b", None)?,
+            ],
+        ))?;
+        block_on(ListableStore::<Sendable, _, _>::remove(
+            &store,
+            *cids.first().ok_or("missing cid")?,
+        ))?;
+
+        // Writes only append to the log
+        assert_eq!(fs::read(dir.path().join(INDEX_FILE))?, snapshot);
+        assert!(dir.path().join(INDEX_LOG).exists());
+
+        // A record cut short by a crash is ignored on replay
+        let mut log = fs::OpenOptions::new()
+            .append(true)
+            .open(dir.path().join(INDEX_LOG))?;
+        log.write_all(&[0xa1])?;
+        let reopened = FsStore::<Ed25519Did>::open_indexed(dir.path())?;
+        let all = block_on(DelegationIndex::<Sendable, _, _>::query(
+            &reopened,
+            &DelegationQuery::new(),
+        ))?;
+        assert_eq!(
+            all.into_iter().map(|(c, _)| c).collect::<Vec<_>>(),
+            cids.get(1..).unwrap_or_default()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_miss_skips_files_with_the_same_hash() -> TestResult {
+        let dir = TempDir::new("miss")?;
+        let store = FsStore::<Ed25519Did>::open(dir.path())?;
+        let stored = block_on(insert::<Sendable, _, _, _>(
+            &store,
+            delegation_with(1, 2, DelegatedSubject::Any, "/a", None)?,
+        ))?;
+        fs::write(dir.path().join(format!("{stored}.cbor")), b"corrupt")?;
+
+        // A SHA2-256 CID cannot be stored under another SHA2-256 name, so
+        // the corrupt file is never read
+        let missing =
+            delegation_with::<Delegation<Ed25519Did>>(3, 4, DelegatedSubject::Any, "/b", None)?
+                .to_cid();
+        assert!(matches!(
+            block_on(DelegationStore::<Sendable, _, _>::get_all(&store, &[missing])),
+            Err(FsStoreError::Missing(Missing(c))) if c == missing
+        ));
+        assert!(!block_on(ListableStore::<Sendable, _, _>::contains(
+            &store, missing
+        ))?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_skips_the_scan_without_other_hashes() -> TestResult {
+        let dir = TempDir::new("other-hashes")?;
+        let store = FsStore::<Ed25519Did>::open_indexed(dir.path())?;
+        let dlg: Arc<Delegation<Ed25519Did>> =
+            delegation_with(1, 2, DelegatedSubject::Any, "/a", None)?;
+        let sha256 = dlg.to_cid();
+        let sha512 = dlg.to_cid_with(&CidBuilder::new::<Sha2_512>());
+        let other_hashes = || -> TestResult<usize> {
+            Ok(lock(store.index.as_ref().ok_or("not indexed")?)?.other_hashes)
+        };
+
+        block_on(ListableStore::<Sendable, _, _>::insert_many_by_cid(
+            &store,
+            vec![(sha512, dlg.clone()), (sha512, dlg.clone())],
+        ))?;
+        assert_eq!(other_hashes()?, 1);
+        assert!(block_on(ListableStore::<Sendable, _, _>::contains(
+            &store, sha256
+        ))?);
+        block_on(ListableStore::<Sendable, _, _>::remove(&store, sha256))?;
+        assert_eq!(other_hashes()?, 0);
+
+        // Written behind the index's back: only the unindexed store scans for it
+        let bytes = serde_ipld_dagcbor::to_vec(&*dlg)?;
+        fs::write(dir.path().join(format!("{sha512}.cbor")), bytes)?;
+        assert!(!block_on(ListableStore::<Sendable, _, _>::contains(
+            &store, sha256
+        ))?);
+        let unindexed = FsStore::<Ed25519Did>::open(dir.path())?;
+        assert!(block_on(ListableStore::<Sendable, _, _>::contains(
+            &unindexed, sha256
+        ))?);
+
+        // Reopening rebuilds the index, which then counts the file
+        let store = FsStore::<Ed25519Did>::open_indexed(dir.path())?;
+        assert_eq!(
+            lock(store.index.as_ref().ok_or("not indexed")?)?.other_hashes,
+            1
+        );
+        assert!(block_on(ListableStore::<Sendable, _, _>::contains(
+            &store, sha256
+        ))?);
+        Ok(())
+    }
+}
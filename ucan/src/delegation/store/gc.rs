@@ -0,0 +1,329 @@
+//! Expiry garbage collection for delegation stores.
+//!
+//! Nothing removes a delegation from a store once its `exp` has passed.
+//! [`ExpiryCollector`] scans any [`DelegationStore`] page by page and removes
+//! delegations that expired more than a grace period ago, except those still
+//! needed as proofs by invocations the caller is keeping around.
+//!
+//! The collector does not own a timer: [`ExpiryCollector::run`] takes a
+//! stream of timestamps, so the caller decides when (and how often) to sweep.
+
+use alloc::{collections::BTreeSet, vec::Vec};
+use core::{borrow::Borrow, error::Error, time::Duration};
+
+use future_form::FutureForm;
+use futures::{Stream, StreamExt};
+use ipld_core::cid::Cid;
+use thiserror::Error;
+
+use crate::{
+    cid::CidBuilder, delegation::Delegation, did::Did, invocation::Invocation,
+    time::timestamp::Timestamp,
+};
+
+use super::{ListableStore, Page};
+
+/// The number of delegations fetched per page while scanning.
+const DEFAULT_BATCH_SIZE: usize = 256;
+
+/// Removes expired delegations from a store.
+///
+/// ```
+/// # use core::time::Duration;
+/// # use ucan::{delegation::store::gc::ExpiryCollector, did::Ed25519Did, invocation::Invocation};
+/// # fn example(pending: &[Invocation<Ed25519Did>]) {
+/// let collector = ExpiryCollector::new()
+///     .grace(Duration::from_secs(60 * 60))
+///     .retain_proofs_of(pending);
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExpiryCollector {
+    grace: Duration,
+    retained: BTreeSet<Cid>,
+    batch_size: usize,
+}
+
+impl ExpiryCollector {
+    /// A collector with no grace period that retains nothing.
+    #[must_use]
+    pub const fn new() -> Self {
+        ExpiryCollector {
+            grace: Duration::ZERO,
+            retained: BTreeSet::new(),
+            batch_size: DEFAULT_BATCH_SIZE,
+        }
+    }
+
+    /// Only remove delegations that expired at least `grace` ago.
+    ///
+    /// Sub-second precision is ignored, as timestamps are whole seconds.
+    #[must_use]
+    pub const fn grace(mut self, grace: Duration) -> Self {
+        self.grace = grace;
+        self
+    }
+
+    /// Never remove the delegation addressed by `cid`.
+    #[must_use]
+    pub fn retain(mut self, cid: Cid) -> Self {
+        self.retained.insert(cid);
+        self
+    }
+
+    /// Never remove the proofs of any of `invocations`.
+    #[must_use]
+    pub fn retain_proofs_of<'a, D: Did + 'a>(
+        mut self,
+        invocations: impl IntoIterator<Item = &'a Invocation<D>>,
+    ) -> Self {
+        for invocation in invocations {
+            self.retained.extend(invocation.proofs().iter().copied());
+        }
+        self
+    }
+
+    /// Fetch `batch_size` delegations per page while scanning (at least one).
+    #[must_use]
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Whether `delegation` expired at least the grace period before `now`.
+    #[must_use]
+    pub fn is_expired<D: Did>(&self, delegation: &Delegation<D>, now: Timestamp) -> bool {
+        delegation
+            .expiration()
+            .is_some_and(|exp| exp.to_unix().saturating_add(self.grace.as_secs()) <= now.to_unix())
+    }
+
+    /// Whether the delegation stored under `cid` is retained, under that CID
+    /// or an equivalent one with another hash.
+    fn is_retained<D: Did>(&self, cid: &Cid, delegation: &Delegation<D>) -> bool {
+        self.retained.contains(cid)
+            || self.retained.iter().any(|retained| {
+                retained.hash().code() != cid.hash().code()
+                    && CidBuilder::for_cid(retained)
+                        .is_some_and(|builder| delegation.to_cid_with(&builder) == *retained)
+            })
+    }
+
+    /// Remove every collectable delegation from `store`, as of `now`.
+    ///
+    /// # Errors
+    ///
+    /// If listing or removing fails. Delegations removed before the failure
+    /// stay removed.
+    pub async fn collect<
+        K: FutureForm,
+        D: Did,
+        T: Borrow<Delegation<D>>,
+        S: ListableStore<K, D, T>,
+    >(
+        &self,
+        store: &S,
+        now: Timestamp,
+    ) -> Result<GcReport, GcError<S::ListError, S::RemoveError>> {
+        let mut report = GcReport::default();
+        let mut page = Page::first(self.batch_size);
+
+        loop {
+            let entries = store.list(page).await.map_err(GcError::List)?;
+            let Some((last, _)) = entries.last() else {
+                break;
+            };
+            page = page.after(*last);
+            let full = entries.len() == self.batch_size;
+
+            for (cid, dlg) in entries {
+                report.scanned += 1;
+                let dlg: &Delegation<D> = dlg.borrow();
+                if !self.is_expired(dlg, now) {
+                    continue;
+                }
+                if self.is_retained(&cid, dlg) {
+                    report.retained.push(cid);
+                } else if store.remove(cid).await.map_err(GcError::Remove)?.is_some() {
+                    report.removed.push(cid);
+                }
+            }
+
+            if !full {
+                break;
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Run [`ExpiryCollector::collect`] at every timestamp yielded by
+    /// `schedule`, passing each outcome to `on_sweep`.
+    ///
+    /// Returns when `schedule` ends. A failed sweep does not stop later ones.
+    pub async fn run<K: FutureForm, D: Did, T: Borrow<Delegation<D>>, S: ListableStore<K, D, T>>(
+        &self,
+        store: &S,
+        schedule: impl Stream<Item = Timestamp>,
+        mut on_sweep: impl FnMut(Result<GcReport, GcError<S::ListError, S::RemoveError>>),
+    ) {
+        let mut schedule = core::pin::pin!(schedule);
+        while let Some(now) = schedule.next().await {
+            on_sweep(self.collect(store, now).await);
+        }
+    }
+}
+
+impl Default for ExpiryCollector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The outcome of one [`ExpiryCollector::collect`] sweep.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GcReport {
+    /// The number of delegations examined.
+    pub scanned: usize,
+
+    /// CIDs of the delegations removed.
+    pub removed: Vec<Cid>,
+
+    /// CIDs of expired delegations kept because they are retained.
+    pub retained: Vec<Cid>,
+}
+
+/// Errors from an [`ExpiryCollector`] sweep.
+#[derive(Debug, Error)]
+pub enum GcError<L: Error, R: Error> {
+    /// The store could not be listed.
+    #[error("unable to list delegations: {0}")]
+    List(L),
+
+    /// A delegation could not be removed.
+    #[error("unable to remove delegation: {0}")]
+    Remove(R),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        delegation::{
+            store::{insert_many, DelegationStore},
+            subject::DelegatedSubject,
+        },
+        did::Ed25519Did,
+        invocation::builder::InvocationBuilder,
+        test_support::{at, block_on, delegation_with, signer},
+    };
+    use alloc::{rc::Rc, vec};
+    use core::cell::RefCell;
+    use future_form::Local;
+    use testresult::TestResult;
+    use varsig::hash::Sha2_512;
+
+    type Store = Rc<RefCell<alloc::collections::BTreeMap<Cid, Rc<Delegation<Ed25519Did>>>>>;
+
+    /// Stores delegations expiring at 100, 200 and never.
+    fn store() -> TestResult<(Store, Vec<Cid>)> {
+        let store = Store::default();
+        let cids = block_on(insert_many::<Local, _, _, _>(
+            &store,
+            vec![
+                delegation_with(
+                    1,
+                    2,
+                    DelegatedSubject::Specific(*signer(1).did()),
+                    "/a",
+                    Some(100),
+                )?,
+                delegation_with(
+                    1,
+                    2,
+                    DelegatedSubject::Specific(*signer(1).did()),
+                    "/b",
+                    Some(200),
+                )?,
+                delegation_with(
+                    1,
+                    2,
+                    DelegatedSubject::Specific(*signer(1).did()),
+                    "/c",
+                    None,
+                )?,
+            ],
+        ))?;
+        Ok((store, cids))
+    }
+
+    #[test]
+    fn test_removes_expired_after_grace() -> TestResult {
+        let (store, cids) = store()?;
+        let collector = ExpiryCollector::new()
+            .grace(Duration::from_secs(50))
+            .batch_size(1);
+
+        let report = block_on(collector.collect(&store, at(149)?))?;
+        assert_eq!(
+            report,
+            GcReport {
+                scanned: 3,
+                ..GcReport::default()
+            }
+        );
+
+        let report = block_on(collector.collect(&store, at(150)?))?;
+        assert_eq!(report.removed, vec![*cids.first().ok_or("no cid")?]);
+
+        let report = block_on(collector.collect(&store, at(1_000)?))?;
+        assert_eq!(report.scanned, 2);
+        assert_eq!(report.removed, vec![*cids.get(1).ok_or("no cid")?]);
+        assert_eq!(block_on(ListableStore::<Local, _, _>::count(&store))?, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_retains_proofs() -> TestResult {
+        let (store, cids) = store()?;
+        let proof = block_on(DelegationStore::<Local, _, _>::get_all(
+            &store,
+            cids.get(..1).unwrap_or_default(),
+        ))?;
+        let invocation = InvocationBuilder::new()
+            .issuer(signer(2))
+            .audience(*signer(1).did())
+            .subject(*signer(1).did())
+            .command_from_str("/a")?
+            .proofs(vec![proof
+                .first()
+                .ok_or("no proof")?
+                .to_cid_with(&CidBuilder::new::<Sha2_512>())])
+            .try_build()?;
+
+        let report = block_on(
+            ExpiryCollector::new()
+                .retain_proofs_of([&invocation])
+                .collect(&store, at(1_000)?),
+        )?;
+        assert_eq!(report.retained, cids.get(..1).unwrap_or_default());
+        assert_eq!(report.removed, cids.get(1..2).unwrap_or_default());
+        Ok(())
+    }
+
+    #[test]
+    fn test_run_on_schedule() -> TestResult {
+        let (store, _) = store()?;
+        let mut removed = Vec::new();
+        block_on(ExpiryCollector::new().run(
+            &store,
+            futures::stream::iter([at(150)?, at(160)?, at(250)?]),
+            |sweep| removed.push(sweep.map(|report| report.removed.len())),
+        ));
+        assert_eq!(
+            removed.into_iter().collect::<Result<Vec<_>, _>>()?,
+            vec![1, 0, 1]
+        );
+        Ok(())
+    }
+}
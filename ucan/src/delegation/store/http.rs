@@ -0,0 +1,1063 @@
+//! Delegation stores shared over HTTP.
+//!
+//! [`HttpStoreServer`] exposes any [`DelegationStore`] over a small HTTP/1.1
+//! protocol, and [`HttpStoreClient`] is a [`DelegationStore`] that talks to it,
+//! so several services can share one proof repository. Bodies are DAG-CBOR.
+//!
+//! | Request | Body | Response |
+//! |---------|------|----------|
+//! | `GET /dlg/<cid>` | | The delegation, or `404` |
+//! | `POST /dlg/get` | Up to [`MAX_GET_CIDS`] CIDs, at most [`MAX_GET_REHASHED`] not SHA2-256 | List of delegations in the same order, or `404` with the first missing CID |
+//! | `PUT /dlg` | A delegation | `201` with its CID |
+//! | `HEAD /dlg/<cid>` | | `200`, or `404` |
+//! | `DELETE /dlg/<cid>` | | The removed delegation, or `404`; `405` unless [`HttpStoreServer::allow_remove`] |
+//! | `GET /dlg?after=<cid>&limit=<n>` | | One page of CIDs (both parameters optional) |
+//! | `GET /dlg/count` | | The number of delegations |
+//!
+//! The server rejects delegations whose signature does not verify, and the
+//! client checks every delegation it receives against the CID it asked for.
+//! Each request uses its own connection, and all I/O is blocking, including
+//! inside the returned futures. There is no TLS or authentication: serve on
+//! localhost or behind a proxy that provides them. Since any client could then
+//! delete proofs, the server refuses `DELETE` unless enabled.
+
+use alloc::{
+    collections::TryReserveError,
+    format,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use core::{borrow::Borrow, convert::Infallible, marker::PhantomData, time::Duration};
+use std::{
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Instant,
+};
+
+use future_form::{future_form, FutureForm, Local, Sendable};
+use futures::executor::block_on;
+use ipld_core::cid::Cid;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use varsig::{
+    hash::{Multihasher, Sha2_256},
+    verify::Verify,
+};
+
+use crate::{cid::CidBuilder, delegation::Delegation, did::Did};
+
+use super::{insert, DelegationStore, Found, ListableStore, Matches, Missing, Page};
+
+/// Media type of request and response bodies.
+pub const CONTENT_TYPE: &str = "application/vnd.ipld.dag-cbor";
+
+/// Largest body either side will read, in bytes.
+pub const MAX_BODY_LEN: usize = 1 << 24;
+
+/// Largest request or status line plus headers either side will read, in bytes.
+const MAX_HEAD_LEN: u64 = 16 << 10;
+
+/// Most CIDs the server looks up in one `POST /dlg/get`.
+pub const MAX_GET_CIDS: usize = 1 << 10;
+
+/// Most CIDs not hashed with SHA2-256 the server looks up in one
+/// `POST /dlg/get`, since a store may rehash every delegation to find each.
+pub const MAX_GET_REHASHED: usize = 16;
+
+/// Default limit on connections an [`HttpStoreServer`] serves at once.
+pub const DEFAULT_MAX_CONNECTIONS: usize = 64;
+
+/// Default time an [`HttpStoreServer`] allows for reading a request, and for
+/// each write of its response.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A response from [`HttpStoreServer::respond`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpResponse {
+    /// The HTTP status code.
+    pub status: u16,
+
+    /// The media type of `body`.
+    pub content_type: &'static str,
+
+    /// The response body.
+    pub body: Vec<u8>,
+}
+
+impl HttpResponse {
+    fn cbor<T: Serialize>(status: u16, value: &T) -> Self {
+        match serde_ipld_dagcbor::to_vec(value) {
+            Ok(body) => HttpResponse {
+                status,
+                content_type: CONTENT_TYPE,
+                body,
+            },
+            Err(e) => Self::text(500, &e.to_string()),
+        }
+    }
+
+    fn text(status: u16, message: &str) -> Self {
+        HttpResponse {
+            status,
+            content_type: "text/plain; charset=utf-8",
+            body: message.as_bytes().to_vec(),
+        }
+    }
+
+    const fn empty(status: u16) -> Self {
+        HttpResponse {
+            status,
+            content_type: CONTENT_TYPE,
+            body: Vec::new(),
+        }
+    }
+}
+
+/// Serves a [`DelegationStore`] over HTTP.
+///
+/// Requests are answered by blocking on the store's futures, so the store may
+/// be [`Local`] or [`Sendable`]; pick one with e.g. `serve::<Sendable, _, _>`.
+#[derive(Debug)]
+pub struct HttpStoreServer<S> {
+    store: S,
+    allow_remove: bool,
+    timeout: Duration,
+    max_connections: usize,
+    connections: AtomicUsize,
+}
+
+impl<S: Default> Default for HttpStoreServer<S> {
+    fn default() -> Self {
+        Self::new(S::default())
+    }
+}
+
+impl<S> HttpStoreServer<S> {
+    /// Serve `store`, refusing `DELETE`.
+    #[must_use]
+    pub const fn new(store: S) -> Self {
+        HttpStoreServer {
+            store,
+            allow_remove: false,
+            timeout: DEFAULT_TIMEOUT,
+            max_connections: DEFAULT_MAX_CONNECTIONS,
+            connections: AtomicUsize::new(0),
+        }
+    }
+
+    /// Whether to answer `DELETE /dlg/<cid>` by removing the delegation.
+    /// Otherwise it fails with `405`.
+    #[must_use]
+    pub const fn allow_remove(mut self, allow: bool) -> Self {
+        self.allow_remove = allow;
+        self
+    }
+
+    /// Close connections that take longer than `timeout` to send their
+    /// request, or stall that long writing the response (default
+    /// [`DEFAULT_TIMEOUT`]).
+    #[must_use]
+    pub const fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Serve at most `max` connections at once (default
+    /// [`DEFAULT_MAX_CONNECTIONS`]). Connections beyond that are closed
+    /// immediately.
+    #[must_use]
+    pub const fn with_max_connections(mut self, max: usize) -> Self {
+        self.max_connections = max;
+        self
+    }
+
+    /// The store being served.
+    #[must_use]
+    pub const fn store(&self) -> &S {
+        &self.store
+    }
+
+    /// Answer one request, e.g. from another HTTP server framework.
+    ///
+    /// `target` is the request path with its query string.
+    #[must_use]
+    pub fn respond<K, D, T>(&self, method: &str, target: &str, body: &[u8]) -> HttpResponse
+    where
+        K: FutureForm,
+        D: Did,
+        T: Borrow<Delegation<D>> + From<Delegation<D>>,
+        S: ListableStore<K, D, T>,
+        <D::VarsigConfig as Verify>::Signature: for<'de> Deserialize<'de>,
+    {
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        match (method, path) {
+            ("GET", "/dlg/count") => match block_on(self.store.count()) {
+                Ok(count) => HttpResponse::cbor(200, &count),
+                Err(e) => HttpResponse::text(500, &e.to_string()),
+            },
+            ("GET", "/dlg") => match parse_page(query) {
+                Some(page) => match block_on(self.store.list_cids(page)) {
+                    Ok(cids) => HttpResponse::cbor(200, &cids),
+                    Err(e) => HttpResponse::text(500, &e.to_string()),
+                },
+                None => HttpResponse::text(400, "invalid page"),
+            },
+            ("POST", "/dlg/get") => match serde_ipld_dagcbor::from_slice::<Vec<Cid>>(body) {
+                Ok(cids) if cids.len() > MAX_GET_CIDS => HttpResponse::text(400, "too many CIDs"),
+                Ok(cids)
+                    if cids.iter().filter(|cid| !is_canonical(cid)).count() > MAX_GET_REHASHED =>
+                {
+                    HttpResponse::text(400, "too many CIDs not hashed with SHA2-256")
+                }
+                Ok(cids) => match self.get(&cids) {
+                    Ok(dlgs) => {
+                        let dlgs: Vec<&Delegation<D>> = dlgs.iter().map(Borrow::borrow).collect();
+                        HttpResponse::cbor(200, &dlgs)
+                    }
+                    Err(response) => response,
+                },
+                Err(e) => HttpResponse::text(400, &e.to_string()),
+            },
+            ("PUT", "/dlg") => self.put(body),
+            (_, "/dlg" | "/dlg/get") => HttpResponse::text(405, "method not allowed"),
+            (method, path) => {
+                let Some(cid) = path
+                    .strip_prefix("/dlg/")
+                    .and_then(|cid| Cid::try_from(cid).ok())
+                else {
+                    return HttpResponse::text(404, "not found");
+                };
+                match method {
+                    "GET" => match self.get(&[cid]) {
+                        Ok(dlgs) => dlgs.first().map_or_else(
+                            || HttpResponse::empty(404),
+                            |dlg| HttpResponse::cbor(200, dlg.borrow()),
+                        ),
+                        Err(response) => response,
+                    },
+                    "HEAD" => match block_on(self.store.contains(cid)) {
+                        Ok(true) => HttpResponse::empty(200),
+                        Ok(false) => HttpResponse::empty(404),
+                        Err(e) => HttpResponse::text(500, &e.to_string()),
+                    },
+                    "DELETE" if self.allow_remove => match block_on(self.store.remove(cid)) {
+                        Ok(Some(dlg)) => HttpResponse::cbor(200, dlg.borrow()),
+                        Ok(None) => HttpResponse::empty(404),
+                        Err(e) => HttpResponse::text(500, &e.to_string()),
+                    },
+                    _ => HttpResponse::text(405, "method not allowed"),
+                }
+            }
+        }
+    }
+
+    /// Fetch `cids`, or the response explaining why not.
+    ///
+    /// One [`ListableStore::find_all`] tells a missing delegation apart from
+    /// a failing store, without looking anything up twice.
+    fn get<K, D, T>(&self, cids: &[Cid]) -> Result<Vec<T>, HttpResponse>
+    where
+        K: FutureForm,
+        D: Did,
+        T: Borrow<Delegation<D>>,
+        S: ListableStore<K, D, T>,
+    {
+        let found = block_on(self.store.find_all(cids))
+            .map_err(|e| HttpResponse::text(500, &e.to_string()))?;
+        cids.iter()
+            .zip(found)
+            .map(|(cid, hit)| {
+                hit.map(|(_, dlg)| dlg)
+                    .ok_or_else(|| HttpResponse::cbor(404, cid))
+            })
+            .collect()
+    }
+
+    fn put<K, D, T>(&self, body: &[u8]) -> HttpResponse
+    where
+        K: FutureForm,
+        D: Did,
+        T: Borrow<Delegation<D>> + From<Delegation<D>>,
+        S: ListableStore<K, D, T>,
+        <D::VarsigConfig as Verify>::Signature: for<'de> Deserialize<'de>,
+    {
+        let dlg: Delegation<D> = match serde_ipld_dagcbor::from_slice(body) {
+            Ok(dlg) => dlg,
+            Err(e) => return HttpResponse::text(400, &e.to_string()),
+        };
+        if dlg.verify_signature().is_err() {
+            return HttpResponse::text(400, "invalid signature");
+        }
+        match block_on(insert(&self.store, T::from(dlg))) {
+            Ok(cid) => HttpResponse::cbor(201, &cid),
+            Err(e) => HttpResponse::text(500, &e.to_string()),
+        }
+    }
+
+    /// Answer the request on `stream`, then close it.
+    ///
+    /// # Errors
+    ///
+    /// If the request cannot be read or the response cannot be written,
+    /// including within [`Self::with_timeout`].
+    pub fn handle_connection<K, D, T>(&self, stream: &TcpStream) -> Result<(), HttpStoreError>
+    where
+        K: FutureForm,
+        D: Did,
+        T: Borrow<Delegation<D>> + From<Delegation<D>>,
+        S: ListableStore<K, D, T>,
+        <D::VarsigConfig as Verify>::Signature: for<'de> Deserialize<'de>,
+    {
+        stream.set_read_timeout(Some(self.timeout))?;
+        stream.set_write_timeout(Some(self.timeout))?;
+        let mut reader = BufReader::new(Deadline {
+            stream,
+            deadline: Instant::now().checked_add(self.timeout),
+        });
+        let response = match read_message(&mut reader, true) {
+            Ok(request) => {
+                let mut parts = request.start.split(' ');
+                match (parts.next(), parts.next()) {
+                    (Some(method), Some(target)) => self.respond(method, target, &request.body),
+                    _ => HttpResponse::text(400, "malformed request line"),
+                }
+            }
+            Err(HttpStoreError::BodyTooLarge(_)) => HttpResponse::text(413, "body too large"),
+            Err(HttpStoreError::Malformed) => HttpResponse::text(400, "malformed request"),
+            Err(e) => return Err(e),
+        };
+        write_response(&mut &*stream, &response)
+    }
+
+    /// Accept connections on `listener`, one thread per connection.
+    ///
+    /// At most [`Self::with_max_connections`] connections are served at
+    /// once; further connections are closed without a response.
+    ///
+    /// # Errors
+    ///
+    /// If accepting a connection fails.
+    pub fn serve<K, D, T>(self: Arc<Self>, listener: &TcpListener) -> io::Result<()>
+    where
+        K: FutureForm,
+        D: Did,
+        T: Borrow<Delegation<D>> + From<Delegation<D>>,
+        S: ListableStore<K, D, T> + Send + Sync + 'static,
+        <D::VarsigConfig as Verify>::Signature: for<'de> Deserialize<'de>,
+    {
+        for stream in listener.incoming() {
+            let stream = stream?;
+            if self.connections.fetch_add(1, Ordering::AcqRel) >= self.max_connections {
+                self.connections.fetch_sub(1, Ordering::AcqRel);
+                tracing::warn!("delegation store at connection limit; closing connection");
+                continue;
+            }
+            let server = Arc::clone(&self);
+            std::thread::spawn(move || {
+                if let Err(e) = server.handle_connection::<K, D, T>(&stream) {
+                    tracing::warn!("delegation store connection failed: {e}");
+                }
+                server.connections.fetch_sub(1, Ordering::AcqRel);
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Whether `cid` uses the default CID hash, SHA2-256.
+const fn is_canonical(cid: &Cid) -> bool {
+    cid.hash().code() == Sha2_256::MULTIHASH_TAG
+}
+
+/// How many of `cids` fit in one `POST /dlg/get`.
+fn batch_len(cids: &[Cid]) -> usize {
+    let mut rehashed = 0;
+    cids.iter()
+        .take(MAX_GET_CIDS)
+        .take_while(|cid| {
+            rehashed += usize::from(!is_canonical(cid));
+            rehashed <= MAX_GET_REHASHED
+        })
+        .count()
+}
+
+/// Reads a stream until a deadline, however slowly the peer sends.
+///
+/// A read timeout alone only bounds each read, so a peer trickling bytes
+/// could hold a connection open indefinitely.
+struct Deadline<'a> {
+    stream: &'a TcpStream,
+    deadline: Option<Instant>,
+}
+
+impl Read for Deadline<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if let Some(deadline) = self.deadline {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(io::ErrorKind::TimedOut.into());
+            }
+            self.stream.set_read_timeout(Some(remaining))?;
+        }
+        let mut stream = self.stream;
+        stream.read(buf)
+    }
+}
+
+/// Parse `after=<cid>&limit=<n>`.
+fn parse_page(query: &str) -> Option<Page> {
+    let mut page = Page::all();
+    for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+        match pair.split_once('=')? {
+            ("after", cid) => page = page.after(Cid::try_from(cid).ok()?),
+            ("limit", limit) => page.limit = limit.parse().ok()?,
+            _ => return None,
+        }
+    }
+    Some(page)
+}
+
+/// The start line and body of an HTTP/1.1 message.
+#[derive(Debug)]
+struct Message {
+    start: String,
+    status: u16,
+    body: Vec<u8>,
+}
+
+/// Read one message, with a body only if `with_body` (i.e. not answering `HEAD`).
+fn read_message<R: BufRead>(reader: &mut R, with_body: bool) -> Result<Message, HttpStoreError> {
+    let mut head = reader.by_ref().take(MAX_HEAD_LEN);
+    let mut start = String::new();
+    if head.read_line(&mut start)? == 0 || !start.ends_with('\n') {
+        return Err(HttpStoreError::Malformed);
+    }
+
+    let mut len = 0;
+    loop {
+        let mut line = String::new();
+        if head.read_line(&mut line)? == 0 || !line.ends_with('\n') {
+            return Err(HttpStoreError::Malformed);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        let (name, value) = line.split_once(':').ok_or(HttpStoreError::Malformed)?;
+        if name.eq_ignore_ascii_case("content-length") {
+            len = value
+                .trim()
+                .parse()
+                .map_err(|_| HttpStoreError::Malformed)?;
+        }
+    }
+
+    if len > MAX_BODY_LEN {
+        return Err(HttpStoreError::BodyTooLarge(len));
+    }
+    // Grow with the data rather than trusting the declared length
+    let len = if with_body { len } else { 0 };
+    let mut body = Vec::new();
+    reader.take(len as u64).read_to_end(&mut body)?;
+    if body.len() < len {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+    }
+
+    let start = start.trim_end().to_string();
+    // Only meaningful for responses: `HTTP/1.1 200 OK`
+    let status = start
+        .split(' ')
+        .nth(1)
+        .and_then(|code| code.parse().ok())
+        .unwrap_or_default();
+    Ok(Message {
+        start,
+        status,
+        body,
+    })
+}
+
+fn write_response<W: Write>(writer: &mut W, response: &HttpResponse) -> Result<(), HttpStoreError> {
+    let reason = match response.status {
+        200 => "OK",
+        201 => "Created",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        413 => "Content Too Large",
+        _ => "Internal Server Error",
+    };
+    write!(
+        writer,
+        "HTTP/1.1 {} {reason}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status,
+        response.content_type,
+        response.body.len()
+    )?;
+    writer.write_all(&response.body)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// A [`DelegationStore`] served by an [`HttpStoreServer`].
+#[derive(Debug, Clone)]
+pub struct HttpStoreClient<D: Did> {
+    addrs: Vec<SocketAddr>,
+    timeout: Option<Duration>,
+    _did: PhantomData<fn() -> D>,
+}
+
+impl<D: Did> HttpStoreClient<D>
+where
+    <D::VarsigConfig as Verify>::Signature: for<'de> Deserialize<'de>,
+{
+    /// A client for the server at `addr`, e.g. `"127.0.0.1:8080"`.
+    ///
+    /// # Errors
+    ///
+    /// If `addr` cannot be resolved.
+    pub fn new<A: ToSocketAddrs>(addr: A) -> Result<Self, HttpStoreError> {
+        let addrs: Vec<SocketAddr> = addr.to_socket_addrs()?.collect();
+        if addrs.is_empty() {
+            return Err(io::Error::from(io::ErrorKind::AddrNotAvailable).into());
+        }
+        Ok(HttpStoreClient {
+            addrs,
+            timeout: None,
+            _did: PhantomData,
+        })
+    }
+
+    /// Give up on connecting, reading or writing after `timeout`.
+    #[must_use]
+    pub const fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    fn call(&self, method: &str, target: &str, body: &[u8]) -> Result<Message, HttpStoreError> {
+        let stream = match self.timeout {
+            Some(timeout) => self
+                .addrs
+                .iter()
+                .find_map(|addr| TcpStream::connect_timeout(addr, timeout).ok())
+                .ok_or_else(|| io::Error::from(io::ErrorKind::ConnectionRefused))?,
+            None => TcpStream::connect(self.addrs.as_slice())?,
+        };
+        stream.set_read_timeout(self.timeout)?;
+        stream.set_write_timeout(self.timeout)?;
+
+        let host = self
+            .addrs
+            .first()
+            .map(ToString::to_string)
+            .unwrap_or_default();
+        let mut writer = &stream;
+        write!(
+            writer,
+            "{method} {target} HTTP/1.1\r\nHost: {host}\r\nContent-Type: {CONTENT_TYPE}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            body.len()
+        )?;
+        writer.write_all(body)?;
+        writer.flush()?;
+
+        read_message(&mut BufReader::new(&stream), method != "HEAD")
+    }
+
+    /// Decode a successful response, or turn an error status into an error.
+    fn expect<T: for<'de> Deserialize<'de>>(
+        response: &Message,
+        status: u16,
+    ) -> Result<T, HttpStoreError> {
+        if response.status != status {
+            return Err(unexpected(response));
+        }
+        Ok(serde_ipld_dagcbor::from_slice(&response.body)?)
+    }
+
+    fn fetch(&self, cids: &[Cid]) -> Result<Vec<Arc<Delegation<D>>>, HttpStoreError> {
+        let mut dlgs = Vec::with_capacity(cids.len());
+        let mut rest = cids;
+        while let Some((batch, tail)) = rest.split_at_checked(batch_len(rest)) {
+            if batch.is_empty() {
+                break;
+            }
+            dlgs.extend(self.fetch_batch(batch)?);
+            rest = tail;
+        }
+        Ok(dlgs)
+    }
+
+    /// Fetch one batch of delegations that fits in a request (see [`batch_len`]).
+    fn fetch_batch(&self, cids: &[Cid]) -> Result<Vec<Arc<Delegation<D>>>, HttpStoreError> {
+        let response = self.call("POST", "/dlg/get", &serde_ipld_dagcbor::to_vec(cids)?)?;
+        if response.status == 404 {
+            let missing: Cid = serde_ipld_dagcbor::from_slice(&response.body)?;
+            return Err(Missing(missing).into());
+        }
+        let dlgs: Vec<Delegation<D>> = Self::expect(&response, 200)?;
+        if dlgs.len() != cids.len() {
+            return Err(HttpStoreError::Malformed);
+        }
+
+        cids.iter()
+            .zip(dlgs)
+            .map(|(cid, dlg)| {
+                let builder =
+                    CidBuilder::for_cid(cid).ok_or(HttpStoreError::UnsupportedHash(*cid))?;
+                if dlg.to_cid_with(&builder) != *cid {
+                    return Err(HttpStoreError::CidMismatch(*cid));
+                }
+                Ok(Arc::new(dlg))
+            })
+            .collect()
+    }
+
+    fn put(&self, cid: Cid, delegation: &Delegation<D>) -> Result<(), HttpStoreError> {
+        let bytes = serde_ipld_dagcbor::to_vec(delegation)?;
+        let builder = CidBuilder::for_cid(&cid).ok_or(HttpStoreError::UnsupportedHash(cid))?;
+        if builder.build_from_dagcbor(&bytes) != cid {
+            return Err(HttpStoreError::CidMismatch(cid));
+        }
+        let response = self.call("PUT", "/dlg", &bytes)?;
+        Self::expect::<Cid>(&response, 201)?;
+        Ok(())
+    }
+
+    fn list_page(&self, page: Page) -> Result<Vec<Cid>, HttpStoreError> {
+        let mut target = format!("/dlg?limit={}", page.limit);
+        if let Some(after) = page.after {
+            target = format!("{target}&after={after}");
+        }
+        Self::expect(&self.call("GET", &target, &[])?, 200)
+    }
+}
+
+fn unexpected(response: &Message) -> HttpStoreError {
+    HttpStoreError::Status(
+        response.status,
+        String::from_utf8_lossy(&response.body).into_owned(),
+    )
+}
+
+#[future_form(
+    Local,
+    Sendable where
+        D: Send + Sync,
+        <D as Did>::VarsigConfig: Send + Sync,
+        <<D as Did>::VarsigConfig as Verify>::Signature: Send + Sync
+)]
+impl<K: FutureForm, D: Did> DelegationStore<K, D, Arc<Delegation<D>>> for HttpStoreClient<D>
+where
+    <D::VarsigConfig as Verify>::Signature: for<'de> Deserialize<'de>,
+{
+    type InsertError = HttpStoreError;
+    type GetError = HttpStoreError;
+
+    fn get_all<'a>(
+        &'a self,
+        cid: &'a [Cid],
+    ) -> K::Future<'a, Result<Vec<Arc<Delegation<D>>>, Self::GetError>> {
+        K::from_future(async move { self.fetch(cid) })
+    }
+
+    fn insert_by_cid(
+        &self,
+        cid: Cid,
+        delegation: Arc<Delegation<D>>,
+    ) -> K::Future<'_, Result<(), Self::InsertError>> {
+        K::from_future(async move { self.put(cid, &delegation) })
+    }
+}
+
+#[future_form(
+    Local,
+    Sendable where
+        D: Send + Sync,
+        <D as Did>::VarsigConfig: Send + Sync,
+        <<D as Did>::VarsigConfig as Verify>::Signature: Send + Sync
+)]
+impl<K: FutureForm, D: Did> ListableStore<K, D, Arc<Delegation<D>>> for HttpStoreClient<D>
+where
+    <D::VarsigConfig as Verify>::Signature: for<'de> Deserialize<'de>,
+{
+    type ListError = HttpStoreError;
+    type RemoveError = HttpStoreError;
+
+    /// The server does not report the keys it stores delegations under, so
+    /// each is returned under the requested CID. One missing CID fails the
+    /// whole batch on the server, so the batch is then retried one CID at a
+    /// time.
+    fn find_all<'a>(
+        &'a self,
+        cid: &'a [Cid],
+    ) -> K::Future<'a, Result<Found<Arc<Delegation<D>>>, Self::GetError>> {
+        K::from_future(async move {
+            match self.fetch(cid) {
+                Ok(dlgs) => return Ok(cid.iter().copied().zip(dlgs).map(Some).collect()),
+                Err(HttpStoreError::Missing(_)) => {}
+                Err(e) => return Err(e),
+            }
+            cid.iter()
+                .map(|c| match self.fetch(core::slice::from_ref(c)) {
+                    Ok(mut dlgs) => Ok(dlgs.pop().map(|dlg| (*c, dlg))),
+                    Err(HttpStoreError::Missing(_)) => Ok(None),
+                    Err(e) => Err(e),
+                })
+                .collect()
+        })
+    }
+
+    fn insert_many_by_cid(
+        &self,
+        delegations: Vec<(Cid, Arc<Delegation<D>>)>,
+    ) -> K::Future<'_, Result<(), Self::InsertError>> {
+        K::from_future(async move {
+            for (cid, delegation) in delegations {
+                self.put(cid, &delegation)?;
+            }
+            Ok(())
+        })
+    }
+
+    fn remove(
+        &self,
+        cid: Cid,
+    ) -> K::Future<'_, Result<Option<Arc<Delegation<D>>>, Self::RemoveError>> {
+        K::from_future(async move {
+            let response = self.call("DELETE", &format!("/dlg/{cid}"), &[])?;
+            if response.status == 404 {
+                return Ok(None);
+            }
+            Ok(Some(Arc::new(Self::expect(&response, 200)?)))
+        })
+    }
+
+    fn contains(&self, cid: Cid) -> K::Future<'_, Result<bool, Self::ListError>> {
+        K::from_future(async move {
+            let response = self.call("HEAD", &format!("/dlg/{cid}"), &[])?;
+            match response.status {
+                200 => Ok(true),
+                404 => Ok(false),
+                _ => Err(unexpected(&response)),
+            }
+        })
+    }
+
+    fn count(&self) -> K::Future<'_, Result<usize, Self::ListError>> {
+        K::from_future(async move { Self::expect(&self.call("GET", "/dlg/count", &[])?, 200) })
+    }
+
+    fn list_cids(&self, page: Page) -> K::Future<'_, Result<Vec<Cid>, Self::ListError>> {
+        K::from_future(async move { self.list_page(page) })
+    }
+
+    fn list(
+        &self,
+        page: Page,
+    ) -> K::Future<'_, Result<Matches<Arc<Delegation<D>>>, Self::ListError>> {
+        K::from_future(async move {
+            let cids = self.list_page(page)?;
+            let dlgs = self.fetch(&cids)?;
+            Ok(cids.into_iter().zip(dlgs).collect())
+        })
+    }
+}
+
+/// Errors from an [`HttpStoreClient`] or [`HttpStoreServer`].
+#[derive(Debug, Error)]
+pub enum HttpStoreError {
+    /// Socket I/O failed.
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    /// A body could not be encoded.
+    #[error("unable to encode body: {0}")]
+    Encode(#[from] serde_ipld_dagcbor::EncodeError<TryReserveError>),
+
+    /// A body could not be decoded.
+    #[error("unable to decode body: {0}")]
+    Decode(#[from] serde_ipld_dagcbor::DecodeError<Infallible>),
+
+    /// A message is not valid HTTP/1.1, or not a response this protocol allows.
+    #[error("malformed HTTP message")]
+    Malformed,
+
+    /// A body exceeded [`MAX_BODY_LEN`].
+    #[error("body of {0} bytes exceeds the maximum")]
+    BodyTooLarge(usize),
+
+    /// The server answered with an unexpected status.
+    #[error("server responded {0}: {1}")]
+    Status(u16, String),
+
+    /// The server returned a delegation that the requested CID does not address.
+    #[error("server returned a delegation that does not match CID {0}")]
+    CidMismatch(Cid),
+
+    /// The CID's hash is not compiled in.
+    #[error("unsupported hash in CID {0}")]
+    UnsupportedHash(Cid),
+
+    /// A requested delegation is not stored.
+    #[error(transparent)]
+    Missing(#[from] Missing),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        delegation::store::{insert_many, ShardedStore},
+        did::Ed25519Did,
+        test_support::{block_on, delegation},
+    };
+    use testresult::TestResult;
+    use varsig::hash::Sha2_512;
+
+    type Client = HttpStoreClient<Ed25519Did>;
+
+    type Server = HttpStoreServer<ShardedStore<Ed25519Did>>;
+
+    /// Serve a fresh store that allows removal on an ephemeral localhost port.
+    fn serve() -> TestResult<(Arc<Server>, Client)> {
+        serve_with(HttpStoreServer::new(ShardedStore::new()).allow_remove(true))
+    }
+
+    fn serve_with(server: Server) -> TestResult<(Arc<Server>, Client)> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let client = Client::new(listener.local_addr()?)?.with_timeout(Duration::from_secs(10));
+        let server = Arc::new(server);
+        let serving = Arc::clone(&server);
+        std::thread::spawn(move || {
+            serving.serve::<Sendable, Ed25519Did, Arc<Delegation<Ed25519Did>>>(&listener)
+        });
+        Ok((server, client))
+    }
+
+    #[test]
+    fn test_round_trip() -> TestResult {
+        let (server, client) = serve()?;
+        let dlgs = vec![delegation("/a")?, delegation("/b")?, delegation("/c")?];
+        let mut cids = block_on(insert_many::<Sendable, _, _, _>(&client, dlgs.clone()))?;
+        assert_eq!(
+            block_on(ListableStore::<Sendable, _, _>::count(server.store()))?,
+            3
+        );
+
+        let found = block_on(DelegationStore::<Sendable, _, _>::get_all(&client, &cids))?;
+        assert_eq!(found.iter().map(|d| d.to_cid()).collect::<Vec<_>>(), cids);
+
+        let first = dlgs.first().ok_or("no delegations")?;
+        let sha512 = first.to_cid_with(&CidBuilder::new::<Sha2_512>());
+        let found = block_on(DelegationStore::<Local, _, _>::get_all(&client, &[sha512]))?;
+        assert_eq!(found.first().map(|d| d.to_cid()), Some(first.to_cid()));
+
+        cids.sort();
+        let page = block_on(ListableStore::<Sendable, _, _>::list(
+            &client,
+            Page::first(2),
+        ))?;
+        assert_eq!(
+            page.iter()
+                .map(|(cid, _)| *cid)
+                .collect::<Vec<_>>()
+                .as_slice(),
+            cids.get(..2).unwrap_or_default()
+        );
+        let rest = block_on(ListableStore::<Sendable, _, _>::list_cids(
+            &client,
+            Page::all().after(*cids.get(1).ok_or("no cid")?),
+        ))?;
+        assert_eq!(rest.as_slice(), cids.get(2..).unwrap_or_default());
+
+        let removed = *cids.first().ok_or("no cid")?;
+        assert!(block_on(ListableStore::<Sendable, _, _>::remove(&client, removed))?.is_some());
+        assert!(block_on(ListableStore::<Sendable, _, _>::remove(&client, removed))?.is_none());
+        assert!(!block_on(ListableStore::<Sendable, _, _>::contains(
+            &client, removed
+        ))?);
+        assert!(matches!(
+            block_on(DelegationStore::<Sendable, _, _>::get_all(&client, &cids)),
+            Err(HttpStoreError::Missing(Missing(m))) if m == removed
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_single_get() -> TestResult {
+        let (server, client) = serve()?;
+        let cid = block_on(insert::<Sendable, _, _, _>(
+            server.store(),
+            delegation("/a")?,
+        ))?;
+
+        let response = client.call("GET", &format!("/dlg/{cid}"), &[])?;
+        assert_eq!(response.status, 200);
+        assert_eq!(
+            CidBuilder::for_cid(&cid).map(|b| b.build_from_dagcbor(&response.body)),
+            Some(cid)
+        );
+
+        let missing = delegation::<Delegation<Ed25519Did>>("/b")?.to_cid();
+        assert_eq!(
+            client.call("GET", &format!("/dlg/{missing}"), &[])?.status,
+            404
+        );
+        assert_eq!(client.call("GET", "/dlg/not-a-cid", &[])?.status, 404);
+        assert_eq!(client.call("PATCH", "/dlg", &[])?.status, 405);
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejects_bad_input() -> TestResult {
+        let (server, client) = serve()?;
+        let dlg: Arc<Delegation<Ed25519Did>> = delegation("/a")?;
+
+        // A delegation filed under the wrong CID never leaves the client
+        let wrong = delegation::<Delegation<Ed25519Did>>("/b")?.to_cid();
+        assert!(matches!(
+            block_on(DelegationStore::<Sendable, _, _>::insert_by_cid(&client, wrong, dlg)),
+            Err(HttpStoreError::CidMismatch(c)) if c == wrong
+        ));
+
+        let response = client.call("PUT", "/dlg", b"not cbor")?;
+        assert_eq!(response.status, 400);
+        assert_eq!(
+            block_on(ListableStore::<Sendable, _, _>::count(server.store()))?,
+            0
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_refuses_delete_by_default() -> TestResult {
+        let (server, client) = serve_with(HttpStoreServer::new(ShardedStore::new()))?;
+        let cid = block_on(insert::<Sendable, _, _, _>(
+            server.store(),
+            delegation("/a")?,
+        ))?;
+
+        assert!(matches!(
+            block_on(ListableStore::<Sendable, _, _>::remove(&client, cid)),
+            Err(HttpStoreError::Status(405, _))
+        ));
+        assert!(block_on(ListableStore::<Sendable, _, _>::contains(
+            server.store(),
+            cid
+        ))?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_caps_cids_per_request() -> TestResult {
+        let (server, client) = serve()?;
+        let cid = block_on(insert::<Sendable, _, _, _>(
+            server.store(),
+            delegation("/a")?,
+        ))?;
+        let cids = vec![cid; MAX_GET_CIDS + 1];
+
+        let response = client.call("POST", "/dlg/get", &serde_ipld_dagcbor::to_vec(&cids)?)?;
+        assert_eq!(response.status, 400);
+
+        // The client splits larger lookups into several requests
+        let found = block_on(DelegationStore::<Sendable, _, _>::get_all(&client, &cids))?;
+        assert_eq!(found.len(), cids.len());
+        Ok(())
+    }
+
+    #[test]
+    fn test_caps_rehashed_cids_per_request() -> TestResult {
+        let (server, client) = serve()?;
+        let dlg: Arc<Delegation<Ed25519Did>> = delegation("/a")?;
+        block_on(insert::<Sendable, _, _, _>(server.store(), dlg.clone()))?;
+        let sha512 = dlg.to_cid_with(&CidBuilder::new::<Sha2_512>());
+        let cids = vec![sha512; MAX_GET_REHASHED + 1];
+
+        let response = client.call("POST", "/dlg/get", &serde_ipld_dagcbor::to_vec(&cids)?)?;
+        assert_eq!(response.status, 400);
+
+        assert_eq!(batch_len(&cids), MAX_GET_REHASHED);
+        let found = block_on(DelegationStore::<Sendable, _, _>::get_all(&client, &cids))?;
+        assert_eq!(found.len(), cids.len());
+        Ok(())
+    }
+
+    #[test]
+    fn test_connection_limit() -> TestResult {
+        let (_, client) =
+            serve_with(HttpStoreServer::new(ShardedStore::new()).with_max_connections(0))?;
+        assert!(block_on(ListableStore::<Sendable, _, _>::count(&client)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_closes_stalled_connections() -> TestResult {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let stream = TcpStream::connect(listener.local_addr()?)?;
+        stream.set_read_timeout(Some(Duration::from_secs(10)))?;
+        let server = Arc::new(
+            HttpStoreServer::new(ShardedStore::<Ed25519Did>::new())
+                .with_timeout(Duration::from_millis(50)),
+        );
+        std::thread::spawn(move || {
+            server.serve::<Sendable, Ed25519Did, Arc<Delegation<Ed25519Did>>>(&listener)
+        });
+
+        // Send nothing: the server gives up and closes without a response
+        let mut response = Vec::new();
+        (&stream).read_to_end(&mut response)?;
+        assert!(response.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_closes_slow_connections() -> TestResult {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let stream = TcpStream::connect(listener.local_addr()?)?;
+        stream.set_read_timeout(Some(Duration::from_secs(10)))?;
+        let server = Arc::new(
+            HttpStoreServer::new(ShardedStore::<Ed25519Did>::new())
+                .with_timeout(Duration::from_millis(200)),
+        );
+        std::thread::spawn(move || {
+            server.serve::<Sendable, Ed25519Did, Arc<Delegation<Ed25519Did>>>(&listener)
+        });
+
+        // Trickle a header faster than the timeout: only the deadline closes it
+        let start = Instant::now();
+        let stop = std::sync::atomic::AtomicBool::new(false);
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                (&stream).write_all(b"GET /dlg/count HTTP/1.1\r\nX-Slow: ")?;
+                while !stop.load(Ordering::Acquire) {
+                    (&stream).write_all(b"x")?;
+                    std::thread::sleep(Duration::from_millis(20));
+                }
+                io::Result::Ok(())
+            });
+            let mut response = Vec::new();
+            let read = (&stream).read_to_end(&mut response);
+            stop.store(true, Ordering::Release);
+            // The server may reset the connection rather than close it
+            assert!(
+                matches!(read, Ok(0))
+                    || matches!(read, Err(e) if e.kind() == io::ErrorKind::ConnectionReset)
+            );
+        });
+        assert!(start.elapsed() < Duration::from_secs(5));
+        Ok(())
+    }
+
+    #[test]
+    fn test_short_body() {
+        let message = b"PUT /dlg HTTP/1.1\r\nContent-Length: 16000000\r\n\r\nshort";
+        assert!(matches!(
+            read_message(&mut &message[..], true),
+            Err(HttpStoreError::Io(e)) if e.kind() == io::ErrorKind::UnexpectedEof
+        ));
+    }
+}
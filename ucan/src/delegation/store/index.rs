@@ -0,0 +1,432 @@
+//! Querying delegation stores.
+
+use alloc::rc::Rc;
+use core::{borrow::Borrow, cell::RefCell, convert::Infallible, error::Error};
+
+use alloc::collections::BTreeMap;
+use future_form::{FutureForm, Local};
+use ipld_core::cid::Cid;
+
+use crate::{
+    command::Command,
+    delegation::{subject::DelegatedSubject, Delegation},
+    did::Did,
+    time::timestamp::Timestamp,
+};
+
+use super::{DelegationStore, Matches};
+
+#[cfg(feature = "std")]
+use {
+    super::StorePoisoned,
+    alloc::sync::Arc,
+    future_form::{future_form, Sendable},
+    std::{collections::HashMap, hash::BuildHasher, sync::Mutex},
+    varsig::verify::Verify,
+};
+
+/// Criteria for finding delegations in a [`DelegationIndex`].
+///
+/// Every criterion that is set must match. An empty query matches everything.
+///
+/// ```
+/// # use ucan::{command::Command, delegation::store::DelegationQuery, did::Ed25519Did, time::timestamp::Timestamp};
+/// # fn example(me: Ed25519Did, doc: Ed25519Did) -> Result<(), Box<dyn core::error::Error>> {
+/// // Which delegations let me write to `doc` right now?
+/// let query = DelegationQuery::new()
+///     .audience(me)
+///     .subject(doc.into())
+///     .grants(Command::parse("/crud/write")?)
+///     .valid_at(Timestamp::now());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct DelegationQuery<D: Did> {
+    issuer: Option<D>,
+    audience: Option<D>,
+    subject: Option<DelegatedSubject<D>>,
+    command_prefix: Option<Command>,
+    grants: Option<Command>,
+    valid_at: Option<Timestamp>,
+}
+
+impl<D: Did> DelegationQuery<D> {
+    /// A query that matches every delegation.
+    #[must_use]
+    pub const fn new() -> Self {
+        DelegationQuery {
+            issuer: None,
+            audience: None,
+            subject: None,
+            command_prefix: None,
+            grants: None,
+            valid_at: None,
+        }
+    }
+
+    /// Only delegations issued by `issuer`.
+    #[must_use]
+    pub fn issuer(mut self, issuer: D) -> Self {
+        self.issuer = Some(issuer);
+        self
+    }
+
+    /// Only delegations to `audience`.
+    #[must_use]
+    pub fn audience(mut self, audience: D) -> Self {
+        self.audience = Some(audience);
+        self
+    }
+
+    /// Only delegations that apply to `subject`.
+    ///
+    /// A [`DelegatedSubject::Specific`] subject matches delegations for that
+    /// subject and powerline (`Any`) delegations, since both grant authority
+    /// over it. [`DelegatedSubject::Any`] matches only powerline delegations.
+    #[must_use]
+    pub fn subject(mut self, subject: DelegatedSubject<D>) -> Self {
+        self.subject = Some(subject);
+        self
+    }
+
+    /// Only delegations whose command is `prefix` or below it,
+    /// e.g. `/crud` matches `/crud/read` and `/crud/write`.
+    #[must_use]
+    pub fn command_prefix(mut self, prefix: Command) -> Self {
+        self.command_prefix = Some(prefix);
+        self
+    }
+
+    /// Only delegations that grant `command`, i.e. whose command is
+    /// `command` or one of its ancestors, e.g. `/` and `/crud` grant `/crud/write`.
+    #[must_use]
+    pub fn grants(mut self, command: Command) -> Self {
+        self.grants = Some(command);
+        self
+    }
+
+    /// Only delegations that are neither expired nor not yet valid at `time`.
+    #[must_use]
+    pub const fn valid_at(mut self, time: Timestamp) -> Self {
+        self.valid_at = Some(time);
+        self
+    }
+
+    /// The issuer criterion, if set.
+    #[cfg(feature = "sqlite")]
+    pub(crate) const fn issuer_criterion(&self) -> Option<&D> {
+        self.issuer.as_ref()
+    }
+
+    /// The audience criterion, if set.
+    #[cfg(feature = "std")]
+    pub(crate) const fn audience_criterion(&self) -> Option<&D> {
+        self.audience.as_ref()
+    }
+
+    /// The subject criterion, if set.
+    #[cfg(feature = "sqlite")]
+    pub(crate) const fn subject_criterion(&self) -> Option<&DelegatedSubject<D>> {
+        self.subject.as_ref()
+    }
+
+    /// The command prefix criterion, if set.
+    #[cfg(feature = "sqlite")]
+    pub(crate) const fn command_prefix_criterion(&self) -> Option<&Command> {
+        self.command_prefix.as_ref()
+    }
+
+    /// The granted command criterion, if set.
+    #[cfg(feature = "sqlite")]
+    pub(crate) const fn grants_criterion(&self) -> Option<&Command> {
+        self.grants.as_ref()
+    }
+
+    /// The validity time criterion, if set.
+    #[cfg(feature = "sqlite")]
+    pub(crate) const fn valid_at_criterion(&self) -> Option<Timestamp> {
+        self.valid_at
+    }
+
+    /// Whether `command` satisfies the command criteria of this query.
+    pub(crate) fn command_matches(&self, command: &Command) -> bool {
+        self.command_prefix
+            .as_ref()
+            .is_none_or(|prefix| command.starts_with(prefix))
+            && self
+                .grants
+                .as_ref()
+                .is_none_or(|granted| granted.starts_with(command))
+    }
+
+    /// Whether `delegation` satisfies every criterion of this query.
+    pub fn matches(&self, delegation: &Delegation<D>) -> bool {
+        self.issuer
+            .as_ref()
+            .is_none_or(|issuer| delegation.issuer() == issuer)
+            && self
+                .audience
+                .as_ref()
+                .is_none_or(|audience| delegation.audience() == audience)
+            && self.subject.as_ref().is_none_or(|subject| match subject {
+                DelegatedSubject::Specific(did) => delegation.subject().allows(did),
+                DelegatedSubject::Any => *delegation.subject() == DelegatedSubject::Any,
+            })
+            && self.command_matches(delegation.command())
+            && self.valid_at.is_none_or(|time| {
+                delegation.expiration().is_none_or(|exp| time < exp)
+                    && delegation.not_before().is_none_or(|nbf| time >= nbf)
+            })
+    }
+}
+
+impl<D: Did> Default for DelegationQuery<D> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A [`DelegationStore`] that can be searched with a [`DelegationQuery`].
+pub trait DelegationIndex<K: FutureForm, D: Did, T: Borrow<Delegation<D>>>:
+    DelegationStore<K, D, T>
+{
+    /// Error type for query operations.
+    type QueryError: Error;
+
+    /// Retrieves every delegation matching `query`, with the CID it is stored under.
+    ///
+    /// The order of the results is unspecified.
+    fn query<'a>(
+        &'a self,
+        query: &'a DelegationQuery<D>,
+    ) -> K::Future<'a, Result<Matches<T>, Self::QueryError>>;
+}
+
+/// Collect the entries of a map-backed store that match `query`.
+pub(super) fn scan<'a, D: Did + 'a, T: Borrow<Delegation<D>> + Clone + 'a>(
+    entries: impl IntoIterator<Item = (&'a Cid, &'a T)>,
+    query: &DelegationQuery<D>,
+) -> Matches<T> {
+    entries
+        .into_iter()
+        .filter(|(_, dlg)| query.matches((*dlg).borrow()))
+        .map(|(cid, dlg)| (*cid, dlg.clone()))
+        .collect()
+}
+
+// ---------------------------------------------------------------------------
+// no_std: Rc<RefCell<BTreeMap>> store
+// ---------------------------------------------------------------------------
+
+impl<D: Did> DelegationIndex<Local, D, Rc<Delegation<D>>>
+    for Rc<RefCell<BTreeMap<Cid, Rc<Delegation<D>>>>>
+{
+    type QueryError = Infallible;
+
+    fn query<'a>(
+        &'a self,
+        query: &'a DelegationQuery<D>,
+    ) -> <Local as FutureForm>::Future<'a, Result<Matches<Rc<Delegation<D>>>, Self::QueryError>>
+    {
+        Local::from_future(async move { Ok(scan(RefCell::borrow(self).iter(), query)) })
+    }
+}
+
+// ---------------------------------------------------------------------------
+// std: Rc<RefCell<HashMap>> store
+// ---------------------------------------------------------------------------
+
+#[cfg(feature = "std")]
+impl<D: Did, H: BuildHasher> DelegationIndex<Local, D, Rc<Delegation<D>>>
+    for Rc<RefCell<HashMap<Cid, Rc<Delegation<D>>, H>>>
+{
+    type QueryError = Infallible;
+
+    fn query<'a>(
+        &'a self,
+        query: &'a DelegationQuery<D>,
+    ) -> <Local as FutureForm>::Future<'a, Result<Matches<Rc<Delegation<D>>>, Self::QueryError>>
+    {
+        Local::from_future(async move { Ok(scan(RefCell::borrow(self).iter(), query)) })
+    }
+}
+
+// ---------------------------------------------------------------------------
+// std: Arc<Mutex<HashMap>> store (Send + !Send variants)
+// ---------------------------------------------------------------------------
+
+#[cfg(feature = "std")]
+#[future_form(
+    Local,
+    Sendable where
+        D: Send + Sync,
+        H: Send,
+        <D as Did>::VarsigConfig: Send + Sync,
+        <<D as Did>::VarsigConfig as Verify>::Signature: Send + Sync
+)]
+impl<K: FutureForm, D: Did, H: BuildHasher> DelegationIndex<K, D, Arc<Delegation<D>>>
+    for Arc<Mutex<HashMap<Cid, Arc<Delegation<D>>, H>>>
+{
+    type QueryError = StorePoisoned;
+
+    fn query<'a>(
+        &'a self,
+        query: &'a DelegationQuery<D>,
+    ) -> K::Future<'a, Result<Matches<Arc<Delegation<D>>>, Self::QueryError>> {
+        K::from_future(async move {
+            let locked = self.lock().map_err(|_| StorePoisoned)?;
+            Ok(scan(locked.iter(), query))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        delegation::store::insert,
+        did::Ed25519Did,
+        test_support::{block_on, delegation_with, signer},
+    };
+    use testresult::TestResult;
+
+    fn command(s: &str) -> TestResult<Command> {
+        Ok(Command::parse(s)?)
+    }
+
+    type Store = Rc<RefCell<BTreeMap<Cid, Rc<Delegation<Ed25519Did>>>>>;
+
+    fn fixture() -> TestResult<(Store, [Cid; 4])> {
+        let store = Store::default();
+        let doc = *signer(9).did();
+        let add = |dlg: Delegation<Ed25519Did>| block_on(insert(&store, Rc::new(dlg)));
+        let cids = [
+            add(delegation_with(1, 2, doc.into(), "/crud/write", None)?)?,
+            add(delegation_with(1, 2, doc.into(), "/crud/read", Some(100))?)?,
+            add(delegation_with(1, 2, DelegatedSubject::Any, "/crud", None)?)?,
+            add(delegation_with(3, 4, doc.into(), "/", None)?)?,
+        ];
+        Ok((store, cids))
+    }
+
+    fn run(store: &Store, query: &DelegationQuery<Ed25519Did>) -> TestResult<Vec<Cid>> {
+        let mut cids: Vec<Cid> = block_on(store.query(query))?
+            .into_iter()
+            .map(|(cid, _)| cid)
+            .collect();
+        cids.sort();
+        Ok(cids)
+    }
+
+    fn sorted<const N: usize>(mut cids: [Cid; N]) -> Vec<Cid> {
+        cids.sort();
+        cids.to_vec()
+    }
+
+    #[test]
+    fn test_query_by_principal() -> TestResult {
+        let (store, [write, read, any, root]) = fixture()?;
+        assert_eq!(run(&store, &DelegationQuery::new())?.len(), 4);
+        assert_eq!(
+            run(&store, &DelegationQuery::new().audience(*signer(2).did()))?,
+            sorted([write, read, any])
+        );
+        assert_eq!(
+            run(&store, &DelegationQuery::new().issuer(*signer(3).did()))?,
+            vec![root]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_query_by_subject() -> TestResult {
+        let (store, [write, read, any, root]) = fixture()?;
+        let doc = *signer(9).did();
+        assert_eq!(
+            run(&store, &DelegationQuery::new().subject(doc.into()))?,
+            sorted([write, read, any, root])
+        );
+        assert_eq!(
+            run(
+                &store,
+                &DelegationQuery::new().subject(DelegatedSubject::Specific(*signer(8).did()))
+            )?,
+            vec![any]
+        );
+        assert_eq!(
+            run(
+                &store,
+                &DelegationQuery::new().subject(DelegatedSubject::Any)
+            )?,
+            vec![any]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_query_by_command() -> TestResult {
+        let (store, [write, read, any, root]) = fixture()?;
+        assert_eq!(
+            run(
+                &store,
+                &DelegationQuery::new().command_prefix(command("/crud")?)
+            )?,
+            sorted([write, read, any])
+        );
+        assert_eq!(
+            run(
+                &store,
+                &DelegationQuery::new().grants(command("/crud/write")?)
+            )?,
+            sorted([write, any, root])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_query_valid_at() -> TestResult {
+        let (store, [write, read, any, root]) = fixture()?;
+        let at = |secs: u64| -> TestResult<DelegationQuery<Ed25519Did>> {
+            Ok(DelegationQuery::new().valid_at(Timestamp::try_from(i128::from(secs))?))
+        };
+        assert_eq!(run(&store, &at(99)?)?, sorted([write, read, any, root]));
+        assert_eq!(run(&store, &at(100)?)?, sorted([write, any, root]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_query_combined() -> TestResult {
+        let (store, [write, _, any, _]) = fixture()?;
+        let query = DelegationQuery::new()
+            .audience(*signer(2).did())
+            .subject(DelegatedSubject::Specific(*signer(9).did()))
+            .grants(command("/crud/write")?)
+            .valid_at(Timestamp::try_from(1_000_i128)?);
+        assert_eq!(run(&store, &query)?, sorted([write, any]));
+        Ok(())
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_locked_store_query() -> TestResult {
+        let store: Arc<Mutex<HashMap<Cid, Arc<Delegation<Ed25519Did>>>>> = Arc::default();
+        let doc = *signer(9).did();
+        let cid = block_on(insert::<Sendable, _, _, _>(
+            &store,
+            delegation_with(1, 2, doc.into(), "/crud/write", None)?,
+        ))?;
+        block_on(insert::<Sendable, _, _, _>(
+            &store,
+            delegation_with(1, 2, doc.into(), "/msg/send", None)?,
+        ))?;
+
+        let query = DelegationQuery::new().command_prefix(command("/crud")?);
+        let found = block_on(DelegationIndex::<Sendable, _, _>::query(&store, &query))?;
+        assert_eq!(
+            found.into_iter().map(|(c, _)| c).collect::<Vec<_>>(),
+            vec![cid]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,452 @@
+//! Composite delegation stores.
+//!
+//! [`Layered`] stacks two [`DelegationStore`]s, e.g. a local cache over a
+//! shared persistent store. Deeper stacks nest: the lower layer of a
+//! [`Layered`] may itself be a [`Layered`].
+//!
+//! ```
+//! # use std::{collections::BTreeMap, num::NonZeroUsize, rc::Rc, cell::RefCell};
+//! # use ucan::{delegation::{store::{Layered, LruStore}, Delegation}, did::Ed25519Did};
+//! # use ipld_core::cid::Cid;
+//! # type Shared = Rc<RefCell<BTreeMap<Cid, Rc<Delegation<Ed25519Did>>>>>;
+//! # fn example(shared: Shared, bundled: Shared) {
+//! // Cache -> shared store -> proofs bundled with the request
+//! let capacity = NonZeroUsize::new(128).unwrap_or(NonZeroUsize::MIN);
+//! let cache: LruStore<Rc<Delegation<Ed25519Did>>> = LruStore::new(capacity);
+//! let proofs = Layered::new(cache, Layered::new(shared, bundled)).promote_hits(true);
+//! # }
+//! ```
+
+use alloc::{vec, vec::Vec};
+use core::{borrow::Borrow, error::Error, iter::Peekable};
+
+use future_form::{future_form, FutureForm, Local, Sendable};
+use ipld_core::cid::Cid;
+use thiserror::Error;
+
+use crate::{delegation::Delegation, did::Did};
+
+use super::{DelegationStore, Found, ListableStore, Matches, Page};
+
+/// Reads from `upper`, falling back to `lower`; writes go to both.
+///
+/// * [`DelegationStore::get_all`] looks the CIDs up in `upper` in one batch
+///   and fetches the misses from `lower` in another. A delegation is only
+///   reported missing (by `lower`) once every layer has been tried. With
+///   [`promote_hits`](Layered::promote_hits), delegations found in `lower`
+///   are then inserted into `upper` under the key `lower` stores them by.
+/// * Inserts write through to `lower`, then `upper`.
+/// * Removals remove from both layers.
+/// * Listing and counting see the union of both layers' CIDs.
+#[derive(Debug, Clone, Default)]
+pub struct Layered<U, L> {
+    upper: U,
+    lower: L,
+    promote: bool,
+}
+
+impl<U, L> Layered<U, L> {
+    /// Stack `upper` over `lower`, without promotion.
+    #[must_use]
+    pub const fn new(upper: U, lower: L) -> Self {
+        Layered {
+            upper,
+            lower,
+            promote: false,
+        }
+    }
+
+    /// Whether delegations read from `lower` are copied into `upper`.
+    ///
+    /// Failing to promote is logged and does not fail the read.
+    #[must_use]
+    pub const fn promote_hits(mut self, promote: bool) -> Self {
+        self.promote = promote;
+        self
+    }
+
+    /// The upper layer.
+    #[must_use]
+    pub const fn upper(&self) -> &U {
+        &self.upper
+    }
+
+    /// The lower layer.
+    #[must_use]
+    pub const fn lower(&self) -> &L {
+        &self.lower
+    }
+
+    /// Split into the upper and lower layers.
+    #[must_use]
+    pub fn into_parts(self) -> (U, L) {
+        (self.upper, self.lower)
+    }
+
+    /// Find `cid` in `upper`, then the misses in `lower`, promoting what
+    /// `lower` finds if enabled. An error from `upper` counts as a miss.
+    async fn find_layered<K: FutureForm, D: Did, T: Borrow<Delegation<D>> + Clone>(
+        &self,
+        cid: &[Cid],
+    ) -> Result<Found<T>, L::GetError>
+    where
+        U: ListableStore<K, D, T>,
+        L: ListableStore<K, D, T>,
+        U::InsertError: 'static,
+        U::GetError: 'static,
+    {
+        let mut found = match self.upper.find_all(cid).await {
+            Ok(found) if found.len() == cid.len() => found,
+            _ => vec![None; cid.len()],
+        };
+        let misses: Vec<Cid> = cid
+            .iter()
+            .zip(&found)
+            .filter(|(_, hit)| hit.is_none())
+            .map(|(c, _)| *c)
+            .collect();
+        if misses.is_empty() {
+            return Ok(found);
+        }
+
+        let fetched = self.lower.find_all(&misses).await?;
+        if self.promote {
+            let promoted: Vec<(Cid, T)> = fetched.iter().flatten().cloned().collect();
+            if !promoted.is_empty() {
+                if let Err(e) = self.upper.insert_many_by_cid(promoted).await {
+                    tracing::warn!("unable to promote delegations to upper layer: {e}");
+                }
+            }
+        }
+
+        let mut fetched = fetched.into_iter();
+        for slot in found.iter_mut().filter(|slot| slot.is_none()) {
+            *slot = fetched.next().flatten();
+        }
+        Ok(found)
+    }
+}
+
+/// Merge two ascending, deduplicated runs of entries, preferring `upper` on ties.
+///
+/// Both layers must page in [`Cid`]'s `Ord`, as
+/// [`ListableStore::list_cids`] requires.
+fn merge<E>(
+    upper: impl IntoIterator<Item = E>,
+    lower: impl IntoIterator<Item = E>,
+    key: impl Fn(&E) -> Cid,
+    limit: usize,
+) -> Vec<E> {
+    fn next<E, I: Iterator<Item = E>>(
+        upper: &mut Peekable<I>,
+        lower: &mut Peekable<impl Iterator<Item = E>>,
+        key: &impl Fn(&E) -> Cid,
+    ) -> Option<E> {
+        match (upper.peek().map(key), lower.peek().map(key)) {
+            (Some(u), Some(l)) if u == l => {
+                lower.next();
+                upper.next()
+            }
+            (Some(u), Some(l)) if l < u => lower.next(),
+            (Some(_), _) => upper.next(),
+            (None, _) => lower.next(),
+        }
+    }
+
+    let mut upper = upper.into_iter().peekable();
+    let mut lower = lower.into_iter().peekable();
+    core::iter::from_fn(|| next(&mut upper, &mut lower, &key))
+        .take(limit)
+        .collect()
+}
+
+#[future_form(
+    Local,
+    Sendable where
+        U: Sync,
+        L: Sync,
+        T: Send + Sync
+)]
+impl<K: FutureForm, D: Did, T: Borrow<Delegation<D>> + Clone + 'static, U, L>
+    DelegationStore<K, D, T> for Layered<U, L>
+where
+    U: ListableStore<K, D, T>,
+    L: ListableStore<K, D, T>,
+    U::InsertError: 'static,
+    U::GetError: 'static,
+    U::ListError: 'static,
+    L::InsertError: 'static,
+    L::GetError: 'static,
+    L::ListError: 'static,
+{
+    type InsertError = LayeredError<U::InsertError, L::InsertError>;
+    type GetError = L::GetError;
+
+    fn get_all<'a>(&'a self, cid: &'a [Cid]) -> K::Future<'a, Result<Vec<T>, Self::GetError>> {
+        K::from_future(async move {
+            let found = self.find_layered::<K, D, T>(cid).await?;
+            let mut dlgs = Vec::with_capacity(cid.len());
+            for (c, hit) in cid.iter().zip(found) {
+                match hit {
+                    Some((_, dlg)) => dlgs.push(dlg),
+                    // Let `lower` report the miss in its own error type
+                    None => dlgs.extend(self.lower.get_all(core::slice::from_ref(c)).await?),
+                }
+            }
+            Ok(dlgs)
+        })
+    }
+
+    fn insert_by_cid(
+        &self,
+        cid: Cid,
+        delegation: T,
+    ) -> K::Future<'_, Result<(), Self::InsertError>> {
+        K::from_future(async move {
+            self.lower
+                .insert_by_cid(cid, delegation.clone())
+                .await
+                .map_err(LayeredError::Lower)?;
+            self.upper
+                .insert_by_cid(cid, delegation)
+                .await
+                .map_err(LayeredError::Upper)
+        })
+    }
+}
+
+#[future_form(
+    Local,
+    Sendable where
+        U: Sync,
+        L: Sync,
+        T: Send + Sync
+)]
+impl<K: FutureForm, D: Did, T: Borrow<Delegation<D>> + Clone + 'static, U, L> ListableStore<K, D, T>
+    for Layered<U, L>
+where
+    U: ListableStore<K, D, T>,
+    L: ListableStore<K, D, T>,
+    U::InsertError: 'static,
+    U::GetError: 'static,
+    U::ListError: 'static,
+    L::InsertError: 'static,
+    L::GetError: 'static,
+    L::ListError: 'static,
+    U::RemoveError: 'static,
+    L::RemoveError: 'static,
+{
+    type ListError = LayeredError<U::ListError, L::ListError>;
+    type RemoveError = LayeredError<U::RemoveError, L::RemoveError>;
+
+    fn find_all<'a>(&'a self, cid: &'a [Cid]) -> K::Future<'a, Result<Found<T>, Self::GetError>> {
+        K::from_future(async move { self.find_layered::<K, D, T>(cid).await })
+    }
+
+    fn insert_many_by_cid(
+        &self,
+        delegations: Vec<(Cid, T)>,
+    ) -> K::Future<'_, Result<(), Self::InsertError>> {
+        K::from_future(async move {
+            self.lower
+                .insert_many_by_cid(delegations.clone())
+                .await
+                .map_err(LayeredError::Lower)?;
+            self.upper
+                .insert_many_by_cid(delegations)
+                .await
+                .map_err(LayeredError::Upper)
+        })
+    }
+
+    fn remove(&self, cid: Cid) -> K::Future<'_, Result<Option<T>, Self::RemoveError>> {
+        K::from_future(async move {
+            let upper = self.upper.remove(cid).await.map_err(LayeredError::Upper)?;
+            let lower = self.lower.remove(cid).await.map_err(LayeredError::Lower)?;
+            Ok(upper.or(lower))
+        })
+    }
+
+    fn contains(&self, cid: Cid) -> K::Future<'_, Result<bool, Self::ListError>> {
+        K::from_future(async move {
+            Ok(self
+                .upper
+                .contains(cid)
+                .await
+                .map_err(LayeredError::Upper)?
+                || self
+                    .lower
+                    .contains(cid)
+                    .await
+                    .map_err(LayeredError::Lower)?)
+        })
+    }
+
+    fn count(&self) -> K::Future<'_, Result<usize, Self::ListError>> {
+        K::from_future(async move {
+            let upper = self
+                .upper
+                .list_cids(Page::all())
+                .await
+                .map_err(LayeredError::Upper)?;
+            let lower = self
+                .lower
+                .list_cids(Page::all())
+                .await
+                .map_err(LayeredError::Lower)?;
+            Ok(merge(upper, lower, |cid| *cid, usize::MAX).len())
+        })
+    }
+
+    fn list_cids(&self, page: Page) -> K::Future<'_, Result<Vec<Cid>, Self::ListError>> {
+        K::from_future(async move {
+            let upper = self
+                .upper
+                .list_cids(page)
+                .await
+                .map_err(LayeredError::Upper)?;
+            let lower = self
+                .lower
+                .list_cids(page)
+                .await
+                .map_err(LayeredError::Lower)?;
+            Ok(merge(upper, lower, |cid| *cid, page.limit))
+        })
+    }
+
+    fn list(&self, page: Page) -> K::Future<'_, Result<Matches<T>, Self::ListError>> {
+        K::from_future(async move {
+            let upper = self.upper.list(page).await.map_err(LayeredError::Upper)?;
+            let lower = self.lower.list(page).await.map_err(LayeredError::Lower)?;
+            Ok(merge(upper, lower, |(cid, _)| *cid, page.limit))
+        })
+    }
+}
+
+/// An error from one layer of a [`Layered`] store.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum LayeredError<U: Error, L: Error> {
+    /// The upper layer failed.
+    #[error("upper layer: {0}")]
+    Upper(U),
+
+    /// The lower layer failed.
+    #[error("lower layer: {0}")]
+    Lower(L),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cid::CidBuilder,
+        delegation::store::{insert, insert_many, Missing},
+        did::Ed25519Did,
+        test_support::{block_on, delegation},
+    };
+    use alloc::{collections::BTreeMap, rc::Rc, vec};
+    use core::cell::RefCell;
+    use testresult::TestResult;
+    use varsig::hash::Sha2_512;
+
+    type Store = Rc<RefCell<BTreeMap<Cid, Rc<Delegation<Ed25519Did>>>>>;
+
+    #[test]
+    fn test_falls_through_and_promotes() -> TestResult {
+        let cache = Store::default();
+        let shared = Store::default();
+        let bundled = Store::default();
+        let a = block_on(insert::<Local, _, _, _>(&cache, delegation("/a")?))?;
+        let b = block_on(insert::<Local, _, _, _>(&shared, delegation("/b")?))?;
+        let c = block_on(insert::<Local, _, _, _>(&bundled, delegation("/c")?))?;
+
+        let lower = Layered::new(shared.clone(), bundled).promote_hits(true);
+        let stack = Layered::new(cache.clone(), lower).promote_hits(true);
+        let found = block_on(DelegationStore::<Local, _, _>::get_all(&stack, &[c, a, b]))?;
+        assert_eq!(
+            found.iter().map(|d| d.to_cid()).collect::<Vec<_>>(),
+            vec![c, a, b]
+        );
+
+        // Every hit below the cache is promoted into it, and `c` into `shared`
+        assert_eq!(RefCell::borrow(&cache).len(), 3);
+        assert!(RefCell::borrow(&shared).contains_key(&c));
+
+        let missing = delegation::<Delegation<Ed25519Did>>("/d")?.to_cid();
+        assert!(matches!(
+            block_on(DelegationStore::<Local, _, _>::get_all(&stack, &[a, missing])),
+            Err(Missing(m)) if m == missing
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_promotes_under_the_lower_key() -> TestResult {
+        let cache = Store::default();
+        let shared = Store::default();
+        let dlg: Rc<Delegation<Ed25519Did>> = delegation("/a")?;
+        let cid = block_on(insert::<Local, _, _, _>(&shared, dlg.clone()))?;
+        let sha512 = dlg.to_cid_with(&CidBuilder::new::<Sha2_512>());
+
+        let stack = Layered::new(cache.clone(), shared).promote_hits(true);
+        let found = block_on(ListableStore::<Local, _, _>::find_all(&stack, &[sha512]))?;
+        assert_eq!(
+            found
+                .into_iter()
+                .flatten()
+                .map(|(key, _)| key)
+                .collect::<Vec<_>>(),
+            vec![cid]
+        );
+        assert_eq!(
+            RefCell::borrow(&cache).keys().copied().collect::<Vec<_>>(),
+            vec![cid]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_without_promotion() -> TestResult {
+        let cache = Store::default();
+        let shared = Store::default();
+        let cid = block_on(insert::<Local, _, _, _>(&shared, delegation("/a")?))?;
+
+        let stack = Layered::new(cache.clone(), shared);
+        block_on(DelegationStore::<Local, _, _>::get_all(&stack, &[cid]))?;
+        assert!(RefCell::borrow(&cache).is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_writes_and_lists_union() -> TestResult {
+        let stack = Layered::new(Store::default(), Store::default());
+        let mut cids = block_on(insert_many::<Local, _, _, _>(
+            stack.lower(),
+            vec![delegation("/a")?, delegation("/b")?],
+        ))?;
+        cids.push(block_on(insert::<Local, _, _, _>(
+            &stack,
+            delegation("/c")?,
+        ))?);
+        cids.sort();
+
+        assert_eq!(RefCell::borrow(stack.upper()).len(), 1);
+        assert_eq!(RefCell::borrow(stack.lower()).len(), 3);
+        assert_eq!(block_on(ListableStore::<Local, _, _>::count(&stack))?, 3);
+        assert_eq!(
+            block_on(ListableStore::<Local, _, _>::list_cids(
+                &stack,
+                Page::first(2)
+            ))?
+            .as_slice(),
+            cids.get(..2).unwrap_or_default()
+        );
+
+        let removed = *cids.first().ok_or("no cids")?;
+        assert!(block_on(ListableStore::<Local, _, _>::remove(&stack, removed))?.is_some());
+        assert!(!block_on(ListableStore::<Local, _, _>::contains(
+            &stack, removed
+        ))?);
+        Ok(())
+    }
+}
@@ -0,0 +1,254 @@
+//! Bounded in-memory delegation cache.
+
+use alloc::{collections::BTreeMap, vec::Vec};
+use core::{borrow::Borrow, num::NonZeroUsize};
+use std::sync::{Mutex, MutexGuard};
+
+use future_form::{future_form, FutureForm, Local, Sendable};
+use ipld_core::cid::Cid;
+
+use crate::{delegation::Delegation, did::Did};
+
+use super::{
+    DelegationStore, Found, ListableStore, LockedStoreGetError, Matches, Missing, Page,
+    StorePoisoned,
+};
+
+/// A [`DelegationStore`] holding at most `capacity` delegations.
+///
+/// Inserting into a full store evicts the least recently used delegation,
+/// where inserts and [`DelegationStore::get_all`] hits count as uses. Meant
+/// as the upper layer of a [`Layered`](super::Layered) store.
+///
+/// Lookups only match the exact CID a delegation was inserted under; a
+/// cache is not the place to rehash every entry on a miss. [`Layered`](super::Layered)
+/// promotes delegations under the key the lower layer stores them by.
+#[derive(Debug)]
+pub struct LruStore<T> {
+    capacity: NonZeroUsize,
+    state: Mutex<LruState<T>>,
+}
+
+/// Entries with their last use, and the reverse mapping to find the oldest.
+#[derive(Debug)]
+struct LruState<T> {
+    entries: BTreeMap<Cid, (T, u64)>,
+    by_use: BTreeMap<u64, Cid>,
+    clock: u64,
+}
+
+impl<T> LruState<T> {
+    /// Mark `cid` as just used.
+    fn touch(&mut self, cid: &Cid) {
+        if let Some((_, used)) = self.entries.get_mut(cid) {
+            self.by_use.remove(used);
+            self.clock += 1;
+            *used = self.clock;
+            self.by_use.insert(self.clock, *cid);
+        }
+    }
+
+    fn insert(&mut self, cid: Cid, delegation: T, capacity: NonZeroUsize) {
+        self.remove(&cid);
+        while self.entries.len() >= capacity.get() {
+            let Some((_, oldest)) = self.by_use.pop_first() else {
+                break;
+            };
+            self.entries.remove(&oldest);
+        }
+        self.clock += 1;
+        self.entries.insert(cid, (delegation, self.clock));
+        self.by_use.insert(self.clock, cid);
+    }
+
+    fn remove(&mut self, cid: &Cid) -> Option<T> {
+        let (delegation, used) = self.entries.remove(cid)?;
+        self.by_use.remove(&used);
+        Some(delegation)
+    }
+
+    /// The delegation stored under exactly `cid`, marked as just used.
+    fn get(&mut self, cid: &Cid) -> Option<T>
+    where
+        T: Clone,
+    {
+        self.touch(cid);
+        self.entries.get(cid).map(|(dlg, _)| dlg.clone())
+    }
+}
+
+impl<T> LruStore<T> {
+    /// An empty cache holding at most `capacity` delegations.
+    #[must_use]
+    pub const fn new(capacity: NonZeroUsize) -> Self {
+        LruStore {
+            capacity,
+            state: Mutex::new(LruState {
+                entries: BTreeMap::new(),
+                by_use: BTreeMap::new(),
+                clock: 0,
+            }),
+        }
+    }
+
+    /// The maximum number of delegations held.
+    #[must_use]
+    pub const fn capacity(&self) -> NonZeroUsize {
+        self.capacity
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, LruState<T>>, StorePoisoned> {
+        self.state.lock().map_err(|_| StorePoisoned)
+    }
+}
+
+#[future_form(
+    Local,
+    Sendable where
+        T: Send + Sync
+)]
+impl<K: FutureForm, D: Did, T: Borrow<Delegation<D>> + Clone> DelegationStore<K, D, T>
+    for LruStore<T>
+{
+    type InsertError = StorePoisoned;
+    type GetError = LockedStoreGetError;
+
+    fn get_all<'a>(&'a self, cid: &'a [Cid]) -> K::Future<'a, Result<Vec<T>, Self::GetError>> {
+        K::from_future(async move {
+            let mut locked = self.lock()?;
+            let mut dlgs = Vec::new();
+            for c in cid {
+                dlgs.push(locked.get(c).ok_or(Missing(*c))?);
+            }
+            Ok(dlgs)
+        })
+    }
+
+    fn insert_by_cid(
+        &self,
+        cid: Cid,
+        delegation: T,
+    ) -> K::Future<'_, Result<(), Self::InsertError>> {
+        K::from_future(async move {
+            self.lock()?.insert(cid, delegation, self.capacity);
+            Ok(())
+        })
+    }
+}
+
+#[future_form(
+    Local,
+    Sendable where
+        T: Send + Sync
+)]
+impl<K: FutureForm, D: Did, T: Borrow<Delegation<D>> + Clone> ListableStore<K, D, T>
+    for LruStore<T>
+{
+    type ListError = StorePoisoned;
+    type RemoveError = StorePoisoned;
+
+    fn find_all<'a>(&'a self, cid: &'a [Cid]) -> K::Future<'a, Result<Found<T>, Self::GetError>> {
+        K::from_future(async move {
+            let mut locked = self.lock()?;
+            Ok(cid
+                .iter()
+                .map(|c| locked.get(c).map(|dlg| (*c, dlg)))
+                .collect())
+        })
+    }
+
+    fn insert_many_by_cid(
+        &self,
+        delegations: Vec<(Cid, T)>,
+    ) -> K::Future<'_, Result<(), Self::InsertError>> {
+        K::from_future(async move {
+            let mut locked = self.lock()?;
+            for (cid, delegation) in delegations {
+                locked.insert(cid, delegation, self.capacity);
+            }
+            Ok(())
+        })
+    }
+
+    fn remove(&self, cid: Cid) -> K::Future<'_, Result<Option<T>, Self::RemoveError>> {
+        K::from_future(async move { Ok(self.lock()?.remove(&cid)) })
+    }
+
+    fn contains(&self, cid: Cid) -> K::Future<'_, Result<bool, Self::ListError>> {
+        K::from_future(async move { Ok(self.lock()?.entries.contains_key(&cid)) })
+    }
+
+    fn count(&self) -> K::Future<'_, Result<usize, Self::ListError>> {
+        K::from_future(async move { Ok(self.lock()?.entries.len()) })
+    }
+
+    fn list_cids(&self, page: Page) -> K::Future<'_, Result<Vec<Cid>, Self::ListError>> {
+        K::from_future(async move {
+            let locked = self.lock()?;
+            Ok(page
+                .select(locked.entries.iter().map(|(key, (dlg, _))| (key, dlg)))
+                .into_iter()
+                .map(|(cid, _)| cid)
+                .collect())
+        })
+    }
+
+    fn list(&self, page: Page) -> K::Future<'_, Result<Matches<T>, Self::ListError>> {
+        K::from_future(async move {
+            let locked = self.lock()?;
+            Ok(page.select(locked.entries.iter().map(|(key, (dlg, _))| (key, dlg))))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        delegation::store::insert,
+        did::Ed25519Did,
+        test_support::{block_on, delegation},
+    };
+    use alloc::sync::Arc;
+    use testresult::TestResult;
+    use varsig::hash::Sha2_512;
+
+    #[test]
+    fn test_evicts_least_recently_used() -> TestResult {
+        let store = LruStore::new(NonZeroUsize::new(2).ok_or("zero")?);
+        let first: Arc<Delegation<Ed25519Did>> = delegation("/a")?;
+        let a = block_on(insert::<Sendable, _, _, _>(&store, first.clone()))?;
+        let b = block_on(insert::<Sendable, _, _, _>(&store, delegation("/b")?))?;
+
+        // Only the exact key matches
+        let sha512 = first.to_cid_with(&crate::cid::CidBuilder::new::<Sha2_512>());
+        assert!(!block_on(ListableStore::<Sendable, _, _>::contains(
+            &store, sha512
+        ))?);
+        assert!(
+            block_on(ListableStore::<Sendable, _, _>::find_all(&store, &[sha512]))?
+                .iter()
+                .all(Option::is_none)
+        );
+
+        // Reading `a` makes `b` the least recently used
+        block_on(DelegationStore::<Sendable, _, _>::get_all(&store, &[a]))?;
+        let c = block_on(insert::<Sendable, _, _, _>(&store, delegation("/c")?))?;
+
+        assert!(block_on(ListableStore::<Sendable, _, _>::contains(
+            &store, a
+        ))?);
+        assert!(!block_on(ListableStore::<Local, _, _>::contains(
+            &store, b
+        ))?);
+        assert!(block_on(ListableStore::<Sendable, _, _>::contains(
+            &store, c
+        ))?);
+        assert_eq!(block_on(ListableStore::<Sendable, _, _>::count(&store))?, 2);
+        assert!(matches!(
+            block_on(DelegationStore::<Sendable, _, _>::get_all(&store, &[b])),
+            Err(LockedStoreGetError::Missing(Missing(m))) if m == b
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,524 @@
+//! Lock-striped concurrent delegation store.
+//!
+//! [`ShardedStore`] spreads delegations over several `RwLock`ed maps chosen by
+//! CID, so readers never wait for each other and writers only block the one
+//! shard they touch. Unlike `Arc<Mutex<HashMap>>`, it never reports a poisoned
+//! lock: every operation is a single map update, so a panic elsewhere cannot
+//! leave a shard half-modified, and the lock is simply recovered.
+
+use alloc::{boxed::Box, sync::Arc, vec::Vec};
+use core::{
+    convert::Infallible,
+    num::NonZeroUsize,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+use std::{
+    collections::HashMap,
+    sync::{PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard},
+};
+
+use future_form::{future_form, FutureForm, Local, Sendable};
+use ipld_core::cid::Cid;
+use varsig::{
+    hash::{Multihasher, Sha2_256},
+    verify::Verify,
+};
+
+use crate::{delegation::Delegation, did::Did};
+
+use super::{
+    find_equivalent, index::scan, DelegationIndex, DelegationQuery, DelegationStore, Found,
+    ListableStore, Matches, Missing, Page,
+};
+
+/// The number of shards used by [`ShardedStore::new`].
+pub const DEFAULT_SHARDS: NonZeroUsize = match NonZeroUsize::new(16) {
+    Some(shards) => shards,
+    None => NonZeroUsize::MIN,
+};
+
+type Shard<D> = RwLock<HashMap<Cid, Arc<Delegation<D>>>>;
+
+/// A [`DelegationStore`] for many concurrent readers and writers.
+///
+/// Lookups by a CID with a different hash than the one a delegation was
+/// stored under fall back to scanning every shard, as for the other stores.
+/// The store counts its keys hashed with anything but SHA2-256, so a missed
+/// SHA2-256 lookup skips that scan while every key is SHA2-256.
+#[derive(Debug)]
+pub struct ShardedStore<D: Did> {
+    shards: Box<[Shard<D>]>,
+    other_hashes: AtomicUsize,
+}
+
+impl<D: Did> ShardedStore<D> {
+    /// An empty store with [`DEFAULT_SHARDS`] shards.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_shards(DEFAULT_SHARDS)
+    }
+
+    /// An empty store with `shards` shards.
+    #[must_use]
+    pub fn with_shards(shards: NonZeroUsize) -> Self {
+        ShardedStore {
+            shards: (0..shards.get()).map(|_| RwLock::default()).collect(),
+            other_hashes: AtomicUsize::new(0),
+        }
+    }
+
+    /// The number of shards.
+    #[must_use]
+    pub const fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// The index of the shard holding `cid`.
+    fn shard_index(&self, cid: &Cid) -> usize {
+        // Digests are uniformly distributed, so their leading bytes suffice
+        let prefix = cid
+            .hash()
+            .digest()
+            .iter()
+            .take(8)
+            .fold(0u64, |acc, byte| (acc << 8) | u64::from(*byte));
+        usize::try_from(prefix % self.shards.len() as u64).unwrap_or_default()
+    }
+
+    #[allow(clippy::indexing_slicing)] // `shard_index` is always below the shard count
+    fn shard(&self, cid: &Cid) -> &Shard<D> {
+        &self.shards[self.shard_index(cid)]
+    }
+
+    /// Find the shard and key under which `cid`, or a CID equivalent to it, is stored.
+    fn locate(&self, cid: &Cid) -> Option<(&Shard<D>, Cid)> {
+        let home = self.shard(cid);
+        if read(home).contains_key(cid) {
+            return Some((home, *cid));
+        }
+        if !self.may_hold_equivalent(cid) {
+            return None;
+        }
+        self.shards.iter().find_map(|shard| {
+            find_equivalent(read(shard).iter(), cid).map(|(key, _)| (shard, *key))
+        })
+    }
+
+    /// The delegation stored under `cid` or a CID equivalent to it, with its key.
+    fn find(&self, cid: &Cid) -> Option<(Cid, Arc<Delegation<D>>)> {
+        if let Some(dlg) = read(self.shard(cid)).get(cid) {
+            return Some((*cid, dlg.clone()));
+        }
+        if !self.may_hold_equivalent(cid) {
+            return None;
+        }
+        self.shards.iter().find_map(|shard| {
+            find_equivalent(read(shard).iter(), cid).map(|(key, dlg)| (*key, dlg.clone()))
+        })
+    }
+
+    /// Whether a key with another hash than `cid`'s may be stored.
+    fn may_hold_equivalent(&self, cid: &Cid) -> bool {
+        cid.hash().code() != Sha2_256::MULTIHASH_TAG
+            || self.other_hashes.load(Ordering::Acquire) > 0
+    }
+
+    /// Insert into a write-locked shard, counting keys not hashed with SHA2-256.
+    fn insert_into(
+        &self,
+        shard: &mut HashMap<Cid, Arc<Delegation<D>>>,
+        cid: Cid,
+        delegation: Arc<Delegation<D>>,
+    ) {
+        if shard.insert(cid, delegation).is_none() && cid.hash().code() != Sha2_256::MULTIHASH_TAG {
+            self.other_hashes.fetch_add(1, Ordering::Release);
+        }
+    }
+
+    /// Remove from a write-locked shard, counting keys not hashed with SHA2-256.
+    fn remove_from(
+        &self,
+        shard: &mut HashMap<Cid, Arc<Delegation<D>>>,
+        cid: &Cid,
+    ) -> Option<Arc<Delegation<D>>> {
+        let removed = shard.remove(cid)?;
+        if cid.hash().code() != Sha2_256::MULTIHASH_TAG {
+            self.other_hashes.fetch_sub(1, Ordering::Release);
+        }
+        Some(removed)
+    }
+
+    /// Every shard, read-locked in order.
+    fn read_all(&self) -> Vec<RwLockReadGuard<'_, HashMap<Cid, Arc<Delegation<D>>>>> {
+        self.shards.iter().map(read).collect()
+    }
+}
+
+impl<D: Did> Default for ShardedStore<D> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn read<T>(lock: &RwLock<T>) -> RwLockReadGuard<'_, T> {
+    lock.read().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn write<T>(lock: &RwLock<T>) -> RwLockWriteGuard<'_, T> {
+    lock.write().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[future_form(
+    Local,
+    Sendable where
+        D: Send + Sync,
+        <D as Did>::VarsigConfig: Send + Sync,
+        <<D as Did>::VarsigConfig as Verify>::Signature: Send + Sync
+)]
+impl<K: FutureForm, D: Did> DelegationStore<K, D, Arc<Delegation<D>>> for ShardedStore<D> {
+    type InsertError = Infallible;
+    type GetError = Missing;
+
+    fn get_all<'a>(
+        &'a self,
+        cid: &'a [Cid],
+    ) -> K::Future<'a, Result<Vec<Arc<Delegation<D>>>, Self::GetError>> {
+        K::from_future(async move {
+            cid.iter()
+                .map(|c| self.find(c).map(|(_, dlg)| dlg).ok_or(Missing(*c)))
+                .collect()
+        })
+    }
+
+    fn insert_by_cid(
+        &self,
+        cid: Cid,
+        delegation: Arc<Delegation<D>>,
+    ) -> K::Future<'_, Result<(), Self::InsertError>> {
+        K::from_future(async move {
+            self.insert_into(&mut write(self.shard(&cid)), cid, delegation);
+            Ok(())
+        })
+    }
+}
+
+#[future_form(
+    Local,
+    Sendable where
+        D: Send + Sync,
+        <D as Did>::VarsigConfig: Send + Sync,
+        <<D as Did>::VarsigConfig as Verify>::Signature: Send + Sync
+)]
+impl<K: FutureForm, D: Did> ListableStore<K, D, Arc<Delegation<D>>> for ShardedStore<D> {
+    type ListError = Infallible;
+    type RemoveError = Infallible;
+
+    fn find_all<'a>(
+        &'a self,
+        cid: &'a [Cid],
+    ) -> K::Future<'a, Result<Found<Arc<Delegation<D>>>, Self::GetError>> {
+        K::from_future(async move { Ok(cid.iter().map(|c| self.find(c)).collect()) })
+    }
+
+    fn insert_many_by_cid(
+        &self,
+        delegations: Vec<(Cid, Arc<Delegation<D>>)>,
+    ) -> K::Future<'_, Result<(), Self::InsertError>> {
+        K::from_future(async move {
+            // Lock each shard once
+            let mut by_shard: Vec<Vec<(Cid, Arc<Delegation<D>>)>> =
+                self.shards.iter().map(|_| Vec::new()).collect();
+            for (cid, dlg) in delegations {
+                if let Some(entries) = by_shard.get_mut(self.shard_index(&cid)) {
+                    entries.push((cid, dlg));
+                }
+            }
+            for (shard, entries) in self.shards.iter().zip(by_shard) {
+                if !entries.is_empty() {
+                    let mut locked = write(shard);
+                    for (cid, dlg) in entries {
+                        self.insert_into(&mut locked, cid, dlg);
+                    }
+                }
+            }
+            Ok(())
+        })
+    }
+
+    fn remove(
+        &self,
+        cid: Cid,
+    ) -> K::Future<'_, Result<Option<Arc<Delegation<D>>>, Self::RemoveError>> {
+        K::from_future(async move {
+            Ok(self
+                .locate(&cid)
+                .and_then(|(shard, key)| self.remove_from(&mut write(shard), &key)))
+        })
+    }
+
+    fn contains(&self, cid: Cid) -> K::Future<'_, Result<bool, Self::ListError>> {
+        K::from_future(async move { Ok(self.locate(&cid).is_some()) })
+    }
+
+    fn count(&self) -> K::Future<'_, Result<usize, Self::ListError>> {
+        K::from_future(async move { Ok(self.shards.iter().map(|shard| read(shard).len()).sum()) })
+    }
+
+    fn list_cids(&self, page: Page) -> K::Future<'_, Result<Vec<Cid>, Self::ListError>> {
+        K::from_future(async move {
+            let shards = self.read_all();
+            Ok(page
+                .select(shards.iter().flat_map(|shard| shard.iter()))
+                .into_iter()
+                .map(|(cid, _)| cid)
+                .collect())
+        })
+    }
+
+    fn list(
+        &self,
+        page: Page,
+    ) -> K::Future<'_, Result<Matches<Arc<Delegation<D>>>, Self::ListError>> {
+        K::from_future(async move {
+            let shards = self.read_all();
+            Ok(page.select(shards.iter().flat_map(|shard| shard.iter())))
+        })
+    }
+}
+
+#[future_form(
+    Local,
+    Sendable where
+        D: Send + Sync,
+        <D as Did>::VarsigConfig: Send + Sync,
+        <<D as Did>::VarsigConfig as Verify>::Signature: Send + Sync
+)]
+impl<K: FutureForm, D: Did> DelegationIndex<K, D, Arc<Delegation<D>>> for ShardedStore<D> {
+    type QueryError = Infallible;
+
+    fn query<'a>(
+        &'a self,
+        query: &'a DelegationQuery<D>,
+    ) -> K::Future<'a, Result<Matches<Arc<Delegation<D>>>, Self::QueryError>> {
+        K::from_future(async move {
+            Ok(self
+                .shards
+                .iter()
+                .flat_map(|shard| scan(read(shard).iter(), query))
+                .collect())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        delegation::store::{insert, insert_many, Layered},
+        did::Ed25519Did,
+        test_support::{block_on, delegation},
+    };
+    use alloc::format;
+    use core::time::Duration;
+    use testresult::TestResult;
+    use varsig::hash::Sha2_512;
+
+    #[test]
+    fn test_store_operations() -> TestResult {
+        let store = ShardedStore::<Ed25519Did>::with_shards(NonZeroUsize::MIN.saturating_add(3));
+        let dlgs = (0..32)
+            .map(|i| delegation(&format!("/cmd/{i}")))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut cids = block_on(insert_many::<Sendable, _, _, _>(&store, dlgs.clone()))?;
+        cids.sort();
+
+        assert_eq!(
+            block_on(ListableStore::<Sendable, _, _>::count(&store))?,
+            32
+        );
+        assert!(store.shards.iter().all(|shard| !read(shard).is_empty()));
+        assert_eq!(
+            block_on(ListableStore::<Sendable, _, _>::list_cids(
+                &store,
+                Page::all()
+            ))?,
+            cids
+        );
+
+        let first = dlgs.first().ok_or("no delegations")?;
+        let sha512 = first.to_cid_with(&crate::cid::CidBuilder::new::<Sha2_512>());
+        let found = block_on(DelegationStore::<Local, _, _>::get_all(&store, &[sha512]))?;
+        assert_eq!(found.first().map(|d| d.to_cid()), Some(first.to_cid()));
+
+        assert!(block_on(ListableStore::<Sendable, _, _>::remove(&store, sha512))?.is_some());
+        assert!(!block_on(ListableStore::<Sendable, _, _>::contains(
+            &store,
+            first.to_cid()
+        ))?);
+        assert!(matches!(
+            block_on(DelegationStore::<Sendable, _, _>::get_all(&store, &[sha512])),
+            Err(Missing(m)) if m == sha512
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_recovers_from_panicking_writer() -> TestResult {
+        let store = Arc::new(ShardedStore::<Ed25519Did>::with_shards(NonZeroUsize::MIN));
+        let dlg: Arc<Delegation<Ed25519Did>> = delegation("/a")?;
+
+        let poisoner = store.clone();
+        #[allow(clippy::panic)]
+        let panicked = std::thread::spawn(move || {
+            let _guard = poisoner.shards.first().map(write);
+            panic!("writer panicked");
+        })
+        .join()
+        .is_err();
+        assert!(panicked);
+        assert!(store.shards.first().is_some_and(RwLock::is_poisoned));
+
+        let cid = block_on(super::super::insert::<Sendable, _, _, _>(&*store, dlg))?;
+        assert!(block_on(ListableStore::<Sendable, _, _>::contains(
+            &*store, cid
+        ))?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_concurrent_readers_and_writers() -> TestResult {
+        let store = Arc::new(ShardedStore::<Ed25519Did>::new());
+        let dlgs = (0..64)
+            .map(|i| delegation(&format!("/cmd/{i}")))
+            .collect::<Result<Vec<_>, _>>()?;
+        let (seeded, added) = dlgs.split_at(32);
+        let cids = block_on(insert_many::<Sendable, _, _, _>(&*store, seeded.to_vec()))?;
+
+        std::thread::scope(|scope| {
+            for chunk in added.chunks(8) {
+                let store = &store;
+                scope.spawn(move || {
+                    block_on(insert_many::<Sendable, _, _, _>(&**store, chunk.to_vec()))
+                });
+            }
+            for _ in 0..4 {
+                let (store, cids) = (&store, &cids);
+                scope.spawn(move || {
+                    for _ in 0..100 {
+                        let found =
+                            block_on(DelegationStore::<Sendable, _, _>::get_all(&**store, cids));
+                        assert_eq!(found.map(|d| d.len()).ok(), Some(cids.len()));
+                    }
+                });
+            }
+        });
+
+        assert_eq!(
+            block_on(ListableStore::<Sendable, _, _>::count(&*store))?,
+            64
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_counts_keys_with_other_hashes() -> TestResult {
+        let store = ShardedStore::<Ed25519Did>::new();
+        let dlg: Arc<Delegation<Ed25519Did>> = delegation("/a")?;
+        let sha512 = dlg.to_cid_with(&crate::cid::CidBuilder::new::<Sha2_512>());
+        let other: Arc<Delegation<Ed25519Did>> = delegation("/b")?;
+        let other = other.to_cid();
+
+        block_on(super::super::insert::<Sendable, _, _, _>(
+            &store,
+            dlg.clone(),
+        ))?;
+        assert_eq!(store.other_hashes.load(Ordering::Acquire), 0);
+        assert!(!store.may_hold_equivalent(&other));
+
+        for _ in 0..2 {
+            block_on(DelegationStore::<Sendable, _, _>::insert_by_cid(
+                &store,
+                sha512,
+                dlg.clone(),
+            ))?;
+        }
+        assert_eq!(store.other_hashes.load(Ordering::Acquire), 1);
+        assert!(store.may_hold_equivalent(&other));
+
+        block_on(ListableStore::<Sendable, _, _>::remove(&store, sha512))?;
+        assert_eq!(store.other_hashes.load(Ordering::Acquire), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_readers_skip_write_locked_shards() -> TestResult {
+        let store = ShardedStore::<Ed25519Did>::with_shards(NonZeroUsize::MIN.saturating_add(1));
+        let locked = &block_on(insert::<Sendable, _, _, _>(&store, delegation("/cmd/0")?))?;
+        // Delegations are random, so keep generating until one lands in the other shard
+        let free = &loop {
+            let cid = block_on(insert::<Sendable, _, _, _>(&store, delegation("/cmd/1")?))?;
+            if store.shard_index(&cid) != store.shard_index(locked) {
+                break cid;
+            }
+        };
+
+        let guard = write(store.shard(locked));
+        std::thread::scope(|scope| {
+            let (tx, rx) = std::sync::mpsc::channel();
+            let store = &store;
+            scope.spawn(move || {
+                let found = block_on(DelegationStore::<Sendable, _, _>::get_all(
+                    store,
+                    core::slice::from_ref(free),
+                ));
+                tx.send(found.is_ok())
+            });
+            // Only a reader waiting on the writer would exhaust this
+            let answered = rx.recv_timeout(Duration::from_secs(30)).ok();
+            drop(guard);
+            assert_eq!(answered, Some(true), "reader waited on another shard");
+        });
+        Ok(())
+    }
+
+    #[test]
+    fn test_mutex_store_blocks_readers() -> TestResult {
+        let store: Arc<std::sync::Mutex<HashMap<Cid, Arc<Delegation<Ed25519Did>>>>> =
+            Arc::default();
+        let cid = block_on(super::super::insert::<Sendable, _, _, _>(
+            &store,
+            delegation("/a")?,
+        ))?;
+
+        let guard = store.lock().map_err(|_| "poisoned")?;
+        std::thread::scope(|scope| {
+            let (tx, rx) = std::sync::mpsc::channel();
+            let store = &store;
+            scope.spawn(move || {
+                let found = block_on(DelegationStore::<Sendable, _, _>::get_all(store, &[cid]));
+                tx.send(found.is_ok())
+            });
+            // A reader that got through would answer well within this
+            let blocked = rx.recv_timeout(Duration::from_millis(100)).is_err();
+            drop(guard);
+            assert!(blocked, "reader did not wait for the lock holder");
+            assert_eq!(rx.recv().ok(), Some(true));
+        });
+        Ok(())
+    }
+
+    #[test]
+    fn test_as_layer() -> TestResult {
+        let stack = Layered::new(ShardedStore::new(), ShardedStore::new()).promote_hits(true);
+        let cid = block_on(super::super::insert::<Sendable, _, _, _>(
+            stack.lower(),
+            delegation("/a")?,
+        ))?;
+        block_on(DelegationStore::<Sendable, _, _>::get_all(&stack, &[cid]))?;
+        assert!(block_on(ListableStore::<Sendable, _, _>::contains(
+            stack.upper(),
+            cid
+        ))?);
+        Ok(())
+    }
+}
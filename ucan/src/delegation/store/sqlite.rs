@@ -0,0 +1,805 @@
+//! SQLite-backed delegation store.
+//!
+//! [`SqliteStore`] keeps each delegation's DAG-CBOR envelope as a blob keyed by
+//! its binary CID, next to indexed `iss`, `aud`, `sub`, `cmd`, `exp`, `nbf`,
+//! `hash` (the CID's multihash code) and `sort_key` (see [`sort_key`]) columns. [`DelegationIndex`] queries are answered from those columns, so
+//! finding the proofs for an invocation does not decode the whole store.
+//!
+//! Every read checks the envelope against its CID, and
+//! [`DelegationStore::insert_many_by_cid`] runs in a single transaction.
+//!
+//! All I/O is blocking, including inside the returned futures.
+
+use alloc::{
+    collections::TryReserveError,
+    format,
+    string::{String, ToString},
+    sync::Arc,
+    vec,
+    vec::Vec,
+};
+use core::{convert::Infallible, marker::PhantomData};
+use std::{
+    path::Path,
+    sync::{Mutex, MutexGuard},
+};
+
+use future_form::{future_form, FutureForm, Local, Sendable};
+use ipld_core::cid::{Cid, Version};
+use rusqlite::{params, params_from_iter, types::Value, Connection, OptionalExtension};
+use serde::Deserialize;
+use thiserror::Error;
+use varsig::verify::Verify;
+
+use crate::{
+    cid::CidBuilder,
+    command::Command,
+    delegation::{subject::DelegatedSubject, Delegation},
+    did::Did,
+    time::timestamp::Timestamp,
+};
+
+use super::{
+    DelegationIndex, DelegationQuery, DelegationStore, Found, ListableStore, Matches, Missing, Page,
+};
+
+/// Creates the table and its indexes, if they do not exist.
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS delegations (
+    cid      BLOB PRIMARY KEY NOT NULL,
+    envelope BLOB NOT NULL,
+    iss      TEXT NOT NULL,
+    aud      TEXT NOT NULL,
+    sub      TEXT,
+    cmd      TEXT NOT NULL,
+    exp      INTEGER,
+    nbf      INTEGER,
+    hash     INTEGER,
+    sort_key BLOB
+);
+CREATE INDEX IF NOT EXISTS delegations_iss ON delegations (iss);
+CREATE INDEX IF NOT EXISTS delegations_aud ON delegations (aud);
+CREATE INDEX IF NOT EXISTS delegations_sub ON delegations (sub);
+CREATE INDEX IF NOT EXISTS delegations_cmd ON delegations (cmd);
+CREATE INDEX IF NOT EXISTS delegations_exp ON delegations (exp);
+";
+
+/// Indexes the `hash` and `sort_key` columns, once tables created before
+/// them have them.
+const DERIVED_INDEXES: &str = "
+CREATE INDEX IF NOT EXISTS delegations_hash ON delegations (hash);
+CREATE UNIQUE INDEX IF NOT EXISTS delegations_sort_key ON delegations (sort_key);
+";
+
+/// A [`DelegationStore`] backed by an `SQLite` database.
+///
+/// Pages from [`ListableStore::list`] are ordered by [`Cid`]'s `Ord`, like
+/// every other store, using the `sort_key` column: the CIDs' binary encoding
+/// orders them differently (varints are little-endian, and `CIDv0` has no
+/// version prefix).
+#[derive(Debug)]
+pub struct SqliteStore<D: Did> {
+    conn: Mutex<Connection>,
+    _did: PhantomData<fn() -> D>,
+}
+
+impl<D: Did> SqliteStore<D>
+where
+    <D::VarsigConfig as Verify>::Signature: for<'de> Deserialize<'de>,
+{
+    /// Open (or create) a database file.
+    ///
+    /// # Errors
+    ///
+    /// If the database cannot be opened or the schema cannot be created.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, SqliteStoreError> {
+        Self::from_connection(Connection::open(path)?)
+    }
+
+    /// Open a private in-memory database.
+    ///
+    /// # Errors
+    ///
+    /// If the schema cannot be created.
+    pub fn open_in_memory() -> Result<Self, SqliteStoreError> {
+        Self::from_connection(Connection::open_in_memory()?)
+    }
+
+    /// Use an existing connection, creating the schema if needed.
+    ///
+    /// Tables created without the `hash` or `sort_key` columns gain them,
+    /// filled in from the stored CIDs.
+    ///
+    /// # Errors
+    ///
+    /// If the schema cannot be created or migrated.
+    pub fn from_connection(conn: Connection) -> Result<Self, SqliteStoreError> {
+        conn.execute_batch(SCHEMA)?;
+        for (column, ty) in [("hash", "INTEGER"), ("sort_key", "BLOB")] {
+            let exists = conn
+                .prepare("SELECT 1 FROM pragma_table_info('delegations') WHERE name = ?1")?
+                .exists([column])?;
+            if !exists {
+                conn.execute_batch(&format!("ALTER TABLE delegations ADD COLUMN {column} {ty}"))?;
+            }
+        }
+        Self::fill_derived_columns(&conn)?;
+        conn.execute_batch(DERIVED_INDEXES)?;
+        Ok(SqliteStore {
+            conn: Mutex::new(conn),
+            _did: PhantomData,
+        })
+    }
+
+    /// Delete every delegation that has expired at `now`, returning how many were removed.
+    ///
+    /// # Errors
+    ///
+    /// If the database cannot be updated.
+    pub fn remove_expired(&self, now: Timestamp) -> Result<usize, SqliteStoreError> {
+        Ok(self.lock()?.execute(
+            "DELETE FROM delegations WHERE exp IS NOT NULL AND exp <= ?1",
+            [unix(now)],
+        )?)
+    }
+
+    /// Set the `hash` and `sort_key` columns of rows missing either.
+    fn fill_derived_columns(conn: &Connection) -> Result<(), SqliteStoreError> {
+        let mut select =
+            conn.prepare("SELECT cid FROM delegations WHERE hash IS NULL OR sort_key IS NULL")?;
+        let mut update =
+            conn.prepare("UPDATE delegations SET hash = ?1, sort_key = ?2 WHERE cid = ?3")?;
+        let mut rows = select.query([])?;
+        while let Some(row) = rows.next()? {
+            let bytes: Vec<u8> = row.get(0)?;
+            let cid = Cid::try_from(bytes.as_slice()).map_err(|_| SqliteStoreError::InvalidCid)?;
+            update.execute(params![hash_code(&cid), sort_key(&cid), bytes])?;
+        }
+        Ok(())
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, Connection>, SqliteStoreError> {
+        self.conn.lock().map_err(|_| SqliteStoreError::Poisoned)
+    }
+
+    /// Insert delegations in one transaction, after checking their CIDs.
+    fn insert_all(
+        &self,
+        delegations: &[(Cid, Arc<Delegation<D>>)],
+    ) -> Result<(), SqliteStoreError> {
+        let mut conn = self.lock()?;
+        let tx = conn.transaction()?;
+        {
+            let mut stmt = tx.prepare_cached(
+                "INSERT OR IGNORE INTO delegations
+                     (cid, envelope, iss, aud, sub, cmd, exp, nbf, hash, sort_key)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            )?;
+            for (cid, dlg) in delegations {
+                let envelope = serde_ipld_dagcbor::to_vec(&**dlg)?;
+                let builder =
+                    CidBuilder::for_cid(cid).ok_or(SqliteStoreError::UnsupportedHash(*cid))?;
+                if builder.build_from_dagcbor(&envelope) != *cid {
+                    return Err(SqliteStoreError::CidMismatch(*cid));
+                }
+
+                let sub = match dlg.subject() {
+                    DelegatedSubject::Specific(did) => Some(did.to_string()),
+                    DelegatedSubject::Any => None,
+                };
+                stmt.execute(params![
+                    cid.to_bytes(),
+                    envelope,
+                    dlg.issuer().to_string(),
+                    dlg.audience().to_string(),
+                    sub,
+                    dlg.command().to_string(),
+                    dlg.expiration().map(unix),
+                    dlg.not_before().map(unix),
+                    hash_code(cid),
+                    sort_key(cid),
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Read the delegation stored under exactly `cid`.
+    fn read(conn: &Connection, cid: &Cid) -> Result<Option<Delegation<D>>, SqliteStoreError> {
+        let envelope: Option<Vec<u8>> = conn
+            .prepare_cached("SELECT envelope FROM delegations WHERE cid = ?1")?
+            .query_row([cid.to_bytes()], |row| row.get(0))
+            .optional()?;
+        envelope.map(|bytes| decode(cid, &bytes)).transpose()
+    }
+
+    /// Read the delegation stored under `cid` or a CID equivalent to it.
+    ///
+    /// After a miss, only rows keyed with a different hash than `cid` are
+    /// rehashed: one with the same hash would have been found.
+    fn find(
+        conn: &Connection,
+        cid: &Cid,
+    ) -> Result<Option<(Cid, Delegation<D>)>, SqliteStoreError> {
+        if let Some(dlg) = Self::read(conn, cid)? {
+            return Ok(Some((*cid, dlg)));
+        }
+
+        let Some(builder) = CidBuilder::for_cid(cid) else {
+            return Ok(None);
+        };
+        let mut stmt =
+            conn.prepare_cached("SELECT cid, envelope FROM delegations WHERE hash != ?1")?;
+        let mut rows = stmt.query([hash_code(cid)])?;
+        while let Some(row) = rows.next()? {
+            let stored = Cid::try_from(row.get::<_, Vec<u8>>(0)?)
+                .map_err(|_| SqliteStoreError::InvalidCid)?;
+            let envelope: Vec<u8> = row.get(1)?;
+            if builder.build_from_dagcbor(&envelope) == *cid {
+                return Ok(Some((stored, decode(&stored, &envelope)?)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Run `SELECT cid, envelope FROM delegations {clause}` and decode the rows.
+    fn select(
+        conn: &Connection,
+        clause: &str,
+        args: Vec<Value>,
+    ) -> Result<Matches<Arc<Delegation<D>>>, SqliteStoreError> {
+        let mut stmt = conn.prepare(&format!("SELECT cid, envelope FROM delegations {clause}"))?;
+        let mut rows = stmt.query(params_from_iter(args))?;
+        let mut found = Vec::new();
+        while let Some(row) = rows.next()? {
+            let cid = Cid::try_from(row.get::<_, Vec<u8>>(0)?)
+                .map_err(|_| SqliteStoreError::InvalidCid)?;
+            let envelope: Vec<u8> = row.get(1)?;
+            found.push((cid, Arc::new(decode(&cid, &envelope)?)));
+        }
+        Ok(found)
+    }
+
+    fn query_all(
+        &self,
+        query: &DelegationQuery<D>,
+    ) -> Result<Matches<Arc<Delegation<D>>>, SqliteStoreError> {
+        let (clause, args) = where_clause(query);
+        let found = Self::select(&*self.lock()?, &clause, args)?;
+        // The SQL filter is exact, but checking again keeps the semantics
+        // identical to the in-memory stores
+        Ok(found
+            .into_iter()
+            .filter(|(_, dlg)| query.matches(dlg))
+            .collect())
+    }
+}
+
+/// Seconds since the Unix epoch, as stored in the `exp` and `nbf` columns.
+fn unix(time: Timestamp) -> i64 {
+    i64::try_from(time.to_unix()).unwrap_or(i64::MAX)
+}
+
+/// The CID's multihash code, as stored in the `hash` column.
+const fn hash_code(cid: &Cid) -> i64 {
+    cid.hash().code().cast_signed()
+}
+
+/// A key whose byte order is [`Cid`]'s `Ord`, as stored in the `sort_key` column.
+///
+/// `Ord` compares the version, codec, multihash code, digest size and digest
+/// in turn, so they are laid out in that order, with the integers big-endian.
+pub(super) fn sort_key(cid: &Cid) -> Vec<u8> {
+    let hash = cid.hash();
+    let mut key = Vec::with_capacity(18 + hash.digest().len());
+    key.push(u8::from(cid.version() == Version::V1));
+    key.extend_from_slice(&cid.codec().to_be_bytes());
+    key.extend_from_slice(&hash.code().to_be_bytes());
+    key.push(hash.size());
+    key.extend_from_slice(hash.digest());
+    key
+}
+
+/// Decode an envelope, after checking that it hashes to `cid`.
+fn decode<D: Did>(cid: &Cid, envelope: &[u8]) -> Result<Delegation<D>, SqliteStoreError>
+where
+    <D::VarsigConfig as Verify>::Signature: for<'de> Deserialize<'de>,
+{
+    let builder = CidBuilder::for_cid(cid).ok_or(SqliteStoreError::UnsupportedHash(*cid))?;
+    if builder.build_from_dagcbor(envelope) != *cid {
+        return Err(SqliteStoreError::Corrupt(*cid));
+    }
+    Ok(serde_ipld_dagcbor::from_slice(envelope)?)
+}
+
+/// Translate a query into a `WHERE` clause and its parameters.
+fn where_clause<D: Did>(query: &DelegationQuery<D>) -> (String, Vec<Value>) {
+    let mut conditions: Vec<String> = Vec::new();
+    let mut args: Vec<Value> = Vec::new();
+    let bind = |args: &mut Vec<Value>, value: Value| {
+        args.push(value);
+        format!("?{}", args.len())
+    };
+
+    if let Some(issuer) = query.issuer_criterion() {
+        let p = bind(&mut args, Value::Text(issuer.to_string()));
+        conditions.push(format!("iss = {p}"));
+    }
+    if let Some(audience) = query.audience_criterion() {
+        let p = bind(&mut args, Value::Text(audience.to_string()));
+        conditions.push(format!("aud = {p}"));
+    }
+    match query.subject_criterion() {
+        Some(DelegatedSubject::Specific(did)) => {
+            let p = bind(&mut args, Value::Text(did.to_string()));
+            conditions.push(format!("(sub = {p} OR sub IS NULL)"));
+        }
+        Some(DelegatedSubject::Any) => conditions.push("sub IS NULL".into()),
+        None => {}
+    }
+    if let Some(prefix) = query.command_prefix_criterion() {
+        // The root command is a prefix of every command
+        if !prefix.segments().is_empty() {
+            let exact = bind(&mut args, Value::Text(prefix.to_string()));
+            let below = bind(&mut args, Value::Text(format!("{prefix}/")));
+            conditions.push(format!(
+                "(cmd = {exact} OR substr(cmd, 1, length({below})) = {below})"
+            ));
+        }
+    }
+    if let Some(command) = query.grants_criterion() {
+        let ancestors: Vec<String> = (0..=command.segments().len())
+            .map(|n| {
+                let segments = command.segments().get(..n).unwrap_or_default().to_vec();
+                bind(&mut args, Value::Text(Command::new(segments).to_string()))
+            })
+            .collect();
+        conditions.push(format!("cmd IN ({})", ancestors.join(", ")));
+    }
+    if let Some(time) = query.valid_at_criterion() {
+        let p = bind(&mut args, Value::Integer(unix(time)));
+        conditions.push(format!(
+            "(exp IS NULL OR exp > {p}) AND (nbf IS NULL OR nbf <= {p})"
+        ));
+    }
+
+    if conditions.is_empty() {
+        (String::new(), args)
+    } else {
+        (format!("WHERE {}", conditions.join(" AND ")), args)
+    }
+}
+
+#[future_form(
+    Local,
+    Sendable where
+        D: Send + Sync,
+        <D as Did>::VarsigConfig: Send + Sync,
+        <<D as Did>::VarsigConfig as Verify>::Signature: Send + Sync
+)]
+impl<K: FutureForm, D: Did> DelegationStore<K, D, Arc<Delegation<D>>> for SqliteStore<D>
+where
+    <D::VarsigConfig as Verify>::Signature: for<'de> Deserialize<'de>,
+{
+    type InsertError = SqliteStoreError;
+    type GetError = SqliteStoreError;
+
+    fn get_all<'a>(
+        &'a self,
+        cid: &'a [Cid],
+    ) -> K::Future<'a, Result<Vec<Arc<Delegation<D>>>, Self::GetError>> {
+        K::from_future(async move {
+            let conn = self.lock()?;
+            let mut dlgs = Vec::new();
+            for c in cid {
+                let (_, dlg) = Self::find(&conn, c)?.ok_or(Missing(*c))?;
+                dlgs.push(Arc::new(dlg));
+            }
+            Ok(dlgs)
+        })
+    }
+
+    fn insert_by_cid(
+        &self,
+        cid: Cid,
+        delegation: Arc<Delegation<D>>,
+    ) -> K::Future<'_, Result<(), Self::InsertError>> {
+        K::from_future(async move { self.insert_all(&[(cid, delegation)]) })
+    }
+}
+
+#[future_form(
+    Local,
+    Sendable where
+        D: Send + Sync,
+        <D as Did>::VarsigConfig: Send + Sync,
+        <<D as Did>::VarsigConfig as Verify>::Signature: Send + Sync
+)]
+impl<K: FutureForm, D: Did> ListableStore<K, D, Arc<Delegation<D>>> for SqliteStore<D>
+where
+    <D::VarsigConfig as Verify>::Signature: for<'de> Deserialize<'de>,
+{
+    type ListError = SqliteStoreError;
+    type RemoveError = SqliteStoreError;
+
+    fn find_all<'a>(
+        &'a self,
+        cid: &'a [Cid],
+    ) -> K::Future<'a, Result<Found<Arc<Delegation<D>>>, Self::GetError>> {
+        K::from_future(async move {
+            let conn = self.lock()?;
+            cid.iter()
+                .map(|c| Ok(Self::find(&conn, c)?.map(|(key, dlg)| (key, Arc::new(dlg)))))
+                .collect()
+        })
+    }
+
+    fn insert_many_by_cid(
+        &self,
+        delegations: Vec<(Cid, Arc<Delegation<D>>)>,
+    ) -> K::Future<'_, Result<(), Self::InsertError>> {
+        K::from_future(async move { self.insert_all(&delegations) })
+    }
+
+    fn remove(
+        &self,
+        cid: Cid,
+    ) -> K::Future<'_, Result<Option<Arc<Delegation<D>>>, Self::RemoveError>> {
+        K::from_future(async move {
+            let conn = self.lock()?;
+            let Some((stored, dlg)) = Self::find(&conn, &cid)? else {
+                return Ok(None);
+            };
+            conn.execute(
+                "DELETE FROM delegations WHERE cid = ?1",
+                [stored.to_bytes()],
+            )?;
+            Ok(Some(Arc::new(dlg)))
+        })
+    }
+
+    fn contains(&self, cid: Cid) -> K::Future<'_, Result<bool, Self::ListError>> {
+        K::from_future(async move {
+            let conn = self.lock()?;
+            let exact = conn
+                .prepare_cached("SELECT 1 FROM delegations WHERE cid = ?1")?
+                .exists([cid.to_bytes()])?;
+            Ok(exact || Self::find(&conn, &cid)?.is_some())
+        })
+    }
+
+    fn count(&self) -> K::Future<'_, Result<usize, Self::ListError>> {
+        K::from_future(async move {
+            let count: i64 =
+                self.lock()?
+                    .query_row("SELECT COUNT(*) FROM delegations", [], |row| row.get(0))?;
+            Ok(usize::try_from(count).unwrap_or_default())
+        })
+    }
+
+    fn list_cids(&self, page: Page) -> K::Future<'_, Result<Vec<Cid>, Self::ListError>> {
+        K::from_future(async move {
+            let conn = self.lock()?;
+            let mut stmt = conn.prepare_cached(
+                "SELECT cid FROM delegations WHERE ?1 IS NULL OR sort_key > ?1
+                 ORDER BY sort_key LIMIT ?2",
+            )?;
+            let mut rows = stmt.query(params![
+                page.after.as_ref().map(sort_key),
+                i64::try_from(page.limit).unwrap_or(i64::MAX)
+            ])?;
+            let mut cids = Vec::new();
+            while let Some(row) = rows.next()? {
+                cids.push(
+                    Cid::try_from(row.get::<_, Vec<u8>>(0)?)
+                        .map_err(|_| SqliteStoreError::InvalidCid)?,
+                );
+            }
+            Ok(cids)
+        })
+    }
+
+    fn list(
+        &self,
+        page: Page,
+    ) -> K::Future<'_, Result<Matches<Arc<Delegation<D>>>, Self::ListError>> {
+        K::from_future(async move {
+            Self::select(
+                &*self.lock()?,
+                "WHERE ?1 IS NULL OR sort_key > ?1 ORDER BY sort_key LIMIT ?2",
+                vec![
+                    page.after
+                        .map_or(Value::Null, |cid| Value::Blob(sort_key(&cid))),
+                    Value::Integer(i64::try_from(page.limit).unwrap_or(i64::MAX)),
+                ],
+            )
+        })
+    }
+}
+
+#[future_form(
+    Local,
+    Sendable where
+        D: Send + Sync,
+        <D as Did>::VarsigConfig: Send + Sync,
+        <<D as Did>::VarsigConfig as Verify>::Signature: Send + Sync
+)]
+impl<K: FutureForm, D: Did> DelegationIndex<K, D, Arc<Delegation<D>>> for SqliteStore<D>
+where
+    <D::VarsigConfig as Verify>::Signature: for<'de> Deserialize<'de>,
+{
+    type QueryError = SqliteStoreError;
+
+    fn query<'a>(
+        &'a self,
+        query: &'a DelegationQuery<D>,
+    ) -> K::Future<'a, Result<Matches<Arc<Delegation<D>>>, Self::QueryError>> {
+        K::from_future(async move { self.query_all(query) })
+    }
+}
+
+/// Errors from a [`SqliteStore`].
+#[derive(Debug, Error)]
+pub enum SqliteStoreError {
+    /// The database returned an error.
+    #[error(transparent)]
+    Sqlite(#[from] rusqlite::Error),
+
+    /// A delegation could not be encoded.
+    #[error("unable to encode delegation: {0}")]
+    Encode(#[from] serde_ipld_dagcbor::EncodeError<TryReserveError>),
+
+    /// A stored delegation could not be decoded.
+    #[error("unable to decode delegation: {0}")]
+    Decode(#[from] serde_ipld_dagcbor::DecodeError<Infallible>),
+
+    /// A stored envelope does not hash to its CID.
+    #[error("stored delegation {0} does not match its CID")]
+    Corrupt(Cid),
+
+    /// A delegation was inserted under a CID that does not address it.
+    #[error("delegation does not match CID {0}")]
+    CidMismatch(Cid),
+
+    /// The CID's hash is not compiled in.
+    #[error("unsupported hash in CID {0}")]
+    UnsupportedHash(Cid),
+
+    /// A stored CID could not be parsed.
+    #[error("invalid CID in database")]
+    InvalidCid,
+
+    /// A requested delegation is not stored.
+    #[error(transparent)]
+    Missing(#[from] Missing),
+
+    /// Another thread panicked while using the connection.
+    #[error("database connection lock poisoned")]
+    Poisoned,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        delegation::store::{insert, insert_many},
+        did::Ed25519Did,
+        test_support::{at, block_on, delegation_with, signer, TempDir},
+    };
+    use ipld_core::cid::multihash::Multihash;
+    use testresult::TestResult;
+    use varsig::hash::Sha2_512;
+
+    type Store = SqliteStore<Ed25519Did>;
+
+    fn cids(found: Matches<Arc<Delegation<Ed25519Did>>>) -> Vec<Cid> {
+        let mut cids: Vec<Cid> = found.into_iter().map(|(cid, _)| cid).collect();
+        cids.sort();
+        cids
+    }
+
+    #[test]
+    fn test_insert_get_remove() -> TestResult {
+        let store = Store::open_in_memory()?;
+        let dlg: Arc<Delegation<Ed25519Did>> =
+            delegation_with(1, 2, DelegatedSubject::Any, "/crud/read", None)?;
+        let cid = block_on(insert::<Sendable, _, _, _>(&store, dlg.clone()))?;
+
+        let found = block_on(DelegationStore::<Sendable, _, _>::get_all(&store, &[cid]))?;
+        assert_eq!(found.first().map(|d| d.to_cid()), Some(cid));
+
+        let sha512 = dlg.to_cid_with(&CidBuilder::new::<Sha2_512>());
+        assert!(block_on(ListableStore::<Local, _, _>::contains(
+            &store, sha512
+        ))?);
+
+        assert!(block_on(ListableStore::<Sendable, _, _>::remove(&store, cid))?.is_some());
+        assert!(matches!(
+            block_on(DelegationStore::<Sendable, _, _>::get_all(&store, &[cid])),
+            Err(SqliteStoreError::Missing(Missing(c))) if c == cid
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_insert_many_is_transactional() -> TestResult {
+        let store = Store::open_in_memory()?;
+        let good: Arc<Delegation<Ed25519Did>> =
+            delegation_with(1, 2, DelegatedSubject::Any, "/a", None)?;
+        let bad: Arc<Delegation<Ed25519Did>> =
+            delegation_with(1, 3, DelegatedSubject::Any, "/b", None)?;
+        let unrelated: Arc<Delegation<Ed25519Did>> =
+            delegation_with(1, 4, DelegatedSubject::Any, "/c", None)?;
+
+        // The second delegation is filed under the wrong CID, so neither is stored
+        let result = block_on(ListableStore::<Sendable, _, _>::insert_many_by_cid(
+            &store,
+            vec![(good.to_cid(), good), (unrelated.to_cid(), bad)],
+        ));
+        assert!(matches!(result, Err(SqliteStoreError::CidMismatch(_))));
+        assert_eq!(block_on(ListableStore::<Sendable, _, _>::count(&store))?, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_query() -> TestResult {
+        let store = Store::open_in_memory()?;
+        let doc = *signer(9).did();
+        let write: Arc<Delegation<Ed25519Did>> =
+            delegation_with(1, 2, doc.into(), "/crud/write", None)?;
+        let read: Arc<Delegation<Ed25519Did>> =
+            delegation_with(1, 2, doc.into(), "/crud/read", Some(100))?;
+        let any: Arc<Delegation<Ed25519Did>> =
+            delegation_with(1, 2, DelegatedSubject::Any, "/crud", None)?;
+        let root: Arc<Delegation<Ed25519Did>> = delegation_with(1, 3, doc.into(), "/", None)?;
+        let mut all = block_on(insert_many::<Sendable, _, _, _>(
+            &store,
+            vec![write.clone(), read.clone(), any.clone(), root.clone()],
+        ))?;
+        all.sort();
+
+        let run = |query: DelegationQuery<Ed25519Did>| -> TestResult<Vec<Cid>> {
+            Ok(cids(block_on(DelegationIndex::<Sendable, _, _>::query(
+                &store, &query,
+            ))?))
+        };
+        let sorted = |dlgs: &[&Arc<Delegation<Ed25519Did>>]| -> Vec<Cid> {
+            let mut cids: Vec<Cid> = dlgs.iter().map(|d| d.to_cid()).collect();
+            cids.sort();
+            cids
+        };
+
+        assert_eq!(run(DelegationQuery::new())?, all);
+        assert_eq!(
+            run(DelegationQuery::new().audience(*signer(2).did()))?,
+            sorted(&[&write, &read, &any])
+        );
+        assert_eq!(
+            run(DelegationQuery::new().subject(DelegatedSubject::Specific(*signer(8).did())))?,
+            sorted(&[&any])
+        );
+        assert_eq!(
+            run(DelegationQuery::new().command_prefix(Command::parse("/crud")?))?,
+            sorted(&[&write, &read, &any])
+        );
+        assert_eq!(
+            run(DelegationQuery::new().grants(Command::parse("/crud/write")?))?,
+            sorted(&[&write, &any, &root])
+        );
+        assert_eq!(
+            run(DelegationQuery::new().valid_at(at(100)?))?,
+            sorted(&[&write, &any, &root])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_remove_expired_and_paginate() -> TestResult {
+        let store = Store::open_in_memory()?;
+        block_on(insert_many::<Sendable, _, _, _>(
+            &store,
+            vec![
+                delegation_with(1, 2, DelegatedSubject::Any, "/a", Some(100))?,
+                delegation_with(1, 2, DelegatedSubject::Any, "/b", Some(200))?,
+                delegation_with(1, 2, DelegatedSubject::Any, "/c", None)?,
+            ],
+        ))?;
+
+        assert_eq!(store.remove_expired(at(150)?)?, 1);
+        assert_eq!(block_on(ListableStore::<Sendable, _, _>::count(&store))?, 2);
+
+        let first = block_on(ListableStore::<Sendable, _, _>::list_cids(
+            &store,
+            Page::first(1),
+        ))?;
+        let last = *first.last().ok_or("empty page")?;
+        let second = block_on(ListableStore::<Sendable, _, _>::list(
+            &store,
+            Page::first(1).after(last),
+        ))?;
+        assert_eq!(second.len(), 1);
+        assert!(second.iter().all(|(cid, _)| *cid > last));
+        Ok(())
+    }
+
+    #[test]
+    fn test_adds_derived_columns_to_old_tables() -> TestResult {
+        let dir = TempDir::new("sqlite-migrate")?;
+        let path = dir.path().join("store.db");
+        let dlg: Arc<Delegation<Ed25519Did>> =
+            delegation_with(1, 2, DelegatedSubject::Any, "/a", None)?;
+        let cid = block_on(insert::<Sendable, _, _, _>(
+            &Store::open(&path)?,
+            dlg.clone(),
+        ))?;
+
+        // Recreate a table from before the hash and sort_key columns
+        Connection::open(&path)?.execute_batch(
+            "DROP INDEX delegations_hash; ALTER TABLE delegations DROP COLUMN hash;
+             DROP INDEX delegations_sort_key; ALTER TABLE delegations DROP COLUMN sort_key;",
+        )?;
+
+        let store = Store::open(&path)?;
+        let (hash, key): (i64, Vec<u8>) =
+            store
+                .lock()?
+                .query_row("SELECT hash, sort_key FROM delegations", [], |row| {
+                    Ok((row.get(0)?, row.get(1)?))
+                })?;
+        assert_eq!(hash, hash_code(&cid));
+        assert_eq!(key, sort_key(&cid));
+
+        let sha512 = dlg.to_cid_with(&CidBuilder::new::<Sha2_512>());
+        let found = block_on(DelegationStore::<Sendable, _, _>::get_all(
+            &store,
+            &[sha512],
+        ))?;
+        assert_eq!(found.first().map(|d| d.to_cid()), Some(cid));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sort_key_follows_cid_order() -> TestResult {
+        let mh = |code: u64, digest: &[u8]| Multihash::<64>::wrap(code, digest);
+        // Each pair is ordered differently by `Ord` and by the binary encoding
+        let mut cids = vec![
+            Cid::new_v0(mh(0x12, &[7; 32])?)?,
+            Cid::new_v1(0x71, mh(0x12, &[9; 32])?),
+            Cid::new_v1(0xff, mh(0x12, &[0; 32])?),
+            Cid::new_v1(0x100, mh(0x12, &[0; 32])?),
+            Cid::new_v1(0x71, mh(0xff, &[0; 4])?),
+            Cid::new_v1(0x71, mh(0x100, &[0; 4])?),
+        ];
+        cids.sort();
+        let mut by_key = cids.clone();
+        by_key.sort_by_key(sort_key);
+        assert_eq!(by_key, cids);
+
+        let mut by_bytes = cids.clone();
+        by_bytes.sort_by_key(Cid::to_bytes);
+        assert_ne!(by_bytes, cids);
+        Ok(())
+    }
+
+    #[test]
+    fn test_detects_corruption() -> TestResult {
+        let store = Store::open_in_memory()?;
+        let cid = block_on(insert::<Sendable, _, _, _>(
+            &store,
+            delegation_with(1, 2, DelegatedSubject::Any, "/a", None)?,
+        ))?;
+        let other: Arc<Delegation<Ed25519Did>> =
+            delegation_with(1, 3, DelegatedSubject::Any, "/b", None)?;
+        store.lock()?.execute(
+            "UPDATE delegations SET envelope = ?1",
+            [serde_ipld_dagcbor::to_vec(&*other)?],
+        )?;
+        assert!(matches!(
+            block_on(DelegationStore::<Sendable, _, _>::get_all(&store, &[cid])),
+            Err(SqliteStoreError::Corrupt(c)) if c == cid
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,772 @@
+//! Replication between delegation stores by range-based set reconciliation.
+//!
+//! Two peers each holding a [`DelegationStore`] converge on the union of their
+//! delegations. The CIDs are ordered, and the peers compare fingerprints of
+//! CID ranges, splitting the ranges that differ until they are small enough
+//! to list outright. Only the delegations a peer lacks are sent, and every
+//! one is checked against its CID and signature before it is stored.
+//!
+//! The protocol is a sequence of [`SyncMessage`] exchanges. [`Replicator::reconcile`]
+//! drives it from one side through a [`SyncTransport`], and
+//! [`Replicator::respond`] answers each message on the other. The responder keeps
+//! no state between messages, so any request/response transport will do;
+//! [`InProcess`] connects to a local store directly.
+
+use alloc::{collections::BTreeSet, vec::Vec};
+use core::{borrow::Borrow, convert::Infallible, fmt::Debug, marker::PhantomData};
+
+use future_form::{future_form, FutureForm, Local, Sendable};
+use ipld_core::cid::Cid;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use varsig::verify::Verify;
+
+use crate::{cid::CidBuilder, delegation::Delegation, did::Did};
+
+use super::{DelegationStore, ListableStore, Page};
+
+/// Ranges with at most this many CIDs are listed rather than split.
+const DEFAULT_MAX_IDS: usize = 16;
+
+/// The number of ranges a differing range is split into.
+const DEFAULT_BRANCHES: usize = 16;
+
+/// The number of exchanges after which reconciliation gives up.
+const DEFAULT_MAX_ROUNDS: usize = 64;
+
+/// The most ranges, and the most CIDs listed across them, accepted in one message.
+const DEFAULT_MAX_RANGES: usize = 1 << 16;
+
+/// The most wanted CIDs accepted in one message.
+const DEFAULT_MAX_WANT: usize = 1 << 16;
+
+/// A summary of the CIDs in a range: their count, and the wrapping sum of the
+/// first 16 bytes of each CID's digest.
+///
+/// Matching fingerprints mean the sets are equal with overwhelming probability
+/// for honestly generated CIDs. A peer that grinds CIDs to collide can only
+/// hide its own delegations or miss the other peer's.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Fingerprint {
+    /// The number of CIDs in the range.
+    pub count: u64,
+
+    /// The sum of the CIDs' digest prefixes.
+    pub sum: u128,
+}
+
+impl Fingerprint {
+    /// The fingerprint of `cids`.
+    #[must_use]
+    pub fn of<'a>(cids: impl IntoIterator<Item = &'a Cid>) -> Self {
+        cids.into_iter().fold(Fingerprint::default(), |fp, cid| {
+            let mut prefix = [0; 16];
+            for (byte, digest) in prefix.iter_mut().zip(cid.hash().digest()) {
+                *byte = *digest;
+            }
+            Fingerprint {
+                count: fp.count + 1,
+                sum: fp.sum.wrapping_add(u128::from_le_bytes(prefix)),
+            }
+        })
+    }
+}
+
+/// The CIDs from `lower` (inclusive) to `upper` (exclusive), where `None`
+/// is unbounded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CidRange {
+    /// The smallest CID in the range, or `None` from the first CID.
+    pub lower: Option<Cid>,
+
+    /// The first CID past the range, or `None` through the last CID.
+    pub upper: Option<Cid>,
+}
+
+impl CidRange {
+    /// Every CID.
+    #[must_use]
+    pub const fn all() -> Self {
+        CidRange {
+            lower: None,
+            upper: None,
+        }
+    }
+
+    /// Whether `cid` is in the range.
+    #[must_use]
+    pub fn contains(&self, cid: &Cid) -> bool {
+        self.lower.is_none_or(|lower| *cid >= lower) && self.upper.is_none_or(|upper| *cid < upper)
+    }
+
+    /// The CIDs of the sorted slice `cids` that are in the range.
+    fn slice<'a>(&self, cids: &'a [Cid]) -> &'a [Cid] {
+        let start = self
+            .lower
+            .map_or(0, |lower| cids.partition_point(|cid| *cid < lower));
+        let end = self
+            .upper
+            .map_or(cids.len(), |upper| cids.partition_point(|cid| *cid < upper));
+        cids.get(start..end.max(start)).unwrap_or_default()
+    }
+}
+
+/// What the sender holds in a range.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RangeSummary {
+    /// Too many CIDs to list; the receiver compares fingerprints.
+    Fingerprint(Fingerprint),
+
+    /// All of the sender's CIDs in the range. The receiver resolves the range.
+    Cids(Vec<Cid>),
+}
+
+/// One step of the protocol.
+///
+/// A message with nothing in it ends reconciliation.
+#[derive(Serialize, Deserialize)]
+#[serde(bound(
+    serialize = "",
+    deserialize = "<D::VarsigConfig as Verify>::Signature: for<'ze> Deserialize<'ze>"
+))]
+pub struct SyncMessage<D: Did> {
+    /// Ranges the receiver should compare against its own CIDs.
+    pub ranges: Vec<(CidRange, RangeSummary)>,
+
+    /// CIDs the sender lacks and asks the receiver for.
+    pub want: Vec<Cid>,
+
+    /// Delegations the receiver lacks, each with the CID it is stored under.
+    pub delegations: Vec<(Cid, Delegation<D>)>,
+}
+
+impl<D: Did> SyncMessage<D> {
+    /// A message with nothing in it.
+    #[must_use]
+    pub const fn new() -> Self {
+        SyncMessage {
+            ranges: Vec::new(),
+            want: Vec::new(),
+            delegations: Vec::new(),
+        }
+    }
+
+    /// Whether the message ends reconciliation.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.ranges.is_empty() && self.want.is_empty() && self.delegations.is_empty()
+    }
+}
+
+impl<D: Did> Default for SyncMessage<D> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<D: Did + Clone> Clone for SyncMessage<D> {
+    fn clone(&self) -> Self {
+        SyncMessage {
+            ranges: self.ranges.clone(),
+            want: self.want.clone(),
+            delegations: self.delegations.clone(),
+        }
+    }
+}
+
+impl<D: Did> Debug for SyncMessage<D> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("SyncMessage")
+            .field("ranges", &self.ranges)
+            .field("want", &self.want)
+            .field("delegations", &self.delegations)
+            .finish()
+    }
+}
+
+/// Carries a [`SyncMessage`] to the remote peer and returns its reply, which
+/// the remote computes with [`Replicator::respond`].
+pub trait SyncTransport<K: FutureForm, D: Did> {
+    /// The error returned when the exchange fails.
+    type Error: Debug;
+
+    /// Send `message` and wait for the reply.
+    fn exchange(
+        &mut self,
+        message: SyncMessage<D>,
+    ) -> K::Future<'_, Result<SyncMessage<D>, Self::Error>>;
+}
+
+/// A [`SyncTransport`] to a store in the same process.
+#[derive(Debug, Clone)]
+pub struct InProcess<'a, S, T> {
+    replicator: Replicator,
+    store: &'a S,
+    _delegation: PhantomData<fn() -> T>,
+}
+
+impl<'a, S, T> InProcess<'a, S, T> {
+    /// Answer with `replicator` from `store`.
+    #[must_use]
+    pub const fn new(replicator: Replicator, store: &'a S) -> Self {
+        InProcess {
+            replicator,
+            store,
+            _delegation: PhantomData,
+        }
+    }
+}
+
+#[future_form(
+    Local,
+    Sendable where
+        D: Send + Sync,
+        T: Send,
+        S: Sync,
+        <D as Did>::VarsigConfig: Send + Sync,
+        <<D as Did>::VarsigConfig as Verify>::Signature: Send + Sync,
+        <S as ListableStore<Sendable, D, T>>::ListError: Send,
+        <S as DelegationStore<Sendable, D, T>>::GetError: Send,
+        <S as DelegationStore<Sendable, D, T>>::InsertError: Send
+)]
+impl<K: FutureForm, D: Did + Clone + 'static, T: 'static, S> SyncTransport<K, D>
+    for InProcess<'_, S, T>
+where
+    T: Borrow<Delegation<D>> + From<Delegation<D>>,
+    S: ListableStore<K, D, T>,
+    S::ListError: 'static,
+    S::GetError: 'static,
+    S::InsertError: 'static,
+{
+    type Error = SyncError<S::ListError, S::GetError, S::InsertError>;
+
+    fn exchange(
+        &mut self,
+        message: SyncMessage<D>,
+    ) -> K::Future<'_, Result<SyncMessage<D>, Self::Error>> {
+        K::from_future(async move { self.replicator.respond(self.store, message).await })
+    }
+}
+
+/// Reconciles a [`DelegationStore`] with a peer's.
+///
+/// Both peers should use the same settings. Differing `max_ids` or `branches`
+/// only cost extra rounds, but a peer rejects messages past its own
+/// `max_ranges` or `max_want`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Replicator {
+    max_ids: usize,
+    branches: usize,
+    max_rounds: usize,
+    max_ranges: usize,
+    max_want: usize,
+}
+
+impl Replicator {
+    /// A replicator with the default settings.
+    #[must_use]
+    pub const fn new() -> Self {
+        Replicator {
+            max_ids: DEFAULT_MAX_IDS,
+            branches: DEFAULT_BRANCHES,
+            max_rounds: DEFAULT_MAX_ROUNDS,
+            max_ranges: DEFAULT_MAX_RANGES,
+            max_want: DEFAULT_MAX_WANT,
+        }
+    }
+
+    /// List ranges holding at most `max_ids` CIDs instead of splitting them.
+    #[must_use]
+    pub const fn max_ids(mut self, max_ids: usize) -> Self {
+        self.max_ids = max_ids;
+        self
+    }
+
+    /// Split differing ranges into `branches` parts (at least two).
+    #[must_use]
+    pub fn branches(mut self, branches: usize) -> Self {
+        self.branches = branches.max(2);
+        self
+    }
+
+    /// Give up after `max_rounds` exchanges.
+    #[must_use]
+    pub const fn max_rounds(mut self, max_rounds: usize) -> Self {
+        self.max_rounds = max_rounds;
+        self
+    }
+
+    /// Reject messages with more than `max_ranges` ranges, or listing more
+    /// than `max_ranges` CIDs across them.
+    #[must_use]
+    pub const fn max_ranges(mut self, max_ranges: usize) -> Self {
+        self.max_ranges = max_ranges;
+        self
+    }
+
+    /// Reject messages wanting more than `max_want` CIDs.
+    #[must_use]
+    pub const fn max_want(mut self, max_want: usize) -> Self {
+        self.max_want = max_want;
+        self
+    }
+
+    /// Bring `store` and the peer behind `transport` to the union of their
+    /// delegations.
+    ///
+    /// # Errors
+    ///
+    /// If the store or transport fails, the peer sends a message that is too
+    /// large, or it does not finish within the maximum number of rounds.
+    /// Delegations exchanged before the failure are kept.
+    pub async fn reconcile<K, D, T, S, X>(
+        &self,
+        store: &S,
+        transport: &mut X,
+    ) -> Result<SyncReport, SyncError<S::ListError, S::GetError, S::InsertError, X::Error>>
+    where
+        K: FutureForm,
+        D: Did + Clone,
+        T: Borrow<Delegation<D>> + From<Delegation<D>>,
+        S: ListableStore<K, D, T>,
+        X: SyncTransport<K, D>,
+    {
+        // Listed once; the rounds then track what they store themselves
+        let mut cids = snapshot(store).await.map_err(SyncError::List)?;
+        let mut report = SyncReport::default();
+        let mut message = SyncMessage::new();
+        message.ranges.push((
+            CidRange::all(),
+            RangeSummary::Fingerprint(Fingerprint::of(&cids)),
+        ));
+
+        while !message.is_empty() {
+            if report.rounds == self.max_rounds {
+                return Err(SyncError::TooManyRounds);
+            }
+            report.rounds += 1;
+            report
+                .sent
+                .extend(message.delegations.iter().map(|(cid, _)| *cid));
+
+            let reply = transport
+                .exchange(message)
+                .await
+                .map_err(SyncError::Transport)?;
+            self.check_size(&reply)?;
+            let (next, received) = self
+                .step(store, &mut cids, reply)
+                .await
+                .map_err(SyncError::widen)?;
+            report.received.extend(received.received);
+            report.rejected.extend(received.rejected);
+            message = next;
+        }
+
+        Ok(report)
+    }
+
+    /// Answer a message from a peer running [`Replicator::reconcile`],
+    /// storing the delegations it carries.
+    ///
+    /// # Errors
+    ///
+    /// If the store fails, or the message is too large.
+    pub async fn respond<K, D, T, S>(
+        &self,
+        store: &S,
+        message: SyncMessage<D>,
+    ) -> Result<SyncMessage<D>, SyncError<S::ListError, S::GetError, S::InsertError>>
+    where
+        K: FutureForm,
+        D: Did + Clone,
+        T: Borrow<Delegation<D>> + From<Delegation<D>>,
+        S: ListableStore<K, D, T>,
+    {
+        self.check_size(&message)?;
+        let mut cids = snapshot(store).await.map_err(SyncError::List)?;
+        Ok(self.step(store, &mut cids, message).await?.0)
+    }
+
+    /// Reject a message with more ranges, listed CIDs or wanted CIDs than allowed.
+    fn check_size<D: Did, L, G, I, X>(
+        &self,
+        message: &SyncMessage<D>,
+    ) -> Result<(), SyncError<L, G, I, X>> {
+        let listed = message
+            .ranges
+            .iter()
+            .map(|(_, summary)| match summary {
+                RangeSummary::Fingerprint(_) => 0,
+                RangeSummary::Cids(cids) => cids.len(),
+            })
+            .fold(0, usize::saturating_add);
+        if message.ranges.len() > self.max_ranges
+            || listed > self.max_ranges
+            || message.want.len() > self.max_want
+        {
+            return Err(SyncError::TooLarge);
+        }
+        Ok(())
+    }
+
+    /// Store the delegations in `message`, and compute the reply. The caller
+    /// checks the message's size.
+    ///
+    /// `cids` is the store's sorted CIDs, and gains those stored here.
+    async fn step<K, D, T, S>(
+        &self,
+        store: &S,
+        cids: &mut Vec<Cid>,
+        message: SyncMessage<D>,
+    ) -> Result<(SyncMessage<D>, SyncReport), SyncError<S::ListError, S::GetError, S::InsertError>>
+    where
+        K: FutureForm,
+        D: Did + Clone,
+        T: Borrow<Delegation<D>> + From<Delegation<D>>,
+        S: ListableStore<K, D, T>,
+    {
+        let mut report = SyncReport::default();
+        let mut accepted = Vec::new();
+        for (cid, dlg) in message.delegations {
+            if addresses(&cid, &dlg) && dlg.verify_signature().is_ok() {
+                accepted.push((cid, T::from(dlg)));
+                report.received.push(cid);
+            } else {
+                tracing::warn!("rejected replicated delegation {cid}");
+                report.rejected.push(cid);
+            }
+        }
+        if !accepted.is_empty() {
+            store
+                .insert_many_by_cid(accepted)
+                .await
+                .map_err(SyncError::Insert)?;
+            for cid in &report.received {
+                if let Err(at) = cids.binary_search(cid) {
+                    cids.insert(at, *cid);
+                }
+            }
+        }
+
+        let mut reply = SyncMessage::new();
+        let mut push = message.want;
+        for (range, summary) in message.ranges {
+            let ours = range.slice(cids);
+            match summary {
+                RangeSummary::Fingerprint(theirs) => {
+                    if Fingerprint::of(ours) != theirs {
+                        self.describe(range, ours, &mut reply.ranges);
+                    }
+                }
+                RangeSummary::Cids(theirs) => {
+                    let theirs: BTreeSet<Cid> = theirs
+                        .into_iter()
+                        .filter(|cid| range.contains(cid))
+                        .collect();
+                    reply
+                        .want
+                        .extend(theirs.iter().filter(|cid| ours.binary_search(cid).is_err()));
+                    push.extend(ours.iter().filter(|cid| !theirs.contains(cid)));
+                }
+            }
+        }
+
+        // Only send what is still stored; a CID may have been removed since
+        // the snapshot, or never been ours
+        push.retain(|cid| cids.binary_search(cid).is_ok());
+        if !push.is_empty() {
+            let found = store.find_all(&push).await.map_err(SyncError::Get)?;
+            reply.delegations = push
+                .into_iter()
+                .zip(found)
+                .filter_map(|(cid, found)| {
+                    found.map(|(_, dlg)| (cid, Borrow::<Delegation<D>>::borrow(&dlg).clone()))
+                })
+                .collect();
+        }
+
+        Ok((reply, report))
+    }
+
+    /// Summarize the sorted CIDs `ours` in `range`: listed if few enough,
+    /// otherwise split into fingerprinted subranges.
+    fn describe(&self, range: CidRange, ours: &[Cid], out: &mut Vec<(CidRange, RangeSummary)>) {
+        if ours.len() <= self.max_ids {
+            out.push((range, RangeSummary::Cids(ours.to_vec())));
+            return;
+        }
+
+        let chunk = ours.len().div_ceil(self.branches);
+        let mut lower = range.lower;
+        let mut chunks = ours.chunks(chunk).peekable();
+        while let Some(part) = chunks.next() {
+            let upper = match chunks.peek() {
+                Some(next) => next.first().copied(),
+                None => range.upper,
+            };
+            out.push((
+                CidRange { lower, upper },
+                RangeSummary::Fingerprint(Fingerprint::of(part)),
+            ));
+            lower = upper;
+        }
+    }
+}
+
+impl Default for Replicator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Every CID in `store`, sorted.
+async fn snapshot<K: FutureForm, D: Did, T: Borrow<Delegation<D>>, S: ListableStore<K, D, T>>(
+    store: &S,
+) -> Result<Vec<Cid>, S::ListError> {
+    let mut cids = store.list_cids(Page::all()).await?;
+    cids.sort_unstable();
+    Ok(cids)
+}
+
+/// Whether `cid` addresses `delegation`.
+fn addresses<D: Did>(cid: &Cid, delegation: &Delegation<D>) -> bool {
+    CidBuilder::for_cid(cid).is_some_and(|builder| delegation.to_cid_with(&builder) == *cid)
+}
+
+/// The outcome of reconciliation, from one peer's side.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SyncReport {
+    /// The number of messages sent to the peer.
+    pub rounds: usize,
+
+    /// CIDs of the delegations sent to the peer.
+    pub sent: Vec<Cid>,
+
+    /// CIDs of the delegations received and stored.
+    pub received: Vec<Cid>,
+
+    /// CIDs of received delegations dropped because the CID does not address
+    /// the delegation or its signature is invalid.
+    pub rejected: Vec<Cid>,
+}
+
+/// Errors from [`Replicator`].
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum SyncError<L, G, I, X = Infallible> {
+    /// Listing the local store failed.
+    #[error("unable to list delegations: {0}")]
+    List(L),
+
+    /// Reading from the local store failed.
+    #[error("unable to read delegations: {0}")]
+    Get(G),
+
+    /// Writing to the local store failed.
+    #[error("unable to store delegations: {0}")]
+    Insert(I),
+
+    /// The exchange with the peer failed.
+    #[error("transport failed: {0:?}")]
+    Transport(X),
+
+    /// The peer kept sending ranges past the maximum number of rounds.
+    #[error("reconciliation did not finish within the maximum number of rounds")]
+    TooManyRounds,
+
+    /// A message had more ranges or wanted CIDs than allowed.
+    #[error("message exceeds the maximum number of ranges or wanted CIDs")]
+    TooLarge,
+}
+
+impl<L, G, I> SyncError<L, G, I> {
+    /// Reuse a local error where a transport error is also possible.
+    fn widen<X>(self) -> SyncError<L, G, I, X> {
+        match self {
+            SyncError::List(e) => SyncError::List(e),
+            SyncError::Get(e) => SyncError::Get(e),
+            SyncError::Insert(e) => SyncError::Insert(e),
+            SyncError::Transport(never) => match never {},
+            SyncError::TooManyRounds => SyncError::TooManyRounds,
+            SyncError::TooLarge => SyncError::TooLarge,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        delegation::store::{insert, insert_many, ShardedStore},
+        did::Ed25519Did,
+        test_support::{block_on, delegation},
+    };
+    use alloc::{format, sync::Arc};
+    use testresult::TestResult;
+
+    type Store = ShardedStore<Ed25519Did>;
+
+    fn delegations(prefix: &str, n: usize) -> TestResult<Vec<Arc<Delegation<Ed25519Did>>>> {
+        (0..n)
+            .map(|i| delegation(&format!("/{prefix}/{i}")))
+            .collect()
+    }
+
+    fn cids(store: &Store) -> TestResult<Vec<Cid>> {
+        let mut cids = block_on(ListableStore::<Sendable, _, _>::list_cids(
+            store,
+            Page::all(),
+        ))?;
+        cids.sort_unstable();
+        Ok(cids)
+    }
+
+    /// Reconcile `local` with `remote` in process.
+    fn reconcile(replicator: Replicator, local: &Store, remote: &Store) -> TestResult<SyncReport> {
+        let mut transport = InProcess::new(replicator, remote);
+        Ok(block_on(
+            replicator.reconcile::<Sendable, _, _, _, _>(local, &mut transport),
+        )?)
+    }
+
+    #[test]
+    fn test_converges() -> TestResult {
+        let shared = delegations("shared", 500)?;
+        let local = Store::new();
+        let remote = Store::new();
+        block_on(insert_many::<Sendable, _, _, _>(&local, shared.clone()))?;
+        block_on(insert_many::<Sendable, _, _, _>(&remote, shared))?;
+        let local_only = block_on(insert_many::<Sendable, _, _, _>(
+            &local,
+            delegations("local", 3)?,
+        ))?;
+        let remote_only = block_on(insert_many::<Sendable, _, _, _>(
+            &remote,
+            delegations("remote", 40)?,
+        ))?;
+
+        let report = reconcile(Replicator::new(), &local, &remote)?;
+        assert_eq!(cids(&local)?, cids(&remote)?);
+        assert_eq!(cids(&local)?.len(), 543);
+
+        let mut sent = report.sent;
+        sent.sort_unstable();
+        let mut expected = local_only;
+        expected.sort_unstable();
+        assert_eq!(sent, expected);
+        let mut received = report.received;
+        received.sort_unstable();
+        let mut expected = remote_only;
+        expected.sort_unstable();
+        assert_eq!(received, expected);
+        assert!(report.rejected.is_empty());
+
+        // Now in sync: one round that only compares fingerprints
+        let report = reconcile(Replicator::new(), &local, &remote)?;
+        assert_eq!(report.rounds, 1);
+        assert!(report.sent.is_empty() && report.received.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_empty_peers() -> TestResult {
+        let local = Store::new();
+        let remote = Store::new();
+        let report = reconcile(Replicator::new(), &local, &remote)?;
+        assert_eq!(report.rounds, 1);
+
+        let cids = block_on(insert_many::<Sendable, _, _, _>(
+            &remote,
+            delegations("remote", 100)?,
+        ))?;
+        let report = reconcile(Replicator::new().max_ids(2).branches(2), &local, &remote)?;
+        assert_eq!(report.received.len(), cids.len());
+        assert_eq!(block_on(ListableStore::<Local, _, _>::count(&local))?, 100);
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejects_mismatched_delegation() -> TestResult {
+        let store = Store::new();
+        let dlg: Arc<Delegation<Ed25519Did>> = delegation("/a")?;
+        let wrong = delegation::<Delegation<Ed25519Did>>("/b")?.to_cid();
+
+        let mut message = SyncMessage::new();
+        message.delegations.push((wrong, (*dlg).clone()));
+        message.delegations.push((dlg.to_cid(), (*dlg).clone()));
+        let (_, report) = block_on(
+            Replicator::new().step::<Sendable, Ed25519Did, Arc<Delegation<Ed25519Did>>, _>(
+                &store,
+                &mut Vec::new(),
+                message,
+            ),
+        )?;
+        assert_eq!(report.rejected, vec![wrong]);
+        assert_eq!(report.received, vec![dlg.to_cid()]);
+        assert_eq!(cids(&store)?, vec![dlg.to_cid()]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_message_round_trips() -> TestResult {
+        let store = Store::new();
+        let cid = block_on(insert::<Sendable, _, _, _>(&store, delegation("/a")?))?;
+
+        // The responder lists its single CID and pushes the delegation
+        let mut message = SyncMessage::new();
+        message
+            .ranges
+            .push((CidRange::all(), RangeSummary::Cids(Vec::new())));
+        let reply = block_on(Replicator::new().respond::<Sendable, _, _, _>(&store, message))?;
+        assert_eq!(
+            reply
+                .delegations
+                .iter()
+                .map(|(c, _)| *c)
+                .collect::<Vec<_>>(),
+            vec![cid]
+        );
+
+        let bytes = serde_ipld_dagcbor::to_vec(&reply)?;
+        let decoded: SyncMessage<Ed25519Did> = serde_ipld_dagcbor::from_slice(&bytes)?;
+        assert_eq!(decoded.ranges, reply.ranges);
+        assert_eq!(decoded.want, reply.want);
+        assert_eq!(
+            decoded.delegations.first().map(|(c, d)| (*c, d.to_cid())),
+            Some((cid, cid))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejects_large_messages() -> TestResult {
+        let store = Store::new();
+        let replicator = Replicator::new().max_ranges(2).max_want(2);
+        let cids = block_on(insert_many::<Sendable, _, _, _>(
+            &store,
+            delegations("a", 3)?,
+        ))?;
+        let respond = |message| {
+            block_on(
+                replicator.respond::<Sendable, Ed25519Did, Arc<Delegation<Ed25519Did>>, _>(
+                    &store, message,
+                ),
+            )
+        };
+
+        let mut message = SyncMessage::new();
+        message.want.clone_from(&cids);
+        assert!(matches!(respond(message), Err(SyncError::TooLarge)));
+
+        let mut message = SyncMessage::new();
+        message
+            .ranges
+            .push((CidRange::all(), RangeSummary::Cids(cids.clone())));
+        assert!(matches!(respond(message), Err(SyncError::TooLarge)));
+
+        let mut message = SyncMessage::new();
+        message.want = cids.get(..2).unwrap_or_default().to_vec();
+        assert_eq!(respond(message)?.delegations.len(), 2);
+        Ok(())
+    }
+}
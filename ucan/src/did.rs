@@ -1,10 +1,27 @@
 //! Decentralized Identifier (DID) helpers.
 
 use alloc::{format, string::ToString, vec::Vec};
+use async_signature::AsyncSigner;
 use core::{fmt::Debug, str::FromStr};
 use serde::{Deserialize, Deserializer, Serialize};
 use thiserror::Error;
-use varsig::{signature::eddsa::Ed25519, signer::Sign};
+use varsig::{signature::eddsa::Ed25519, signer::Sign, verify::Verify};
+
+#[cfg(feature = "hd")]
+pub mod hd;
+#[cfg(feature = "keys")]
+pub mod keys;
+
+#[cfg(feature = "ed448")]
+use varsig::signature::eddsa::{Ed448, Ed448SigningKey, Ed448VerifyingKey};
+
+#[cfg(feature = "bls")]
+use varsig::signature::bls::{Bls12381MinPk, BlsSigningKey, BlsVerifyingKey};
+
+#[cfg(feature = "rsa")]
+use varsig::signature::rsa::{
+    Pkcs1v15, Rsa, RsaKeySize, RsaPadding, RsaSigningKey, RsaVerifyingKey,
+};
 
 /// A trait for [DID]s.
 ///
@@ -20,6 +37,9 @@ pub trait Did:
 
     /// Get the associated `Varsig` configuration.
     fn varsig_config(&self) -> &Self::VarsigConfig;
+
+    /// Get the key that verifies signatures issued by this DID.
+    fn verifier(&self) -> &<Self::VarsigConfig as Verify>::Verifier;
 }
 
 /// A trait for DID signers.
@@ -34,6 +54,38 @@ pub trait DidSigner {
     fn signer(&self) -> &<<Self::Did as Did>::VarsigConfig as Sign>::Signer;
 }
 
+/// A trait for DID signers that sign asynchronously.
+///
+/// Suitable for keys held outside the process (e.g. a remote KMS,
+/// an agent daemon, or `WebCrypto` `SubtleCrypto`). Every [`DidSigner`]
+/// is also an `AsyncDidSigner`.
+pub trait AsyncDidSigner {
+    /// The associated DID type.
+    type Did: Did + Clone;
+
+    /// The asynchronous signer.
+    type AsyncSigner: AsyncSigner<<<Self::Did as Did>::VarsigConfig as Verify>::Signature>;
+
+    /// Get the associated DID.
+    fn did(&self) -> &Self::Did;
+
+    /// Get the associated asynchronous signer instance.
+    fn async_signer(&self) -> &Self::AsyncSigner;
+}
+
+impl<T: DidSigner> AsyncDidSigner for T {
+    type Did = T::Did;
+    type AsyncSigner = <<T::Did as Did>::VarsigConfig as Sign>::Signer;
+
+    fn did(&self) -> &Self::Did {
+        DidSigner::did(self)
+    }
+
+    fn async_signer(&self) -> &Self::AsyncSigner {
+        self.signer()
+    }
+}
+
 /// An `Ed25519` `did:key`.
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Ed25519Did(pub ed25519_dalek::VerifyingKey, Ed25519);
@@ -129,6 +181,10 @@ impl Did for Ed25519Did {
     fn varsig_config(&self) -> &Self::VarsigConfig {
         &self.1
     }
+
+    fn verifier(&self) -> &<Self::VarsigConfig as Verify>::Verifier {
+        &self.0
+    }
 }
 
 impl Serialize for Ed25519Did {
@@ -278,3 +334,891 @@ impl Serialize for Ed25519Signer {
         self.did.serialize(serializer)
     }
 }
+
+/// The multicodec prefix for `ed448-pub` (`0x1203`), LEB128 encoded.
+#[cfg(feature = "ed448")]
+const ED448_PUB: [u8; 2] = [0x83, 0x24];
+
+/// An `Ed448` `did:key`.
+#[cfg(feature = "ed448")]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ed448Did(pub Ed448VerifyingKey, Ed448);
+
+#[cfg(feature = "ed448")]
+impl From<ed448_goldilocks_plus::VerifyingKey> for Ed448Did {
+    fn from(key: ed448_goldilocks_plus::VerifyingKey) -> Self {
+        Ed448Did(Ed448VerifyingKey(key), Ed448::new())
+    }
+}
+
+#[cfg(feature = "ed448")]
+impl From<Ed448VerifyingKey> for Ed448Did {
+    fn from(key: Ed448VerifyingKey) -> Self {
+        Ed448Did(key, Ed448::new())
+    }
+}
+
+#[cfg(feature = "ed448")]
+impl core::fmt::Display for Ed448Did {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let mut raw_bytes = Vec::with_capacity(ED448_PUB.len() + 57);
+        raw_bytes.extend_from_slice(&ED448_PUB);
+        raw_bytes.extend_from_slice(self.0 .0.as_bytes());
+        let b58 = bs58::encode(raw_bytes.as_slice()).into_string();
+        write!(f, "did:key:z{b58}")
+    }
+}
+
+#[cfg(feature = "ed448")]
+impl FromStr for Ed448Did {
+    type Err = Ed448DidFromStrError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let b58 = s
+            .strip_prefix("did:key:")
+            .ok_or(Ed448DidFromStrError::InvalidDidHeader)?
+            .strip_prefix('z')
+            .ok_or(Ed448DidFromStrError::MissingBase58Prefix)?;
+        let raw = bs58::decode(b58)
+            .into_vec()
+            .map_err(|_| Ed448DidFromStrError::InvalidBase58)?;
+        let key_bytes = raw
+            .strip_prefix(&ED448_PUB)
+            .ok_or(Ed448DidFromStrError::InvalidKey)?;
+        let key_arr: [u8; 57] = key_bytes
+            .try_into()
+            .map_err(|_| Ed448DidFromStrError::InvalidKey)?;
+        let key = ed448_goldilocks_plus::VerifyingKey::from_bytes(&key_arr)
+            .map_err(|_| Ed448DidFromStrError::InvalidKey)?;
+        Ok(key.into())
+    }
+}
+
+/// Errors that can occur when parsing an `Ed448Did` from a string.
+#[cfg(feature = "ed448")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Error)]
+pub enum Ed448DidFromStrError {
+    /// The DID header is invalid.
+    #[error("invalid did header")]
+    InvalidDidHeader,
+
+    /// The base58 prefix 'z' is missing.
+    #[error("missing base58 prefix 'z'")]
+    MissingBase58Prefix,
+
+    /// The base58 encoding is invalid.
+    #[error("invalid base58 encoding")]
+    InvalidBase58,
+
+    /// The key bytes are invalid.
+    #[error("invalid key bytes")]
+    InvalidKey,
+}
+
+#[cfg(feature = "ed448")]
+impl Did for Ed448Did {
+    type VarsigConfig = Ed448;
+
+    fn did_method(&self) -> &'static str {
+        "key"
+    }
+
+    fn varsig_config(&self) -> &Self::VarsigConfig {
+        &self.1
+    }
+
+    fn verifier(&self) -> &<Self::VarsigConfig as Verify>::Verifier {
+        &self.0
+    }
+}
+
+#[cfg(feature = "ed448")]
+impl Serialize for Ed448Did {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+#[cfg(feature = "ed448")]
+impl<'de> Deserialize<'de> for Ed448Did {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = alloc::string::String::deserialize(deserializer)?;
+        s.parse()
+            .map_err(|e| serde::de::Error::custom(format!("unable to parse ed448 did:key: {e}")))
+    }
+}
+
+/// An `Ed448` `did:key` signer.
+#[cfg(feature = "ed448")]
+#[derive(Debug, Clone)]
+pub struct Ed448Signer {
+    did: Ed448Did,
+    signer: Ed448SigningKey,
+}
+
+#[cfg(feature = "ed448")]
+impl Ed448Signer {
+    /// Create a new `Ed448Signer` from a signing key.
+    #[must_use]
+    pub fn new(signer: ed448_goldilocks_plus::SigningKey) -> Self {
+        let did = signer.verifying_key().into();
+        Self {
+            did,
+            signer: Ed448SigningKey(signer),
+        }
+    }
+
+    /// Get the associated DID.
+    #[must_use]
+    pub const fn did(&self) -> &Ed448Did {
+        &self.did
+    }
+
+    /// Get the associated signer.
+    #[must_use]
+    pub const fn signer(&self) -> &Ed448SigningKey {
+        &self.signer
+    }
+}
+
+#[cfg(feature = "ed448")]
+impl From<ed448_goldilocks_plus::SigningKey> for Ed448Signer {
+    fn from(signer: ed448_goldilocks_plus::SigningKey) -> Self {
+        Self::new(signer)
+    }
+}
+
+#[cfg(feature = "ed448")]
+impl core::fmt::Display for Ed448Signer {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.did)
+    }
+}
+
+#[cfg(feature = "ed448")]
+impl DidSigner for Ed448Signer {
+    type Did = Ed448Did;
+
+    fn did(&self) -> &Self::Did {
+        &self.did
+    }
+
+    fn signer(&self) -> &<<Self::Did as Did>::VarsigConfig as Sign>::Signer {
+        &self.signer
+    }
+}
+
+#[cfg(feature = "ed448")]
+impl Serialize for Ed448Signer {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        self.did.serialize(serializer)
+    }
+}
+
+/// The multicodec prefix for `rsa-pub` (`0x1205`), LEB128 encoded.
+#[cfg(feature = "rsa")]
+const RSA_PUB: [u8; 2] = [0x85, 0x24];
+
+/// An RSA `did:key`.
+///
+/// The key is encoded as a PKCS #1 DER `RSAPublicKey`. The padding scheme
+/// `P` selects the signature algorithm: [`Pkcs1v15`] (RS256, the default)
+/// or [`RsaPss`] (PS256).
+///
+/// The `did:key` string is encoded once at construction, so [`Display`] and
+/// [`Serialize`] cannot fail.
+///
+/// [`Display`]: core::fmt::Display
+/// [`RsaPss`]: varsig::signature::rsa::RsaPss
+#[cfg(feature = "rsa")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct RsaDid<P: RsaPadding = Pkcs1v15> {
+    verifier: RsaVerifyingKey<P>,
+    config: Rsa<P, varsig::hash::Sha2_256>,
+    encoded: alloc::string::String,
+}
+
+#[cfg(feature = "rsa")]
+impl<P: RsaPadding> RsaDid<P> {
+    /// Get the underlying public key.
+    #[must_use]
+    pub const fn public_key(&self) -> &rsa::RsaPublicKey {
+        &self.verifier.0
+    }
+
+    /// Get the key size.
+    #[must_use]
+    pub const fn key_size(&self) -> RsaKeySize {
+        self.config.key_size()
+    }
+}
+
+#[cfg(feature = "rsa")]
+impl<P: RsaPadding> TryFrom<rsa::RsaPublicKey> for RsaDid<P> {
+    type Error = RsaDidFromStrError;
+
+    fn try_from(key: rsa::RsaPublicKey) -> Result<Self, Self::Error> {
+        use rsa::pkcs1::EncodeRsaPublicKey;
+
+        let key_size =
+            RsaKeySize::from_public_key(&key).ok_or(RsaDidFromStrError::UnsupportedKeySize)?;
+        let der = key
+            .to_pkcs1_der()
+            .map_err(|_| RsaDidFromStrError::InvalidKey)?;
+        let mut raw_bytes = Vec::with_capacity(RSA_PUB.len() + der.as_bytes().len());
+        raw_bytes.extend_from_slice(&RSA_PUB);
+        raw_bytes.extend_from_slice(der.as_bytes());
+        let encoded = format!("did:key:z{}", bs58::encode(raw_bytes).into_string());
+        Ok(RsaDid {
+            verifier: RsaVerifyingKey::new(key),
+            config: Rsa::new(key_size),
+            encoded,
+        })
+    }
+}
+
+#[cfg(feature = "rsa")]
+impl<P: RsaPadding> core::fmt::Display for RsaDid<P> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(&self.encoded)
+    }
+}
+
+#[cfg(feature = "rsa")]
+impl<P: RsaPadding> FromStr for RsaDid<P> {
+    type Err = RsaDidFromStrError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use rsa::pkcs1::DecodeRsaPublicKey;
+
+        let b58 = s
+            .strip_prefix("did:key:")
+            .ok_or(RsaDidFromStrError::InvalidDidHeader)?
+            .strip_prefix('z')
+            .ok_or(RsaDidFromStrError::MissingBase58Prefix)?;
+        let raw = bs58::decode(b58)
+            .into_vec()
+            .map_err(|_| RsaDidFromStrError::InvalidBase58)?;
+        let der = raw
+            .strip_prefix(&RSA_PUB)
+            .ok_or(RsaDidFromStrError::InvalidKey)?;
+        let key =
+            rsa::RsaPublicKey::from_pkcs1_der(der).map_err(|_| RsaDidFromStrError::InvalidKey)?;
+        key.try_into()
+    }
+}
+
+/// Errors that can occur when parsing an `RsaDid` from a string.
+#[cfg(feature = "rsa")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Error)]
+pub enum RsaDidFromStrError {
+    /// The DID header is invalid.
+    #[error("invalid did header")]
+    InvalidDidHeader,
+
+    /// The base58 prefix 'z' is missing.
+    #[error("missing base58 prefix 'z'")]
+    MissingBase58Prefix,
+
+    /// The base58 encoding is invalid.
+    #[error("invalid base58 encoding")]
+    InvalidBase58,
+
+    /// The key bytes are invalid.
+    #[error("invalid key bytes")]
+    InvalidKey,
+
+    /// The modulus is not 2048, 3072, or 4096 bits.
+    #[error("unsupported RSA key size")]
+    UnsupportedKeySize,
+}
+
+#[cfg(feature = "rsa")]
+impl<P: RsaPadding> Did for RsaDid<P> {
+    type VarsigConfig = Rsa<P, varsig::hash::Sha2_256>;
+
+    fn did_method(&self) -> &'static str {
+        "key"
+    }
+
+    fn varsig_config(&self) -> &Self::VarsigConfig {
+        &self.config
+    }
+
+    fn verifier(&self) -> &<Self::VarsigConfig as Verify>::Verifier {
+        &self.verifier
+    }
+}
+
+#[cfg(feature = "rsa")]
+impl<P: RsaPadding> Serialize for RsaDid<P> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.encoded)
+    }
+}
+
+#[cfg(feature = "rsa")]
+impl<'de, P: RsaPadding> Deserialize<'de> for RsaDid<P> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = alloc::string::String::deserialize(deserializer)?;
+        s.parse()
+            .map_err(|e| serde::de::Error::custom(format!("unable to parse rsa did:key: {e}")))
+    }
+}
+
+/// An RSA `did:key` signer.
+#[cfg(feature = "rsa")]
+#[derive(Debug, Clone)]
+pub struct RsaSigner<P: RsaPadding = Pkcs1v15> {
+    did: RsaDid<P>,
+    signer: RsaSigningKey<P>,
+}
+
+#[cfg(feature = "rsa")]
+impl<P: RsaPadding> RsaSigner<P> {
+    /// Create a new `RsaSigner` from a private key.
+    ///
+    /// # Errors
+    ///
+    /// If the key is not 2048, 3072, or 4096 bits.
+    pub fn try_new(signer: rsa::RsaPrivateKey) -> Result<Self, RsaDidFromStrError> {
+        let did = signer.to_public_key().try_into()?;
+        Ok(Self {
+            did,
+            signer: RsaSigningKey::new(signer),
+        })
+    }
+
+    /// Get the associated DID.
+    #[must_use]
+    pub const fn did(&self) -> &RsaDid<P> {
+        &self.did
+    }
+
+    /// Get the associated signer.
+    #[must_use]
+    pub const fn signer(&self) -> &RsaSigningKey<P> {
+        &self.signer
+    }
+}
+
+#[cfg(feature = "rsa")]
+impl<P: RsaPadding> TryFrom<rsa::RsaPrivateKey> for RsaSigner<P> {
+    type Error = RsaDidFromStrError;
+
+    fn try_from(signer: rsa::RsaPrivateKey) -> Result<Self, Self::Error> {
+        Self::try_new(signer)
+    }
+}
+
+#[cfg(feature = "rsa")]
+impl<P: RsaPadding> core::fmt::Display for RsaSigner<P> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.did)
+    }
+}
+
+#[cfg(feature = "rsa")]
+impl<P: RsaPadding> DidSigner for RsaSigner<P> {
+    type Did = RsaDid<P>;
+
+    fn did(&self) -> &Self::Did {
+        &self.did
+    }
+
+    fn signer(&self) -> &<<Self::Did as Did>::VarsigConfig as Sign>::Signer {
+        &self.signer
+    }
+}
+
+#[cfg(feature = "rsa")]
+impl<P: RsaPadding> Serialize for RsaSigner<P> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        self.did.serialize(serializer)
+    }
+}
+
+/// The multicodec prefix for `bls12_381-g1-pub` (`0xea`), LEB128 encoded.
+#[cfg(feature = "bls")]
+const BLS12_381_G1_PUB: [u8; 2] = [0xea, 0x01];
+
+/// A BLS12-381 `did:key` (public key in G1).
+///
+/// Chains made entirely of these DIDs can have all of their signatures
+/// checked at once with [`crate::aggregate`].
+#[cfg(feature = "bls")]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bls12381Did(pub BlsVerifyingKey, Bls12381MinPk);
+
+#[cfg(feature = "bls")]
+impl From<BlsVerifyingKey> for Bls12381Did {
+    fn from(key: BlsVerifyingKey) -> Self {
+        Bls12381Did(key, Bls12381MinPk::new())
+    }
+}
+
+#[cfg(feature = "bls")]
+impl core::fmt::Display for Bls12381Did {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let mut raw_bytes = Vec::with_capacity(BLS12_381_G1_PUB.len() + 48);
+        raw_bytes.extend_from_slice(&BLS12_381_G1_PUB);
+        raw_bytes.extend_from_slice(&self.0.to_bytes());
+        let b58 = bs58::encode(raw_bytes.as_slice()).into_string();
+        write!(f, "did:key:z{b58}")
+    }
+}
+
+#[cfg(feature = "bls")]
+impl FromStr for Bls12381Did {
+    type Err = Bls12381DidFromStrError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let b58 = s
+            .strip_prefix("did:key:")
+            .ok_or(Bls12381DidFromStrError::InvalidDidHeader)?
+            .strip_prefix('z')
+            .ok_or(Bls12381DidFromStrError::MissingBase58Prefix)?;
+        let raw = bs58::decode(b58)
+            .into_vec()
+            .map_err(|_| Bls12381DidFromStrError::InvalidBase58)?;
+        let key_bytes = raw
+            .strip_prefix(&BLS12_381_G1_PUB)
+            .ok_or(Bls12381DidFromStrError::InvalidKey)?;
+        let key_arr: [u8; 48] = key_bytes
+            .try_into()
+            .map_err(|_| Bls12381DidFromStrError::InvalidKey)?;
+        let key = BlsVerifyingKey::from_bytes(&key_arr)
+            .map_err(|_| Bls12381DidFromStrError::InvalidKey)?;
+        Ok(key.into())
+    }
+}
+
+/// Errors that can occur when parsing a `Bls12381Did` from a string.
+#[cfg(feature = "bls")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Error)]
+pub enum Bls12381DidFromStrError {
+    /// The DID header is invalid.
+    #[error("invalid did header")]
+    InvalidDidHeader,
+
+    /// The base58 prefix 'z' is missing.
+    #[error("missing base58 prefix 'z'")]
+    MissingBase58Prefix,
+
+    /// The base58 encoding is invalid.
+    #[error("invalid base58 encoding")]
+    InvalidBase58,
+
+    /// The key bytes are invalid.
+    #[error("invalid key bytes")]
+    InvalidKey,
+}
+
+#[cfg(feature = "bls")]
+impl Did for Bls12381Did {
+    type VarsigConfig = Bls12381MinPk;
+
+    fn did_method(&self) -> &'static str {
+        "key"
+    }
+
+    fn varsig_config(&self) -> &Self::VarsigConfig {
+        &self.1
+    }
+
+    fn verifier(&self) -> &<Self::VarsigConfig as Verify>::Verifier {
+        &self.0
+    }
+}
+
+#[cfg(feature = "bls")]
+impl Serialize for Bls12381Did {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+#[cfg(feature = "bls")]
+impl<'de> Deserialize<'de> for Bls12381Did {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = alloc::string::String::deserialize(deserializer)?;
+        s.parse().map_err(|e| {
+            serde::de::Error::custom(format!("unable to parse bls12-381 did:key: {e}"))
+        })
+    }
+}
+
+/// A BLS12-381 `did:key` signer.
+#[cfg(feature = "bls")]
+#[derive(Debug, Clone)]
+pub struct Bls12381Signer {
+    did: Bls12381Did,
+    signer: BlsSigningKey,
+}
+
+#[cfg(feature = "bls")]
+impl Bls12381Signer {
+    /// Create a new `Bls12381Signer` from a signing key.
+    #[must_use]
+    pub fn new(signer: BlsSigningKey) -> Self {
+        Self {
+            did: signer.verifying_key().into(),
+            signer,
+        }
+    }
+
+    /// Get the associated DID.
+    #[must_use]
+    pub const fn did(&self) -> &Bls12381Did {
+        &self.did
+    }
+
+    /// Get the associated signer.
+    #[must_use]
+    pub const fn signer(&self) -> &BlsSigningKey {
+        &self.signer
+    }
+}
+
+#[cfg(feature = "bls")]
+impl From<BlsSigningKey> for Bls12381Signer {
+    fn from(signer: BlsSigningKey) -> Self {
+        Self::new(signer)
+    }
+}
+
+#[cfg(feature = "bls")]
+impl core::fmt::Display for Bls12381Signer {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.did)
+    }
+}
+
+#[cfg(feature = "bls")]
+impl DidSigner for Bls12381Signer {
+    type Did = Bls12381Did;
+
+    fn did(&self) -> &Self::Did {
+        &self.did
+    }
+
+    fn signer(&self) -> &<<Self::Did as Did>::VarsigConfig as Sign>::Signer {
+        &self.signer
+    }
+}
+
+#[cfg(feature = "bls")]
+impl Serialize for Bls12381Signer {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        self.did.serialize(serializer)
+    }
+}
+
+#[cfg(all(test, feature = "ed448"))]
+mod tests {
+    use super::*;
+    use crate::{delegation::subject::DelegatedSubject, Delegation, DelegationBuilder};
+    use testresult::TestResult;
+
+    fn ed448_signer(seed: u8) -> Ed448Signer {
+        let secret = ed448_goldilocks_plus::SecretKey::from([seed; 57]);
+        ed448_goldilocks_plus::SigningKey::from(&secret).into()
+    }
+
+    #[test]
+    fn test_ed448_did_string_round_trip() -> TestResult {
+        let did = *ed448_signer(1).did();
+        let s = did.to_string();
+
+        assert!(s.starts_with("did:key:z"));
+        assert_eq!(Ed448Did::from_str(&s)?, did);
+        Ok(())
+    }
+
+    #[test]
+    fn test_ed448_did_rejects_ed25519_key() -> TestResult {
+        let ed25519: Ed25519Did = ed25519_dalek::VerifyingKey::from_bytes(&[0u8; 32])?.into();
+        assert_eq!(
+            Ed448Did::from_str(&ed25519.to_string()),
+            Err(Ed448DidFromStrError::InvalidKey)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_ed448_delegation_round_trip() -> TestResult {
+        let iss = ed448_signer(1);
+        let aud = *ed448_signer(2).did();
+
+        let delegation = DelegationBuilder::new()
+            .issuer(iss.clone())
+            .audience(aud)
+            .subject(DelegatedSubject::Specific(*iss.did()))
+            .command_from_str("/crud/read")?
+            .try_build()?;
+
+        let bytes = serde_ipld_dagcbor::to_vec(&delegation)?;
+        let decoded: Delegation<Ed448Did> = serde_ipld_dagcbor::from_slice(&bytes)?;
+
+        assert_eq!(decoded.issuer(), iss.did());
+        assert_eq!(decoded.audience(), &aud);
+        assert_eq!(decoded.to_cid(), delegation.to_cid());
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "rsa"))]
+mod rsa_tests {
+    use super::*;
+    use crate::{delegation::subject::DelegatedSubject, Delegation, DelegationBuilder};
+    use rsa::rand_core::OsRng;
+    use testresult::TestResult;
+    use varsig::signature::rsa::RsaPss;
+
+    fn rsa_key(bits: usize) -> Result<rsa::RsaPrivateKey, rsa::Error> {
+        rsa::RsaPrivateKey::new(&mut OsRng, bits)
+    }
+
+    #[test]
+    fn test_rsa_did_string_round_trip() -> TestResult {
+        let did: RsaDid = rsa_key(2048)?.to_public_key().try_into()?;
+        let s = did.to_string();
+
+        assert!(s.starts_with("did:key:z"));
+        assert_eq!(RsaDid::from_str(&s)?, did);
+        assert_eq!(did.key_size(), RsaKeySize::Rsa2048);
+        Ok(())
+    }
+
+    #[test]
+    fn test_rsa_did_rejects_unsupported_key_size() -> TestResult {
+        let key = rsa_key(1024)?.to_public_key();
+        assert_eq!(
+            RsaDid::<Pkcs1v15>::try_from(key),
+            Err(RsaDidFromStrError::UnsupportedKeySize)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_rsa_did_rejects_ed25519_key() -> TestResult {
+        let ed25519: Ed25519Did = ed25519_dalek::VerifyingKey::from_bytes(&[0u8; 32])?.into();
+        assert_eq!(
+            RsaDid::<Pkcs1v15>::from_str(&ed25519.to_string()),
+            Err(RsaDidFromStrError::InvalidKey)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_rsa_delegation_round_trip() -> TestResult {
+        let iss: RsaSigner = rsa_key(2048)?.try_into()?;
+        let aud: RsaDid = rsa_key(2048)?.to_public_key().try_into()?;
+
+        let delegation = DelegationBuilder::new()
+            .issuer(iss.clone())
+            .audience(aud.clone())
+            .subject(DelegatedSubject::Specific(iss.did().clone()))
+            .command_from_str("/crud/read")?
+            .try_build()?;
+
+        let bytes = serde_ipld_dagcbor::to_vec(&delegation)?;
+        let decoded: Delegation<RsaDid> = serde_ipld_dagcbor::from_slice(&bytes)?;
+
+        assert_eq!(decoded.issuer(), iss.did());
+        assert_eq!(decoded.audience(), &aud);
+        assert_eq!(decoded.to_cid(), delegation.to_cid());
+        Ok(())
+    }
+
+    #[test]
+    fn test_rsa_pss_delegation_round_trip() -> TestResult {
+        let iss: RsaSigner<RsaPss> = rsa_key(2048)?.try_into()?;
+        let aud: RsaDid<RsaPss> = rsa_key(2048)?.to_public_key().try_into()?;
+
+        let delegation = DelegationBuilder::new()
+            .issuer(iss.clone())
+            .audience(aud.clone())
+            .subject(DelegatedSubject::Specific(iss.did().clone()))
+            .command_from_str("/crud/read")?
+            .try_build()?;
+
+        let bytes = serde_ipld_dagcbor::to_vec(&delegation)?;
+        let decoded: Delegation<RsaDid<RsaPss>> = serde_ipld_dagcbor::from_slice(&bytes)?;
+        decoded.verify_signature()?;
+
+        // The same key under the other padding yields the same DID string,
+        // but the PSS header does not decode as RS256
+        assert_eq!(
+            iss.did().to_string(),
+            RsaDid::<Pkcs1v15>::from_str(&iss.did().to_string())?.to_string()
+        );
+        assert!(serde_ipld_dagcbor::from_slice::<Delegation<RsaDid>>(&bytes).is_err());
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "bls"))]
+mod bls_tests {
+    use super::*;
+    use testresult::TestResult;
+
+    #[test]
+    fn test_bls12_381_did_string_round_trip() -> TestResult {
+        let did = *Bls12381Signer::new(BlsSigningKey::from_ikm(&[1; 32])?).did();
+        let s = did.to_string();
+
+        assert!(s.starts_with("did:key:z"));
+        assert_eq!(Bls12381Did::from_str(&s)?, did);
+        Ok(())
+    }
+
+    #[test]
+    fn test_bls12_381_did_rejects_ed25519_key() -> TestResult {
+        let ed25519: Ed25519Did = ed25519_dalek::VerifyingKey::from_bytes(&[0u8; 32])?.into();
+        assert_eq!(
+            Bls12381Did::from_str(&ed25519.to_string()),
+            Err(Bls12381DidFromStrError::InvalidKey)
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod async_tests {
+    use super::*;
+    use crate::{
+        delegation::subject::DelegatedSubject, invocation::builder::InvocationBuilder,
+        DelegationBuilder,
+    };
+    use futures::executor::block_on;
+    use testresult::TestResult;
+
+    /// Stands in for a key held outside the process.
+    struct RemoteKey(ed25519_dalek::SigningKey);
+
+    impl AsyncSigner<ed25519_dalek::Signature> for RemoteKey {
+        async fn sign_async(
+            &self,
+            msg: &[u8],
+        ) -> Result<ed25519_dalek::Signature, signature::Error> {
+            signature::Signer::try_sign(&self.0, msg)
+        }
+    }
+
+    struct RemoteSigner {
+        did: Ed25519Did,
+        key: RemoteKey,
+    }
+
+    impl AsyncDidSigner for RemoteSigner {
+        type Did = Ed25519Did;
+        type AsyncSigner = RemoteKey;
+
+        fn did(&self) -> &Ed25519Did {
+            &self.did
+        }
+
+        fn async_signer(&self) -> &RemoteKey {
+            &self.key
+        }
+    }
+
+    fn remote_signer(seed: u8) -> RemoteSigner {
+        let sk = ed25519_dalek::SigningKey::from_bytes(&[seed; 32]);
+        RemoteSigner {
+            did: sk.clone().into(),
+            key: RemoteKey(sk),
+        }
+    }
+
+    #[test]
+    fn test_async_delegation_matches_sync() -> TestResult {
+        let remote = remote_signer(1);
+        let local: Ed25519Signer = ed25519_dalek::SigningKey::from_bytes(&[1; 32]).into();
+        let nonce = crate::crypto::nonce::Nonce::generate_16()?;
+
+        let dlg = block_on(
+            DelegationBuilder::new()
+                .issuer(remote)
+                .audience(*local.did())
+                .subject(DelegatedSubject::Any)
+                .command_from_str("/crud/read")?
+                .nonce(nonce.clone())
+                .try_build_async(),
+        )?;
+        dlg.verify_signature()?;
+
+        let sync = DelegationBuilder::new()
+            .issuer(local.clone())
+            .audience(*local.did())
+            .subject(DelegatedSubject::Any)
+            .command_from_str("/crud/read")?
+            .nonce(nonce)
+            .try_build()?;
+        assert_eq!(dlg.to_cid(), sync.to_cid());
+        Ok(())
+    }
+
+    #[test]
+    fn test_async_invocation() -> TestResult {
+        let remote = remote_signer(1);
+        let iss = *AsyncDidSigner::did(&remote);
+
+        let inv = block_on(
+            InvocationBuilder::new()
+                .issuer(remote)
+                .audience(iss)
+                .subject(iss)
+                .command_from_str("/crud/read")?
+                .proofs(vec![])
+                .try_build_async(),
+        )?;
+        inv.verify_signature()?;
+        assert_eq!(inv.issuer(), &iss);
+        Ok(())
+    }
+
+    #[test]
+    fn test_sync_signers_sign_async() -> TestResult {
+        let local: Ed25519Signer = ed25519_dalek::SigningKey::from_bytes(&[2; 32]).into();
+        let inv = block_on(
+            InvocationBuilder::new()
+                .issuer(local.clone())
+                .audience(*local.did())
+                .subject(*local.did())
+                .command_from_str("/crud/read")?
+                .proofs(vec![])
+                .try_build_async(),
+        )?;
+        inv.verify_signature()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,369 @@
+//! Hierarchical deterministic key derivation.
+//!
+//! Derives any number of [`Ed25519Signer`]s from one master seed using
+//! [SLIP-0010], so a single seed backs up every device or session identity.
+//! Ed25519 only supports hardened derivation, so every path component must be
+//! hardened (e.g. `m/0'/1'`).
+//!
+//! ```
+//! # use ucan::did::hd::{DerivationPath, ExtendedSigningKey};
+//! # fn main() -> Result<(), Box<dyn core::error::Error>> {
+//! let path: DerivationPath = "m/0'/7'".parse()?;
+//! let signer = ExtendedSigningKey::from_seed(&[42; 32])?.derive(&path)?.signer();
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! With the `hd_secp256k1` and `hd_p256` features, [`ExtendedSecretKey`]
+//! derives raw secp256k1 ([BIP-32]) and P-256 (SLIP-0010) keys, hardened or
+//! not. There are no DID types for those curves, so they produce ECDSA signing
+//! keys rather than signers. Under BIP-32 a few indices have no valid key;
+//! [`ExtendedSecretKey::derive_next_child`] skips past them.
+//!
+//! [SLIP-0010]: https://github.com/satoshilabs/slips/blob/master/slip-0010.md
+//! [BIP-32]: https://github.com/bitcoin/bips/blob/master/bip-0032.mediawiki
+
+use super::Ed25519Signer;
+use alloc::{string::String, vec::Vec};
+use core::{fmt, str::FromStr};
+use hmac::{Hmac, Mac};
+use sha2::Sha512;
+use thiserror::Error;
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+#[cfg(any(feature = "hd_secp256k1", feature = "hd_p256"))]
+mod bip32;
+
+#[cfg(any(feature = "hd_secp256k1", feature = "hd_p256"))]
+pub use bip32::{Curve, ExtendedSecretKey};
+
+#[cfg(feature = "hd_p256")]
+pub use bip32::NistP256;
+
+#[cfg(feature = "hd_secp256k1")]
+pub use bip32::Secp256k1;
+
+/// The bit set on hardened child indices.
+pub const HARDENED: u32 = 1 << 31;
+
+/// HMAC key for the master node of the Ed25519 curve.
+const ED25519_SEED_KEY: &[u8] = b"ed25519 seed";
+
+/// A path of child indices, written as `m/0'/1`.
+///
+/// Hardened indices (marked `'`, `h` or `H`) are stored with the
+/// [`HARDENED`] bit set.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct DerivationPath(Vec<u32>);
+
+impl DerivationPath {
+    /// Build a path from child indices; those with the [`HARDENED`] bit set
+    /// are hardened.
+    #[must_use]
+    pub const fn new(indices: Vec<u32>) -> Self {
+        DerivationPath(indices)
+    }
+
+    /// The child indices, with the hardened bit set on hardened ones.
+    #[must_use]
+    pub fn indices(&self) -> &[u32] {
+        &self.0
+    }
+}
+
+impl fmt::Display for DerivationPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("m")?;
+        for index in &self.0 {
+            if index & HARDENED == 0 {
+                write!(f, "/{index}")?;
+            } else {
+                write!(f, "/{}'", index & !HARDENED)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for DerivationPath {
+    type Err = HdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut segments = s.split('/');
+        if segments.next() != Some("m") {
+            return Err(HdError::InvalidPath(s.into()));
+        }
+
+        let indices = segments
+            .map(|segment| {
+                let (index, hardened) = segment
+                    .strip_suffix(['\'', 'h', 'H'])
+                    .map_or((segment, 0), |index| (index, HARDENED));
+                // Reject signs and leading zeros, which `u32::from_str` accepts
+                if index.is_empty()
+                    || !index.bytes().all(|b| b.is_ascii_digit())
+                    || (index.len() > 1 && index.starts_with('0'))
+                {
+                    return Err(HdError::InvalidPath(s.into()));
+                }
+                let index = index
+                    .parse::<u32>()
+                    .map_err(|_| HdError::InvalidPath(s.into()))?;
+                if index >= HARDENED {
+                    return Err(HdError::IndexOutOfRange(index));
+                }
+                Ok(index | hardened)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(DerivationPath(indices))
+    }
+}
+
+/// An Ed25519 secret key with its SLIP-0010 chain code.
+#[derive(Clone, Zeroize, ZeroizeOnDrop)]
+pub struct ExtendedSigningKey {
+    #[zeroize(skip)]
+    depth: u8,
+    secret: [u8; 32],
+    chain_code: [u8; 32],
+}
+
+impl ExtendedSigningKey {
+    /// Derive the master key from a seed of 16 to 64 bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`HdError::InvalidSeedLength`] for other seed lengths.
+    pub fn from_seed(seed: &[u8]) -> Result<Self, HdError> {
+        if !(16..=64).contains(&seed.len()) {
+            return Err(HdError::InvalidSeedLength(seed.len()));
+        }
+        Ok(Self::from_hmac(0, ED25519_SEED_KEY, &[seed]))
+    }
+
+    /// Derive the child at `index`, which must have the [`HARDENED`] bit set.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`HdError::NonHardened`] if `index` is not hardened, or
+    /// [`HdError::DepthExceeded`] past depth 255.
+    pub fn derive_child(&self, index: u32) -> Result<Self, HdError> {
+        if index & HARDENED == 0 {
+            return Err(HdError::NonHardened(index));
+        }
+        let depth = self.depth.checked_add(1).ok_or(HdError::DepthExceeded)?;
+        Ok(Self::from_hmac(
+            depth,
+            &self.chain_code,
+            &[&[0], &self.secret, &index.to_be_bytes()],
+        ))
+    }
+
+    /// Derive the descendant at `path`, relative to this key.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`HdError::NonHardened`] if the path has an unhardened segment,
+    /// or [`HdError::DepthExceeded`] if the result would be deeper than 255.
+    pub fn derive(&self, path: &DerivationPath) -> Result<Self, HdError> {
+        path.0
+            .iter()
+            .try_fold(self.clone(), |key, index| key.derive_child(*index))
+    }
+
+    /// The number of derivations from the master key.
+    #[must_use]
+    pub const fn depth(&self) -> u8 {
+        self.depth
+    }
+
+    /// The chain code.
+    #[must_use]
+    pub const fn chain_code(&self) -> &[u8; 32] {
+        &self.chain_code
+    }
+
+    /// The Ed25519 secret key (seed) bytes.
+    #[must_use]
+    pub const fn secret_bytes(&self) -> &[u8; 32] {
+        &self.secret
+    }
+
+    /// The signer for this key.
+    #[must_use]
+    pub fn signer(&self) -> Ed25519Signer {
+        ed25519_dalek::SigningKey::from_bytes(&self.secret).into()
+    }
+
+    fn from_hmac(depth: u8, key: &[u8], data: &[&[u8]]) -> Self {
+        let mut output = hmac_sha512(key, data);
+        let mut extended = ExtendedSigningKey {
+            depth,
+            secret: [0; 32],
+            chain_code: [0; 32],
+        };
+        let (secret, chain_code) = output.split_at(32);
+        extended.secret.copy_from_slice(secret);
+        extended.chain_code.copy_from_slice(chain_code);
+        output.zeroize();
+        extended
+    }
+}
+
+impl fmt::Debug for ExtendedSigningKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ExtendedSigningKey")
+            .field("depth", &self.depth)
+            .field("did", self.signer().did())
+            .finish_non_exhaustive()
+    }
+}
+
+/// HMAC-SHA512 of the concatenated `data`; the caller zeroizes the output.
+fn hmac_sha512(key: &[u8], data: &[&[u8]]) -> [u8; 64] {
+    #[allow(clippy::expect_used)]
+    let mut mac = Hmac::<Sha512>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    for chunk in data {
+        mac.update(chunk);
+    }
+    mac.finalize().into_bytes().into()
+}
+
+/// Derive the [`Ed25519Signer`] at `path` from a master seed.
+///
+/// # Errors
+///
+/// Returns an [`HdError`] if the seed length is invalid or the path is too deep.
+pub fn derive_ed25519(seed: &[u8], path: &DerivationPath) -> Result<Ed25519Signer, HdError> {
+    Ok(ExtendedSigningKey::from_seed(seed)?.derive(path)?.signer())
+}
+
+/// Errors from hierarchical key derivation.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum HdError {
+    /// Seeds must be 16 to 64 bytes long.
+    #[error("seed must be 16 to 64 bytes, got {0}")]
+    InvalidSeedLength(usize),
+
+    /// A path segment is at or above [`HARDENED`] before marking.
+    #[error("child index {0} is out of range")]
+    IndexOutOfRange(u32),
+
+    /// Ed25519 only supports hardened derivation.
+    #[error("child index {0} is not hardened")]
+    NonHardened(u32),
+
+    /// The path is not of the form `m/0'/1`.
+    #[error("invalid derivation path {0:?}")]
+    InvalidPath(String),
+
+    /// Keys cannot be derived deeper than 255 levels.
+    #[error("derivation depth exceeds 255")]
+    DepthExceeded,
+
+    /// BIP-32 rejects the master key for this seed.
+    #[error("seed does not yield a valid master key")]
+    InvalidMasterKey,
+
+    /// BIP-32 has no key at this child index; try the next one.
+    #[error("child index {0} does not yield a valid key")]
+    InvalidChild(u32),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+    use testresult::TestResult;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().fold(String::new(), |mut out, b| {
+            core::fmt::Write::write_fmt(&mut out, format_args!("{b:02x}")).ok();
+            out
+        })
+    }
+
+    /// SLIP-0010 test vector 1 for ed25519.
+    #[test]
+    fn test_slip10_vector_1() -> TestResult {
+        let seed = [
+            0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d,
+            0x0e, 0x0f,
+        ];
+        let master = ExtendedSigningKey::from_seed(&seed)?;
+        assert_eq!(
+            hex(master.secret_bytes()),
+            "2b4be7f19ee27bbf30c667b642d5f4aa69fd169872f8fc3059c08ebae2eb19e7"
+        );
+        assert_eq!(
+            hex(master.chain_code()),
+            "90046a93de5380a72b5e45010748567d5ea02bbf6522f979e05c0d8d8ca9fffb"
+        );
+
+        let child = master.derive(&"m/0'/1'".parse()?)?;
+        assert_eq!(child.depth(), 2);
+        assert_eq!(
+            hex(child.secret_bytes()),
+            "b1d0bad404bf35da785a64ca1ac54b2617211d2777696fbffaf208f746ae84f2"
+        );
+        assert_eq!(
+            hex(child.chain_code()),
+            "a320425f77d1b5c2505a6b1b27382b37368ee640e3557c315416801243552f14"
+        );
+        assert_eq!(
+            hex(child.signer().did().0.as_bytes().as_slice()),
+            "1932a5270f335bed617d5b935c80aedb1a35bd9fc1e31acafd5372c30f5c1187"
+        );
+
+        let grandchild = child.derive_child(2 | HARDENED)?;
+        assert_eq!(
+            hex(grandchild.secret_bytes()),
+            "92a5b23c0b8a99e37d07df3fb9966917f5d06e02ddbd909c7e184371463e9fc9"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_distinct_paths() -> TestResult {
+        let seed = [7; 32];
+        let laptop = derive_ed25519(&seed, &"m/0'".parse()?)?;
+        let phone = derive_ed25519(&seed, &"m/1'".parse()?)?;
+        assert_ne!(laptop.did(), phone.did());
+        assert_eq!(derive_ed25519(&seed, &"m/0h".parse()?)?.did(), laptop.did());
+        Ok(())
+    }
+
+    #[test]
+    fn test_path_parsing() -> TestResult {
+        let path: DerivationPath = "m/44'/0H/2147483647h/5".parse()?;
+        assert_eq!(path.indices(), &[HARDENED + 44, HARDENED, u32::MAX, 5]);
+        assert_eq!(path.to_string(), "m/44'/0'/2147483647'/5");
+        assert_eq!("m".parse::<DerivationPath>()?, DerivationPath::default());
+
+        assert_eq!(
+            derive_ed25519(&[0; 32], &"m/0'/1".parse()?).err(),
+            Some(HdError::NonHardened(1))
+        );
+        assert!(matches!(
+            "m/2147483648".parse::<DerivationPath>(),
+            Err(HdError::IndexOutOfRange(_))
+        ));
+        for invalid in ["", "0'", "m/", "m/+1'", "m/01'", "m/'", "M/0'"] {
+            assert!(invalid.parse::<DerivationPath>().is_err(), "{invalid}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_seed() {
+        assert_eq!(
+            ExtendedSigningKey::from_seed(&[0; 15]).err(),
+            Some(HdError::InvalidSeedLength(15))
+        );
+        assert_eq!(
+            ExtendedSigningKey::from_seed(&[0; 65]).err(),
+            Some(HdError::InvalidSeedLength(65))
+        );
+    }
+}
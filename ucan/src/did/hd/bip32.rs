@@ -0,0 +1,493 @@
+//! BIP-32 style derivation for the secp256k1 and P-256 curves.
+//!
+//! secp256k1 follows BIP-32 and P-256 follows SLIP-0010. The two only differ
+//! when a derived scalar is out of range (probability below 2^-127): BIP-32
+//! rejects the master key or child index, and the caller moves on to the next
+//! index ([`ExtendedSecretKey::derive_next_child`]), while SLIP-0010 rehashes
+//! and retries, so every index yields a key.
+
+use super::{hmac_sha512, DerivationPath, HdError, HARDENED};
+use core::{fmt, marker::PhantomData};
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// A curve supported by [`ExtendedSecretKey`].
+///
+/// This trait is sealed.
+pub trait Curve: sealed::Sealed + Clone {
+    /// The ECDSA signing key type.
+    type SigningKey;
+
+    /// HMAC key for the master node.
+    #[doc(hidden)]
+    const SEED_KEY: &'static [u8];
+
+    /// Whether an out-of-range scalar is rehashed (SLIP-0010) rather than
+    /// rejected (BIP-32).
+    #[doc(hidden)]
+    const RETRY_INVALID: bool;
+
+    /// `tweak + secret` (or `tweak` alone) as a valid secret key, if the
+    /// tweak is below the group order and the sum is non-zero.
+    #[doc(hidden)]
+    fn add_tweak(tweak: &[u8; 32], secret: Option<&[u8; 32]>) -> Option<[u8; 32]>;
+
+    /// The compressed SEC1 public key for a valid secret key.
+    #[doc(hidden)]
+    fn public_key(secret: &[u8; 32]) -> [u8; 33];
+
+    /// The signing key for a valid secret key.
+    #[doc(hidden)]
+    fn signing_key(secret: &[u8; 32]) -> Self::SigningKey;
+}
+
+macro_rules! impl_curve {
+    ($(#[$meta:meta])* $name:ident, $krate:ident, $seed_key:literal, $retry:literal) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        pub struct $name;
+
+        impl sealed::Sealed for $name {}
+
+        impl Curve for $name {
+            type SigningKey = $krate::ecdsa::SigningKey;
+
+            const SEED_KEY: &'static [u8] = $seed_key;
+
+            const RETRY_INVALID: bool = $retry;
+
+            fn add_tweak(tweak: &[u8; 32], secret: Option<&[u8; 32]>) -> Option<[u8; 32]> {
+                use $krate::elliptic_curve::{ff::PrimeField, Field};
+
+                let scalar = |bytes: &[u8; 32]| {
+                    Option::<$krate::Scalar>::from($krate::Scalar::from_repr((*bytes).into()))
+                };
+                let mut sum = scalar(tweak)?;
+                if let Some(secret) = secret {
+                    sum += scalar(secret)?;
+                }
+                if bool::from(Field::is_zero(&sum)) {
+                    return None;
+                }
+                Some(sum.to_repr().into())
+            }
+
+            fn public_key(secret: &[u8; 32]) -> [u8; 33] {
+                let point = Self::signing_key(secret)
+                    .verifying_key()
+                    .to_encoded_point(true);
+                let mut public_key = [0; 33];
+                public_key.copy_from_slice(point.as_bytes());
+                public_key
+            }
+
+            fn signing_key(secret: &[u8; 32]) -> Self::SigningKey {
+                #[allow(clippy::expect_used)]
+                $krate::ecdsa::SigningKey::from_bytes(&(*secret).into())
+                    .expect("derived secrets are valid scalars")
+            }
+        }
+    };
+}
+
+#[cfg(feature = "hd_secp256k1")]
+impl_curve!(
+    /// The secp256k1 curve, derived as in BIP-32.
+    Secp256k1,
+    k256,
+    b"Bitcoin seed",
+    false
+);
+
+#[cfg(feature = "hd_p256")]
+impl_curve!(
+    /// The NIST P-256 curve (`nist256p1` in SLIP-0010).
+    NistP256,
+    p256,
+    b"Nist256p1 seed",
+    true
+);
+
+/// A secp256k1 or P-256 secret key with its chain code.
+#[derive(Clone, Zeroize, ZeroizeOnDrop)]
+pub struct ExtendedSecretKey<C: Curve> {
+    #[zeroize(skip)]
+    depth: u8,
+    secret: [u8; 32],
+    chain_code: [u8; 32],
+    #[zeroize(skip)]
+    curve: PhantomData<C>,
+}
+
+impl<C: Curve> ExtendedSecretKey<C> {
+    /// Derive the master key from a seed of 16 to 64 bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`HdError::InvalidSeedLength`] for other seed lengths, or
+    /// [`HdError::InvalidMasterKey`] if BIP-32 rejects the seed.
+    pub fn from_seed(seed: &[u8]) -> Result<Self, HdError> {
+        if !(16..=64).contains(&seed.len()) {
+            return Err(HdError::InvalidSeedLength(seed.len()));
+        }
+
+        let mut output = hmac_sha512(C::SEED_KEY, &[seed]);
+        loop {
+            let (tweak, chain_code) = split(&output);
+            if let Some(secret) = C::add_tweak(&tweak, None) {
+                output.zeroize();
+                return Ok(Self::new(0, secret, chain_code));
+            }
+            if !C::RETRY_INVALID {
+                output.zeroize();
+                return Err(HdError::InvalidMasterKey);
+            }
+            output = hmac_sha512(C::SEED_KEY, &[&output]);
+        }
+    }
+
+    /// Derive the child at `index`; it is hardened if the [`HARDENED`] bit
+    /// is set.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`HdError::DepthExceeded`] past depth 255, or
+    /// [`HdError::InvalidChild`] if BIP-32 has no key at `index`.
+    pub fn derive_child(&self, index: u32) -> Result<Self, HdError> {
+        let depth = self.depth.checked_add(1).ok_or(HdError::DepthExceeded)?;
+        let index_bytes = index.to_be_bytes();
+
+        let mut output = if index & HARDENED == 0 {
+            hmac_sha512(&self.chain_code, &[&self.public_key(), &index_bytes])
+        } else {
+            hmac_sha512(&self.chain_code, &[&[0], &self.secret, &index_bytes])
+        };
+        loop {
+            let (tweak, chain_code) = split(&output);
+            if let Some(secret) = C::add_tweak(&tweak, Some(&self.secret)) {
+                output.zeroize();
+                return Ok(Self::new(depth, secret, chain_code));
+            }
+            if !C::RETRY_INVALID {
+                output.zeroize();
+                return Err(HdError::InvalidChild(index));
+            }
+            output = hmac_sha512(&self.chain_code, &[&[1], &chain_code, &index_bytes]);
+        }
+    }
+
+    /// Derive the first valid child at or after `index`, returning its index.
+    ///
+    /// BIP-32 tells callers to proceed with the next index when a child is
+    /// invalid; this does so without leaving the hardened (or unhardened)
+    /// range. Under SLIP-0010 every index is valid, so this is
+    /// [`derive_child`](Self::derive_child) at `index`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`HdError::DepthExceeded`] past depth 255, or
+    /// [`HdError::InvalidChild`] if no valid child is left in the range.
+    pub fn derive_next_child(&self, index: u32) -> Result<(u32, Self), HdError> {
+        let mut index = index;
+        loop {
+            match self.derive_child(index) {
+                Err(HdError::InvalidChild(invalid)) => {
+                    let next = index.wrapping_add(1);
+                    if next & HARDENED != index & HARDENED {
+                        return Err(HdError::InvalidChild(invalid));
+                    }
+                    index = next;
+                }
+                child => return child.map(|child| (index, child)),
+            }
+        }
+    }
+
+    /// Derive the descendant at `path`, relative to this key.
+    ///
+    /// Every index in the path must be valid; use
+    /// [`derive_next_child`](Self::derive_next_child) to pick indices.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`HdError::DepthExceeded`] if the result would be deeper than
+    /// 255, or [`HdError::InvalidChild`] if BIP-32 has no key at an index.
+    pub fn derive(&self, path: &DerivationPath) -> Result<Self, HdError> {
+        path.0
+            .iter()
+            .try_fold(self.clone(), |key, index| key.derive_child(*index))
+    }
+
+    /// The number of derivations from the master key.
+    #[must_use]
+    pub const fn depth(&self) -> u8 {
+        self.depth
+    }
+
+    /// The chain code.
+    #[must_use]
+    pub const fn chain_code(&self) -> &[u8; 32] {
+        &self.chain_code
+    }
+
+    /// The big-endian secret scalar.
+    #[must_use]
+    pub const fn secret_bytes(&self) -> &[u8; 32] {
+        &self.secret
+    }
+
+    /// The compressed SEC1 public key.
+    #[must_use]
+    pub fn public_key(&self) -> [u8; 33] {
+        C::public_key(&self.secret)
+    }
+
+    /// The ECDSA signing key for this key.
+    #[must_use]
+    pub fn signing_key(&self) -> C::SigningKey {
+        C::signing_key(&self.secret)
+    }
+
+    const fn new(depth: u8, secret: [u8; 32], chain_code: [u8; 32]) -> Self {
+        ExtendedSecretKey {
+            depth,
+            secret,
+            chain_code,
+            curve: PhantomData,
+        }
+    }
+}
+
+impl<C: Curve> fmt::Debug for ExtendedSecretKey<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ExtendedSecretKey")
+            .field("depth", &self.depth)
+            .field("public_key", &self.public_key())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Split an HMAC output into its left (key) and right (chain code) halves.
+const fn split(output: &[u8; 64]) -> ([u8; 32], [u8; 32]) {
+    let mut left = [0; 32];
+    let mut right = [0; 32];
+    let (l, r) = output.split_at(32);
+    left.copy_from_slice(l);
+    right.copy_from_slice(r);
+    (left, right)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::String;
+    use testresult::TestResult;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().fold(String::new(), |mut out, b| {
+            core::fmt::Write::write_fmt(&mut out, format_args!("{b:02x}")).ok();
+            out
+        })
+    }
+
+    const SEED: [u8; 16] = [
+        0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e,
+        0x0f,
+    ];
+
+    /// BIP-32 test vector 1.
+    #[cfg(feature = "hd_secp256k1")]
+    #[test]
+    fn test_bip32_vector_1() -> TestResult {
+        let master = ExtendedSecretKey::<Secp256k1>::from_seed(&SEED)?;
+        assert_eq!(
+            hex(master.secret_bytes()),
+            "e8f32e723decf4051aefac8e2c93c9c5b214313817cdb01a1494b917c8436b35"
+        );
+        assert_eq!(
+            hex(master.chain_code()),
+            "873dff81c02f525623fd1fe5167eac3a55a049de3d314bb42ee227ffed37d508"
+        );
+        assert_eq!(
+            hex(&master.public_key()),
+            "0339a36013301597daef41fbe593a02cc513d0b55527ec2df1050e2e8ff49c85c2"
+        );
+
+        let child = master.derive(&"m/0'/1".parse()?)?;
+        assert_eq!(child.depth(), 2);
+        assert_eq!(
+            hex(child.secret_bytes()),
+            "3c6cb8d0f6a264c91ea8b5030fadaa8e538b020f0a387421a12de9319dc93368"
+        );
+        assert_eq!(
+            hex(child.chain_code()),
+            "2a7857631386ba23dacac34180dd1983734e444fdbf774041578e9b6adb37c19"
+        );
+        Ok(())
+    }
+
+    /// BIP-32 test vector 2.
+    #[cfg(feature = "hd_secp256k1")]
+    #[test]
+    fn test_bip32_vector_2() -> TestResult {
+        let seed = [
+            0xff, 0xfc, 0xf9, 0xf6, 0xf3, 0xf0, 0xed, 0xea, 0xe7, 0xe4, 0xe1, 0xde, 0xdb, 0xd8,
+            0xd5, 0xd2, 0xcf, 0xcc, 0xc9, 0xc6, 0xc3, 0xc0, 0xbd, 0xba, 0xb7, 0xb4, 0xb1, 0xae,
+            0xab, 0xa8, 0xa5, 0xa2, 0x9f, 0x9c, 0x99, 0x96, 0x93, 0x90, 0x8d, 0x8a, 0x87, 0x84,
+            0x81, 0x7e, 0x7b, 0x78, 0x75, 0x72, 0x6f, 0x6c, 0x69, 0x66, 0x63, 0x60, 0x5d, 0x5a,
+            0x57, 0x54, 0x51, 0x4e, 0x4b, 0x48, 0x45, 0x42,
+        ];
+        let master = ExtendedSecretKey::<Secp256k1>::from_seed(&seed)?;
+        assert_eq!(
+            hex(master.secret_bytes()),
+            "4b03d6fc340455b363f51020ad3ecca4f0850280cf436c70c727923f6db46c3e"
+        );
+        assert_eq!(
+            hex(master.chain_code()),
+            "60499f801b896d83179a4374aeb7822aaeaceaa0db1f85ee3e904c4defbd9689"
+        );
+
+        let child = master.derive(&"m/0/2147483647'/1/2147483646'/2".parse()?)?;
+        assert_eq!(child.depth(), 5);
+        assert_eq!(
+            hex(child.secret_bytes()),
+            "bb7d39bdb83ecf58f2fd82b6d918341cbef428661ef01ab97c28a4842125ac23"
+        );
+        assert_eq!(
+            hex(child.chain_code()),
+            "9452b549be8cea3ecb7a84bec10dcfd94afe4d129ebfd3b3cb58eedf394ed271"
+        );
+        assert_eq!(
+            hex(&child.public_key()),
+            "024d902e1a2fc7a8755ab5b694c575fce742c48d9ff192e63df5193e4c7afe1f9c"
+        );
+        Ok(())
+    }
+
+    /// BIP-32 test vector 3, which checks that leading zeros are kept.
+    #[cfg(feature = "hd_secp256k1")]
+    #[test]
+    fn test_bip32_vector_3() -> TestResult {
+        let seed = [
+            0x4b, 0x38, 0x15, 0x41, 0x58, 0x3b, 0xe4, 0x42, 0x33, 0x46, 0xc6, 0x43, 0x85, 0x0d,
+            0xa4, 0xb3, 0x20, 0xe4, 0x6a, 0x87, 0xae, 0x3d, 0x2a, 0x4e, 0x6d, 0xa1, 0x1e, 0xba,
+            0x81, 0x9c, 0xd4, 0xac, 0xba, 0x45, 0xd2, 0x39, 0x31, 0x9a, 0xc1, 0x4f, 0x86, 0x3b,
+            0x8d, 0x5a, 0xb5, 0xa0, 0xd0, 0xc6, 0x4d, 0x2e, 0x8a, 0x1e, 0x7d, 0x14, 0x57, 0xdf,
+            0x2e, 0x5a, 0x3c, 0x51, 0xc7, 0x32, 0x35, 0xbe,
+        ];
+        let master = ExtendedSecretKey::<Secp256k1>::from_seed(&seed)?;
+        assert_eq!(
+            hex(master.secret_bytes()),
+            "00ddb80b067e0d4993197fe10f2657a844a384589847602d56f0c629c81aae32"
+        );
+
+        let child = master.derive_child(HARDENED)?;
+        assert_eq!(
+            hex(child.secret_bytes()),
+            "491f7a2eebc7b57028e0d3faa0acda02e75c33b03c48fb288c41e2ea44e1daef"
+        );
+        assert_eq!(
+            hex(child.chain_code()),
+            "e5fea12a97b927fc9dc3d2cb0d1ea1cf50aa5a1fdc1f933e8906bb38df3377bd"
+        );
+        Ok(())
+    }
+
+    /// secp256k1 that also rejects every odd tweak, so that the BIP-32
+    /// invalid-key paths (otherwise hit with probability 2^-127) run.
+    #[cfg(feature = "hd_secp256k1")]
+    #[derive(Debug, Clone, Copy)]
+    struct OddTweaksInvalid;
+
+    #[cfg(feature = "hd_secp256k1")]
+    impl sealed::Sealed for OddTweaksInvalid {}
+
+    #[cfg(feature = "hd_secp256k1")]
+    impl Curve for OddTweaksInvalid {
+        type SigningKey = k256::ecdsa::SigningKey;
+
+        const SEED_KEY: &'static [u8] = Secp256k1::SEED_KEY;
+
+        const RETRY_INVALID: bool = false;
+
+        fn add_tweak(tweak: &[u8; 32], secret: Option<&[u8; 32]>) -> Option<[u8; 32]> {
+            if tweak.last().is_some_and(|b| b & 1 == 1) {
+                return None;
+            }
+            Secp256k1::add_tweak(tweak, secret)
+        }
+
+        fn public_key(secret: &[u8; 32]) -> [u8; 33] {
+            Secp256k1::public_key(secret)
+        }
+
+        fn signing_key(secret: &[u8; 32]) -> Self::SigningKey {
+            Secp256k1::signing_key(secret)
+        }
+    }
+
+    #[cfg(feature = "hd_secp256k1")]
+    #[test]
+    fn test_bip32_skips_invalid_children() -> TestResult {
+        // Vector 1's master tweak ends in 0x35
+        assert!(matches!(
+            ExtendedSecretKey::<OddTweaksInvalid>::from_seed(&SEED),
+            Err(HdError::InvalidMasterKey)
+        ));
+
+        let seed = [0x42; 32];
+        let flaky = ExtendedSecretKey::<OddTweaksInvalid>::from_seed(&seed)?;
+        let real = ExtendedSecretKey::<Secp256k1>::from_seed(&seed)?;
+        assert_eq!(flaky.secret_bytes(), real.secret_bytes());
+
+        let invalid = (0..32)
+            .find(|index| {
+                matches!(flaky.derive_child(*index), Err(HdError::InvalidChild(i)) if i == *index)
+            })
+            .ok_or("no invalid child")?;
+        assert!(matches!(
+            flaky.derive(&DerivationPath::new(alloc::vec![invalid])),
+            Err(HdError::InvalidChild(_))
+        ));
+
+        // The next valid index, with the key plain BIP-32 derives there
+        let (index, child) = flaky.derive_next_child(invalid)?;
+        assert!(index > invalid);
+        assert!((invalid..index).all(|i| flaky.derive_child(i).is_err()));
+        assert_eq!(
+            child.secret_bytes(),
+            real.derive_child(index)?.secret_bytes()
+        );
+        assert_eq!(flaky.derive_next_child(index)?.0, index);
+        Ok(())
+    }
+
+    /// SLIP-0010 test vector 1 for nist256p1.
+    #[cfg(feature = "hd_p256")]
+    #[test]
+    fn test_slip10_p256_vector_1() -> TestResult {
+        let master = ExtendedSecretKey::<NistP256>::from_seed(&SEED)?;
+        assert_eq!(
+            hex(master.secret_bytes()),
+            "612091aaa12e22dd2abef664f8a01a82cae99ad7441b7ef8110424915c268bc2"
+        );
+        assert_eq!(
+            hex(master.chain_code()),
+            "beeb672fe4621673f722f38529c07392fecaa61015c80c34f29ce8b41b3cb6ea"
+        );
+
+        let child = master.derive_child(HARDENED)?;
+        assert_eq!(
+            hex(child.secret_bytes()),
+            "6939694369114c67917a182c59ddb8cafc3004e63ca5d3b84403ba8613debc0c"
+        );
+        assert_eq!(
+            hex(child.chain_code()),
+            "3460cea53e6a6bb5fb391eeef3237ffd8724bf0a40e94943c98b83825342ee11"
+        );
+        Ok(())
+    }
+}
@@ -6,14 +6,14 @@
 pub mod builder;
 
 use crate::{
-    cid::to_dagcbor_cid,
+    cid::{to_dagcbor_cid, to_dagcbor_cid_with_hash, CidBuilder},
     command::Command,
     crypto::nonce::Nonce,
     delegation::{
         policy::predicate::{Predicate, RunError},
         store::DelegationStore,
     },
-    did::{Did, DidSigner},
+    did::{AsyncDidSigner, Did},
     envelope::{payload_tag::PayloadTag, Envelope},
     promise::{Promised, WaitingOn},
     time::timestamp::Timestamp,
@@ -26,8 +26,14 @@ use core::{borrow::Borrow, fmt::Debug};
 use future_form::FutureForm;
 use ipld_core::{cid::Cid, ipld::Ipld};
 use serde::{Deserialize, Serialize};
+use signature::Verifier;
 use thiserror::Error;
-use varsig::verify::Verify;
+use varsig::{
+    codec::{Codec, DagCborCodec},
+    policy::{PolicyViolation, VerificationPolicy},
+    verify::{VerificationError, Verify},
+    Varsig,
+};
 
 /// Top-level UCAN Invocation.
 ///
@@ -41,8 +47,8 @@ pub struct Invocation<D: Did>(
 impl<D: Did> Invocation<D> {
     /// Creates a blank [`InvocationBuilder`] instance.
     #[must_use]
-    pub const fn builder<S: DidSigner<Did = D>>() -> InvocationBuilder<S, Unset, Unset, Unset, Unset>
-    {
+    pub const fn builder<S: AsyncDidSigner<Did = D>>(
+    ) -> InvocationBuilder<S, Unset, Unset, Unset, Unset> {
         InvocationBuilder::new()
     }
 
@@ -96,17 +102,224 @@ impl<D: Did> Invocation<D> {
         &self.0 .1.payload.nonce
     }
 
-    // FIXME delegation store trait
-    // pub fn check(&self, proof_store: BTreeMap<Cid, Verified<Delegation<D>>> -> Result<(), ()> {
-    //     self.try_verify(self.codec(), self.verifier(), self.signature(), self.payload())?;
+    /// Getter for the signed payload.
+    pub const fn payload(&self) -> &InvocationPayload<D> {
+        &self.0 .1.payload
+    }
+
+    /// Getter for the Varsig header.
+    pub const fn header(&self) -> &Varsig<D::VarsigConfig, DagCborCodec, InvocationPayload<D>> {
+        &self.0 .1.header
+    }
+
+    /// Getter for the envelope signature.
+    pub const fn signature(&self) -> &<D::VarsigConfig as Verify>::Signature {
+        &self.0 .0
+    }
+
+    /// Verify the envelope signature against the issuer's key.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`VerificationError`] if the payload cannot be encoded or the
+    /// signature is invalid.
+    pub fn verify_signature(
+        &self,
+    ) -> Result<(), VerificationError<<DagCborCodec as Codec<InvocationPayload<D>>>::EncodingError>>
+    {
+        self.verify_signature_with_policy(&VerificationPolicy::default())
+    }
+
+    /// Verify the envelope signature, subject to a [`VerificationPolicy`].
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`VerificationError`] if the policy rejects the header, the
+    /// payload cannot be encoded, or the signature is invalid.
+    pub fn verify_signature_with_policy(
+        &self,
+        policy: &VerificationPolicy,
+    ) -> Result<(), VerificationError<<DagCborCodec as Codec<InvocationPayload<D>>>::EncodingError>>
+    {
+        self.0 .1.header.try_verify_with_policy(
+            policy,
+            self.issuer().verifier(),
+            self.payload(),
+            self.signature(),
+        )
+    }
+
+    /// Compute the CID for this invocation envelope using the hash named in its Varsig header.
+    ///
+    /// Returns `None` if the header names no hash, or one that is not compiled in.
+    pub fn to_cid_with_header_hash(&self) -> Option<Cid> {
+        to_dagcbor_cid_with_hash(&self, self.0 .1.header.hash_tag()?)
+    }
+
+    /// Verify the signatures on this invocation and the given proofs.
+    ///
+    /// All envelopes are handed to the Varsig configuration's
+    /// [`Verify::verify_batch`] in one go. If the batch fails, each signature
+    /// is rechecked individually to report which one is bad. Each payload is
+    /// encoded with the codec named in its own header.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`SignatureCheckError`] naming the first invalid signature.
+    pub fn verify_signatures<'a, I: IntoIterator<Item = &'a Delegation<D>>>(
+        &'a self,
+        proofs: I,
+    ) -> Result<(), SignatureCheckError>
+    where
+        D: 'a,
+    {
+        self.verify_signatures_with_policy(proofs, &VerificationPolicy::default())
+    }
+
+    /// Verify the signatures on this invocation and the given proofs,
+    /// subject to a [`VerificationPolicy`].
+    ///
+    /// Every header is checked against the policy, and every key and
+    /// signature against its header (see [`Verify::check_verifier`]), before
+    /// any signature is verified.
+    /// A strict policy checks each signature with [`Verify::verify_strict`]
+    /// instead of batching, since batch verification is not strict.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`SignatureCheckError`] naming the first rejected header or
+    /// invalid signature.
+    pub fn verify_signatures_with_policy<'a, I: IntoIterator<Item = &'a Delegation<D>>>(
+        &'a self,
+        proofs: I,
+        policy: &VerificationPolicy,
+    ) -> Result<(), SignatureCheckError>
+    where
+        D: 'a,
+    {
+        let proofs: Vec<&Delegation<D>> = proofs.into_iter().collect();
+
+        policy
+            .check(self.header())
+            .map_err(SignatureCheckError::InvocationPolicyViolation)?;
+        for proof in &proofs {
+            policy
+                .check(proof.header())
+                .map_err(|e| SignatureCheckError::ProofPolicyViolation(proof.to_cid(), e))?;
+        }
+
+        // The signatures below are checked over encoded bytes, so the
+        // header's own key checks (e.g. RSA key size) have to run first
+        self.header()
+            .verifier_cfg()
+            .check_verifier(self.issuer().verifier(), self.signature())
+            .map_err(|_| SignatureCheckError::InvalidInvocationSignature)?;
+        for proof in &proofs {
+            proof
+                .header()
+                .verifier_cfg()
+                .check_verifier(proof.issuer().verifier(), proof.signature())
+                .map_err(|_| SignatureCheckError::InvalidProofSignature(proof.to_cid()))?;
+        }
 
-    //     let mut realized_proofs = Vec::new();
-    //     for proof_cid in self.proofs() {
-    //         let found = proof_store.get(proof_cid).expect("FIXME");
-    //         realized_proofs.push(found);
-    //     }
-    //     todo!("FIXME")
-    // }
+        let mut encoded = Vec::with_capacity(proofs.len() + 1);
+        encoded.push(
+            self.header()
+                .codec()
+                .encode_payload(self.payload())
+                .map_err(|_| SignatureCheckError::EncodingFailed)?,
+        );
+        for proof in &proofs {
+            encoded.push(
+                proof
+                    .header()
+                    .codec()
+                    .encode_payload(proof.payload())
+                    .map_err(|_| SignatureCheckError::EncodingFailed)?,
+            );
+        }
+
+        let signers = core::iter::once((self.issuer().verifier(), self.signature())).chain(
+            proofs
+                .iter()
+                .map(|proof| (proof.issuer().verifier(), proof.signature())),
+        );
+        let items: Vec<_> = signers
+            .zip(&encoded)
+            .map(|((verifier, signature), msg)| (verifier, msg.as_slice(), signature))
+            .collect();
+
+        if !policy.is_strict() && <D::VarsigConfig as Verify>::verify_batch(&items).is_ok() {
+            return Ok(());
+        }
+
+        let verify_one = |verifier: &<D::VarsigConfig as Verify>::Verifier, msg, signature| {
+            if policy.is_strict() {
+                <D::VarsigConfig as Verify>::verify_strict(verifier, msg, signature)
+            } else {
+                verifier.verify(msg, signature)
+            }
+        };
+
+        // Pinpoint the failure (or, when strict, check each signature)
+        let mut items = items.into_iter();
+        if let Some((verifier, msg, signature)) = items.next() {
+            verify_one(verifier, msg, signature)
+                .map_err(|_| SignatureCheckError::InvalidInvocationSignature)?;
+        }
+        for ((verifier, msg, signature), proof) in items.zip(&proofs) {
+            verify_one(verifier, msg, signature)
+                .map_err(|_| SignatureCheckError::InvalidProofSignature(proof.to_cid()))?;
+        }
+
+        if policy.is_strict() {
+            return Ok(());
+        }
+
+        // The batch and individual checks disagree; never accept in that case
+        Err(SignatureCheckError::BatchVerificationMismatch)
+    }
+
+    /// Validate this invocation against proofs held in a delegation store.
+    ///
+    /// This checks every signature in the chain (see [`Self::verify_signatures`])
+    /// and then runs [`InvocationPayload::syntatic_checks`].
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`StoredCheckError`] if a proof is missing or any check fails.
+    pub async fn check<K: FutureForm, T: Borrow<Delegation<D>>, S: DelegationStore<K, D, T>>(
+        &self,
+        proof_store: &S,
+    ) -> Result<(), StoredCheckError<K, D, T, S>> {
+        self.check_with_policy(proof_store, &VerificationPolicy::default())
+            .await
+    }
+
+    /// Validate this invocation against proofs held in a delegation store,
+    /// verifying signatures subject to a [`VerificationPolicy`].
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`StoredCheckError`] if a proof is missing or any check fails.
+    pub async fn check_with_policy<
+        K: FutureForm,
+        T: Borrow<Delegation<D>>,
+        S: DelegationStore<K, D, T>,
+    >(
+        &self,
+        proof_store: &S,
+        policy: &VerificationPolicy,
+    ) -> Result<(), StoredCheckError<K, D, T, S>> {
+        let realized_proofs: Vec<T> = proof_store
+            .get_all(self.proofs())
+            .await
+            .map_err(StoredCheckError::GetError)?;
+        let dlgs: Vec<&Delegation<D>> = realized_proofs.iter().map(Borrow::borrow).collect();
+        self.verify_signatures_with_policy(dlgs.iter().copied(), policy)?;
+        self.payload().syntatic_checks(dlgs)?;
+        Ok(())
+    }
 }
 
 impl<D: Did> Debug for Invocation<D> {
@@ -227,6 +440,11 @@ impl<D: Did> InvocationPayload<D> {
         to_dagcbor_cid(&self)
     }
 
+    /// Compute the CID for this invocation with a specific hash.
+    pub fn to_cid_with(&self, builder: &CidBuilder) -> Cid {
+        builder.build(&self)
+    }
+
     /// Check if an [`InvocationPayload`] with proofs stored in a delegation store is valid.
     ///
     /// # Errors
@@ -358,17 +576,56 @@ pub enum StoredCheckError<
     /// Proof check failed
     #[error(transparent)]
     CheckFailed(#[from] CheckFailed),
+
+    /// Signature check failed
+    #[error(transparent)]
+    SignatureCheckFailed(#[from] SignatureCheckError),
+}
+
+/// Errors that can occur when verifying the signatures in an invocation chain
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum SignatureCheckError {
+    /// A payload could not be encoded for verification
+    #[error("unable to encode payload for signature verification")]
+    EncodingFailed,
+
+    /// The invocation's own signature is invalid
+    #[error("invalid invocation signature")]
+    InvalidInvocationSignature,
+
+    /// The signature on the proof with this CID is invalid
+    #[error("invalid signature on proof {0}")]
+    InvalidProofSignature(Cid),
+
+    /// Batch verification failed, but every signature passed on its own
+    #[error("batch verification failed but each signature verified individually")]
+    BatchVerificationMismatch,
+
+    /// The invocation's Varsig header is rejected by the verification policy
+    #[error("invocation header rejected: {0}")]
+    InvocationPolicyViolation(PolicyViolation),
+
+    /// The Varsig header on the proof with this CID is rejected by the verification policy
+    #[error("header on proof {0} rejected: {1}")]
+    ProofPolicyViolation(Cid, PolicyViolation),
 }
 
 #[cfg(test)]
 mod tests {
     use crate::{
+        delegation::{subject::DelegatedSubject, DelegationPayload},
         did::{Ed25519Did, Ed25519Signer},
+        envelope::Envelope,
         invocation::builder::InvocationBuilder,
+        test_support::{block_on, signer},
+        DelegationBuilder,
     };
 
     use super::*;
+    use alloc::rc::Rc;
+    use core::cell::RefCell;
     use testresult::TestResult;
+    use varsig::signature::eddsa::Ed25519;
 
     #[test]
     fn issuer_round_trip() -> TestResult {
@@ -396,4 +653,152 @@ mod tests {
         assert_eq!(invocation.issuer().to_string(), iss.to_string());
         Ok(())
     }
+
+    /// A root -> a -> b delegation chain and an invocation by `b`.
+    fn chain() -> TestResult<(Vec<Delegation<Ed25519Did>>, Invocation<Ed25519Did>)> {
+        let root = signer(0);
+        let a = signer(1);
+        let b = signer(2);
+
+        let mut proofs = Vec::new();
+        for (iss, aud) in [(&root, &a), (&a, &b)] {
+            proofs.push(
+                DelegationBuilder::new()
+                    .issuer(iss.clone())
+                    .audience(*aud.did())
+                    .subject(DelegatedSubject::Specific(*root.did()))
+                    .command_from_str("/crud")?
+                    .try_build()?,
+            );
+        }
+
+        let invocation = InvocationBuilder::new()
+            .issuer(b)
+            .audience(*root.did())
+            .subject(*root.did())
+            .command_from_str("/crud/read")?
+            .proofs(proofs.iter().map(Delegation::to_cid).collect())
+            .try_build()?;
+
+        Ok((proofs, invocation))
+    }
+
+    /// Re-sign a delegation's payload with the wrong key.
+    fn forge(dlg: &Delegation<Ed25519Did>) -> TestResult<Delegation<Ed25519Did>> {
+        let mut envelope: Envelope<
+            Ed25519,
+            DelegationPayload<Ed25519Did>,
+            ed25519_dalek::Signature,
+        > = serde_ipld_dagcbor::from_slice(&serde_ipld_dagcbor::to_vec(dlg)?)?;
+        envelope.0 = signature::Signer::try_sign(
+            signer(99).signer(),
+            &serde_ipld_dagcbor::to_vec(dlg.payload())?,
+        )?;
+        Ok(serde_ipld_dagcbor::from_slice(
+            &serde_ipld_dagcbor::to_vec(&envelope)?,
+        )?)
+    }
+
+    #[test]
+    fn test_verify_signatures_accepts_valid_chain() -> TestResult {
+        let (proofs, invocation) = chain()?;
+        for proof in &proofs {
+            proof.verify_signature()?;
+        }
+        invocation.verify_signature()?;
+        invocation.verify_signatures(&proofs)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_signatures_pinpoints_bad_proof() -> TestResult {
+        let (mut proofs, invocation) = chain()?;
+        let forged = forge(proofs.get(1).ok_or("missing proof")?)?;
+        let forged_cid = forged.to_cid();
+        assert!(forged.verify_signature().is_err());
+        *proofs.get_mut(1).ok_or("missing proof")? = forged;
+
+        assert_eq!(
+            invocation.verify_signatures(&proofs),
+            Err(SignatureCheckError::InvalidProofSignature(forged_cid))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_verifies_signatures_from_store() -> TestResult {
+        let (proofs, invocation) = chain()?;
+        let store: Rc<RefCell<BTreeMap<Cid, Rc<Delegation<Ed25519Did>>>>> = Rc::default();
+        for proof in &proofs {
+            store
+                .borrow_mut()
+                .insert(proof.to_cid(), Rc::new(proof.clone()));
+        }
+        block_on(invocation.check(&store))?;
+
+        // Swap in a forged proof under the original CID
+        let original = proofs.first().ok_or("missing proof")?;
+        store
+            .borrow_mut()
+            .insert(original.to_cid(), Rc::new(forge(original)?));
+        assert!(matches!(
+            block_on(invocation.check(&store)),
+            Err(StoredCheckError::SignatureCheckFailed(
+                SignatureCheckError::InvalidProofSignature(_)
+            ))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_signatures_with_strict_policy() -> TestResult {
+        let (proofs, invocation) = chain()?;
+        let strict = VerificationPolicy::permissive().strict(true);
+        invocation.verify_signatures_with_policy(&proofs, &strict)?;
+
+        let mut forged_proofs = proofs.clone();
+        let forged = forge(proofs.first().ok_or("missing proof")?)?;
+        let forged_cid = forged.to_cid();
+        *forged_proofs.first_mut().ok_or("missing proof")? = forged;
+        assert_eq!(
+            invocation.verify_signatures_with_policy(&forged_proofs, &strict),
+            Err(SignatureCheckError::InvalidProofSignature(forged_cid))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_signatures_rejects_policy_violations() -> TestResult {
+        let (proofs, invocation) = chain()?;
+
+        let too_weak = VerificationPolicy::permissive().min_security_bits(192);
+        assert_eq!(
+            invocation.verify_signatures_with_policy(&proofs, &too_weak),
+            Err(SignatureCheckError::InvocationPolicyViolation(
+                PolicyViolation::InsufficientSecurity {
+                    required: 192,
+                    actual: Some(128)
+                }
+            ))
+        );
+        assert!(matches!(
+            invocation.verify_signature_with_policy(&too_weak),
+            Err(VerificationError::PolicyViolation(_))
+        ));
+
+        // The proofs share the invocation's Ed25519/DAG-CBOR header
+        let ed25519_only = VerificationPolicy::permissive().allow_varsig(invocation.header());
+        invocation.verify_signatures_with_policy(&proofs, &ed25519_only)?;
+
+        let es256_only = VerificationPolicy::permissive().allow(
+            &varsig::header::AnyVarsigHeader::try_from_tags(vec![0xec, 0x1201, 0x15, 0x71])?,
+        );
+        assert_eq!(
+            invocation.verify_signatures_with_policy(&proofs, &es256_only),
+            Err(SignatureCheckError::InvocationPolicyViolation(
+                PolicyViolation::NotAllowed
+            ))
+        );
+        Ok(())
+    }
 }
@@ -3,7 +3,8 @@
 use crate::{
     command::{Command, CommandParseError},
     crypto::nonce::Nonce,
-    did::{Did, DidSigner},
+    delegation::builder::resolve_nonce,
+    did::{AsyncDidSigner, Did, DidSigner},
     envelope::{Envelope, EnvelopePayload},
     promise::Promised,
     sealed::{CommandOrUnset, DidOrUnset, DidSignerOrUnset, ProofsOrUnset},
@@ -11,7 +12,7 @@ use crate::{
     unset::Unset,
 };
 use alloc::{collections::BTreeMap, string::String, vec::Vec};
-use core::marker::PhantomData;
+use core::{convert::Infallible, marker::PhantomData};
 use ipld_core::{cid::Cid, ipld::Ipld};
 use serde::Serialize;
 use varsig::{
@@ -25,7 +26,7 @@ use varsig::{
 #[allow(private_bounds)]
 #[derive(Default, Debug, Clone)]
 pub struct InvocationBuilder<
-    D: DidSigner,
+    D: AsyncDidSigner,
     Issuer: DidSignerOrUnset = Unset,
     Audience: DidOrUnset = Unset,
     Subject: DidOrUnset = Unset,
@@ -68,7 +69,7 @@ pub struct InvocationBuilder<
     _did: PhantomData<D>,
 }
 
-impl<D: DidSigner> InvocationBuilder<D, Unset, Unset, Unset, Unset, Unset> {
+impl<D: AsyncDidSigner> InvocationBuilder<D, Unset, Unset, Unset, Unset, Unset> {
     /// Creates a blank [`InvocationBuilder`] instance.
     #[must_use]
     pub const fn new() -> Self {
@@ -91,7 +92,7 @@ impl<D: DidSigner> InvocationBuilder<D, Unset, Unset, Unset, Unset, Unset> {
 
 #[allow(private_bounds)]
 impl<
-        D: DidSigner,
+        D: AsyncDidSigner,
         Issuer: DidSignerOrUnset,
         Audience: DidOrUnset,
         Subject: DidOrUnset,
@@ -361,18 +362,8 @@ impl<D: DidSigner + Serialize> InvocationBuilder<D, D, D::Did, D::Did, Command,
     /// Panics if random number generator fails when generating a nonce.
     /// This will never happen if a nonce is provided, and is not recoverable
     /// because a broken RNG is a serious problem.
-    #[allow(clippy::expect_used)]
     pub fn build(self) -> super::InvocationPayload<D::Did> {
-        let nonce = self.nonce.unwrap_or_else(|| {
-            #[cfg(feature = "getrandom")]
-            {
-                Nonce::generate_16().expect("failed to generate nonce")
-            }
-            #[cfg(not(feature = "getrandom"))]
-            {
-                panic!("nonce is required: either call .nonce() or enable the `getrandom` feature")
-            }
-        });
+        let nonce = resolve_nonce(self.nonce);
 
         super::InvocationPayload {
             issuer: self.issuer.did().clone(),
@@ -402,7 +393,6 @@ impl<D: DidSigner + Serialize> InvocationBuilder<D, D, D::Did, D::Did, Command,
     ///
     /// Panics if no nonce was provided and the `getrandom` feature is enabled
     /// but the CSPRNG fails.
-    #[allow(clippy::expect_used)]
     #[allow(clippy::type_complexity)]
     pub fn try_build(
         self,
@@ -413,17 +403,7 @@ impl<D: DidSigner + Serialize> InvocationBuilder<D, D, D::Did, D::Did, Command,
             <<D::Did as Did>::VarsigConfig as Sign>::SignError,
         >,
     > {
-        let nonce = self.nonce.unwrap_or_else(|| {
-            #[cfg(feature = "getrandom")]
-            {
-                #[allow(clippy::expect_used)]
-                Nonce::generate_16().expect("failed to generate nonce")
-            }
-            #[cfg(not(feature = "getrandom"))]
-            {
-                panic!("nonce is required: either call .nonce() or enable the `getrandom` feature")
-            }
-        });
+        let nonce = resolve_nonce(self.nonce);
 
         let payload: super::InvocationPayload<D::Did> = super::InvocationPayload {
             issuer: self.issuer.did().clone(),
@@ -466,3 +446,62 @@ impl<D: DidSigner + Serialize> InvocationBuilder<D, D, D::Did, D::Did, Command,
         Ok(super::Invocation(envelope))
     }
 }
+
+#[allow(clippy::mismatching_type_param_order)]
+impl<D: AsyncDidSigner> InvocationBuilder<D, D, D::Did, D::Did, Command, Vec<Cid>> {
+    /// Builds the complete, signed [`Invocation`] with an asynchronous signer.
+    ///
+    /// A nonce must either have been provided via [`InvocationBuilder::nonce`],
+    /// or the `getrandom` feature must be enabled.
+    ///
+    /// # Errors
+    ///
+    /// * `SignerError` if signing the invocation fails.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no nonce was provided and the `getrandom` feature is enabled
+    /// but the CSPRNG fails.
+    #[allow(clippy::type_complexity)]
+    pub async fn try_build_async(
+        self,
+    ) -> Result<
+        super::Invocation<D::Did>,
+        SignerError<
+            <DagCborCodec as varsig::codec::Codec<super::InvocationPayload<D::Did>>>::EncodingError,
+            Infallible,
+        >,
+    > {
+        let nonce = resolve_nonce(self.nonce);
+        let issuer = AsyncDidSigner::did(&self.issuer);
+
+        let payload: super::InvocationPayload<D::Did> = super::InvocationPayload {
+            issuer: issuer.clone(),
+            audience: self.audience,
+            subject: self.subject,
+            command: self.command,
+            arguments: self.arguments,
+            expiration: self.expiration,
+            issued_at: self.issued_at,
+            proofs: self.proofs,
+            cause: self.cause,
+            meta: self.meta,
+            nonce,
+        };
+
+        let header: Varsig<
+            <D::Did as Did>::VarsigConfig,
+            DagCborCodec,
+            super::InvocationPayload<D::Did>,
+        > = Varsig::new(issuer.varsig_config().clone(), DagCborCodec);
+
+        let (sig, _) = header
+            .try_sign_with_async(self.issuer.async_signer(), &payload)
+            .await?;
+
+        Ok(super::Invocation(Envelope(
+            sig,
+            EnvelopePayload { header, payload },
+        )))
+    }
+}
@@ -0,0 +1,715 @@
+//! Encrypted on-disk keystore.
+//!
+//! A [`Keystore`] holds named [`DidSigner`]s of any supported DID type, so a
+//! service can keep its identities across restarts. On disk it is a single
+//! DAG-CBOR file: the entries are stored as private [`Jwk`]s and encrypted with
+//! XChaCha20-Poly1305 under a key derived from a passphrase with Argon2id. The
+//! KDF parameters and salt are stored in the clear and authenticated as
+//! associated data, so tampering with them fails decryption.
+//!
+//! ```no_run
+//! # use ucan::{did::Ed25519Signer, keystore::Keystore};
+//! # fn main() -> Result<(), ucan::keystore::KeystoreError> {
+//! let keystore = Keystore::open("service.keys", b"passphrase")?;
+//! let signer: Ed25519Signer = keystore.get("issuer")?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::did::{
+    keys::{Jwk, KeyError, PrivateKeyFormat},
+    DidSigner,
+};
+use alloc::{
+    collections::{BTreeMap, TryReserveError},
+    string::{String, ToString},
+    vec::Vec,
+};
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    XChaCha20Poly1305, XNonce,
+};
+use core::convert::Infallible;
+use serde::{Deserialize, Serialize};
+use std::{fs, io::Write, path::Path};
+use thiserror::Error;
+use zeroize::Zeroizing;
+
+/// Current keystore file format version.
+pub const KEYSTORE_VERSION: u64 = 1;
+
+/// Length of the Argon2id salt, in bytes.
+const SALT_LEN: usize = 16;
+
+/// Argon2id cost parameters.
+///
+/// The defaults are the Argon2 crate's recommended minimums
+/// (19 MiB, 2 passes, 1 lane).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KdfParams {
+    /// Memory cost, in KiB.
+    #[serde(rename = "m")]
+    pub memory_kib: u32,
+
+    /// Number of passes.
+    #[serde(rename = "t")]
+    pub iterations: u32,
+
+    /// Degree of parallelism.
+    #[serde(rename = "p")]
+    pub parallelism: u32,
+}
+
+impl KdfParams {
+    /// Largest memory cost accepted, in KiB (1 GiB).
+    pub const MAX_MEMORY_KIB: u32 = 1 << 20;
+
+    /// Largest number of passes accepted.
+    pub const MAX_ITERATIONS: u32 = 64;
+
+    /// Largest degree of parallelism accepted.
+    pub const MAX_PARALLELISM: u32 = 64;
+
+    /// Whether every cost is within the `MAX_*` limits.
+    ///
+    /// The parameters in a keystore file are only authenticated once the key
+    /// they derive has been used, so a crafted file could otherwise make
+    /// [`Keystore::open`] allocate terabytes before failing.
+    #[must_use]
+    pub const fn is_within_limits(&self) -> bool {
+        self.memory_kib <= Self::MAX_MEMORY_KIB
+            && self.iterations <= Self::MAX_ITERATIONS
+            && self.parallelism <= Self::MAX_PARALLELISM
+    }
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        KdfParams {
+            memory_kib: Params::DEFAULT_M_COST,
+            iterations: Params::DEFAULT_T_COST,
+            parallelism: Params::DEFAULT_P_COST,
+        }
+    }
+}
+
+/// The authenticated, unencrypted part of a keystore file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Header {
+    #[serde(rename = "v")]
+    version: u64,
+
+    kdf: KdfParams,
+
+    #[serde(with = "serde_bytes")]
+    salt: Vec<u8>,
+}
+
+/// A keystore file as stored on disk.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct KeystoreFile {
+    header: Header,
+
+    #[serde(with = "serde_bytes")]
+    nonce: Vec<u8>,
+
+    #[serde(rename = "ct", with = "serde_bytes")]
+    ciphertext: Vec<u8>,
+}
+
+/// A named signer, as stored in the encrypted part of the file.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Entry {
+    name: String,
+    did: String,
+    jwk: Jwk,
+}
+
+/// A set of named DID signers, encrypted under a passphrase.
+///
+/// Changes are held in memory until [`Keystore::save`] (or
+/// [`Keystore::encrypt`]) is called.
+pub struct Keystore {
+    header: Header,
+    key: Zeroizing<[u8; 32]>,
+    entries: BTreeMap<String, Entry>,
+}
+
+impl Keystore {
+    /// Create an empty keystore with the default [`KdfParams`].
+    ///
+    /// # Errors
+    ///
+    /// If the salt cannot be generated or the key cannot be derived.
+    pub fn new(passphrase: &[u8]) -> Result<Self, KeystoreError> {
+        Self::with_params(passphrase, KdfParams::default())
+    }
+
+    /// Create an empty keystore with the given [`KdfParams`].
+    ///
+    /// # Errors
+    ///
+    /// If the salt cannot be generated, or the parameters are invalid.
+    pub fn with_params(passphrase: &[u8], kdf: KdfParams) -> Result<Self, KeystoreError> {
+        let header = Header::generate(kdf)?;
+        let key = header.derive_key(passphrase)?;
+        Ok(Keystore {
+            header,
+            key,
+            entries: BTreeMap::new(),
+        })
+    }
+
+    /// Read and decrypt a keystore file.
+    ///
+    /// # Errors
+    ///
+    /// If the file cannot be read, or [`Keystore::decrypt`] fails.
+    pub fn open<P: AsRef<Path>>(path: P, passphrase: &[u8]) -> Result<Self, KeystoreError> {
+        Self::decrypt(&fs::read(path)?, passphrase)
+    }
+
+    /// Encrypt and write the keystore to `path`.
+    ///
+    /// The file is written to a new, randomly named file next to `path` and
+    /// renamed into place, so a crash never leaves a partial keystore and an
+    /// existing file (or symlink) at the temporary name is never written
+    /// through. On Unix it is created with mode `0600`.
+    ///
+    /// # Errors
+    ///
+    /// If encryption or any file operation fails.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), KeystoreError> {
+        let path = path.as_ref();
+        let bytes = self.encrypt()?;
+
+        let mut suffix = [0u8; 8];
+        getrandom::getrandom(&mut suffix)?;
+        let mut tmp_name = path.as_os_str().to_owned();
+        tmp_name.push(format!(".{}.tmp", u64::from_be_bytes(suffix)));
+        let tmp_path = Path::new(&tmp_name);
+
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+        let mut file = options.open(tmp_path)?;
+        let written = file
+            .write_all(&bytes)
+            .and_then(|()| file.sync_all())
+            .and_then(|()| fs::rename(tmp_path, path));
+        if written.is_err() {
+            fs::remove_file(tmp_path).ok();
+        }
+        Ok(written?)
+    }
+
+    /// Decrypt a keystore from its serialized bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`KeystoreError::Decrypt`] if the passphrase is wrong or the
+    /// file was modified, and other variants if it is malformed.
+    pub fn decrypt(bytes: &[u8], passphrase: &[u8]) -> Result<Self, KeystoreError> {
+        let file: KeystoreFile = serde_ipld_dagcbor::from_slice(bytes)?;
+        if file.header.version != KEYSTORE_VERSION {
+            return Err(KeystoreError::UnsupportedVersion(file.header.version));
+        }
+
+        let nonce = XNonce::from(
+            <[u8; 24]>::try_from(file.nonce.as_slice()).map_err(|_| KeystoreError::Decrypt)?,
+        );
+        let key = file.header.derive_key(passphrase)?;
+        let aad = serde_ipld_dagcbor::to_vec(&file.header)?;
+
+        let plaintext = Zeroizing::new(
+            XChaCha20Poly1305::new(key.as_ref().into())
+                .decrypt(
+                    &nonce,
+                    Payload {
+                        msg: &file.ciphertext,
+                        aad: &aad,
+                    },
+                )
+                .map_err(|_| KeystoreError::Decrypt)?,
+        );
+
+        let list: Vec<Entry> = serde_ipld_dagcbor::from_slice(&plaintext)?;
+        let mut entries = BTreeMap::new();
+        for entry in list {
+            if entries.contains_key(&entry.name) {
+                return Err(KeystoreError::DuplicateName(entry.name.clone()));
+            }
+            entries.insert(entry.name.clone(), entry);
+        }
+
+        Ok(Keystore {
+            header: file.header,
+            key,
+            entries,
+        })
+    }
+
+    /// Encrypt the keystore to bytes, with a fresh nonce.
+    ///
+    /// # Errors
+    ///
+    /// If the nonce cannot be generated or the entries cannot be encoded.
+    pub fn encrypt(&self) -> Result<Vec<u8>, KeystoreError> {
+        let list: Vec<&Entry> = self.entries.values().collect();
+        let plaintext = Zeroizing::new(serde_ipld_dagcbor::to_vec(&list)?);
+        let aad = serde_ipld_dagcbor::to_vec(&self.header)?;
+
+        let mut nonce = [0; 24];
+        getrandom::getrandom(&mut nonce)?;
+
+        let ciphertext = XChaCha20Poly1305::new(self.key.as_ref().into())
+            .encrypt(
+                &XNonce::from(nonce),
+                Payload {
+                    msg: &plaintext,
+                    aad: &aad,
+                },
+            )
+            .map_err(|_| KeystoreError::Encrypt)?;
+
+        Ok(serde_ipld_dagcbor::to_vec(&KeystoreFile {
+            header: self.header.clone(),
+            nonce: nonce.to_vec(),
+            ciphertext,
+        })?)
+    }
+
+    /// Re-key the keystore under a new passphrase, with a fresh salt.
+    ///
+    /// # Errors
+    ///
+    /// If the salt cannot be generated, or the parameters are invalid.
+    pub fn change_passphrase(
+        &mut self,
+        passphrase: &[u8],
+        kdf: KdfParams,
+    ) -> Result<(), KeystoreError> {
+        let header = Header::generate(kdf)?;
+        self.key = header.derive_key(passphrase)?;
+        self.header = header;
+        Ok(())
+    }
+
+    /// The KDF parameters currently in use.
+    #[must_use]
+    pub const fn kdf_params(&self) -> &KdfParams {
+        &self.header.kdf
+    }
+
+    /// The names and DIDs of every stored signer, ordered by name.
+    pub fn list(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries
+            .values()
+            .map(|entry| (entry.name.as_str(), entry.did.as_str()))
+    }
+
+    /// Whether a signer is stored under `name`.
+    #[must_use]
+    pub fn contains(&self, name: &str) -> bool {
+        self.entries.contains_key(name)
+    }
+
+    /// The number of stored signers.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the keystore is empty.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Store a signer under a new name.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`KeystoreError::DuplicateName`] if the name is taken.
+    pub fn add<S: DidSigner + PrivateKeyFormat>(
+        &mut self,
+        name: &str,
+        signer: &S,
+    ) -> Result<(), KeystoreError> {
+        if self.contains(name) {
+            return Err(KeystoreError::DuplicateName(name.to_string()));
+        }
+        self.entries
+            .insert(name.to_string(), Entry::new(name, signer));
+        Ok(())
+    }
+
+    /// Remove the signer stored under `name`, returning its DID.
+    pub fn remove(&mut self, name: &str) -> Option<String> {
+        self.entries.remove(name).map(|entry| entry.did.clone())
+    }
+
+    /// Replace the signer stored under `name`, returning the previous DID.
+    ///
+    /// The new signer may be of a different DID type.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`KeystoreError::UnknownName`] if nothing is stored under `name`.
+    pub fn rotate<S: DidSigner + PrivateKeyFormat>(
+        &mut self,
+        name: &str,
+        signer: &S,
+    ) -> Result<String, KeystoreError> {
+        let entry = self
+            .entries
+            .get_mut(name)
+            .ok_or_else(|| KeystoreError::UnknownName(name.to_string()))?;
+        let previous = entry.did.clone();
+        *entry = Entry::new(name, signer);
+        Ok(previous)
+    }
+
+    /// Load the signer stored under `name`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`KeystoreError::UnknownName`] if nothing is stored under
+    /// `name`, or [`KeystoreError::Key`] if it is not a key of type `S`.
+    pub fn get<S: PrivateKeyFormat>(&self, name: &str) -> Result<S, KeystoreError> {
+        let entry = self
+            .entries
+            .get(name)
+            .ok_or_else(|| KeystoreError::UnknownName(name.to_string()))?;
+        Ok(S::from_jwk(&entry.jwk)?)
+    }
+
+    /// Load the signer for `did`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`KeystoreError::UnknownDid`] if no stored signer has this DID.
+    pub fn load<S: DidSigner + PrivateKeyFormat>(&self, did: &S::Did) -> Result<S, KeystoreError> {
+        let did = did.to_string();
+        let entry = self
+            .entries
+            .values()
+            .find(|entry| entry.did == did)
+            .ok_or_else(|| KeystoreError::UnknownDid(did.clone()))?;
+
+        let signer = S::from_jwk(&entry.jwk)?;
+        if signer.did().to_string() != did {
+            return Err(KeystoreError::Key(KeyError::KeyMismatch));
+        }
+        Ok(signer)
+    }
+}
+
+impl core::fmt::Debug for Keystore {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Keystore")
+            .field("kdf", &self.header.kdf)
+            .field("entries", &self.list().collect::<Vec<_>>())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Header {
+    fn generate(kdf: KdfParams) -> Result<Self, KeystoreError> {
+        let mut salt = alloc::vec![0; SALT_LEN];
+        getrandom::getrandom(&mut salt)?;
+        Ok(Header {
+            version: KEYSTORE_VERSION,
+            kdf,
+            salt,
+        })
+    }
+
+    fn derive_key(&self, passphrase: &[u8]) -> Result<Zeroizing<[u8; 32]>, KeystoreError> {
+        if !self.kdf.is_within_limits() {
+            return Err(KeystoreError::KdfTooExpensive(self.kdf));
+        }
+
+        let params = Params::new(
+            self.kdf.memory_kib,
+            self.kdf.iterations,
+            self.kdf.parallelism,
+            Some(32),
+        )
+        .map_err(KeystoreError::Kdf)?;
+
+        let mut key = Zeroizing::new([0; 32]);
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(passphrase, &self.salt, key.as_mut())
+            .map_err(KeystoreError::Kdf)?;
+        Ok(key)
+    }
+}
+
+impl Entry {
+    fn new<S: DidSigner + PrivateKeyFormat>(name: &str, signer: &S) -> Self {
+        Entry {
+            name: name.to_string(),
+            did: signer.did().to_string(),
+            jwk: signer.to_jwk(),
+        }
+    }
+}
+
+/// Errors from reading, writing, or using a [`Keystore`].
+#[derive(Debug, Error)]
+pub enum KeystoreError {
+    /// File I/O failed.
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    /// The keystore could not be encoded.
+    #[error("unable to encode keystore: {0}")]
+    Encode(#[from] serde_ipld_dagcbor::EncodeError<TryReserveError>),
+
+    /// The keystore could not be decoded.
+    #[error("unable to decode keystore: {0}")]
+    Decode(#[from] serde_ipld_dagcbor::DecodeError<Infallible>),
+
+    /// The file was written by an unsupported format version.
+    #[error("unsupported keystore version {0}")]
+    UnsupportedVersion(u64),
+
+    /// Key derivation failed, e.g. because the parameters are out of range.
+    #[error("key derivation failed: {0}")]
+    Kdf(argon2::Error),
+
+    /// The KDF parameters exceed the `KdfParams::MAX_*` limits.
+    #[error("key derivation parameters exceed the limits: {0:?}")]
+    KdfTooExpensive(KdfParams),
+
+    /// Random bytes could not be generated.
+    #[error("unable to generate randomness: {0}")]
+    Random(#[from] getrandom::Error),
+
+    /// Encryption failed.
+    #[error("unable to encrypt keystore")]
+    Encrypt,
+
+    /// Wrong passphrase, or the file was modified.
+    #[error("unable to decrypt keystore: wrong passphrase or corrupted file")]
+    Decrypt,
+
+    /// A signer is already stored under this name.
+    #[error("a key named {0:?} already exists")]
+    DuplicateName(String),
+
+    /// No signer is stored under this name.
+    #[error("no key named {0:?}")]
+    UnknownName(String),
+
+    /// No stored signer has this DID.
+    #[error("no key for {0}")]
+    UnknownDid(String),
+
+    /// A stored key could not be loaded as the requested type.
+    #[error(transparent)]
+    Key(#[from] KeyError),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        did::{Ed25519Did, Ed25519Signer},
+        test_support::{signer, TempDir},
+    };
+    use testresult::TestResult;
+
+    /// Cheap parameters, so tests run quickly in debug builds.
+    const TEST_KDF: KdfParams = KdfParams {
+        memory_kib: 64,
+        iterations: 1,
+        parallelism: 1,
+    };
+
+    #[test]
+    fn test_round_trip() -> TestResult {
+        let mut keystore = Keystore::with_params(b"hunter2", TEST_KDF)?;
+        keystore.add("issuer", &signer(1))?;
+        keystore.add("backup", &signer(2))?;
+
+        let bytes = keystore.encrypt()?;
+        let restored = Keystore::decrypt(&bytes, b"hunter2")?;
+
+        assert_eq!(
+            restored.list().collect::<Vec<_>>(),
+            vec![
+                ("backup", signer(2).did().to_string().as_str()),
+                ("issuer", signer(1).did().to_string().as_str()),
+            ]
+        );
+        let issuer: Ed25519Signer = restored.get("issuer")?;
+        assert_eq!(issuer.did(), signer(1).did());
+        assert_eq!(restored.kdf_params(), &TEST_KDF);
+        Ok(())
+    }
+
+    #[test]
+    fn test_wrong_passphrase() -> TestResult {
+        let keystore = Keystore::with_params(b"hunter2", TEST_KDF)?;
+        let bytes = keystore.encrypt()?;
+        assert!(matches!(
+            Keystore::decrypt(&bytes, b"hunter3"),
+            Err(KeystoreError::Decrypt)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_tampered_kdf_params() -> TestResult {
+        let keystore = Keystore::with_params(b"hunter2", TEST_KDF)?;
+        let mut file: KeystoreFile = serde_ipld_dagcbor::from_slice(&keystore.encrypt()?)?;
+        file.header.kdf.iterations = 2;
+        let bytes = serde_ipld_dagcbor::to_vec(&file)?;
+        assert!(matches!(
+            Keystore::decrypt(&bytes, b"hunter2"),
+            Err(KeystoreError::Decrypt)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_remove_rotate() -> TestResult {
+        let mut keystore = Keystore::with_params(b"hunter2", TEST_KDF)?;
+        keystore.add("issuer", &signer(1))?;
+        assert!(matches!(
+            keystore.add("issuer", &signer(2)),
+            Err(KeystoreError::DuplicateName(_))
+        ));
+
+        let previous = keystore.rotate("issuer", &signer(2))?;
+        assert_eq!(previous, signer(1).did().to_string());
+        assert!(matches!(
+            keystore.load::<Ed25519Signer>(signer(1).did()),
+            Err(KeystoreError::UnknownDid(_))
+        ));
+        assert_eq!(
+            keystore.load::<Ed25519Signer>(signer(2).did())?.did(),
+            signer(2).did()
+        );
+
+        assert_eq!(keystore.remove("issuer"), Some(signer(2).did().to_string()));
+        assert!(keystore.is_empty());
+        assert!(matches!(
+            keystore.rotate("issuer", &signer(3)),
+            Err(KeystoreError::UnknownName(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_change_passphrase() -> TestResult {
+        let mut keystore = Keystore::with_params(b"hunter2", TEST_KDF)?;
+        keystore.add("issuer", &signer(1))?;
+        keystore.change_passphrase(b"correct horse", TEST_KDF)?;
+
+        let bytes = keystore.encrypt()?;
+        assert!(Keystore::decrypt(&bytes, b"hunter2").is_err());
+        assert_eq!(Keystore::decrypt(&bytes, b"correct horse")?.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_save_and_open() -> TestResult {
+        let dir = TempDir::new("keystore")?;
+        let path = dir.path().join("ucan.keys");
+
+        let mut keystore = Keystore::with_params(b"hunter2", TEST_KDF)?;
+        keystore.add("issuer", &signer(1))?;
+        keystore.save(&path)?;
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(fs::metadata(&path)?.permissions().mode() & 0o777, 0o600);
+        }
+
+        let opened = Keystore::open(&path, b"hunter2")?;
+        assert_eq!(
+            opened.load::<Ed25519Signer>(signer(1).did())?.did(),
+            signer(1).did()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejects_expensive_kdf_params() -> TestResult {
+        let keystore = Keystore::with_params(b"hunter2", TEST_KDF)?;
+        let mut file: KeystoreFile = serde_ipld_dagcbor::from_slice(&keystore.encrypt()?)?;
+        file.header.kdf.memory_kib = u32::MAX;
+        let bytes = serde_ipld_dagcbor::to_vec(&file)?;
+
+        assert!(matches!(
+            Keystore::decrypt(&bytes, b"hunter2"),
+            Err(KeystoreError::KdfTooExpensive(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_wrong_type() -> TestResult {
+        let mut keystore = Keystore::with_params(b"hunter2", TEST_KDF)?;
+        keystore.add("issuer", &signer(1))?;
+        let did: Ed25519Did = *signer(1).did();
+        assert_eq!(keystore.load::<Ed25519Signer>(&did)?.did(), &did);
+
+        #[cfg(feature = "ed448")]
+        assert!(matches!(
+            keystore.get::<crate::did::Ed448Signer>("issuer"),
+            Err(KeystoreError::Key(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_mixed_did_types() -> TestResult {
+        let mut keystore = Keystore::with_params(b"hunter2", TEST_KDF)?;
+        keystore.add("ed25519", &signer(1))?;
+
+        #[cfg(feature = "ed448")]
+        let ed448 = {
+            let signer = crate::did::Ed448Signer::from_private_key_bytes(&[7; 57])?;
+            keystore.add("ed448", &signer)?;
+            signer
+        };
+
+        #[cfg(feature = "bls")]
+        let bls = {
+            let signer = crate::did::Bls12381Signer::new(
+                varsig::signature::bls::BlsSigningKey::from_ikm(&[4; 32])?,
+            );
+            keystore.add("bls", &signer)?;
+            signer
+        };
+
+        let restored = Keystore::decrypt(&keystore.encrypt()?, b"hunter2")?;
+        assert_eq!(
+            restored.load::<Ed25519Signer>(signer(1).did())?.did(),
+            signer(1).did()
+        );
+
+        #[cfg(feature = "ed448")]
+        assert_eq!(
+            restored.load::<crate::did::Ed448Signer>(ed448.did())?.did(),
+            ed448.did()
+        );
+
+        #[cfg(feature = "bls")]
+        assert_eq!(
+            restored
+                .load::<crate::did::Bls12381Signer>(bls.did())?
+                .did(),
+            bls.did()
+        );
+        Ok(())
+    }
+}
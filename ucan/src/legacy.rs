@@ -0,0 +1,675 @@
+//! Legacy UCAN 0.10 JWT tokens.
+//!
+//! UCAN 0.10 tokens are JWTs whose payload carries capabilities in an `att`
+//! map (`{ resource: { ability: [caveat, ...] } }`), proofs as CID strings in
+//! `prf`, and facts in `fct`. This module parses and verifies those tokens and
+//! maps their capabilities onto 1.0 [`Command`]s and [`Predicate`] policies
+//! so that a single validator can handle both formats during a migration.
+//!
+//! The mapping is not always exact. Anything that does not survive the trip
+//! is reported as a [`Lossy`] entry on the [`LegacyMapping`], rather than being
+//! silently dropped.
+
+use crate::{
+    command::{Command, CommandParseError},
+    delegation::{
+        policy::{
+            predicate::Predicate,
+            selector::{filter::Filter, select::Select},
+        },
+        subject::DelegatedSubject,
+    },
+    did::Ed25519Did,
+    time::{error::TimeBoundError, timestamp::Timestamp},
+};
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+    vec::Vec,
+};
+use base64::prelude::*;
+use core::str::FromStr;
+use ipld_core::ipld::Ipld;
+use serde::Deserialize;
+use signature::Verifier;
+use thiserror::Error;
+
+/// A parsed UCAN 0.10 JWT.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LegacyUcan {
+    header: LegacyHeader,
+    payload: LegacyPayload,
+    signed_data: String,
+    signature: Vec<u8>,
+}
+
+/// The JOSE header of a UCAN 0.10 JWT.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct LegacyHeader {
+    /// Signature algorithm (e.g. `EdDSA`).
+    pub alg: String,
+
+    /// Token type (always `JWT`).
+    pub typ: String,
+}
+
+/// The claims of a UCAN 0.10 JWT.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct LegacyPayload {
+    /// UCAN semantic version (e.g. `0.10.0`).
+    pub ucv: String,
+
+    /// Issuer DID.
+    pub iss: String,
+
+    /// Audience DID.
+    pub aud: String,
+
+    /// Not-before time in Unix seconds.
+    #[serde(default)]
+    pub nbf: Option<u64>,
+
+    /// Expiration time in Unix seconds (`null` for no expiry).
+    pub exp: Option<u64>,
+
+    /// Nonce.
+    #[serde(default)]
+    pub nnc: Option<String>,
+
+    /// Facts.
+    #[serde(default)]
+    pub fct: Option<BTreeMap<String, Ipld>>,
+
+    /// Proofs, as CID strings of other 0.10 JWTs.
+    #[serde(default)]
+    pub prf: Vec<String>,
+
+    /// Attenuations: resource → ability → caveats.
+    pub att: BTreeMap<String, BTreeMap<String, Vec<BTreeMap<String, Ipld>>>>,
+}
+
+impl LegacyUcan {
+    /// Getter for the JWT header.
+    #[must_use]
+    pub const fn header(&self) -> &LegacyHeader {
+        &self.header
+    }
+
+    /// Getter for the JWT claims.
+    #[must_use]
+    pub const fn payload(&self) -> &LegacyPayload {
+        &self.payload
+    }
+
+    /// Getter for the raw signature bytes.
+    #[must_use]
+    pub const fn signature(&self) -> &[u8] {
+        self.signature.as_slice()
+    }
+
+    /// The issuer as an Ed25519 `did:key`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`LegacyError::InvalidDid`] if the issuer is not an Ed25519 `did:key`.
+    pub fn issuer(&self) -> Result<Ed25519Did, LegacyError> {
+        Ed25519Did::from_str(&self.payload.iss)
+            .map_err(|_| LegacyError::InvalidDid(self.payload.iss.clone()))
+    }
+
+    /// The audience as an Ed25519 `did:key`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`LegacyError::InvalidDid`] if the audience is not an Ed25519 `did:key`.
+    pub fn audience(&self) -> Result<Ed25519Did, LegacyError> {
+        Ed25519Did::from_str(&self.payload.aud)
+            .map_err(|_| LegacyError::InvalidDid(self.payload.aud.clone()))
+    }
+
+    /// Verify the JWT signature against the issuer's `did:key`.
+    ///
+    /// Only `EdDSA` tokens issued by Ed25519 `did:key`s are supported.
+    ///
+    /// # Errors
+    ///
+    /// * [`LegacyError::UnsupportedAlgorithm`] if the `alg` is not `EdDSA`
+    /// * [`LegacyError::InvalidDid`] if the issuer is not an Ed25519 `did:key`
+    /// * [`LegacyError::InvalidSignature`] if the signature does not verify
+    pub fn verify_signature(&self) -> Result<(), LegacyError> {
+        if self.header.alg != "EdDSA" {
+            return Err(LegacyError::UnsupportedAlgorithm(self.header.alg.clone()));
+        }
+
+        let issuer = self.issuer()?;
+        let sig = ed25519_dalek::Signature::from_slice(&self.signature)
+            .map_err(|_| LegacyError::InvalidSignature)?;
+
+        issuer
+            .0
+            .verify(self.signed_data.as_bytes(), &sig)
+            .map_err(|_| LegacyError::InvalidSignature)
+    }
+
+    /// Check the `nbf` and `exp` claims against the given time.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`TimeBoundError`] if the token is expired or not yet valid.
+    pub const fn check_time(&self, now: Timestamp) -> Result<(), TimeBoundError> {
+        let now = now.to_unix();
+
+        if let Some(exp) = self.payload.exp {
+            if now >= exp {
+                return Err(TimeBoundError::Expired);
+            }
+        }
+
+        if let Some(nbf) = self.payload.nbf {
+            if now < nbf {
+                return Err(TimeBoundError::NotYetValid);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Facts (`fct`) of the token, suitable for use as 1.0 `meta`.
+    #[must_use]
+    pub fn meta(&self) -> BTreeMap<String, Ipld> {
+        self.payload.fct.clone().unwrap_or_default()
+    }
+
+    /// Map the token's capabilities onto 1.0 commands and policies.
+    ///
+    /// Every capability that cannot be represented exactly is recorded
+    /// in [`LegacyMapping::lossy`].
+    #[must_use]
+    pub fn to_mapping(&self) -> LegacyMapping {
+        let mut capabilities = Vec::new();
+        let mut lossy = Vec::new();
+
+        if !self.payload.prf.is_empty() {
+            lossy.push(Lossy::Proofs(self.payload.prf.clone()));
+        }
+
+        for (resource, abilities) in &self.payload.att {
+            let subject = if let Some(target) = resource.strip_prefix("ucan:") {
+                lossy.push(Lossy::Redelegation {
+                    resource: resource.clone(),
+                });
+                redelegation_subject(target)
+            } else if let Ok(did) = Ed25519Did::from_str(resource) {
+                Some(DelegatedSubject::Specific(did))
+            } else {
+                lossy.push(Lossy::ResourceUri {
+                    resource: resource.clone(),
+                });
+                None
+            };
+
+            for (ability, caveats) in abilities {
+                let command = match ability_to_command(ability) {
+                    Ok(command) => command,
+                    Err(error) => {
+                        lossy.push(Lossy::UnmappableAbility {
+                            ability: ability.clone(),
+                            error,
+                        });
+                        continue;
+                    }
+                };
+
+                if caveats.is_empty() {
+                    lossy.push(Lossy::EmptyCaveats {
+                        resource: resource.clone(),
+                        ability: ability.clone(),
+                    });
+                    continue;
+                }
+
+                let policy = caveats_to_policy(caveats);
+                if !policy.is_empty() {
+                    lossy.push(Lossy::CaveatsApproximated {
+                        resource: resource.clone(),
+                        ability: ability.clone(),
+                    });
+                }
+
+                capabilities.push(MappedCapability {
+                    resource: resource.clone(),
+                    subject: subject.clone(),
+                    command,
+                    policy,
+                });
+            }
+        }
+
+        LegacyMapping {
+            capabilities,
+            lossy,
+        }
+    }
+}
+
+impl FromStr for LegacyUcan {
+    type Err = LegacyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.trim().split('.');
+        let (Some(header_b64), Some(payload_b64), Some(sig_b64), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(LegacyError::MalformedJwt);
+        };
+
+        let header_bytes = BASE64_URL_SAFE_NO_PAD
+            .decode(header_b64)
+            .map_err(|_| LegacyError::InvalidBase64("header"))?;
+        let payload_bytes = BASE64_URL_SAFE_NO_PAD
+            .decode(payload_b64)
+            .map_err(|_| LegacyError::InvalidBase64("payload"))?;
+        let signature = BASE64_URL_SAFE_NO_PAD
+            .decode(sig_b64)
+            .map_err(|_| LegacyError::InvalidBase64("signature"))?;
+
+        let header: LegacyHeader = serde_json::from_slice(&header_bytes)
+            .map_err(|e| LegacyError::InvalidJson(e.to_string()))?;
+        let payload: LegacyPayload = serde_json::from_slice(&payload_bytes)
+            .map_err(|e| LegacyError::InvalidJson(e.to_string()))?;
+
+        if !payload.ucv.starts_with("0.10.") {
+            return Err(LegacyError::UnsupportedVersion(payload.ucv));
+        }
+
+        Ok(LegacyUcan {
+            header,
+            payload,
+            signed_data: alloc::format!("{header_b64}.{payload_b64}"),
+            signature,
+        })
+    }
+}
+
+/// The result of mapping a [`LegacyUcan`] onto 1.0 concepts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LegacyMapping {
+    /// Capabilities that could be mapped.
+    pub capabilities: Vec<MappedCapability>,
+
+    /// Everything that was lost or approximated along the way.
+    pub lossy: Vec<Lossy>,
+}
+
+impl LegacyMapping {
+    /// Whether the mapping is exact.
+    #[must_use]
+    pub const fn is_lossless(&self) -> bool {
+        self.lossy.is_empty()
+    }
+}
+
+/// A single 0.10 capability expressed as a 1.0 command and policy.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MappedCapability {
+    /// The original 0.10 resource URI.
+    pub resource: String,
+
+    /// The 1.0 subject, if the resource identifies one.
+    pub subject: Option<DelegatedSubject<Ed25519Did>>,
+
+    /// The 1.0 command derived from the 0.10 ability.
+    pub command: Command,
+
+    /// The 1.0 policy derived from the 0.10 caveats.
+    pub policy: Vec<Predicate>,
+}
+
+/// A part of a 0.10 token that does not map exactly onto 1.0.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Lossy {
+    /// The resource is a URI rather than a DID, so it has no 1.0 subject.
+    ResourceUri {
+        /// The 0.10 resource.
+        resource: String,
+    },
+
+    /// The resource is a `ucan:` redelegation. Only `ucan:*` maps to a
+    /// powerline (`sub: null`); `ucan:<did>` and `ucan://<did>/...` map to that
+    /// DID, and anything else (e.g. a proof CID) has no 1.0 subject.
+    Redelegation {
+        /// The 0.10 resource.
+        resource: String,
+    },
+
+    /// Caveats were mapped to equality checks on invocation arguments.
+    ///
+    /// 0.10 caveat semantics are application defined,
+    /// so this is only an approximation.
+    CaveatsApproximated {
+        /// The 0.10 resource.
+        resource: String,
+
+        /// The 0.10 ability.
+        ability: String,
+    },
+
+    /// An empty caveat array grants nothing, so the capability was dropped.
+    EmptyCaveats {
+        /// The 0.10 resource.
+        resource: String,
+
+        /// The 0.10 ability.
+        ability: String,
+    },
+
+    /// The ability is not a valid 1.0 command.
+    UnmappableAbility {
+        /// The 0.10 ability.
+        ability: String,
+
+        /// Why it could not be parsed as a command.
+        error: CommandParseError,
+    },
+
+    /// Proofs reference 0.10 JWTs and are not carried over.
+    Proofs(Vec<String>),
+}
+
+/// The 1.0 subject of a `ucan:` resource, given what follows `ucan:`.
+///
+/// Only `*` (everything the issuer holds) becomes a powerline. A DID, bare or
+/// as `//<did>/<scope>`, narrows to that subject, and proof selectors such as
+/// `./*` or a CID cannot be resolved without the 0.10 proofs.
+fn redelegation_subject(target: &str) -> Option<DelegatedSubject<Ed25519Did>> {
+    if target == "*" {
+        return Some(DelegatedSubject::Any);
+    }
+    let target = target.strip_prefix("//").unwrap_or(target);
+    let did = target.split_once('/').map_or(target, |(did, _)| did);
+    Ed25519Did::from_str(did)
+        .ok()
+        .map(DelegatedSubject::Specific)
+}
+
+/// Convert a 0.10 ability (e.g. `crud/read` or `*`) into a [`Command`].
+///
+/// Abilities are case-insensitive in 0.10, so they are lowercased.
+///
+/// # Errors
+///
+/// Returns a [`CommandParseError`] if the ability has empty segments.
+pub fn ability_to_command(ability: &str) -> Result<Command, CommandParseError> {
+    if ability == "*" {
+        return Ok(Command::new(Vec::new()));
+    }
+
+    Command::parse(&alloc::format!("/{}", ability.to_lowercase()))
+}
+
+/// Convert a 0.10 caveat array into a 1.0 policy.
+///
+/// Each caveat object becomes a conjunction of `==` checks on the named
+/// arguments, and the array as a whole becomes a disjunction. The
+/// unrestricted `[{}]` caveat produces an empty policy.
+#[must_use]
+pub fn caveats_to_policy(caveats: &[BTreeMap<String, Ipld>]) -> Vec<Predicate> {
+    if caveats.iter().any(BTreeMap::is_empty) {
+        return Vec::new();
+    }
+
+    let alternatives: Vec<Predicate> = caveats
+        .iter()
+        .map(|caveat| {
+            let mut checks: Vec<Predicate> = caveat
+                .iter()
+                .map(|(field, value)| {
+                    Predicate::Equal(
+                        Select::new(alloc::vec![Filter::Field(field.clone())]),
+                        value.clone(),
+                    )
+                })
+                .collect();
+
+            if checks.len() == 1 {
+                checks.remove(0)
+            } else {
+                Predicate::And(checks)
+            }
+        })
+        .collect();
+
+    if alternatives.len() == 1 {
+        alternatives
+    } else {
+        alloc::vec![Predicate::Or(alternatives)]
+    }
+}
+
+/// Errors that can occur when handling legacy tokens.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum LegacyError {
+    /// The token does not have three dot-separated segments.
+    #[error("malformed JWT: expected three dot-separated segments")]
+    MalformedJwt,
+
+    /// A segment is not valid unpadded base64url.
+    #[error("invalid base64url in JWT {0}")]
+    InvalidBase64(&'static str),
+
+    /// The header or payload is not valid JSON for a 0.10 UCAN.
+    #[error("invalid JWT JSON: {0}")]
+    InvalidJson(String),
+
+    /// The `ucv` claim is not a 0.10 version.
+    #[error("unsupported UCAN version: {0}")]
+    UnsupportedVersion(String),
+
+    /// The signature algorithm is not supported.
+    #[error("unsupported JWT algorithm: {0}")]
+    UnsupportedAlgorithm(String),
+
+    /// A DID is not an Ed25519 `did:key`.
+    #[error("invalid or unsupported DID: {0}")]
+    InvalidDid(String),
+
+    /// The signature does not verify.
+    #[error("invalid signature")]
+    InvalidSignature,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::Signer;
+    use testresult::TestResult;
+
+    fn encode_jwt(
+        sk: &ed25519_dalek::SigningKey,
+        payload: &serde_json::Value,
+    ) -> TestResult<String> {
+        let header = BASE64_URL_SAFE_NO_PAD.encode(r#"{"alg":"EdDSA","typ":"JWT"}"#);
+        let body = BASE64_URL_SAFE_NO_PAD.encode(serde_json::to_vec(payload)?);
+        let signed = format!("{header}.{body}");
+        let sig = BASE64_URL_SAFE_NO_PAD.encode(sk.sign(signed.as_bytes()).to_bytes());
+        Ok(format!("{signed}.{sig}"))
+    }
+
+    fn fixture() -> TestResult<(ed25519_dalek::SigningKey, String)> {
+        let sk = ed25519_dalek::SigningKey::from_bytes(&[7u8; 32]);
+        let aud = ed25519_dalek::SigningKey::from_bytes(&[8u8; 32]);
+        let iss = Ed25519Did::from(sk.verifying_key()).to_string();
+        let aud = Ed25519Did::from(aud.verifying_key()).to_string();
+
+        let payload = serde_json::json!({
+            "ucv": "0.10.0",
+            "iss": iss,
+            "aud": aud,
+            "nbf": 1_000,
+            "exp": 2_000,
+            "fct": { "note": "hello" },
+            "prf": ["bafkreihdwdcefgh4dqkjv67uzcmw7ojee6xedzdetojuzjevtenxquvyku"],
+            "att": {
+                iss.clone(): { "crud/read": [{}] },
+                "mailto:alice@example.com": { "msg/SEND": [{ "to": "bob" }, { "to": "carol" }] },
+                "https://example.com": { "*": [] }
+            }
+        });
+
+        let jwt = encode_jwt(&sk, &payload)?;
+        Ok((sk, jwt))
+    }
+
+    #[test]
+    fn test_parse_and_verify() -> TestResult {
+        let (sk, jwt) = fixture()?;
+        let ucan = LegacyUcan::from_str(&jwt)?;
+
+        assert_eq!(ucan.header().alg, "EdDSA");
+        assert_eq!(ucan.issuer()?, Ed25519Did::from(sk.verifying_key()));
+        ucan.verify_signature()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_tampered_payload_fails_verification() -> TestResult {
+        let (sk, jwt) = fixture()?;
+        let did = Ed25519Did::from(sk.verifying_key()).to_string();
+        let forged = encode_jwt(
+            &sk,
+            &serde_json::json!({ "ucv": "0.10.0", "iss": did, "aud": did, "exp": null, "att": {} }),
+        )?;
+
+        let mut parts = jwt.split('.');
+        let header = parts.next().ok_or("missing header")?;
+        let sig = parts.nth(1).ok_or("missing signature")?;
+        let payload = forged.split('.').nth(1).ok_or("missing payload")?;
+
+        let ucan = LegacyUcan::from_str(&format!("{header}.{payload}.{sig}"))?;
+        assert_eq!(ucan.verify_signature(), Err(LegacyError::InvalidSignature));
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejects_other_versions() -> TestResult {
+        let sk = ed25519_dalek::SigningKey::from_bytes(&[7u8; 32]);
+        let did = Ed25519Did::from(sk.verifying_key()).to_string();
+        let jwt = encode_jwt(
+            &sk,
+            &serde_json::json!({ "ucv": "0.9.1", "iss": did, "aud": did, "exp": null, "att": {} }),
+        )?;
+
+        assert_eq!(
+            LegacyUcan::from_str(&jwt),
+            Err(LegacyError::UnsupportedVersion("0.9.1".to_string()))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_time() -> TestResult {
+        let (_, jwt) = fixture()?;
+        let ucan = LegacyUcan::from_str(&jwt)?;
+
+        assert_eq!(
+            ucan.check_time(Timestamp::from_unix(999)?),
+            Err(TimeBoundError::NotYetValid)
+        );
+        assert_eq!(ucan.check_time(Timestamp::from_unix(1_500)?), Ok(()));
+        assert_eq!(
+            ucan.check_time(Timestamp::from_unix(2_000)?),
+            Err(TimeBoundError::Expired)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_mapping() -> TestResult {
+        let (sk, jwt) = fixture()?;
+        let ucan = LegacyUcan::from_str(&jwt)?;
+        let mapping = ucan.to_mapping();
+
+        assert!(!mapping.is_lossless());
+        assert_eq!(mapping.capabilities.len(), 2);
+
+        let read_cmd = Command::parse("/crud/read")?;
+        let read = mapping
+            .capabilities
+            .iter()
+            .find(|c| c.command == read_cmd)
+            .ok_or("missing /crud/read")?;
+        assert_eq!(
+            read.subject,
+            Some(DelegatedSubject::Specific(sk.verifying_key().into()))
+        );
+        assert!(read.policy.is_empty());
+
+        let send_cmd = Command::parse("/msg/send")?;
+        let send = mapping
+            .capabilities
+            .iter()
+            .find(|c| c.command == send_cmd)
+            .ok_or("missing /msg/send")?;
+        assert_eq!(send.subject, None);
+        assert_eq!(
+            send.policy,
+            vec![Predicate::Or(vec![
+                Predicate::Equal(Select::from_str(".to")?, Ipld::String("bob".into())),
+                Predicate::Equal(Select::from_str(".to")?, Ipld::String("carol".into())),
+            ])]
+        );
+
+        assert!(mapping.lossy.contains(&Lossy::ResourceUri {
+            resource: "mailto:alice@example.com".to_string()
+        }));
+        assert!(mapping.lossy.contains(&Lossy::EmptyCaveats {
+            resource: "https://example.com".to_string(),
+            ability: "*".to_string()
+        }));
+        assert!(mapping
+            .lossy
+            .iter()
+            .any(|l| matches!(l, Lossy::Proofs(prf) if prf.len() == 1)));
+
+        assert_eq!(
+            ucan.meta().get("note"),
+            Some(&Ipld::String("hello".to_string()))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_redelegation_subject() {
+        let did =
+            Ed25519Did::from(ed25519_dalek::SigningKey::from_bytes(&[7u8; 32]).verifying_key());
+
+        assert_eq!(redelegation_subject("*"), Some(DelegatedSubject::Any));
+        assert_eq!(
+            redelegation_subject(&did.to_string()),
+            Some(DelegatedSubject::Specific(did))
+        );
+        assert_eq!(
+            redelegation_subject(&format!("//{did}/*")),
+            Some(DelegatedSubject::Specific(did))
+        );
+        assert_eq!(redelegation_subject("./*"), None);
+        assert_eq!(
+            redelegation_subject("bafkreihdwdcefgh4dqkjv67uzcmw7ojee6xedzdetojuzjevtenxquvyku"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_ability_to_command() -> TestResult {
+        assert_eq!(ability_to_command("*")?, Command::new(vec![]));
+        assert_eq!(
+            ability_to_command("crud/READ")?,
+            Command::parse("/crud/read")?
+        );
+        assert_eq!(
+            ability_to_command("crud//read"),
+            Err(CommandParseError::EmptySegment)
+        );
+        Ok(())
+    }
+}
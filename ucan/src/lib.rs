@@ -6,6 +6,10 @@
 
 extern crate alloc;
 
+#[cfg(all(feature = "agent", unix))]
+pub mod agent;
+#[cfg(feature = "bls")]
+pub mod aggregate;
 pub mod cid;
 pub mod collection;
 pub mod collections;
@@ -15,6 +19,10 @@ pub mod delegation;
 pub mod did;
 pub mod envelope;
 pub mod invocation;
+#[cfg(feature = "keystore")]
+pub mod keystore;
+#[cfg(feature = "legacy")]
+pub mod legacy;
 pub mod number;
 pub mod promise;
 pub mod time;
@@ -23,6 +31,8 @@ pub mod unset;
 // Internal modules
 mod ipld;
 mod sealed;
+#[cfg(test)]
+mod test_support;
 
 pub use delegation::{builder::DelegationBuilder, Delegation};
 // pub use invocation::{builder::InvocationBuilder, Invocation};
@@ -1,7 +1,7 @@
 use crate::{
     command::Command,
-    delegation::subject::DelegatedSubject,
-    did::{Did, DidSigner},
+    delegation::{detached::DetachedIssuer, subject::DelegatedSubject},
+    did::{AsyncDidSigner, Did},
     unset::Unset,
 };
 use alloc::vec::Vec;
@@ -15,7 +15,8 @@ impl<D: Did> DidOrUnset for D {}
 #[doc(hidden)]
 pub trait DidSignerOrUnset {}
 impl DidSignerOrUnset for Unset {}
-impl<D: DidSigner> DidSignerOrUnset for D {}
+impl<D: AsyncDidSigner> DidSignerOrUnset for D {}
+impl<D: Did> DidSignerOrUnset for DetachedIssuer<D> {}
 
 #[doc(hidden)]
 pub trait DelegatedSubjectOrUnset {}
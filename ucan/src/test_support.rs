@@ -0,0 +1,81 @@
+//! Fixtures shared by the unit tests.
+
+use crate::{
+    delegation::subject::DelegatedSubject,
+    did::{Ed25519Did, Ed25519Signer},
+    time::timestamp::Timestamp,
+    Delegation, DelegationBuilder,
+};
+use testresult::TestResult;
+
+pub(crate) use futures::executor::block_on;
+
+/// A deterministic Ed25519 signer; equal seeds give equal keys.
+pub(crate) fn signer(seed: u8) -> Ed25519Signer {
+    ed25519_dalek::SigningKey::from_bytes(&[seed; 32]).into()
+}
+
+/// A timestamp `secs` seconds after the Unix epoch.
+pub(crate) fn at(secs: u64) -> TestResult<Timestamp> {
+    Ok(Timestamp::from_unix(secs)?)
+}
+
+/// A powerline delegation of `command` from `signer(1)` to itself.
+///
+/// Generic over the wrapper so stores can take it as `Rc` or `Arc` directly.
+pub(crate) fn delegation<T: From<Delegation<Ed25519Did>>>(command: &str) -> TestResult<T> {
+    delegation_with(1, 1, DelegatedSubject::Any, command, None)
+}
+
+/// A delegation of `command` from `signer(iss)` to `signer(aud)`.
+pub(crate) fn delegation_with<T: From<Delegation<Ed25519Did>>>(
+    iss: u8,
+    aud: u8,
+    subject: DelegatedSubject<Ed25519Did>,
+    command: &str,
+    expiration: Option<u64>,
+) -> TestResult<T> {
+    let builder = DelegationBuilder::new()
+        .issuer(signer(iss))
+        .audience(*signer(aud).did())
+        .subject(subject)
+        .command_from_str(command)?;
+    Ok(match expiration {
+        Some(secs) => builder.expiration(at(secs)?),
+        None => builder,
+    }
+    .try_build()?
+    .into())
+}
+
+/// A fresh, empty directory under the system temp dir, removed on drop.
+#[cfg(feature = "std")]
+pub(crate) struct TempDir(std::path::PathBuf);
+
+#[cfg(feature = "std")]
+impl TempDir {
+    pub(crate) fn new(name: &str) -> std::io::Result<Self> {
+        use core::sync::atomic::{AtomicU64, Ordering};
+
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        let dir = std::env::temp_dir().join(format!(
+            "ucan-test-{name}-{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::remove_dir_all(&dir).ok();
+        std::fs::create_dir_all(&dir)?;
+        Ok(TempDir(dir))
+    }
+
+    pub(crate) fn path(&self) -> &std::path::Path {
+        &self.0
+    }
+}
+
+#[cfg(feature = "std")]
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        std::fs::remove_dir_all(&self.0).ok();
+    }
+}
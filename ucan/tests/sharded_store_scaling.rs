@@ -0,0 +1,104 @@
+//! Benchmark showing that reads from a `ShardedStore` scale with threads.
+//!
+//! Timing-sensitive, so ignored by default. Run on a multi-core machine with
+//! `cargo test --release --test sharded_store_scaling -- --ignored --nocapture`.
+mod sharded_store_scaling {
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use future_form::Sendable;
+    use futures::executor::block_on;
+    use ipld_core::cid::Cid;
+    use testresult::TestResult;
+    use ucan::{
+        delegation::{
+            store::{insert_many, DelegationStore, ShardedStore},
+            subject::DelegatedSubject,
+        },
+        did::{Ed25519Did, Ed25519Signer},
+        Delegation, DelegationBuilder,
+    };
+
+    const DELEGATIONS: usize = 1_024;
+    const READS_PER_THREAD: usize = 200_000;
+
+    fn delegations() -> TestResult<Vec<Arc<Delegation<Ed25519Did>>>> {
+        let signer: Ed25519Signer = ed25519_dalek::SigningKey::from_bytes(&[1; 32]).into();
+        (0..DELEGATIONS)
+            .map(|i| {
+                Ok(Arc::new(
+                    DelegationBuilder::new()
+                        .issuer(signer.clone())
+                        .audience(*signer.did())
+                        .subject(DelegatedSubject::Any)
+                        .command_from_str(&format!("/cmd/{i}"))?
+                        .try_build()?,
+                ))
+            })
+            .collect()
+    }
+
+    /// Total reads per second across `threads` threads, each reading `READS_PER_THREAD` CIDs.
+    fn reads_per_second<S>(store: &S, cids: &[Cid], threads: usize) -> f64
+    where
+        S: DelegationStore<Sendable, Ed25519Did, Arc<Delegation<Ed25519Did>>> + Sync,
+    {
+        let start = Instant::now();
+        thread::scope(|scope| {
+            for offset in 0..threads {
+                scope.spawn(move || {
+                    for cid in cids.iter().cycle().skip(offset).take(READS_PER_THREAD) {
+                        assert!(block_on(store.get_all(std::slice::from_ref(cid))).is_ok());
+                    }
+                });
+            }
+        });
+        let elapsed = start.elapsed().max(Duration::from_nanos(1)).as_secs_f64();
+        #[allow(clippy::cast_precision_loss)]
+        let reads = (threads * READS_PER_THREAD) as f64;
+        reads / elapsed
+    }
+
+    #[test]
+    #[ignore = "benchmark; run with --release -- --ignored"]
+    fn test_concurrent_reads_scale() -> TestResult {
+        let threads = thread::available_parallelism()?.get().min(4);
+        if threads < 2 {
+            eprintln!("skipping: only one CPU available");
+            return Ok(());
+        }
+
+        let dlgs = delegations()?;
+        let sharded = ShardedStore::<Ed25519Did>::new();
+        let cids = block_on(insert_many::<Sendable, _, _, _>(&sharded, dlgs.clone()))?;
+        let mutex: Arc<Mutex<HashMap<Cid, Arc<Delegation<Ed25519Did>>>>> = Arc::default();
+        block_on(insert_many::<Sendable, _, _, _>(&mutex, dlgs))?;
+
+        let sharded_single = reads_per_second(&sharded, &cids, 1);
+        let sharded_parallel = reads_per_second(&sharded, &cids, threads);
+        let mutex_single = reads_per_second(&mutex, &cids, 1);
+        let mutex_parallel = reads_per_second(&mutex, &cids, threads);
+
+        let speedup = sharded_parallel / sharded_single;
+        eprintln!(
+            "ShardedStore: {sharded_single:.0} reads/s on 1 thread, {sharded_parallel:.0} on {threads} ({speedup:.2}x)"
+        );
+        eprintln!(
+            "Arc<Mutex<HashMap>>: {mutex_single:.0} reads/s on 1 thread, {mutex_parallel:.0} on {threads} ({:.2}x)",
+            mutex_parallel / mutex_single
+        );
+
+        // Allow for scheduling noise, but require a real gain from each extra thread
+        #[allow(clippy::cast_precision_loss)]
+        let expected = threads as f64 / 2.0;
+        assert!(
+            speedup >= expected,
+            "expected at least {expected:.1}x with {threads} threads, got {speedup:.2}x"
+        );
+        Ok(())
+    }
+}
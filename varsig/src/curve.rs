@@ -41,3 +41,8 @@ pub struct Edwards25519;
 #[cfg(feature = "edwards448")]
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Edwards448;
+
+/// The BLS12-381 pairing-friendly curve
+#[cfg(feature = "bls12_381")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Bls12381;
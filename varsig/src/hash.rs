@@ -1,12 +1,166 @@
 //! Multihash algorithms.
 //!
-//! This is separate from the `multihash-codetable` crate
-//! because we don't need any of the actual hashing functionality.
+//! Each hash is a marker type carrying its multihash tag, plus an incremental
+//! digest backed by the corresponding `RustCrypto` (or `BLAKE3`) crate. Enabling a
+//! hash feature pulls in only the crate that implements it.
+//!
+//! Hashing is incremental so that large payloads can be streamed through
+//! [`StreamingDigest`] rather than buffered whole.
+
+use alloc::vec::Vec;
 
 /// Multihash Prefix
 pub trait Multihasher {
     /// Multihash tag for this hasher.
     const MULTIHASH_TAG: u64;
+
+    /// Length of the digest in bytes.
+    const DIGEST_LEN: usize;
+
+    /// Incremental hashing state.
+    type State: Clone;
+
+    /// Start a new incremental hash.
+    fn init() -> Self::State;
+
+    /// Feed more data into an incremental hash.
+    fn update(state: &mut Self::State, data: &[u8]);
+
+    /// Finish an incremental hash and return the digest.
+    fn finalize(state: Self::State) -> Vec<u8>;
+
+    /// Hash `data` in one shot.
+    #[must_use]
+    fn digest(data: &[u8]) -> Vec<u8> {
+        let mut state = Self::init();
+        Self::update(&mut state, data);
+        Self::finalize(state)
+    }
+}
+
+/// An in-progress digest for the hash `H`.
+///
+/// With the `std` feature this implements [`std::io::Write`],
+/// so a payload can be streamed in with [`std::io::copy`].
+#[derive(Debug, Clone)]
+pub struct StreamingDigest<H: Multihasher>(H::State);
+
+impl<H: Multihasher> StreamingDigest<H> {
+    /// Start a new digest.
+    #[must_use]
+    pub fn new() -> Self {
+        Self(H::init())
+    }
+
+    /// Feed more data.
+    pub fn update(&mut self, data: &[u8]) {
+        H::update(&mut self.0, data);
+    }
+
+    /// Finish and return the digest.
+    #[must_use]
+    pub fn finalize(self) -> Vec<u8> {
+        H::finalize(self.0)
+    }
+}
+
+impl<H: Multihasher> Default for StreamingDigest<H> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<H: Multihasher> std::io::Write for StreamingDigest<H> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A one-shot digest function, as returned by [`digest_fn_by_tag`].
+pub type DigestFn = fn(&[u8]) -> Vec<u8>;
+
+/// The one-shot digest function of whichever compiled-in hash has the given
+/// multihash tag.
+///
+/// Returns `None` if no enabled hash feature matches `tag`.
+#[must_use]
+#[allow(unused_variables, clippy::missing_const_for_fn)]
+pub fn digest_fn_by_tag(tag: u64) -> Option<DigestFn> {
+    #[cfg(feature = "sha2_256")]
+    if tag == Sha2_256::MULTIHASH_TAG {
+        return Some(Sha2_256::digest);
+    }
+
+    #[cfg(feature = "sha2_384")]
+    if tag == Sha2_384::MULTIHASH_TAG {
+        return Some(Sha2_384::digest);
+    }
+
+    #[cfg(feature = "sha2_512")]
+    if tag == Sha2_512::MULTIHASH_TAG {
+        return Some(Sha2_512::digest);
+    }
+
+    #[cfg(feature = "shake_256")]
+    if tag == Shake256::MULTIHASH_TAG {
+        return Some(Shake256::digest);
+    }
+
+    #[cfg(feature = "blake2b")]
+    if tag == Blake2b::MULTIHASH_TAG {
+        return Some(Blake2b::digest);
+    }
+
+    #[cfg(feature = "blake3")]
+    if tag == Blake3::MULTIHASH_TAG {
+        return Some(Blake3::digest);
+    }
+
+    #[cfg(feature = "keccak256")]
+    if tag == Keccak256::MULTIHASH_TAG {
+        return Some(Keccak256::digest);
+    }
+
+    #[cfg(feature = "keccak384")]
+    if tag == Keccak384::MULTIHASH_TAG {
+        return Some(Keccak384::digest);
+    }
+
+    #[cfg(feature = "keccak512")]
+    if tag == Keccak512::MULTIHASH_TAG {
+        return Some(Keccak512::digest);
+    }
+
+    #[cfg(feature = "sha3_256")]
+    if tag == Sha3_256::MULTIHASH_TAG {
+        return Some(Sha3_256::digest);
+    }
+
+    #[cfg(feature = "sha3_384")]
+    if tag == Sha3_384::MULTIHASH_TAG {
+        return Some(Sha3_384::digest);
+    }
+
+    #[cfg(feature = "sha3_512")]
+    if tag == Sha3_512::MULTIHASH_TAG {
+        return Some(Sha3_512::digest);
+    }
+
+    None
+}
+
+/// Hash `data` with whichever compiled-in hash has the given multihash tag.
+///
+/// Returns `None` if no enabled hash feature matches `tag`.
+#[must_use]
+pub fn digest_by_tag(tag: u64, data: &[u8]) -> Option<Vec<u8>> {
+    digest_fn_by_tag(tag).map(|digest| digest(data))
 }
 
 /// SHA2-256 hash algorithm.
@@ -17,6 +171,21 @@ pub struct Sha2_256;
 #[cfg(feature = "sha2_256")]
 impl Multihasher for Sha2_256 {
     const MULTIHASH_TAG: u64 = 0x12;
+    const DIGEST_LEN: usize = 32;
+
+    type State = sha2::Sha256;
+
+    fn init() -> Self::State {
+        <sha2::Sha256 as sha2::Digest>::new()
+    }
+
+    fn update(state: &mut Self::State, data: &[u8]) {
+        sha2::Digest::update(state, data);
+    }
+
+    fn finalize(state: Self::State) -> Vec<u8> {
+        sha2::Digest::finalize(state).to_vec()
+    }
 }
 
 /// SHA2-384 hash algorithm.
@@ -26,7 +195,22 @@ pub struct Sha2_384;
 
 #[cfg(feature = "sha2_384")]
 impl Multihasher for Sha2_384 {
-    const MULTIHASH_TAG: u64 = 0x15;
+    const MULTIHASH_TAG: u64 = 0x20;
+    const DIGEST_LEN: usize = 48;
+
+    type State = sha2::Sha384;
+
+    fn init() -> Self::State {
+        <sha2::Sha384 as sha2::Digest>::new()
+    }
+
+    fn update(state: &mut Self::State, data: &[u8]) {
+        sha2::Digest::update(state, data);
+    }
+
+    fn finalize(state: Self::State) -> Vec<u8> {
+        sha2::Digest::finalize(state).to_vec()
+    }
 }
 
 /// SHA2-512 hash algorithm.
@@ -37,9 +221,27 @@ pub struct Sha2_512;
 #[cfg(feature = "sha2_512")]
 impl Multihasher for Sha2_512 {
     const MULTIHASH_TAG: u64 = 0x13;
+    const DIGEST_LEN: usize = 64;
+
+    type State = sha2::Sha512;
+
+    fn init() -> Self::State {
+        <sha2::Sha512 as sha2::Digest>::new()
+    }
+
+    fn update(state: &mut Self::State, data: &[u8]) {
+        sha2::Digest::update(state, data);
+    }
+
+    fn finalize(state: Self::State) -> Vec<u8> {
+        sha2::Digest::finalize(state).to_vec()
+    }
 }
 
 /// Shake256 hash algorithm.
+///
+/// SHAKE-256 is an extendable-output function; following the multihash
+/// table, the digest is its first 64 bytes.
 #[cfg(feature = "shake_256")]
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Shake256;
@@ -47,9 +249,30 @@ pub struct Shake256;
 #[cfg(feature = "shake_256")]
 impl Multihasher for Shake256 {
     const MULTIHASH_TAG: u64 = 0x19;
+    const DIGEST_LEN: usize = 64;
+
+    type State = sha3::Shake256;
+
+    fn init() -> Self::State {
+        sha3::Shake256::default()
+    }
+
+    fn update(state: &mut Self::State, data: &[u8]) {
+        sha3::digest::Update::update(state, data);
+    }
+
+    fn finalize(state: Self::State) -> Vec<u8> {
+        use sha3::digest::{ExtendableOutput, XofReader};
+
+        let mut out = alloc::vec![0; Self::DIGEST_LEN];
+        state.finalize_xof().read(&mut out);
+        out
+    }
 }
 
 /// Blake2b hash algorithm.
+///
+/// The multihash tag is `blake2b-256`, i.e. a 32-byte `BLAKE2b` digest.
 #[cfg(feature = "blake2b")]
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Blake2b;
@@ -57,6 +280,21 @@ pub struct Blake2b;
 #[cfg(feature = "blake2b")]
 impl Multihasher for Blake2b {
     const MULTIHASH_TAG: u64 = 0xb220;
+    const DIGEST_LEN: usize = 32;
+
+    type State = blake2::Blake2b<blake2::digest::consts::U32>;
+
+    fn init() -> Self::State {
+        <Self::State as blake2::Digest>::new()
+    }
+
+    fn update(state: &mut Self::State, data: &[u8]) {
+        blake2::Digest::update(state, data);
+    }
+
+    fn finalize(state: Self::State) -> Vec<u8> {
+        blake2::Digest::finalize(state).to_vec()
+    }
 }
 
 /// Blake3 hash algorithm.
@@ -67,6 +305,21 @@ pub struct Blake3;
 #[cfg(feature = "blake3")]
 impl Multihasher for Blake3 {
     const MULTIHASH_TAG: u64 = 0x1e;
+    const DIGEST_LEN: usize = 32;
+
+    type State = blake3::Hasher;
+
+    fn init() -> Self::State {
+        blake3::Hasher::new()
+    }
+
+    fn update(state: &mut Self::State, data: &[u8]) {
+        state.update(data);
+    }
+
+    fn finalize(state: Self::State) -> Vec<u8> {
+        state.finalize().as_bytes().to_vec()
+    }
 }
 
 /// Keccak256 hash algorithm.
@@ -77,6 +330,21 @@ pub struct Keccak256;
 #[cfg(feature = "keccak256")]
 impl Multihasher for Keccak256 {
     const MULTIHASH_TAG: u64 = 0x1b;
+    const DIGEST_LEN: usize = 32;
+
+    type State = sha3::Keccak256;
+
+    fn init() -> Self::State {
+        <sha3::Keccak256 as sha3::Digest>::new()
+    }
+
+    fn update(state: &mut Self::State, data: &[u8]) {
+        sha3::Digest::update(state, data);
+    }
+
+    fn finalize(state: Self::State) -> Vec<u8> {
+        sha3::Digest::finalize(state).to_vec()
+    }
 }
 
 /// Keccak384 hash algorithm.
@@ -87,6 +355,21 @@ pub struct Keccak384;
 #[cfg(feature = "keccak384")]
 impl Multihasher for Keccak384 {
     const MULTIHASH_TAG: u64 = 0x1c;
+    const DIGEST_LEN: usize = 48;
+
+    type State = sha3::Keccak384;
+
+    fn init() -> Self::State {
+        <sha3::Keccak384 as sha3::Digest>::new()
+    }
+
+    fn update(state: &mut Self::State, data: &[u8]) {
+        sha3::Digest::update(state, data);
+    }
+
+    fn finalize(state: Self::State) -> Vec<u8> {
+        sha3::Digest::finalize(state).to_vec()
+    }
 }
 
 /// Keccak512 hash algorithm.
@@ -97,6 +380,21 @@ pub struct Keccak512;
 #[cfg(feature = "keccak512")]
 impl Multihasher for Keccak512 {
     const MULTIHASH_TAG: u64 = 0x1d;
+    const DIGEST_LEN: usize = 64;
+
+    type State = sha3::Keccak512;
+
+    fn init() -> Self::State {
+        <sha3::Keccak512 as sha3::Digest>::new()
+    }
+
+    fn update(state: &mut Self::State, data: &[u8]) {
+        sha3::Digest::update(state, data);
+    }
+
+    fn finalize(state: Self::State) -> Vec<u8> {
+        sha3::Digest::finalize(state).to_vec()
+    }
 }
 
 /// SHA3-256 hash algorithm.
@@ -107,6 +405,21 @@ pub struct Sha3_256;
 #[cfg(feature = "sha3_256")]
 impl Multihasher for Sha3_256 {
     const MULTIHASH_TAG: u64 = 0x16;
+    const DIGEST_LEN: usize = 32;
+
+    type State = sha3::Sha3_256;
+
+    fn init() -> Self::State {
+        <sha3::Sha3_256 as sha3::Digest>::new()
+    }
+
+    fn update(state: &mut Self::State, data: &[u8]) {
+        sha3::Digest::update(state, data);
+    }
+
+    fn finalize(state: Self::State) -> Vec<u8> {
+        sha3::Digest::finalize(state).to_vec()
+    }
 }
 
 /// SHA3-384 hash algorithm.
@@ -117,6 +430,21 @@ pub struct Sha3_384;
 #[cfg(feature = "sha3_384")]
 impl Multihasher for Sha3_384 {
     const MULTIHASH_TAG: u64 = 0x15;
+    const DIGEST_LEN: usize = 48;
+
+    type State = sha3::Sha3_384;
+
+    fn init() -> Self::State {
+        <sha3::Sha3_384 as sha3::Digest>::new()
+    }
+
+    fn update(state: &mut Self::State, data: &[u8]) {
+        sha3::Digest::update(state, data);
+    }
+
+    fn finalize(state: Self::State) -> Vec<u8> {
+        sha3::Digest::finalize(state).to_vec()
+    }
 }
 
 /// SHA3-512 hash algorithm.
@@ -127,4 +455,83 @@ pub struct Sha3_512;
 #[cfg(feature = "sha3_512")]
 impl Multihasher for Sha3_512 {
     const MULTIHASH_TAG: u64 = 0x14;
+    const DIGEST_LEN: usize = 64;
+
+    type State = sha3::Sha3_512;
+
+    fn init() -> Self::State {
+        <sha3::Sha3_512 as sha3::Digest>::new()
+    }
+
+    fn update(state: &mut Self::State, data: &[u8]) {
+        sha3::Digest::update(state, data);
+    }
+
+    fn finalize(state: Self::State) -> Vec<u8> {
+        sha3::Digest::finalize(state).to_vec()
+    }
+}
+
+#[cfg(all(
+    test,
+    any(feature = "sha2_256", feature = "sha2_512", feature = "shake_256")
+))]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "sha2_256")]
+    #[test]
+    fn test_sha2_256_known_answer() {
+        assert_eq!(
+            Sha2_256::digest(b"abc"),
+            [
+                0xba, 0x78, 0x16, 0xbf, 0x8f, 0x01, 0xcf, 0xea, 0x41, 0x41, 0x40, 0xde, 0x5d, 0xae,
+                0x22, 0x23, 0xb0, 0x03, 0x61, 0xa3, 0x96, 0x17, 0x7a, 0x9c, 0xb4, 0x10, 0xff, 0x61,
+                0xf2, 0x00, 0x15, 0xad
+            ]
+        );
+    }
+
+    #[cfg(feature = "sha2_512")]
+    #[test]
+    fn test_streaming_matches_one_shot() {
+        let data = [7u8; 1000];
+        let mut streaming = StreamingDigest::<Sha2_512>::new();
+        for chunk in data.chunks(64) {
+            streaming.update(chunk);
+        }
+        assert_eq!(streaming.finalize(), Sha2_512::digest(&data));
+    }
+
+    #[cfg(feature = "sha2_512")]
+    #[test]
+    fn test_digest_by_tag() {
+        assert_eq!(
+            digest_by_tag(Sha2_512::MULTIHASH_TAG, b"abc"),
+            Some(Sha2_512::digest(b"abc"))
+        );
+        assert_eq!(digest_by_tag(0xdead_beef, b"abc"), None);
+        assert_eq!(
+            digest_fn_by_tag(Sha2_512::MULTIHASH_TAG).map(|digest| digest(b"abc")),
+            Some(Sha2_512::digest(b"abc"))
+        );
+        assert!(digest_fn_by_tag(0xdead_beef).is_none());
+    }
+
+    #[cfg(all(feature = "sha2_384", feature = "sha3_384"))]
+    #[test]
+    fn test_sha2_384_and_sha3_384_tags_are_distinct() {
+        assert_eq!(Sha2_384::MULTIHASH_TAG, 0x20);
+        assert_eq!(Sha3_384::MULTIHASH_TAG, 0x15);
+        assert_eq!(
+            digest_by_tag(Sha3_384::MULTIHASH_TAG, b"abc"),
+            Some(Sha3_384::digest(b"abc"))
+        );
+    }
+
+    #[cfg(feature = "shake_256")]
+    #[test]
+    fn test_shake_256_digest_len() {
+        assert_eq!(Shake256::digest(b"abc").len(), Shake256::DIGEST_LEN);
+    }
 }
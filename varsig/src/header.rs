@@ -1,14 +1,17 @@
 //! Varsig header
 
 use alloc::vec::Vec;
-use core::marker::PhantomData;
+use core::{convert::Infallible, marker::PhantomData};
 
 use crate::{
     codec::Codec,
+    policy::VerificationPolicy,
     signer::{AsyncSign, Sign, SignerError},
     verify::Verify,
 };
+use async_signature::AsyncSigner;
 use serde::{Deserialize, Serialize};
+use thiserror::Error;
 
 #[cfg(feature = "dag_cbor")]
 use crate::codec::DagCborCodec;
@@ -49,6 +52,27 @@ impl<V: Verify, C: Codec<T>, T> Varsig<V, C, T> {
         &self.codec
     }
 
+    /// The multihash tag of the hash named in this header, if the signature
+    /// algorithm carries one (see [`AnyVarsigHeader::hash`]).
+    ///
+    /// Pair with [`digest_by_tag`] to hash with the same algorithm at runtime.
+    ///
+    /// [`digest_by_tag`]: crate::hash::digest_by_tag
+    #[must_use]
+    pub fn hash_tag(&self) -> Option<u64> {
+        AnyVarsigHeader::try_from_tags(self.tags()).ok()?.hash()
+    }
+
+    /// All tags after the varsig and version tags: the signature prefix,
+    /// its configuration, and the codec.
+    #[must_use]
+    pub fn tags(&self) -> Vec<u64> {
+        let mut tags = alloc::vec![self.verifier_cfg.prefix()];
+        tags.extend(self.verifier_cfg.config_tags());
+        tags.push(self.codec.multicodec_code());
+        tags
+    }
+
     /// Try to synchronously sign a payload with the provided signing key.
     ///
     /// # Errors
@@ -88,6 +112,33 @@ impl<V: Verify, C: Codec<T>, T> Varsig<V, C, T> {
             .await
     }
 
+    /// Try to asynchronously sign a payload with any [`AsyncSigner`] for this signature type.
+    ///
+    /// Unlike [`Varsig::try_sign_async`], the signer is not fixed by the configuration,
+    /// so keys held by a KMS, an agent process, or `WebCrypto` can sign directly.
+    ///
+    /// # Errors
+    ///
+    /// If encoding or signing fails, a `SignerError` is returned.
+    pub async fn try_sign_with_async<S: AsyncSigner<V::Signature>>(
+        &self,
+        signer: &S,
+        payload: &T,
+    ) -> Result<(V::Signature, Vec<u8>), SignerError<C::EncodingError, Infallible>>
+    where
+        C: Codec<T>,
+    {
+        let buffer = self
+            .codec
+            .encode_payload(payload)
+            .map_err(SignerError::EncodingError)?;
+        let sig = signer
+            .sign_async(&buffer)
+            .await
+            .map_err(SignerError::SigningError)?;
+        Ok((sig, buffer))
+    }
+
     /// Try to verify a signature for some payload.
     ///
     /// # Errors
@@ -102,6 +153,32 @@ impl<V: Verify, C: Codec<T>, T> Varsig<V, C, T> {
         self.verifier_cfg()
             .try_verify(&self.codec, verifier, signature, payload)
     }
+
+    /// Try to verify a signature for some payload, subject to a [`VerificationPolicy`].
+    ///
+    /// The header is checked against the policy first. If the policy is
+    /// strict, the signature is then checked with [`Verify::try_verify_strict`].
+    ///
+    /// # Errors
+    ///
+    /// If the policy rejects the header, or encoding or signature verification fails,
+    /// a `VerificationError` is returned.
+    pub fn try_verify_with_policy(
+        &self,
+        policy: &VerificationPolicy,
+        verifier: &V::Verifier,
+        payload: &T,
+        signature: &V::Signature,
+    ) -> Result<(), crate::verify::VerificationError<C::EncodingError>> {
+        policy.check(self)?;
+
+        if policy.is_strict() {
+            self.verifier_cfg()
+                .try_verify_strict(&self.codec, verifier, signature, payload)
+        } else {
+            self.try_verify(verifier, payload, signature)
+        }
+    }
 }
 
 #[cfg(feature = "dag_cbor")]
@@ -182,32 +259,7 @@ impl<'de, V: Verify, C: Codec<T>, T> Deserialize<'de> for Varsig<V, C, T> {
                 serde::de::Error::custom(format_args!("unable to deserialize varsig header: {e}"))
             })?;
 
-        let data = bytes.as_ref();
-        let mut pos = 0;
-
-        let read_u64 = |pos: &mut usize| -> Result<u64, D::Error> {
-            leb128fmt::decode_uint_slice::<u64, 64>(data, pos)
-                .map_err(|_| serde::de::Error::custom("unable to read leb128 unsigned"))
-        };
-
-        let varsig_tag = read_u64(&mut pos)?;
-        if varsig_tag != 0x34 {
-            return Err(serde::de::Error::custom(format_args!(
-                "expected varsig tag 0x34, found {varsig_tag:#x}"
-            )));
-        }
-
-        let version_tag = read_u64(&mut pos)?;
-        if version_tag != 0x01 {
-            return Err(serde::de::Error::custom(format_args!(
-                "expected varsig version tag 0x01, found {version_tag:#x}"
-            )));
-        }
-
-        let mut remaining = Vec::new();
-        while pos < data.len() {
-            remaining.push(read_u64(&mut pos)?);
-        }
+        let remaining = read_tags(bytes.as_ref()).map_err(serde::de::Error::custom)?;
 
         let (verifier_cfg, more) = V::try_from_tags(remaining.as_slice())
             .ok_or_else(|| serde::de::Error::custom("unable to create verifier from tags"))?;
@@ -222,6 +274,325 @@ impl<'de, V: Verify, C: Codec<T>, T> Deserialize<'de> for Varsig<V, C, T> {
     }
 }
 
+/// Read the LEB128 tag stream of a Varsig header.
+///
+/// Checks the leading varsig and version tags, and returns everything after them.
+fn read_tags(data: &[u8]) -> Result<Vec<u64>, HeaderParseError> {
+    let mut pos = 0;
+
+    let read_u64 = |pos: &mut usize| -> Result<u64, HeaderParseError> {
+        leb128fmt::decode_uint_slice::<u64, 64>(data, pos)
+            .map_err(|_| HeaderParseError::InvalidLeb128(*pos))
+    };
+
+    let varsig_tag = read_u64(&mut pos)?;
+    if varsig_tag != 0x34 {
+        return Err(HeaderParseError::UnexpectedVarsigTag(varsig_tag));
+    }
+
+    let version_tag = read_u64(&mut pos)?;
+    if version_tag != 0x01 {
+        return Err(HeaderParseError::UnexpectedVersionTag(version_tag));
+    }
+
+    let mut remaining = Vec::new();
+    while pos < data.len() {
+        remaining.push(read_u64(&mut pos)?);
+    }
+
+    Ok(remaining)
+}
+
+/// A Varsig header parsed without knowing the signature algorithm or codec.
+///
+/// [`Varsig`] can only be deserialized once the [`Verify`] and [`Codec`] types
+/// are known. This type instead gives a structured view of the raw tag stream,
+/// so that callers can inspect a header (e.g. to route or reject a token)
+/// before picking a concrete type.
+///
+/// The layout of the tags after the signature prefix depends on the algorithm.
+/// For the `EdDSA` (`0xed`) and ECDSA (`0xec`) prefixes, the curve and hash are
+/// read from the next two tags, followed by the encoding. RSA (`0x1205`) has
+/// no curve: the hash comes first, then an optional PSS padding tag (`0x01`)
+/// and the signature length, then the encoding. For any other prefix, every
+/// tag after the prefix is reported in [`AnyVarsigHeader::unknown`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AnyVarsigHeader {
+    signature_prefix: u64,
+    tags: Vec<u64>,
+    curve: Option<u64>,
+    hash: Option<u64>,
+    encoding: Option<u64>,
+    unknown_from: usize,
+}
+
+impl AnyVarsigHeader {
+    /// Parse a header from its raw bytes (without any outer CBOR framing).
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`HeaderParseError`] if the bytes are not a Varsig v1 header.
+    pub fn try_from_bytes(bytes: &[u8]) -> Result<Self, HeaderParseError> {
+        Self::try_from_tags(read_tags(bytes)?)
+    }
+
+    /// Build a header view from the tags that follow the varsig and version tags.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`HeaderParseError::MissingSignaturePrefix`] if there are no tags.
+    pub fn try_from_tags(tags: Vec<u64>) -> Result<Self, HeaderParseError> {
+        let prefix = *tags
+            .first()
+            .ok_or(HeaderParseError::MissingSignaturePrefix)?;
+
+        let (curve, hash, encoding, unknown_from) = match prefix {
+            0xea | 0xec | 0xed => (
+                tags.get(1).copied(),
+                tags.get(2).copied(),
+                tags.get(3).copied(),
+                tags.len().min(4),
+            ),
+            0x1205 => {
+                // [hash, (0x01 for PSS)?, sig_len, encoding]
+                let pss = usize::from(tags.get(2) == Some(&0x01));
+                (
+                    None,
+                    tags.get(1).copied(),
+                    tags.get(3 + pss).copied(),
+                    tags.len().min(4 + pss),
+                )
+            }
+            _ => (None, None, None, 1),
+        };
+
+        Ok(AnyVarsigHeader {
+            signature_prefix: prefix,
+            tags,
+            curve,
+            hash,
+            encoding,
+            unknown_from,
+        })
+    }
+
+    /// The signature algorithm prefix (e.g. `0xed` for `EdDSA`).
+    #[must_use]
+    pub const fn signature_prefix(&self) -> u64 {
+        self.signature_prefix
+    }
+
+    /// The curve tag, if the signature algorithm is known to carry one.
+    #[must_use]
+    pub const fn curve(&self) -> Option<u64> {
+        self.curve
+    }
+
+    /// The hash tag, if the signature algorithm is known to carry one.
+    #[must_use]
+    pub const fn hash(&self) -> Option<u64> {
+        self.hash
+    }
+
+    /// The payload encoding (codec) tag, if it could be located.
+    #[must_use]
+    pub const fn encoding(&self) -> Option<u64> {
+        self.encoding
+    }
+
+    /// Any tags that were not accounted for by the known layout.
+    #[must_use]
+    pub fn unknown(&self) -> &[u64] {
+        self.tags.get(self.unknown_from..).unwrap_or_default()
+    }
+
+    /// All tags after the varsig and version tags.
+    #[must_use]
+    pub fn tags(&self) -> &[u64] {
+        &self.tags
+    }
+
+    /// Check whether this header can be read as the given [`Verify`] configuration.
+    #[must_use]
+    pub fn matches<V: Verify>(&self) -> bool {
+        V::try_from_tags(&self.tags).is_some()
+    }
+
+    /// Convert into a typed [`Varsig`] header, if both the signature
+    /// configuration and the codec match.
+    #[must_use]
+    pub fn to_varsig<V: Verify, C: Codec<T>, T>(&self) -> Option<Varsig<V, C, T>> {
+        let (verifier_cfg, more) = V::try_from_tags(&self.tags)?;
+        let codec = C::try_from_tags(more)?;
+        Some(Varsig::new(verifier_cfg, codec))
+    }
+
+    /// The signature configurations compiled into this crate that match this header.
+    #[must_use]
+    #[allow(
+        clippy::missing_const_for_fn,
+        reason = "only const when no signature features are enabled"
+    )]
+    pub fn known_configs(&self) -> Vec<KnownConfig> {
+        #[allow(unused_mut)]
+        let mut found = Vec::new();
+
+        #[cfg(feature = "ed25519")]
+        if self.matches::<crate::signature::eddsa::Ed25519>() {
+            found.push(KnownConfig::Ed25519);
+        }
+
+        #[cfg(feature = "ed448")]
+        if self.matches::<crate::signature::eddsa::Ed448>() {
+            found.push(KnownConfig::Ed448);
+        }
+
+        #[cfg(feature = "es256")]
+        if self.matches::<crate::signature::ecdsa::Es256>() {
+            found.push(KnownConfig::Es256);
+        }
+
+        #[cfg(feature = "es384")]
+        if self.matches::<crate::signature::ecdsa::Es384>() {
+            found.push(KnownConfig::Es384);
+        }
+
+        #[cfg(feature = "es512")]
+        if self.matches::<crate::signature::ecdsa::Es512>() {
+            found.push(KnownConfig::Es512);
+        }
+
+        #[cfg(feature = "es256k")]
+        if self.matches::<crate::signature::ecdsa::Es256k>() {
+            found.push(KnownConfig::Es256k);
+        }
+
+        #[cfg(feature = "rs256")]
+        if self.matches::<crate::signature::rsa::Rs256>() {
+            found.push(KnownConfig::Rs256);
+        }
+
+        #[cfg(feature = "ps256")]
+        if self.matches::<crate::signature::rsa::Ps256>() {
+            found.push(KnownConfig::Ps256);
+        }
+
+        #[cfg(feature = "bls_min_pk")]
+        if self.matches::<crate::signature::bls::Bls12381MinPk>() {
+            found.push(KnownConfig::Bls12381MinPk);
+        }
+
+        #[cfg(feature = "web_crypto")]
+        if self.matches::<crate::signature::web_crypto::WebCrypto>() {
+            found.push(KnownConfig::WebCrypto);
+        }
+
+        found
+    }
+}
+
+impl Serialize for AnyVarsigHeader {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let mut bytes = Vec::new();
+
+        for tag in [0x34, 0x01].iter().chain(&self.tags) {
+            let mut buf = [0u8; leb128fmt::max_len::<64>()];
+            let mut pos = 0;
+            let len = leb128fmt::encode_uint_slice::<u64, 64>(*tag, &mut buf, &mut pos)
+                .ok_or_else(|| serde::ser::Error::custom("leb128 encode failed"))?;
+            bytes.extend_from_slice(
+                buf.get(..len)
+                    .ok_or_else(|| serde::ser::Error::custom("leb128 encode out of bounds"))?,
+            );
+        }
+
+        serializer.serialize_bytes(&bytes)
+    }
+}
+
+impl<'de> Deserialize<'de> for AnyVarsigHeader {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let bytes: serde_bytes::ByteBuf =
+            serde::Deserialize::deserialize(deserializer).map_err(|e| {
+                serde::de::Error::custom(format_args!("unable to deserialize varsig header: {e}"))
+            })?;
+
+        AnyVarsigHeader::try_from_bytes(bytes.as_ref()).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Signature configurations compiled into this crate.
+///
+/// Returned by [`AnyVarsigHeader::known_configs`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum KnownConfig {
+    /// [`crate::signature::eddsa::Ed25519`]
+    #[cfg(feature = "ed25519")]
+    Ed25519,
+
+    /// [`crate::signature::eddsa::Ed448`]
+    #[cfg(feature = "ed448")]
+    Ed448,
+
+    /// [`crate::signature::ecdsa::Es256`]
+    #[cfg(feature = "es256")]
+    Es256,
+
+    /// [`crate::signature::ecdsa::Es384`]
+    #[cfg(feature = "es384")]
+    Es384,
+
+    /// [`crate::signature::ecdsa::Es512`]
+    #[cfg(feature = "es512")]
+    Es512,
+
+    /// [`crate::signature::ecdsa::Es256k`]
+    #[cfg(feature = "es256k")]
+    Es256k,
+
+    /// [`crate::signature::rsa::Rs256`]
+    #[cfg(feature = "rs256")]
+    Rs256,
+
+    /// [`crate::signature::rsa::Ps256`]
+    #[cfg(feature = "ps256")]
+    Ps256,
+
+    /// [`crate::signature::bls::Bls12381MinPk`]
+    #[cfg(feature = "bls_min_pk")]
+    Bls12381MinPk,
+
+    /// [`crate::signature::web_crypto::WebCrypto`]
+    #[cfg(feature = "web_crypto")]
+    WebCrypto,
+}
+
+/// Errors that can occur when reading a Varsig header's tags.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum HeaderParseError {
+    /// The first tag is not the varsig tag (`0x34`).
+    #[error("expected varsig tag 0x34, found {0:#x}")]
+    UnexpectedVarsigTag(u64),
+
+    /// The second tag is not the version tag (`0x01`).
+    #[error("expected varsig version tag 0x01, found {0:#x}")]
+    UnexpectedVersionTag(u64),
+
+    /// A tag is not a valid unsigned LEB128 integer.
+    #[error("unable to read leb128 unsigned at byte {0}")]
+    InvalidLeb128(usize),
+
+    /// There is no signature prefix after the version tag.
+    #[error("missing signature prefix")]
+    MissingSignaturePrefix,
+}
+
 #[cfg(all(test, feature = "dag_cbor", feature = "ed25519"))]
 mod tests {
     use super::*;
@@ -255,6 +626,20 @@ mod tests {
         assert_eq!(varsig.verifier_cfg(), &EdDsa::new());
     }
 
+    #[test]
+    fn test_hash_tag_names_header_hash() {
+        let varsig: Varsig<Ed25519, DagCborCodec, String> = Varsig::new(EdDsa::new(), DagCborCodec);
+        assert_eq!(varsig.hash_tag(), Some(0x13));
+        assert_eq!(
+            varsig
+                .hash_tag()
+                .and_then(|tag| crate::hash::digest_by_tag(tag, b"abc")),
+            Some(<crate::hash::Sha2_512 as crate::hash::Multihasher>::digest(
+                b"abc"
+            ))
+        );
+    }
+
     #[test]
     fn test_codec_reader() {
         let varsig: Varsig<Ed25519, DagCborCodec, String> = Varsig::new(EdDsa::new(), DagCborCodec);
@@ -284,4 +669,131 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_any_header_ed25519_fixture() -> TestResult {
+        let dag_cbor = [0x48, 0x34, 0x01, 0xed, 0x01, 0xed, 0x01, 0x13, 0x71];
+        let header: AnyVarsigHeader = serde_ipld_dagcbor::from_slice(&dag_cbor)?;
+
+        assert_eq!(header.signature_prefix(), 0xed);
+        assert_eq!(header.curve(), Some(0xed));
+        assert_eq!(header.hash(), Some(0x13));
+        assert_eq!(header.encoding(), Some(0x71));
+        assert!(header.unknown().is_empty());
+        assert!(header.matches::<Ed25519>());
+        assert!(header.known_configs().contains(&KnownConfig::Ed25519));
+        assert_eq!(
+            header.to_varsig::<Ed25519, DagCborCodec, String>(),
+            Some(Varsig::new(EdDsa::new(), DagCborCodec))
+        );
+
+        assert_eq!(serde_ipld_dagcbor::to_vec(&header)?, dag_cbor);
+        Ok(())
+    }
+
+    #[test]
+    fn test_any_header_unknown_trailing_tags() -> TestResult {
+        let header = AnyVarsigHeader::try_from_bytes(&[
+            0x34, 0x01, 0xed, 0x01, 0xed, 0x01, 0x13, 0x71, 0x05,
+        ])?;
+
+        assert_eq!(header.encoding(), Some(0x71));
+        assert_eq!(header.unknown(), &[0x05]);
+        assert!(header.matches::<Ed25519>());
+        assert_eq!(header.to_varsig::<Ed25519, DagCborCodec, String>(), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_any_header_unknown_prefix() -> TestResult {
+        let header = AnyVarsigHeader::try_from_bytes(&[0x34, 0x01, 0x20, 0x05, 0x71])?;
+
+        assert_eq!(header.signature_prefix(), 0x20);
+        assert_eq!(header.curve(), None);
+        assert_eq!(header.encoding(), None);
+        assert_eq!(header.unknown(), &[0x05, 0x71]);
+        assert!(header.known_configs().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_any_header_rejects_bad_tags() {
+        assert_eq!(
+            AnyVarsigHeader::try_from_bytes(&[0x35, 0x01, 0xed]),
+            Err(HeaderParseError::UnexpectedVarsigTag(0x35))
+        );
+        assert_eq!(
+            AnyVarsigHeader::try_from_bytes(&[0x34, 0x02, 0xed]),
+            Err(HeaderParseError::UnexpectedVersionTag(0x02))
+        );
+        assert_eq!(
+            AnyVarsigHeader::try_from_bytes(&[0x34, 0x01]),
+            Err(HeaderParseError::MissingSignaturePrefix)
+        );
+    }
+
+    #[cfg(feature = "rs256")]
+    #[test]
+    fn test_any_header_rs256_round_trip() -> TestResult {
+        use crate::signature::rsa::{Rs256, RsaKeySize};
+
+        let rs256: Varsig<Rs256, DagCborCodec, String> =
+            Varsig::new(Rs256::new(RsaKeySize::Rsa2048), DagCborCodec);
+        let bytes = serde_ipld_dagcbor::to_vec(&rs256)?;
+        let header: AnyVarsigHeader = serde_ipld_dagcbor::from_slice(&bytes)?;
+
+        assert_eq!(header.signature_prefix(), 0x1205);
+        assert_eq!(header.curve(), None);
+        assert_eq!(header.hash(), Some(0x12));
+        assert_eq!(header.encoding(), Some(0x71));
+        assert!(header.unknown().is_empty());
+        assert_eq!(rs256.hash_tag(), Some(0x12));
+        assert_eq!(header.known_configs(), vec![KnownConfig::Rs256]);
+        assert_eq!(
+            header.to_varsig::<Rs256, DagCborCodec, String>(),
+            Some(rs256)
+        );
+
+        assert_eq!(serde_ipld_dagcbor::to_vec(&header)?, bytes);
+        Ok(())
+    }
+
+    #[cfg(feature = "ps256")]
+    #[test]
+    fn test_any_header_ps256_round_trip() -> TestResult {
+        use crate::signature::rsa::{Ps256, RsaKeySize};
+
+        let ps256: Varsig<Ps256, DagCborCodec, String> =
+            Varsig::new(Ps256::new(RsaKeySize::Rsa4096), DagCborCodec);
+        let bytes = serde_ipld_dagcbor::to_vec(&ps256)?;
+        let header: AnyVarsigHeader = serde_ipld_dagcbor::from_slice(&bytes)?;
+
+        assert_eq!(header.hash(), Some(0x12));
+        assert_eq!(header.encoding(), Some(0x71));
+        assert!(header.unknown().is_empty());
+        assert_eq!(header.known_configs(), vec![KnownConfig::Ps256]);
+        assert_eq!(
+            header.to_varsig::<Ps256, DagCborCodec, String>(),
+            Some(ps256)
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "web_crypto")]
+    #[test]
+    fn test_any_header_es256_matches_web_crypto() -> TestResult {
+        use crate::signature::ecdsa::Es256;
+
+        let bytes = serde_ipld_dagcbor::to_vec(&Varsig::<Es256, DagCborCodec, String>::default())?;
+        let header: AnyVarsigHeader = serde_ipld_dagcbor::from_slice(&bytes)?;
+
+        assert_eq!(header.signature_prefix(), 0xec);
+        assert_eq!(header.curve(), Some(0x1201));
+        assert_eq!(header.hash(), Some(0x15));
+        assert_eq!(
+            header.known_configs(),
+            vec![KnownConfig::Es256, KnownConfig::WebCrypto]
+        );
+        Ok(())
+    }
 }
@@ -46,6 +46,7 @@ pub mod curve;
 pub mod encoding;
 pub mod hash;
 pub mod header;
+pub mod policy;
 pub mod signature;
 pub mod signer;
 pub mod verify;
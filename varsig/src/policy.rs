@@ -0,0 +1,246 @@
+//! Verification policy.
+//!
+//! By default, any header that the compiled-in [`Verify`] type can parse is
+//! accepted. A [`VerificationPolicy`] narrows that down: it can restrict the
+//! exact signature/hash/encoding combinations, require a minimum security
+//! level, and opt into strict verification (e.g. Ed25519 `verify_strict`).
+
+use alloc::{collections::BTreeSet, vec::Vec};
+use thiserror::Error;
+
+use crate::{codec::Codec, header::AnyVarsigHeader, verify::Verify, Varsig};
+
+/// Rules that a Varsig header must satisfy before its signature is checked.
+///
+/// The [`Default`] policy is permissive: every header is allowed,
+/// there is no minimum security level, and verification is not strict.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VerificationPolicy {
+    allowed: Option<BTreeSet<Vec<u64>>>,
+    min_security_bits: u16,
+    strict: bool,
+}
+
+impl VerificationPolicy {
+    /// A policy that accepts every header.
+    #[must_use]
+    pub const fn permissive() -> Self {
+        VerificationPolicy {
+            allowed: None,
+            min_security_bits: 0,
+            strict: false,
+        }
+    }
+
+    /// Allow the signature/hash/encoding combination of this header.
+    ///
+    /// Once anything has been allowed, every other combination is rejected.
+    #[must_use]
+    pub fn allow(mut self, header: &AnyVarsigHeader) -> Self {
+        self.allowed
+            .get_or_insert_with(BTreeSet::new)
+            .insert(header.tags().to_vec());
+        self
+    }
+
+    /// Allow the signature/hash/encoding combination of a typed header.
+    ///
+    /// Once anything has been allowed, every other combination is rejected.
+    #[must_use]
+    pub fn allow_varsig<V: Verify, C: Codec<T>, T>(mut self, varsig: &Varsig<V, C, T>) -> Self {
+        self.allowed
+            .get_or_insert_with(BTreeSet::new)
+            .insert(varsig.tags());
+        self
+    }
+
+    /// Require at least this many bits of security (see [`Verify::security_bits`]).
+    ///
+    /// Configurations that do not report a security level are rejected
+    /// whenever this is non-zero.
+    #[must_use]
+    pub const fn min_security_bits(mut self, bits: u16) -> Self {
+        self.min_security_bits = bits;
+        self
+    }
+
+    /// Use strict verification (see [`Verify::verify_strict`]).
+    #[must_use]
+    pub const fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Whether strict verification is required.
+    #[must_use]
+    pub const fn is_strict(&self) -> bool {
+        self.strict
+    }
+
+    /// Check an untyped header against the allow-list.
+    ///
+    /// The security level can only be checked once the configuration is
+    /// known, so this does not enforce [`Self::min_security_bits`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`PolicyViolation::NotAllowed`] if the header is not on the allow-list.
+    pub fn check_header(&self, header: &AnyVarsigHeader) -> Result<(), PolicyViolation> {
+        self.check_tags(header.tags())
+    }
+
+    /// Check a typed header against the allow-list and the minimum security level.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`PolicyViolation`] if the header is not allowed or too weak.
+    pub fn check<V: Verify, C: Codec<T>, T>(
+        &self,
+        varsig: &Varsig<V, C, T>,
+    ) -> Result<(), PolicyViolation> {
+        self.check_tags(&varsig.tags())?;
+
+        if self.min_security_bits > 0 {
+            let actual = varsig.verifier_cfg().security_bits();
+            if actual.is_none_or(|bits| bits < self.min_security_bits) {
+                return Err(PolicyViolation::InsufficientSecurity {
+                    required: self.min_security_bits,
+                    actual,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    fn check_tags(&self, tags: &[u64]) -> Result<(), PolicyViolation> {
+        match &self.allowed {
+            Some(allowed) if !allowed.contains(tags) => Err(PolicyViolation::NotAllowed),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Ways a header can fail a [`VerificationPolicy`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Error)]
+pub enum PolicyViolation {
+    /// The signature/hash/encoding combination is not on the allow-list.
+    #[error("signature algorithm not allowed by policy")]
+    NotAllowed,
+
+    /// The configuration is weaker than the policy's minimum.
+    #[error(
+        "signature algorithm provides {actual:?} bits of security, policy requires {required}"
+    )]
+    InsufficientSecurity {
+        /// The policy's minimum.
+        required: u16,
+
+        /// The configuration's security level, if known.
+        actual: Option<u16>,
+    },
+}
+
+#[cfg(all(test, feature = "dag_cbor", feature = "ed25519"))]
+mod tests {
+    use super::*;
+    use crate::{codec::DagCborCodec, signature::eddsa::Ed25519, verify::VerificationError};
+    use testresult::TestResult;
+
+    fn ed25519_header() -> Varsig<Ed25519, DagCborCodec, String> {
+        Varsig::default()
+    }
+
+    #[test]
+    fn test_permissive_allows_everything() -> TestResult {
+        let policy = VerificationPolicy::default();
+        policy.check(&ed25519_header())?;
+        policy.check_header(&AnyVarsigHeader::try_from_tags(vec![0x1234, 0x71])?)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_allow_list() -> TestResult {
+        let policy = VerificationPolicy::permissive().allow_varsig(&ed25519_header());
+        policy.check(&ed25519_header())?;
+
+        // Same signature algorithm, different encoding
+        let other = AnyVarsigHeader::try_from_tags(vec![0xed, 0xed, 0x13, 0x0129])?;
+        assert_eq!(
+            policy.check_header(&other),
+            Err(PolicyViolation::NotAllowed)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_min_security_bits() -> TestResult {
+        VerificationPolicy::permissive()
+            .min_security_bits(128)
+            .check(&ed25519_header())?;
+
+        assert_eq!(
+            VerificationPolicy::permissive()
+                .min_security_bits(192)
+                .check(&ed25519_header()),
+            Err(PolicyViolation::InsufficientSecurity {
+                required: 192,
+                actual: Some(128)
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_strict_rejects_small_order_key() -> TestResult {
+        use ed25519_dalek::{Signature, VerifyingKey};
+
+        // Identity point as the public key, and a signature with R = identity, s = 0.
+        // This "verifies" for every message under the permissive check.
+        let mut identity = [0u8; 32];
+        if let Some(first) = identity.first_mut() {
+            *first = 1;
+        }
+        let vk = VerifyingKey::from_bytes(&identity)?;
+        let mut sig_bytes = [0u8; 64];
+        if let Some(r) = sig_bytes.get_mut(..32) {
+            r.copy_from_slice(&identity);
+        }
+        let sig = Signature::from_bytes(&sig_bytes);
+        let payload = "anything".to_string();
+
+        let varsig = ed25519_header();
+        varsig.try_verify_with_policy(&VerificationPolicy::permissive(), &vk, &payload, &sig)?;
+
+        assert!(matches!(
+            varsig.try_verify_with_policy(
+                &VerificationPolicy::permissive().strict(true),
+                &vk,
+                &payload,
+                &sig
+            ),
+            Err(VerificationError::VerificationError(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_policy_enforced_before_verification() -> TestResult {
+        let sk = ed25519_dalek::SigningKey::from_bytes(&[7; 32]);
+        let varsig = ed25519_header();
+        let payload = "hello".to_string();
+        let (sig, _) = varsig.try_sign(&sk, &payload)?;
+
+        let policy =
+            VerificationPolicy::permissive().allow(&AnyVarsigHeader::try_from_tags(vec![
+                0xec, 0x1201, 0x15, 0x71,
+            ])?);
+        assert!(matches!(
+            varsig.try_verify_with_policy(&policy, &sk.verifying_key(), &payload, &sig),
+            Err(VerificationError::PolicyViolation(
+                PolicyViolation::NotAllowed
+            ))
+        ));
+        Ok(())
+    }
+}
@@ -1,7 +1,11 @@
 //! Signature configuration.
 
+#[cfg(feature = "bls12_381")]
+pub mod bls;
 pub mod ecdsa;
 pub mod eddsa;
+#[cfg(feature = "rsa")]
+pub mod rsa;
 pub mod web_crypto;
 
 /// The most common signature types used in most contexts.
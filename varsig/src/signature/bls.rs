@@ -0,0 +1,375 @@
+//! BLS signature algorithms.
+//!
+//! BLS signatures over BLS12-381 can be aggregated: any number of signatures
+//! over distinct messages collapse into one, which is then checked with a
+//! single final exponentiation instead of one full pairing check per signature.
+//! See [`aggregate_verify`].
+
+use alloc::{collections::BTreeSet, vec::Vec};
+use blst::{
+    min_pk::{AggregateSignature, PublicKey, SecretKey, Signature},
+    BLST_ERROR,
+};
+use thiserror::Error;
+
+#[cfg(feature = "sha2_256")]
+use alloc::vec;
+
+#[cfg(feature = "sha2_256")]
+use crate::verify::Verify;
+
+/// The multicodec prefix for `bls12_381-g1-pub`.
+pub const BLS12_381_G1_PUB: u64 = 0xea;
+
+/// Domain separation tag for the basic (`NUL`) scheme with public keys in G1.
+///
+/// The basic scheme requires every message in an aggregate to be distinct,
+/// which [`aggregate_verify`] enforces.
+pub const DST: &[u8] = b"BLS_SIG_BLS12381G2_XMD:SHA-256_SSWU_RO_NUL_";
+
+/// Length of a compressed G1 public key.
+pub const PUBLIC_KEY_LENGTH: usize = 48;
+
+/// Length of a compressed G2 signature.
+pub const SIGNATURE_LENGTH: usize = 96;
+
+/// The BLS12-381 "minimal public key size" signature algorithm.
+///
+/// Public keys live in G1 (48 bytes) and signatures in G2 (96 bytes),
+/// with messages hashed to the curve using SHA2-256.
+#[cfg(feature = "sha2_256")]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Bls12381MinPk;
+
+#[cfg(feature = "sha2_256")]
+impl Bls12381MinPk {
+    /// Create a new `Bls12381MinPk` instance.
+    #[must_use]
+    pub const fn new() -> Self {
+        Bls12381MinPk
+    }
+}
+
+/// Newtype around [`blst::min_pk::Signature`] that adds [`SignatureEncoding`].
+///
+/// [`SignatureEncoding`]: signature::SignatureEncoding
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlsSignature(pub Signature);
+
+impl signature::SignatureEncoding for BlsSignature {
+    type Repr = [u8; SIGNATURE_LENGTH];
+}
+
+impl TryFrom<&[u8]> for BlsSignature {
+    type Error = signature::Error;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        if bytes.len() != SIGNATURE_LENGTH {
+            return Err(signature::Error::new());
+        }
+
+        Signature::from_bytes(bytes)
+            .map(Self)
+            .map_err(|_| signature::Error::new())
+    }
+}
+
+impl From<BlsSignature> for [u8; SIGNATURE_LENGTH] {
+    fn from(sig: BlsSignature) -> Self {
+        sig.0.compress()
+    }
+}
+
+impl serde::Serialize for BlsSignature {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(&self.0.compress())
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for BlsSignature {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let bytes = serde_bytes::ByteBuf::deserialize(deserializer)?;
+        Self::try_from(bytes.as_ref()).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Newtype around [`blst::min_pk::PublicKey`] that verifies [`BlsSignature`]s.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlsVerifyingKey(pub PublicKey);
+
+impl BlsVerifyingKey {
+    /// Parse and validate a compressed public key.
+    ///
+    /// # Errors
+    ///
+    /// If the bytes are not a valid, non-identity G1 point.
+    pub fn from_bytes(bytes: &[u8; PUBLIC_KEY_LENGTH]) -> Result<Self, signature::Error> {
+        PublicKey::key_validate(bytes)
+            .map(Self)
+            .map_err(|_| signature::Error::new())
+    }
+
+    /// The compressed public key.
+    #[must_use]
+    pub fn to_bytes(&self) -> [u8; PUBLIC_KEY_LENGTH] {
+        self.0.compress()
+    }
+}
+
+impl signature::Verifier<BlsSignature> for BlsVerifyingKey {
+    fn verify(&self, msg: &[u8], signature: &BlsSignature) -> Result<(), signature::Error> {
+        if signature.0.verify(true, msg, DST, &[], &self.0, true) == BLST_ERROR::BLST_SUCCESS {
+            Ok(())
+        } else {
+            Err(signature::Error::new())
+        }
+    }
+}
+
+impl From<PublicKey> for BlsVerifyingKey {
+    fn from(key: PublicKey) -> Self {
+        Self(key)
+    }
+}
+
+/// Newtype around [`blst::min_pk::SecretKey`] that produces [`BlsSignature`]s.
+#[derive(Debug, Clone)]
+pub struct BlsSigningKey(pub SecretKey);
+
+impl BlsSigningKey {
+    /// Derive a signing key from at least 32 bytes of input keying material.
+    ///
+    /// # Errors
+    ///
+    /// If `ikm` is shorter than 32 bytes.
+    pub fn from_ikm(ikm: &[u8]) -> Result<Self, signature::Error> {
+        SecretKey::key_gen(ikm, &[])
+            .map(Self)
+            .map_err(|_| signature::Error::new())
+    }
+
+    /// Parse a big-endian secret scalar.
+    ///
+    /// # Errors
+    ///
+    /// If the bytes are not a valid, non-zero scalar.
+    pub fn from_bytes(bytes: &[u8; 32]) -> Result<Self, signature::Error> {
+        SecretKey::from_bytes(bytes)
+            .map(Self)
+            .map_err(|_| signature::Error::new())
+    }
+
+    /// The big-endian secret scalar.
+    #[must_use]
+    pub fn to_bytes(&self) -> [u8; 32] {
+        self.0.to_bytes()
+    }
+
+    /// Get the associated verifying key.
+    #[must_use]
+    pub fn verifying_key(&self) -> BlsVerifyingKey {
+        BlsVerifyingKey(self.0.sk_to_pk())
+    }
+}
+
+impl signature::Signer<BlsSignature> for BlsSigningKey {
+    fn try_sign(&self, msg: &[u8]) -> Result<BlsSignature, signature::Error> {
+        Ok(BlsSignature(self.0.sign(msg, DST, &[])))
+    }
+}
+
+impl From<SecretKey> for BlsSigningKey {
+    fn from(key: SecretKey) -> Self {
+        Self(key)
+    }
+}
+
+#[cfg(feature = "sha2_256")]
+impl Verify for Bls12381MinPk {
+    type Signature = BlsSignature;
+    type Verifier = BlsVerifyingKey;
+
+    fn prefix(&self) -> u64 {
+        BLS12_381_G1_PUB
+    }
+
+    fn config_tags(&self) -> Vec<u64> {
+        vec![BLS12_381_G1_PUB, 0x12]
+    }
+
+    fn try_from_tags(bytes: &[u64]) -> Option<(Self, &[u64])> {
+        if *bytes.get(0..=2)? == [BLS12_381_G1_PUB, BLS12_381_G1_PUB, 0x12] {
+            Some((Bls12381MinPk, bytes.get(3..)?))
+        } else {
+            None
+        }
+    }
+
+    /// Current estimates put BLS12-381 somewhat below 128 bits,
+    /// after improvements to the number field sieve.
+    fn security_bits(&self) -> Option<u16> {
+        Some(117)
+    }
+}
+
+#[cfg(feature = "sha2_256")]
+impl crate::signer::Sign for Bls12381MinPk {
+    type Signer = BlsSigningKey;
+    type SignError = signature::Error;
+}
+
+/// Verify many `(key, message, signature)` triples at once.
+///
+/// The signatures are summed into a single aggregate, which is checked
+/// against all key/message pairs with one multi-pairing. This is only sound
+/// for distinct messages, so duplicates are rejected up front.
+///
+/// # Errors
+///
+/// If the input is empty, contains a repeated message, or does not verify.
+pub fn aggregate_verify<'a, I>(items: I) -> Result<(), AggregateVerifyError>
+where
+    I: IntoIterator<Item = (&'a BlsVerifyingKey, &'a [u8], &'a BlsSignature)>,
+{
+    let mut pks = Vec::new();
+    let mut msgs = Vec::new();
+    let mut sigs = Vec::new();
+    let mut seen = BTreeSet::new();
+
+    for (pk, msg, sig) in items {
+        if !seen.insert(msg) {
+            return Err(AggregateVerifyError::DuplicateMessage);
+        }
+        pks.push(&pk.0);
+        msgs.push(msg);
+        sigs.push(&sig.0);
+    }
+
+    if sigs.is_empty() {
+        return Err(AggregateVerifyError::Empty);
+    }
+
+    let aggregate = AggregateSignature::aggregate(&sigs, true)
+        .map_err(|_| AggregateVerifyError::InvalidSignature)?
+        .to_signature();
+
+    if aggregate.aggregate_verify(true, &msgs, DST, &pks, true) == BLST_ERROR::BLST_SUCCESS {
+        Ok(())
+    } else {
+        Err(AggregateVerifyError::VerificationFailed)
+    }
+}
+
+/// Errors from [`aggregate_verify`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Error)]
+pub enum AggregateVerifyError {
+    /// Nothing to verify.
+    #[error("no signatures to aggregate")]
+    Empty,
+
+    /// The same message appears more than once.
+    #[error("duplicate message in aggregate")]
+    DuplicateMessage,
+
+    /// A signature is not a valid G2 point.
+    #[error("invalid signature in aggregate")]
+    InvalidSignature,
+
+    /// The aggregate signature does not verify.
+    #[error("aggregate signature verification failed")]
+    VerificationFailed,
+}
+
+#[cfg(all(test, feature = "sha2_256", feature = "dag_cbor"))]
+mod tests {
+    use super::*;
+    use crate::{codec::DagCborCodec, Varsig};
+    use signature::Signer;
+    use testresult::TestResult;
+
+    fn signing_key(seed: u8) -> Result<BlsSigningKey, signature::Error> {
+        BlsSigningKey::from_ikm(&[seed; 32])
+    }
+
+    #[test]
+    fn test_bls_sign_verify_round_trip() -> TestResult {
+        let sk = signing_key(1)?;
+        let varsig: Varsig<Bls12381MinPk, DagCborCodec, String> = Varsig::default();
+        let payload = "Hello, BLS!".to_string();
+
+        let (sig, _) = varsig.try_sign(&sk, &payload)?;
+        varsig.try_verify(&sk.verifying_key(), &payload, &sig)?;
+
+        assert!(varsig
+            .try_verify(&sk.verifying_key(), &"tampered".to_string(), &sig)
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_bls_header_round_trip() -> TestResult {
+        let varsig: Varsig<Bls12381MinPk, DagCborCodec, String> = Varsig::default();
+        let bytes = serde_ipld_dagcbor::to_vec(&varsig)?;
+        assert_eq!(
+            bytes,
+            [0x48, 0x34, 0x01, 0xea, 0x01, 0xea, 0x01, 0x12, 0x71]
+        );
+
+        let round_tripped: Varsig<Bls12381MinPk, DagCborCodec, String> =
+            serde_ipld_dagcbor::from_slice(&bytes)?;
+        assert_eq!(varsig, round_tripped);
+        Ok(())
+    }
+
+    #[test]
+    fn test_aggregate_verify() -> TestResult {
+        let keys = (1..=4).map(signing_key).collect::<Result<Vec<_>, _>>()?;
+        let msgs: Vec<Vec<u8>> = (0u8..4).map(|i| vec![i; 8]).collect();
+        let sigs = keys
+            .iter()
+            .zip(&msgs)
+            .map(|(sk, msg)| sk.try_sign(msg))
+            .collect::<Result<Vec<_>, _>>()?;
+        let pks: Vec<_> = keys.iter().map(BlsSigningKey::verifying_key).collect();
+
+        aggregate_verify(
+            pks.iter()
+                .zip(&msgs)
+                .zip(&sigs)
+                .map(|((pk, msg), sig)| (pk, msg.as_slice(), sig)),
+        )?;
+
+        // Any one bad signature spoils the whole aggregate
+        let mut tampered = sigs.clone();
+        if let Some(last) = tampered.last_mut() {
+            *last = keys.first().ok_or("no keys")?.try_sign(b"other")?;
+        }
+        assert_eq!(
+            aggregate_verify(
+                pks.iter()
+                    .zip(&msgs)
+                    .zip(&tampered)
+                    .map(|((pk, msg), sig)| (pk, msg.as_slice(), sig)),
+            ),
+            Err(AggregateVerifyError::VerificationFailed)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_aggregate_verify_rejects_duplicates_and_empty() -> TestResult {
+        let sk = signing_key(1)?;
+        let pk = sk.verifying_key();
+        let sig = sk.try_sign(b"same")?;
+
+        assert_eq!(
+            aggregate_verify([(&pk, b"same".as_slice(), &sig), (&pk, b"same", &sig)]),
+            Err(AggregateVerifyError::DuplicateMessage)
+        );
+        assert_eq!(
+            aggregate_verify(core::iter::empty()),
+            Err(AggregateVerifyError::Empty)
+        );
+        Ok(())
+    }
+}
@@ -22,12 +22,93 @@ use crate::curve::Secp384r1;
 #[cfg(feature = "secp521r1")]
 use crate::curve::Secp521r1;
 
+#[cfg(any(
+    feature = "secp256k1",
+    feature = "secp256r1",
+    feature = "secp384r1",
+    feature = "secp521r1"
+))]
+use crate::codec::Codec;
 use core::marker::PhantomData;
+#[cfg(any(
+    feature = "secp256k1",
+    feature = "secp256r1",
+    feature = "secp384r1",
+    feature = "secp521r1"
+))]
+use signature::hazmat::{PrehashSigner, PrehashVerifier};
 
 /// The ECDSA signature algorithm.
+///
+/// ECDSA signs a digest of the message, so the hash `H` named in the header
+/// can be run by the caller instead. This is useful when the payload is large
+/// (and can be streamed through [`StreamingDigest`]), or when the digest is
+/// computed somewhere other than where the key lives.
+///
+/// [`StreamingDigest`]: crate::hash::StreamingDigest
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct EcDsa<C: EcDsaCurve, H: Multihasher>(PhantomData<(C, H)>);
 
+#[cfg(any(
+    feature = "secp256k1",
+    feature = "secp256r1",
+    feature = "secp384r1",
+    feature = "secp521r1"
+))]
+impl<C: EcDsaCurve, H: Multihasher> EcDsa<C, H>
+where
+    Self: Verify,
+{
+    /// Encode a payload with `codec` and hash it with `H`.
+    ///
+    /// # Errors
+    ///
+    /// If the payload cannot be encoded.
+    pub fn prehash<T, Co: Codec<T>>(
+        &self,
+        codec: &Co,
+        payload: &T,
+    ) -> Result<Vec<u8>, Co::EncodingError> {
+        Ok(H::digest(&codec.encode_payload(payload)?))
+    }
+
+    /// Sign a digest already computed with `H`.
+    ///
+    /// # Errors
+    ///
+    /// If the digest is the wrong length for `H`, or signing fails.
+    pub fn try_sign_prehash<S: PrehashSigner<<Self as Verify>::Signature>>(
+        &self,
+        signer: &S,
+        prehash: &[u8],
+    ) -> Result<<Self as Verify>::Signature, signature::Error> {
+        if prehash.len() != H::DIGEST_LEN {
+            return Err(signature::Error::new());
+        }
+        signer.sign_prehash(prehash)
+    }
+
+    /// Verify a signature over a digest already computed with `H`.
+    ///
+    /// # Errors
+    ///
+    /// If the digest is the wrong length for `H`, or the signature is invalid.
+    pub fn try_verify_prehash(
+        &self,
+        verifier: &<Self as Verify>::Verifier,
+        prehash: &[u8],
+        signature: &<Self as Verify>::Signature,
+    ) -> Result<(), signature::Error>
+    where
+        <Self as Verify>::Verifier: PrehashVerifier<<Self as Verify>::Signature>,
+    {
+        if prehash.len() != H::DIGEST_LEN {
+            return Err(signature::Error::new());
+        }
+        verifier.verify_prehash(prehash, signature)
+    }
+}
+
 /// ECDSA-compatible curves
 pub trait EcDsaCurve {}
 
@@ -67,6 +148,16 @@ impl Verify for Es256 {
             None
         }
     }
+
+    fn security_bits(&self) -> Option<u16> {
+        Some(128)
+    }
+}
+
+#[cfg(all(feature = "secp256r1", feature = "sha2_256"))]
+impl crate::signer::Sign for Es256 {
+    type Signer = p256::ecdsa::SigningKey;
+    type SignError = signature::Error;
 }
 
 /// The ES384 signature algorithm.
@@ -93,6 +184,16 @@ impl Verify for Es384 {
             None
         }
     }
+
+    fn security_bits(&self) -> Option<u16> {
+        Some(192)
+    }
+}
+
+#[cfg(all(feature = "secp384r1", feature = "sha2_384"))]
+impl crate::signer::Sign for Es384 {
+    type Signer = p384::ecdsa::SigningKey;
+    type SignError = signature::Error;
 }
 
 /// The ES512 signature algorithm.
@@ -128,6 +229,17 @@ impl signature::Verifier<p521::ecdsa::Signature> for P521VerifyingKey {
     }
 }
 
+#[cfg(all(feature = "secp521r1", feature = "sha2_512"))]
+impl PrehashVerifier<p521::ecdsa::Signature> for P521VerifyingKey {
+    fn verify_prehash(
+        &self,
+        prehash: &[u8],
+        signature: &p521::ecdsa::Signature,
+    ) -> Result<(), signature::Error> {
+        self.0.verify_prehash(prehash, signature)
+    }
+}
+
 #[cfg(all(feature = "secp521r1", feature = "sha2_512"))]
 impl From<p521::ecdsa::VerifyingKey> for P521VerifyingKey {
     fn from(key: p521::ecdsa::VerifyingKey) -> Self {
@@ -155,6 +267,16 @@ impl Verify for Es512 {
             None
         }
     }
+
+    fn security_bits(&self) -> Option<u16> {
+        Some(256)
+    }
+}
+
+#[cfg(all(feature = "secp521r1", feature = "sha2_512"))]
+impl crate::signer::Sign for Es512 {
+    type Signer = p521::ecdsa::SigningKey;
+    type SignError = signature::Error;
 }
 
 /// The ES256K signature algorithm.
@@ -181,4 +303,79 @@ impl Verify for Es256k {
             None
         }
     }
+
+    fn security_bits(&self) -> Option<u16> {
+        Some(128)
+    }
+}
+
+#[cfg(all(feature = "secp256k1", feature = "sha2_256"))]
+impl crate::signer::Sign for Es256k {
+    type Signer = k256::ecdsa::SigningKey;
+    type SignError = signature::Error;
+}
+
+#[cfg(all(test, feature = "dag_cbor"))]
+mod tests {
+    #[allow(unused_imports)]
+    use super::*;
+
+    #[cfg(all(feature = "secp256r1", feature = "sha2_256"))]
+    #[test]
+    fn test_es256_prehash_matches_varsig_signing() -> testresult::TestResult {
+        use crate::{codec::DagCborCodec, Varsig};
+
+        let sk = p256::ecdsa::SigningKey::random(&mut rand::thread_rng());
+        let payload = "Hello, ECDSA!".to_string();
+        let varsig: Varsig<Es256, DagCborCodec, String> = Varsig::default();
+
+        // Sign over the digest, verify over the full payload
+        let prehash = Es256::default().prehash(&DagCborCodec, &payload)?;
+        let sig = Es256::default().try_sign_prehash(&sk, &prehash)?;
+        varsig.try_verify(sk.verifying_key(), &payload, &sig)?;
+
+        // And the other way around
+        let (sig, _) = varsig.try_sign(&sk, &payload)?;
+        Es256::default().try_verify_prehash(sk.verifying_key(), &prehash, &sig)?;
+        Ok(())
+    }
+
+    #[cfg(all(feature = "secp256k1", feature = "sha2_256"))]
+    #[test]
+    fn test_es256k_streamed_prehash() -> testresult::TestResult {
+        use crate::hash::{Sha2_256, StreamingDigest};
+        use signature::Verifier;
+
+        let sk = k256::ecdsa::SigningKey::random(&mut rand::thread_rng());
+        let payload = vec![0xab; 1 << 16];
+
+        let mut digest = StreamingDigest::<Sha2_256>::new();
+        for chunk in payload.chunks(4096) {
+            digest.update(chunk);
+        }
+        let sig = Es256k::default().try_sign_prehash(&sk, &digest.finalize())?;
+        sk.verifying_key().verify(&payload, &sig)?;
+        Ok(())
+    }
+
+    #[cfg(all(feature = "secp521r1", feature = "sha2_512"))]
+    #[test]
+    fn test_es512_prehash_rejects_wrong_digest_length() -> testresult::TestResult {
+        use crate::hash::Sha2_512;
+
+        let sk = p521::ecdsa::SigningKey::random(&mut rand::thread_rng());
+        let vk = P521VerifyingKey::from(p521::ecdsa::VerifyingKey::from(&sk));
+        let prehash = Sha2_512::digest(b"payload");
+
+        let sig = Es512::default().try_sign_prehash(&sk, &prehash)?;
+        Es512::default().try_verify_prehash(&vk, &prehash, &sig)?;
+
+        assert!(Es512::default()
+            .try_sign_prehash(&sk, prehash.get(..32).ok_or("short")?)
+            .is_err());
+        assert!(Es512::default()
+            .try_verify_prehash(&vk, prehash.get(..32).ok_or("short")?, &sig)
+            .is_err());
+        Ok(())
+    }
 }
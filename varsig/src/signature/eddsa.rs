@@ -4,10 +4,16 @@ use core::marker::PhantomData;
 
 use crate::hash::Multihasher;
 
-#[cfg(all(feature = "edwards25519", feature = "sha2_512"))]
+#[cfg(any(
+    all(feature = "edwards25519", feature = "sha2_512"),
+    all(feature = "edwards448", feature = "shake_256")
+))]
 use alloc::{vec, vec::Vec};
 
-#[cfg(all(feature = "edwards25519", feature = "sha2_512"))]
+#[cfg(any(
+    all(feature = "edwards25519", feature = "sha2_512"),
+    all(feature = "edwards448", feature = "shake_256")
+))]
 use crate::verify::Verify;
 
 /// The `EdDSA` signature algorithm.
@@ -28,6 +34,9 @@ pub trait EdDsaCurve: Sized {}
 #[cfg(feature = "edwards25519")]
 impl EdDsaCurve for crate::curve::Edwards25519 {}
 
+#[cfg(feature = "edwards448")]
+impl EdDsaCurve for crate::curve::Edwards448 {}
+
 /// The Ed25519 signature algorithm.
 ///
 /// The `EdDSA` signing algorithm with the Edwards25519 curve with SHA2-512 hashing.
@@ -54,6 +63,56 @@ impl Verify for Ed25519 {
             None
         }
     }
+
+    /// Uses [`ed25519_dalek::VerifyingKey::verify_strict`], which rejects
+    /// small-order public keys and non-canonical signatures.
+    fn verify_strict(
+        verifier: &Self::Verifier,
+        msg: &[u8],
+        signature: &Self::Signature,
+    ) -> Result<(), signature::Error> {
+        verifier.verify_strict(msg, signature)
+    }
+
+    fn security_bits(&self) -> Option<u16> {
+        Some(128)
+    }
+
+    #[cfg(feature = "ed25519_batch")]
+    fn verify_batch(
+        items: &[(&Self::Verifier, &[u8], &Self::Signature)],
+    ) -> Result<(), signature::Error> {
+        verify_batch(items)
+    }
+}
+
+/// Verify a batch of Ed25519 signatures with a single multiscalar multiplication.
+///
+/// This is substantially faster than checking each signature on its own,
+/// but does not report _which_ signature is invalid.
+///
+/// # Errors
+///
+/// If any signature in the batch is invalid.
+#[cfg(feature = "ed25519_batch")]
+pub fn verify_batch(
+    items: &[(
+        &ed25519_dalek::VerifyingKey,
+        &[u8],
+        &ed25519_dalek::Signature,
+    )],
+) -> Result<(), signature::Error> {
+    let mut messages = Vec::with_capacity(items.len());
+    let mut signatures = Vec::with_capacity(items.len());
+    let mut verifying_keys = Vec::with_capacity(items.len());
+
+    for (verifying_key, message, signature) in items {
+        messages.push(*message);
+        signatures.push(**signature);
+        verifying_keys.push(**verifying_key);
+    }
+
+    ed25519_dalek::verify_batch(&messages, &signatures, &verifying_keys)
 }
 
 #[cfg(all(feature = "edwards25519", feature = "sha2_512"))]
@@ -61,3 +120,227 @@ impl crate::signer::Sign for Ed25519 {
     type Signer = ed25519_dalek::SigningKey;
     type SignError = signature::Error;
 }
+
+/// The Ed448 signature algorithm.
+///
+/// The `EdDSA` signing algorithm with the Edwards448 curve with SHAKE-256 hashing.
+#[cfg(all(feature = "edwards448", feature = "shake_256"))]
+pub type Ed448 = EdDsa<crate::curve::Edwards448, crate::hash::Shake256>;
+
+/// Newtype around [`ed448_goldilocks_plus::Signature`] that adds [`SignatureEncoding`].
+///
+/// The upstream crate does not implement [`SignatureEncoding`],
+/// which our [`Verify`] trait requires.
+///
+/// [`SignatureEncoding`]: signature::SignatureEncoding
+#[cfg(all(feature = "edwards448", feature = "shake_256"))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ed448Signature(pub ed448_goldilocks_plus::Signature);
+
+#[cfg(all(feature = "edwards448", feature = "shake_256"))]
+impl signature::SignatureEncoding for Ed448Signature {
+    type Repr = [u8; ed448_goldilocks_plus::SIGNATURE_LENGTH];
+}
+
+#[cfg(all(feature = "edwards448", feature = "shake_256"))]
+impl TryFrom<&[u8]> for Ed448Signature {
+    type Error = signature::Error;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        ed448_goldilocks_plus::Signature::try_from(bytes)
+            .map(Self)
+            .map_err(|_| signature::Error::new())
+    }
+}
+
+#[cfg(all(feature = "edwards448", feature = "shake_256"))]
+impl From<Ed448Signature> for [u8; ed448_goldilocks_plus::SIGNATURE_LENGTH] {
+    fn from(sig: Ed448Signature) -> Self {
+        sig.0.to_bytes()
+    }
+}
+
+#[cfg(all(feature = "edwards448", feature = "shake_256"))]
+impl serde::Serialize for Ed448Signature {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(&self.0.to_bytes())
+    }
+}
+
+#[cfg(all(feature = "edwards448", feature = "shake_256"))]
+impl<'de> serde::Deserialize<'de> for Ed448Signature {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let bytes = serde_bytes::ByteBuf::deserialize(deserializer)?;
+        Self::try_from(bytes.as_ref()).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Newtype around [`ed448_goldilocks_plus::VerifyingKey`] that verifies [`Ed448Signature`]s.
+#[cfg(all(feature = "edwards448", feature = "shake_256"))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Ed448VerifyingKey(pub ed448_goldilocks_plus::VerifyingKey);
+
+#[cfg(all(feature = "edwards448", feature = "shake_256"))]
+impl signature::Verifier<Ed448Signature> for Ed448VerifyingKey {
+    fn verify(&self, msg: &[u8], signature: &Ed448Signature) -> Result<(), signature::Error> {
+        self.0.verify(msg, &signature.0)
+    }
+}
+
+#[cfg(all(feature = "edwards448", feature = "shake_256"))]
+impl From<ed448_goldilocks_plus::VerifyingKey> for Ed448VerifyingKey {
+    fn from(key: ed448_goldilocks_plus::VerifyingKey) -> Self {
+        Self(key)
+    }
+}
+
+/// Newtype around [`ed448_goldilocks_plus::SigningKey`] that produces [`Ed448Signature`]s.
+#[cfg(all(feature = "edwards448", feature = "shake_256"))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ed448SigningKey(pub ed448_goldilocks_plus::SigningKey);
+
+#[cfg(all(feature = "edwards448", feature = "shake_256"))]
+impl Ed448SigningKey {
+    /// Get the associated verifying key.
+    #[must_use]
+    pub fn verifying_key(&self) -> Ed448VerifyingKey {
+        Ed448VerifyingKey(self.0.verifying_key())
+    }
+}
+
+#[cfg(all(feature = "edwards448", feature = "shake_256"))]
+impl signature::Signer<Ed448Signature> for Ed448SigningKey {
+    fn try_sign(&self, msg: &[u8]) -> Result<Ed448Signature, signature::Error> {
+        signature::Signer::try_sign(&self.0, msg).map(Ed448Signature)
+    }
+}
+
+#[cfg(all(feature = "edwards448", feature = "shake_256"))]
+impl From<ed448_goldilocks_plus::SigningKey> for Ed448SigningKey {
+    fn from(key: ed448_goldilocks_plus::SigningKey) -> Self {
+        Self(key)
+    }
+}
+
+#[cfg(all(feature = "edwards448", feature = "shake_256"))]
+impl Verify for Ed448 {
+    type Signature = Ed448Signature;
+    type Verifier = Ed448VerifyingKey;
+
+    fn prefix(&self) -> u64 {
+        0xed
+    }
+
+    fn config_tags(&self) -> Vec<u64> {
+        vec![0x1203, 0x19]
+    }
+
+    fn try_from_tags(bytes: &[u64]) -> Option<(Self, &[u64])> {
+        if *bytes.get(0..=2)? == [0xed, 0x1203, 0x19] {
+            Some((EdDsa(PhantomData), bytes.get(3..)?))
+        } else {
+            None
+        }
+    }
+
+    fn security_bits(&self) -> Option<u16> {
+        Some(224)
+    }
+}
+
+#[cfg(all(feature = "edwards448", feature = "shake_256"))]
+impl crate::signer::Sign for Ed448 {
+    type Signer = Ed448SigningKey;
+    type SignError = signature::Error;
+}
+
+#[cfg(all(
+    test,
+    feature = "edwards448",
+    feature = "shake_256",
+    feature = "dag_cbor"
+))]
+mod tests {
+    use super::*;
+    use crate::{codec::DagCborCodec, Varsig};
+    use testresult::TestResult;
+
+    #[test]
+    fn test_ed448_sign_verify_round_trip() -> TestResult {
+        let sk = Ed448SigningKey(ed448_goldilocks_plus::SigningKey::generate(
+            rand::thread_rng(),
+        ));
+        let varsig: Varsig<Ed448, DagCborCodec, String> = Varsig::default();
+        let payload = "Hello, Ed448!".to_string();
+
+        let (sig, _) = varsig.try_sign(&sk, &payload)?;
+        varsig.try_verify(&sk.verifying_key(), &payload, &sig)?;
+
+        assert!(varsig
+            .try_verify(&sk.verifying_key(), &"tampered".to_string(), &sig)
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_ed448_header_round_trip() -> TestResult {
+        let varsig: Varsig<Ed448, DagCborCodec, String> = Varsig::default();
+        let bytes = serde_ipld_dagcbor::to_vec(&varsig)?;
+        assert_eq!(
+            bytes,
+            [0x48, 0x34, 0x01, 0xed, 0x01, 0x83, 0x24, 0x19, 0x71]
+        );
+
+        let round_tripped: Varsig<Ed448, DagCborCodec, String> =
+            serde_ipld_dagcbor::from_slice(&bytes)?;
+        assert_eq!(varsig, round_tripped);
+        Ok(())
+    }
+
+    #[test]
+    fn test_ed448_signature_encoding_round_trip() -> TestResult {
+        use signature::{SignatureEncoding, Signer};
+
+        let sk = Ed448SigningKey(ed448_goldilocks_plus::SigningKey::generate(
+            rand::thread_rng(),
+        ));
+        let sig: Ed448Signature = sk.try_sign(b"payload")?;
+        let decoded = Ed448Signature::try_from(sig.to_bytes().as_ref())?;
+        assert_eq!(sig, decoded);
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "ed25519_batch"))]
+mod batch_tests {
+    use super::*;
+    use ed25519_dalek::{Signer, SigningKey};
+    use testresult::TestResult;
+
+    #[test]
+    fn test_ed25519_verify_batch() -> TestResult {
+        let keys: Vec<SigningKey> = (0u8..3).map(|i| SigningKey::from_bytes(&[i; 32])).collect();
+        let vks: Vec<_> = keys.iter().map(SigningKey::verifying_key).collect();
+        let msgs: Vec<Vec<u8>> = (0u8..3).map(|i| vec![i; 4]).collect();
+        let sigs: Vec<_> = keys.iter().zip(&msgs).map(|(k, m)| k.sign(m)).collect();
+        let mut swapped = sigs.clone();
+        swapped.swap(0, 1);
+
+        let valid: Vec<_> = vks
+            .iter()
+            .zip(&msgs)
+            .zip(&sigs)
+            .map(|((vk, msg), sig)| (vk, msg.as_slice(), sig))
+            .collect();
+        Ed25519::verify_batch(&valid)?;
+
+        let invalid: Vec<_> = vks
+            .iter()
+            .zip(&msgs)
+            .zip(&swapped)
+            .map(|((vk, msg), sig)| (vk, msg.as_slice(), sig))
+            .collect();
+        assert!(Ed25519::verify_batch(&invalid).is_err());
+        Ok(())
+    }
+}
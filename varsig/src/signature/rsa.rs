@@ -0,0 +1,437 @@
+//! RSA signature algorithms.
+//!
+//! Both RSASSA-PKCS1-v1_5 ([`Rs256`]) and RSASSA-PSS ([`Ps256`]) are supported
+//! with SHA2-256 hashing. The varsig header records the signature length in
+//! bytes (i.e. the modulus size), so a header for an RSA-2048 key is distinct
+//! from one for an RSA-4096 key.
+
+use alloc::{boxed::Box, vec, vec::Vec};
+use core::{fmt::Debug, hash::Hash, marker::PhantomData};
+
+use ::rsa::{
+    rand_core::OsRng,
+    sha2::{Digest, Sha256},
+    traits::PublicKeyParts,
+    Pkcs1v15Sign, Pss, RsaPrivateKey, RsaPublicKey,
+};
+
+use crate::hash::Multihasher;
+
+#[cfg(feature = "sha2_256")]
+use crate::verify::Verify;
+
+/// The multicodec prefix for RSA (`rsa-pub`).
+pub const RSA_PREFIX: u64 = 0x1205;
+
+/// Tag marking RSASSA-PSS padding.
+///
+/// The varsig spec only assigns a layout for PKCS #1 v1.5 RSA signatures.
+/// PSS headers insert this tag between the hash and the signature length;
+/// since it is not a valid signature length, the two layouts never overlap.
+pub const PSS_PADDING_TAG: u64 = 0x01;
+
+/// Supported RSA modulus sizes.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum RsaKeySize {
+    /// 2048-bit modulus (256-byte signatures).
+    #[default]
+    Rsa2048,
+
+    /// 3072-bit modulus (384-byte signatures).
+    Rsa3072,
+
+    /// 4096-bit modulus (512-byte signatures).
+    Rsa4096,
+}
+
+impl RsaKeySize {
+    /// The signature length in bytes.
+    #[must_use]
+    pub const fn signature_len(&self) -> usize {
+        match self {
+            RsaKeySize::Rsa2048 => 256,
+            RsaKeySize::Rsa3072 => 384,
+            RsaKeySize::Rsa4096 => 512,
+        }
+    }
+
+    /// Look up a key size from a signature (modulus) length in bytes.
+    #[must_use]
+    pub const fn from_signature_len(len: usize) -> Option<Self> {
+        match len {
+            256 => Some(RsaKeySize::Rsa2048),
+            384 => Some(RsaKeySize::Rsa3072),
+            512 => Some(RsaKeySize::Rsa4096),
+            _ => None,
+        }
+    }
+
+    /// Security level in bits, per NIST SP 800-57.
+    ///
+    /// 4096-bit keys fall between the 128- and 192-bit levels,
+    /// so they are reported at the lower one.
+    #[must_use]
+    pub const fn security_bits(&self) -> u16 {
+        match self {
+            RsaKeySize::Rsa2048 => 112,
+            RsaKeySize::Rsa3072 | RsaKeySize::Rsa4096 => 128,
+        }
+    }
+
+    /// Look up the key size of a public key.
+    #[must_use]
+    pub fn from_public_key(key: &RsaPublicKey) -> Option<Self> {
+        Self::from_signature_len(key.size())
+    }
+}
+
+/// RSA signature padding schemes.
+pub trait RsaPadding: Debug + Default + Clone + Copy + PartialEq + Eq + Hash {
+    /// Header tags that identify this padding scheme.
+    const TAGS: &'static [u64];
+
+    /// Sign a SHA2-256 digest.
+    ///
+    /// # Errors
+    ///
+    /// If the underlying RSA operation fails.
+    fn sign_digest(key: &RsaPrivateKey, digest: &[u8]) -> Result<Vec<u8>, ::rsa::Error>;
+
+    /// Verify a signature over a SHA2-256 digest.
+    ///
+    /// # Errors
+    ///
+    /// If the signature is invalid for the key and digest.
+    fn verify_digest(key: &RsaPublicKey, digest: &[u8], sig: &[u8]) -> Result<(), ::rsa::Error>;
+}
+
+/// RSASSA-PKCS1-v1_5 padding.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Pkcs1v15;
+
+impl RsaPadding for Pkcs1v15 {
+    const TAGS: &'static [u64] = &[];
+
+    fn sign_digest(key: &RsaPrivateKey, digest: &[u8]) -> Result<Vec<u8>, ::rsa::Error> {
+        key.sign(Pkcs1v15Sign::new::<Sha256>(), digest)
+    }
+
+    fn verify_digest(key: &RsaPublicKey, digest: &[u8], sig: &[u8]) -> Result<(), ::rsa::Error> {
+        key.verify(Pkcs1v15Sign::new::<Sha256>(), digest, sig)
+    }
+}
+
+/// RSASSA-PSS padding (salt length equal to the digest length).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RsaPss;
+
+impl RsaPadding for RsaPss {
+    const TAGS: &'static [u64] = &[PSS_PADDING_TAG];
+
+    fn sign_digest(key: &RsaPrivateKey, digest: &[u8]) -> Result<Vec<u8>, ::rsa::Error> {
+        key.sign_with_rng(&mut OsRng, Pss::new::<Sha256>(), digest)
+    }
+
+    fn verify_digest(key: &RsaPublicKey, digest: &[u8], sig: &[u8]) -> Result<(), ::rsa::Error> {
+        key.verify(Pss::new::<Sha256>(), digest, sig)
+    }
+}
+
+/// The RSA signature algorithm.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Rsa<P: RsaPadding, H: Multihasher> {
+    key_size: RsaKeySize,
+    _marker: PhantomData<(P, H)>,
+}
+
+impl<P: RsaPadding, H: Multihasher> Rsa<P, H> {
+    /// Create a new RSA configuration for the given key size.
+    #[must_use]
+    pub const fn new(key_size: RsaKeySize) -> Self {
+        Rsa {
+            key_size,
+            _marker: PhantomData,
+        }
+    }
+
+    /// The configured key size.
+    #[must_use]
+    pub const fn key_size(&self) -> RsaKeySize {
+        self.key_size
+    }
+}
+
+/// The RS256 signature algorithm (RSASSA-PKCS1-v1_5 with SHA2-256).
+#[cfg(feature = "sha2_256")]
+pub type Rs256 = Rsa<Pkcs1v15, crate::hash::Sha2_256>;
+
+/// The PS256 signature algorithm (RSASSA-PSS with SHA2-256).
+#[cfg(feature = "sha2_256")]
+pub type Ps256 = Rsa<RsaPss, crate::hash::Sha2_256>;
+
+/// An RSA signature.
+///
+/// The upstream `rsa` crate has separate signature types per padding scheme
+/// and neither implements `serde`; this wrapper is shared by both.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RsaSignature(Box<[u8]>);
+
+impl RsaSignature {
+    /// The raw signature bytes.
+    #[must_use]
+    pub const fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl signature::SignatureEncoding for RsaSignature {
+    type Repr = Box<[u8]>;
+}
+
+impl TryFrom<&[u8]> for RsaSignature {
+    type Error = signature::Error;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        if RsaKeySize::from_signature_len(bytes.len()).is_some() {
+            Ok(Self(bytes.into()))
+        } else {
+            Err(signature::Error::new())
+        }
+    }
+}
+
+impl From<RsaSignature> for Box<[u8]> {
+    fn from(sig: RsaSignature) -> Self {
+        sig.0
+    }
+}
+
+impl serde::Serialize for RsaSignature {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(&self.0)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for RsaSignature {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let bytes = serde_bytes::ByteBuf::deserialize(deserializer)?;
+        Self::try_from(bytes.as_ref()).map_err(serde::de::Error::custom)
+    }
+}
+
+/// An RSA public key that verifies [`RsaSignature`]s with padding scheme `P`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RsaVerifyingKey<P: RsaPadding>(pub RsaPublicKey, PhantomData<P>);
+
+impl<P: RsaPadding> RsaVerifyingKey<P> {
+    /// Wrap an RSA public key.
+    #[must_use]
+    pub const fn new(key: RsaPublicKey) -> Self {
+        Self(key, PhantomData)
+    }
+}
+
+impl<P: RsaPadding> From<RsaPublicKey> for RsaVerifyingKey<P> {
+    fn from(key: RsaPublicKey) -> Self {
+        Self::new(key)
+    }
+}
+
+impl<P: RsaPadding> signature::Verifier<RsaSignature> for RsaVerifyingKey<P> {
+    fn verify(&self, msg: &[u8], signature: &RsaSignature) -> Result<(), signature::Error> {
+        let digest = Sha256::digest(msg);
+        P::verify_digest(&self.0, &digest, signature.as_bytes())
+            .map_err(|_| signature::Error::new())
+    }
+}
+
+/// An RSA private key that produces [`RsaSignature`]s with padding scheme `P`.
+#[derive(Debug, Clone)]
+pub struct RsaSigningKey<P: RsaPadding>(pub RsaPrivateKey, PhantomData<P>);
+
+impl<P: RsaPadding> RsaSigningKey<P> {
+    /// Wrap an RSA private key.
+    #[must_use]
+    pub const fn new(key: RsaPrivateKey) -> Self {
+        Self(key, PhantomData)
+    }
+
+    /// Get the associated verifying key.
+    #[must_use]
+    pub fn verifying_key(&self) -> RsaVerifyingKey<P> {
+        RsaVerifyingKey::new(self.0.to_public_key())
+    }
+}
+
+impl<P: RsaPadding> From<RsaPrivateKey> for RsaSigningKey<P> {
+    fn from(key: RsaPrivateKey) -> Self {
+        Self::new(key)
+    }
+}
+
+impl<P: RsaPadding> signature::Signer<RsaSignature> for RsaSigningKey<P> {
+    fn try_sign(&self, msg: &[u8]) -> Result<RsaSignature, signature::Error> {
+        let digest = Sha256::digest(msg);
+        P::sign_digest(&self.0, &digest)
+            .map(|sig| RsaSignature(sig.into_boxed_slice()))
+            .map_err(|_| signature::Error::new())
+    }
+}
+
+#[cfg(feature = "sha2_256")]
+impl<P: RsaPadding> Verify for Rsa<P, crate::hash::Sha2_256> {
+    type Signature = RsaSignature;
+    type Verifier = RsaVerifyingKey<P>;
+
+    fn prefix(&self) -> u64 {
+        RSA_PREFIX
+    }
+
+    fn config_tags(&self) -> Vec<u64> {
+        let mut tags = vec![crate::hash::Sha2_256::MULTIHASH_TAG];
+        tags.extend_from_slice(P::TAGS);
+        tags.push(self.key_size.signature_len() as u64);
+        tags
+    }
+
+    fn try_from_tags(bytes: &[u64]) -> Option<(Self, &[u64])> {
+        let (prefix, rest) = bytes.split_first()?;
+        let (hash, rest) = rest.split_first()?;
+        if *prefix != RSA_PREFIX || *hash != crate::hash::Sha2_256::MULTIHASH_TAG {
+            return None;
+        }
+
+        let rest = rest.strip_prefix(P::TAGS)?;
+        let (len, rest) = rest.split_first()?;
+        let key_size = RsaKeySize::from_signature_len(usize::try_from(*len).ok()?)?;
+        Some((Self::new(key_size), rest))
+    }
+
+    fn check_verifier(
+        &self,
+        verifier: &Self::Verifier,
+        signature: &Self::Signature,
+    ) -> Result<(), signature::Error> {
+        if RsaKeySize::from_public_key(&verifier.0) != Some(self.key_size)
+            || signature.as_bytes().len() != self.key_size.signature_len()
+        {
+            return Err(signature::Error::new());
+        }
+        Ok(())
+    }
+
+    fn security_bits(&self) -> Option<u16> {
+        Some(self.key_size.security_bits())
+    }
+}
+
+#[cfg(feature = "sha2_256")]
+impl<P: RsaPadding> crate::signer::Sign for Rsa<P, crate::hash::Sha2_256> {
+    type Signer = RsaSigningKey<P>;
+    type SignError = signature::Error;
+}
+
+#[cfg(all(test, feature = "sha2_256", feature = "dag_cbor"))]
+mod tests {
+    use super::*;
+    use crate::{codec::DagCborCodec, Varsig};
+    use signature::Signer;
+    use testresult::TestResult;
+
+    fn signing_key<P: RsaPadding>() -> Result<RsaSigningKey<P>, ::rsa::Error> {
+        RsaPrivateKey::new(&mut rand::thread_rng(), 2048).map(RsaSigningKey::new)
+    }
+
+    #[test]
+    fn test_rs256_sign_verify_round_trip() -> TestResult {
+        let sk = signing_key::<Pkcs1v15>()?;
+        let varsig: Varsig<Rs256, DagCborCodec, String> = Varsig::default();
+        let payload = "Hello, RSA!".to_string();
+
+        let (sig, _) = varsig.try_sign(&sk, &payload)?;
+        varsig.try_verify(&sk.verifying_key(), &payload, &sig)?;
+
+        assert!(varsig
+            .try_verify(&sk.verifying_key(), &"tampered".to_string(), &sig)
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_ps256_sign_verify_round_trip() -> TestResult {
+        let sk = signing_key::<RsaPss>()?;
+        let varsig: Varsig<Ps256, DagCborCodec, String> = Varsig::default();
+        let payload = "Hello, PSS!".to_string();
+
+        let (sig, _) = varsig.try_sign(&sk, &payload)?;
+        varsig.try_verify(&sk.verifying_key(), &payload, &sig)?;
+
+        // A PSS signature is not a valid PKCS #1 v1.5 signature
+        let pkcs1: RsaVerifyingKey<Pkcs1v15> = sk.0.to_public_key().into();
+        assert!(signature::Verifier::verify(&pkcs1, b"Hello, PSS!", &sig).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_rsa_rejects_mismatched_key_size() -> TestResult {
+        let sk = signing_key::<Pkcs1v15>()?;
+        let varsig: Varsig<Rs256, DagCborCodec, String> =
+            Varsig::new(Rs256::new(RsaKeySize::Rsa4096), DagCborCodec);
+        let payload = "Hello, RSA!".to_string();
+
+        let (sig, _) = varsig.try_sign(&sk, &payload)?;
+        assert!(varsig
+            .try_verify(&sk.verifying_key(), &payload, &sig)
+            .is_err());
+
+        // Callers that verify encoded bytes directly get the same check
+        assert!(varsig
+            .verifier_cfg()
+            .check_verifier(&sk.verifying_key(), &sig)
+            .is_err());
+        assert!(Rs256::default()
+            .check_verifier(&sk.verifying_key(), &sig)
+            .is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn test_rsa_header_round_trip() -> TestResult {
+        let rs256: Varsig<Rs256, DagCborCodec, String> = Varsig::default();
+        let bytes = serde_ipld_dagcbor::to_vec(&rs256)?;
+        assert_eq!(
+            bytes,
+            [0x48, 0x34, 0x01, 0x85, 0x24, 0x12, 0x80, 0x02, 0x71]
+        );
+        let decoded: Varsig<Rs256, DagCborCodec, String> = serde_ipld_dagcbor::from_slice(&bytes)?;
+        assert_eq!(decoded, rs256);
+
+        let ps256: Varsig<Ps256, DagCborCodec, String> =
+            Varsig::new(Ps256::new(RsaKeySize::Rsa4096), DagCborCodec);
+        let bytes = serde_ipld_dagcbor::to_vec(&ps256)?;
+        assert_eq!(
+            bytes,
+            [0x49, 0x34, 0x01, 0x85, 0x24, 0x12, 0x01, 0x80, 0x04, 0x71]
+        );
+        let decoded: Varsig<Ps256, DagCborCodec, String> = serde_ipld_dagcbor::from_slice(&bytes)?;
+        assert_eq!(decoded, ps256);
+
+        // The two layouts are not interchangeable
+        assert!(
+            serde_ipld_dagcbor::from_slice::<Varsig<Rs256, DagCborCodec, String>>(&bytes).is_err()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_rsa_signature_encoding_round_trip() -> TestResult {
+        use signature::SignatureEncoding;
+
+        let sk = signing_key::<Pkcs1v15>()?;
+        let sig: RsaSignature = sk.try_sign(b"payload")?;
+        let decoded = RsaSignature::try_from(sig.to_bytes().as_ref())?;
+        assert_eq!(sig, decoded);
+        assert!(RsaSignature::try_from([0u8; 10].as_slice()).is_err());
+        Ok(())
+    }
+}
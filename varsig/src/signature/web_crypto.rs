@@ -180,6 +180,28 @@ impl Verify for WebCrypto {
         }
     }
 
+    fn verify_strict(
+        verifier: &Self::Verifier,
+        msg: &[u8],
+        signature: &Self::Signature,
+    ) -> Result<(), Error> {
+        match (verifier, signature) {
+            (WebCryptoVerifier::Ed25519(vk), WebCryptoSignature::Ed25519(sig)) => {
+                eddsa::Ed25519::verify_strict(vk, msg, sig)
+            }
+            _ => verifier.verify(msg, signature),
+        }
+    }
+
+    fn security_bits(&self) -> Option<u16> {
+        match self {
+            Self::Es256(v) => v.security_bits(),
+            Self::Es384(v) => v.security_bits(),
+            Self::Es512(v) => v.security_bits(),
+            Self::Ed25519(v) => v.security_bits(),
+        }
+    }
+
     fn try_from_tags(bytes: &[u64]) -> Option<(Self, &[u64])> {
         let rest = bytes.get(3..)?;
 
@@ -45,6 +45,8 @@ pub trait Verify: Sized + Debug {
         signature: &Self::Signature,
         payload: &T,
     ) -> Result<(), VerificationError<C::EncodingError>> {
+        self.check_verifier(verifier, signature)
+            .map_err(VerificationError::VerificationError)?;
         let buffer = codec
             .encode_payload(payload)
             .map_err(VerificationError::EncodingError)?;
@@ -52,6 +54,98 @@ pub trait Verify: Sized + Debug {
             .verify(&buffer, signature)
             .map_err(VerificationError::VerificationError)
     }
+
+    /// Try to verify a signature using the configuration's strict rules.
+    ///
+    /// The default checks [`Self::check_verifier`], encodes the payload, and
+    /// calls [`Self::verify_strict`].
+    ///
+    /// # Errors
+    ///
+    /// Same as [`Self::try_verify`].
+    fn try_verify_strict<T, C: Codec<T>>(
+        &self,
+        codec: &C,
+        verifier: &Self::Verifier,
+        signature: &Self::Signature,
+        payload: &T,
+    ) -> Result<(), VerificationError<C::EncodingError>> {
+        self.check_verifier(verifier, signature)
+            .map_err(VerificationError::VerificationError)?;
+        let buffer = codec
+            .encode_payload(payload)
+            .map_err(VerificationError::EncodingError)?;
+        Self::verify_strict(verifier, &buffer, signature)
+            .map_err(VerificationError::VerificationError)
+    }
+
+    /// Check that a key and signature fit this configuration.
+    ///
+    /// [`Self::try_verify`] and [`Self::try_verify_strict`] call this first.
+    /// Callers that verify already-encoded payloads directly (with
+    /// [`Verifier::verify`], [`Self::verify_strict`], or [`Self::verify_batch`])
+    /// must call it themselves. The default accepts everything; RSA rejects
+    /// keys and signatures whose size disagrees with the header.
+    ///
+    /// # Errors
+    ///
+    /// If the key or signature does not match the configuration.
+    fn check_verifier(
+        &self,
+        _verifier: &Self::Verifier,
+        _signature: &Self::Signature,
+    ) -> Result<(), signature::Error> {
+        Ok(())
+    }
+
+    /// Verify an already-encoded payload, rejecting inputs that the
+    /// permissive check tolerates.
+    ///
+    /// The default is the same as [`Verifier::verify`]. [`Ed25519`] overrides
+    /// it to reject small-order keys and malleable signatures.
+    ///
+    /// # Errors
+    ///
+    /// If the signature is invalid.
+    ///
+    /// [`Ed25519`]: crate::signature::eddsa::Ed25519
+    fn verify_strict(
+        verifier: &Self::Verifier,
+        msg: &[u8],
+        signature: &Self::Signature,
+    ) -> Result<(), signature::Error> {
+        verifier.verify(msg, signature)
+    }
+
+    /// Approximate security level of this configuration, in bits.
+    ///
+    /// Used by [`VerificationPolicy::min_security_bits`]. The default is
+    /// `None` (unknown), which a policy with a minimum treats as too weak.
+    ///
+    /// [`VerificationPolicy::min_security_bits`]: crate::policy::VerificationPolicy::min_security_bits
+    fn security_bits(&self) -> Option<u16> {
+        None
+    }
+
+    /// Verify several already-encoded payloads at once.
+    ///
+    /// The default checks each item in turn. Configurations with a dedicated
+    /// batch algorithm (e.g. [`Ed25519`] with the `ed25519_batch` feature)
+    /// override this. An error only says that _some_ item failed; callers that
+    /// need to know which one should fall back to [`Verifier::verify`].
+    ///
+    /// # Errors
+    ///
+    /// If any signature in the batch is invalid.
+    ///
+    /// [`Ed25519`]: crate::signature::eddsa::Ed25519
+    fn verify_batch(
+        items: &[(&Self::Verifier, &[u8], &Self::Signature)],
+    ) -> Result<(), signature::Error> {
+        items
+            .iter()
+            .try_for_each(|(verifier, msg, signature)| verifier.verify(msg, signature))
+    }
 }
 
 /// Error type for verification errors.
@@ -64,4 +158,10 @@ pub enum VerificationError<E: Error> {
     /// Verification error.
     #[error("Verification error: {0}")]
     VerificationError(signature::Error),
+
+    /// The header was rejected by a [`VerificationPolicy`].
+    ///
+    /// [`VerificationPolicy`]: crate::policy::VerificationPolicy
+    #[error(transparent)]
+    PolicyViolation(#[from] crate::policy::PolicyViolation),
 }